@@ -0,0 +1,107 @@
+use axum::{
+    Json, Router,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use serde::Deserialize;
+use tracing::{Instrument, warn};
+
+use crate::{AppState, handlers, hygiene, login};
+
+/// How many changes of handlers in shadow mode `/admin/shadow-actions` lists.
+const SHADOW_ACTIONS: u32 = 100;
+
+/// The admin API, mounted under `/admin`. Every request needs `Authorization: Bearer
+/// <ADMIN_TOKEN>` or a login session, and the API doesn't exist without either.
+pub fn router(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/dead-letters", get(dead_letters))
+        .route("/dead-letters/{id}", get(dead_letter).delete(discard))
+        .route("/dead-letters/{id}/redrive", post(redrive))
+        .route("/shadow-actions", get(shadow_actions))
+        .route("/label-hygiene", get(hygiene::label_hygiene))
+        .route("/bulk-operations", get(bulk_operations))
+        .layer(middleware::from_fn_with_state(
+            state,
+            login::require_session,
+        ))
+}
+
+async fn dead_letters(State(state): State<AppState>) -> Response {
+    match state.db.dead_letters() {
+        Ok(letters) => Json(letters).into_response(),
+        Err(e) => {
+            warn!("Failed to read dead letters: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+async fn dead_letter(State(state): State<AppState>, Path(id): Path<i64>) -> Response {
+    match state.db.dead_letter(id) {
+        Ok(Some(letter)) => Json(letter).into_response(),
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            warn!("Failed to read dead letter {}: {}", id, e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+async fn discard(State(state): State<AppState>, Path(id): Path<i64>) -> Response {
+    match state.db.remove_dead_letter(id) {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => {
+            warn!("Failed to remove dead letter {}: {}", id, e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ShadowQuery {
+    handler: Option<String>,
+}
+
+/// The latest changes of handlers in shadow mode, see [`handlers::Registry::is_shadow`].
+async fn shadow_actions(
+    State(state): State<AppState>,
+    Query(query): Query<ShadowQuery>,
+) -> Response {
+    match state
+        .db
+        .shadow_actions(query.handler.as_deref(), SHADOW_ACTIONS)
+    {
+        Ok(actions) => Json(actions).into_response(),
+        Err(e) => {
+            warn!("Failed to read shadow actions: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Where the bulk operations since the start stand, by id.
+async fn bulk_operations(State(state): State<AppState>) -> Response {
+    Json(state.bulk.progress()).into_response()
+}
+
+/// Runs the handler of a dead letter again, answers with the error if it fails again.
+async fn redrive(State(state): State<AppState>, Path(id): Path<i64>) -> Response {
+    let letter = match state.db.dead_letter(id) {
+        Ok(Some(x)) => x,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            warn!("Failed to read dead letter {}: {}", id, e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let span = handlers::delivery_span(&letter.job.delivery, &letter.job.event);
+    match handlers::redrive(&state, &letter).instrument(span).await {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => (StatusCode::BAD_GATEWAY, e.to_string()).into_response(),
+    }
+}
@@ -0,0 +1,165 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use octocrab::{
+    Octocrab,
+    models::{Repository, issues::Issue},
+    params,
+};
+use tracing::{info, warn};
+
+use crate::{AppState, bulk, feed::TRIAGE_LABEL, github, handlers::Error};
+
+pub const UNTRIAGED_7D_LABEL: &str = "untriaged-7d";
+pub const UNTRIAGED_30D_LABEL: &str = "untriaged-30d";
+
+/// The labels of issues waiting for triage by how many days they've been open, longest first.
+const AGING_LABELS: &[(i64, &str)] = &[(30, UNTRIAGED_30D_LABEL), (7, UNTRIAGED_7D_LABEL)];
+
+/// Periodically labels issues that still need triage by how long they've been waiting, so
+/// neglected reports can be searched for. Only the label of the longest wait is on an issue,
+/// and they're all taken off once it's triaged.
+pub async fn aging_loop(state: AppState, period: Duration) {
+    let mut interval = tokio::time::interval(period);
+
+    loop {
+        interval.tick().await;
+        let repos = match github::installed_repos(&state.installations).await {
+            Ok(x) => x,
+            Err(e) => {
+                warn!("Failed to list repos for the triage aging: {}", e);
+                continue;
+            }
+        };
+        for (installation, client, repo) in repos {
+            let full_name = repo.full_name.as_deref().unwrap_or_default();
+            let rules = state.rules();
+            if !rules.handlers.enabled("triager", Some(full_name))
+                || !rules.handlers.feature_enabled("stale-bot", full_name)
+            {
+                continue;
+            }
+            state
+                .rate_limits
+                .wait_for_budget(installation, "triage aging")
+                .await;
+            if let Err(e) = age_repo(&state, &client, &repo).await {
+                warn!(
+                    "Failed to label the untriaged issues of {}: {}",
+                    full_name, e
+                );
+            }
+        }
+    }
+}
+
+/// The aging label an issue open for `days` should have.
+fn aging_label(days: i64) -> Option<&'static str> {
+    AGING_LABELS
+        .iter()
+        .find(|(min, _)| days >= *min)
+        .map(|(_, label)| *label)
+}
+
+/// Whether the issue has exactly the aging label `due`.
+fn is_aged(issue: &Issue, due: Option<&str>) -> bool {
+    AGING_LABELS
+        .iter()
+        .all(|(_, x)| issue.labels.iter().any(|l| l.name == *x) == (Some(*x) == due))
+}
+
+/// Sweeps the issues of the repo as a [`bulk`] operation, only the ones whose label changes.
+async fn age_repo(state: &AppState, client: &Octocrab, repo: &Repository) -> Result<(), Error> {
+    let now = Utc::now();
+    let mut changes: Vec<(Issue, Option<&str>)> = Vec::new();
+    for issue in labeled(client, repo, TRIAGE_LABEL).await? {
+        let due = aging_label((now - issue.created_at).num_days());
+        if !is_aged(&issue, due) {
+            changes.push((issue, due));
+        }
+    }
+    for (_, label) in AGING_LABELS {
+        for issue in labeled(client, repo, label).await? {
+            if !issue.labels.iter().any(|x| x.name == TRIAGE_LABEL)
+                && !changes.iter().any(|(x, _)| x.number == issue.number)
+            {
+                changes.push((issue, None));
+            }
+        }
+    }
+    let id = format!("aging:{}", repo.id);
+    let key = |(issue, _): &(Issue, _)| issue.number.to_string();
+    bulk::run(state, &id, &changes, key, |(issue, due)| async move {
+        set_aging_label(state, client, repo, &issue, due).await
+    })
+    .await
+}
+
+/// The open issues with `label`, without PRs.
+async fn labeled(client: &Octocrab, repo: &Repository, label: &str) -> Result<Vec<Issue>, Error> {
+    let owner = repo.owner.as_ref().ok_or("repository without owner")?;
+    let issues = client.issues(&owner.login, &repo.name);
+    let labels = [label.to_string()];
+    let page = github::retry(|| {
+        issues
+            .list()
+            .labels(&labels)
+            .state(params::State::Open)
+            .per_page(100)
+            .send()
+    })
+    .await?;
+    let mut issues = bulk::pages(client, page).await?;
+    issues.retain(|x| x.pull_request.is_none());
+    Ok(issues)
+}
+
+/// Puts `label` on the issue, and takes the other aging labels off.
+async fn set_aging_label(
+    state: &AppState,
+    client: &Octocrab,
+    repo: &Repository,
+    issue: &Issue,
+    label: Option<&str>,
+) -> Result<(), Error> {
+    let issues = client.issues_by_id(repo.id);
+    for (_, old) in AGING_LABELS {
+        if Some(*old) != label && issue.labels.iter().any(|x| x.name == *old) {
+            github::mutate(
+                state.dry_run,
+                format!("remove label {} from #{}", old, issue.number),
+                || issues.remove_label(issue.number, old),
+            )
+            .await?;
+        }
+    }
+    if let Some(label) = label
+        && !issue.labels.iter().any(|x| x.name == label)
+    {
+        info!(
+            "#{} is waiting for triage, labeling it {}",
+            issue.number, label
+        );
+        let labels = [label.to_string()];
+        github::mutate(
+            state.dry_run,
+            format!("add labels {:?} to #{}", labels, issue.number),
+            || issues.add_labels(issue.number, &labels),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn longest_wait_wins() {
+        assert_eq!(aging_label(3), None);
+        assert_eq!(aging_label(7), Some(UNTRIAGED_7D_LABEL));
+        assert_eq!(aging_label(29), Some(UNTRIAGED_7D_LABEL));
+        assert_eq!(aging_label(120), Some(UNTRIAGED_30D_LABEL));
+    }
+}
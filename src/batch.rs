@@ -0,0 +1,335 @@
+use std::{
+    collections::{BTreeSet, HashMap, hash_map::Entry},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use octocrab::{Octocrab, models::RepositoryId};
+use tracing::{info, warn};
+
+use crate::{AppState, db::Db, github, handlers::Error, outbox, report};
+
+/// Label and comment changes to one issue or PR, made together once the window of the handler
+/// that made the first of them ends.
+#[derive(Debug, Default)]
+struct Pending {
+    add: BTreeSet<String>,
+    remove: BTreeSet<String>,
+    comments: Vec<String>,
+    /// The rows in `batched` of the deliveries and handlers the changes are for, by delivery
+    /// and handler.
+    jobs: HashMap<(String, &'static str), i64>,
+}
+
+impl Pending {
+    /// A label added and then removed within the window is removed, the last change wins.
+    fn add_labels(&mut self, labels: &[String]) {
+        for label in labels {
+            self.remove.remove(label);
+            self.add.insert(label.clone());
+        }
+    }
+
+    fn remove_label(&mut self, label: &str) {
+        self.add.remove(label);
+        self.remove.insert(label.to_string());
+    }
+}
+
+/// Coalesces the changes handlers make to the same issue or PR in a burst of deliveries, e.g.
+/// several commands in quick succession, into one call per kind of change. Only handlers with a
+/// window in `batch_windows` are batched, the others change things right away.
+///
+/// The deliveries changes are waiting for are kept in the database. If making the changes
+/// fails, or the bot stops before, the deliveries become dead letters of their handlers, and a
+/// redrive makes the changes again.
+#[derive(Debug, Clone)]
+pub struct Batches {
+    db: Db,
+    pending: Arc<Mutex<HashMap<(RepositoryId, u64), Pending>>>,
+    /// By handler name.
+    windows: Arc<HashMap<String, Duration>>,
+}
+
+impl Batches {
+    /// `windows` are in milliseconds by handler name.
+    pub fn new(windows: &HashMap<String, u64>, db: Db) -> rusqlite::Result<Self> {
+        let stopped =
+            db.dead_letter_batched(None, "the bot stopped before the batched changes were made")?;
+        if stopped > 0 {
+            warn!(
+                "Kept {} deliveries with batched changes from before the start as dead letters",
+                stopped
+            );
+        }
+        Ok(Self {
+            db,
+            pending: Default::default(),
+            windows: Arc::new(
+                windows
+                    .iter()
+                    .filter(|(_, ms)| **ms > 0)
+                    .map(|(name, ms)| (name.clone(), Duration::from_millis(*ms)))
+                    .collect(),
+            ),
+        })
+    }
+
+    /// The window of the running handler. Dry runs aren't batched, there are no calls to save.
+    fn window(&self, dry_run: bool) -> Option<Duration> {
+        if dry_run {
+            return None;
+        }
+        self.windows.get(outbox::handler()?).copied()
+    }
+
+    /// Adds the change to the pending ones of the issue, and flushes them after `window` if
+    /// there were none.
+    fn queue(
+        &self,
+        client: &Octocrab,
+        repo: RepositoryId,
+        number: u64,
+        window: Duration,
+        f: impl FnOnce(&mut Pending),
+    ) -> Result<(), Error> {
+        let (job, handler) = outbox::job().ok_or("batched changes outside of a handler")?;
+        let mut pending = self.pending.lock().unwrap();
+        let first = !pending.contains_key(&(repo, number));
+        let entry = pending.entry((repo, number)).or_default();
+        if let Entry::Vacant(x) = entry.jobs.entry((job.delivery.clone(), handler)) {
+            x.insert(self.db.add_batched(&job, handler)?);
+        }
+        f(entry);
+        if first {
+            let batches = self.clone();
+            let client = client.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(window).await;
+                let Some(pending) = batches.pending.lock().unwrap().remove(&(repo, number)) else {
+                    return;
+                };
+                batches.flush(&client, repo, number, pending).await;
+            });
+        }
+        Ok(())
+    }
+
+    /// Makes the pending changes, and keeps the deliveries they're for as dead letters if that
+    /// fails.
+    async fn flush(&self, client: &Octocrab, repo: RepositoryId, number: u64, pending: Pending) {
+        let ids: Vec<i64> = pending.jobs.values().copied().collect();
+        let res = match flush(client, repo, number, pending).await {
+            Ok(()) => self.db.remove_batched(&ids),
+            Err(e) => {
+                warn!("Failed to make the batched changes to #{}: {}", number, e);
+                let error = format!("failed to make the batched changes: {}", e);
+                self.db.dead_letter_batched(Some(&ids), &error).map(|_| ())
+            }
+        };
+        if let Err(e) = res {
+            warn!(
+                "Failed to update the batched deliveries of #{}: {}",
+                number, e
+            );
+        }
+    }
+}
+
+/// Adds labels to the issue, batched if the running handler has a window.
+pub async fn add_labels(
+    state: &AppState,
+    client: &Octocrab,
+    repo: RepositoryId,
+    number: u64,
+    labels: &[String],
+) -> Result<(), Error> {
+    let what = format!("add labels {:?} to #{}", labels, number);
+    let Some(window) = state.batches.window(state.dry_run) else {
+        let issues = client.issues_by_id(repo);
+        github::mutate(state.dry_run, what, || issues.add_labels(number, labels)).await?;
+        return Ok(());
+    };
+    report::record(&what);
+    state
+        .batches
+        .queue(client, repo, number, window, |x| x.add_labels(labels))
+}
+
+/// Removes a label from the issue, batched if the running handler has a window. A label that
+/// isn't there is fine.
+pub async fn remove_label(
+    state: &AppState,
+    client: &Octocrab,
+    repo: RepositoryId,
+    number: u64,
+    label: &str,
+) -> Result<(), Error> {
+    let what = format!("remove label {} from #{}", label, number);
+    let Some(window) = state.batches.window(state.dry_run) else {
+        let issues = client.issues_by_id(repo);
+        let res = github::mutate(state.dry_run, what, || issues.remove_label(number, label)).await;
+        return match res {
+            Err(octocrab::Error::GitHub { source, .. }) if source.status_code == 404 => Ok(()),
+            res => res.map_err(Into::into),
+        };
+    };
+    report::record(&what);
+    state
+        .batches
+        .queue(client, repo, number, window, |x| x.remove_label(label))
+}
+
+/// Comments on the issue, batched if the running handler has a window. Batched comments are
+/// posted as one.
+pub async fn comment(
+    state: &AppState,
+    client: &Octocrab,
+    repo: RepositoryId,
+    number: u64,
+    body: &str,
+) -> Result<(), Error> {
+    let Some(window) = state.batches.window(state.dry_run) else {
+        let issues = client.issues_by_id(repo);
+        github::comment(state.dry_run, &issues, number, body).await?;
+        return Ok(());
+    };
+    report::record(&format!("comment on #{}: {}", number, body));
+    state.batches.queue(client, repo, number, window, |x| {
+        x.comments.push(body.to_string())
+    })
+}
+
+async fn flush(
+    client: &Octocrab,
+    repo: RepositoryId,
+    number: u64,
+    pending: Pending,
+) -> Result<(), Error> {
+    info!(
+        "Making the batched changes to #{}: +{:?} -{:?}, {} comment(s)",
+        number,
+        pending.add,
+        pending.remove,
+        pending.comments.len()
+    );
+    let issues = client.issues_by_id(repo);
+    if !pending.add.is_empty() {
+        let labels: Vec<String> = pending.add.into_iter().collect();
+        github::mutate(
+            false,
+            format!("add labels {:?} to #{}", labels, number),
+            || issues.add_labels(number, &labels),
+        )
+        .await?;
+    }
+    for label in &pending.remove {
+        let res = github::mutate(
+            false,
+            format!("remove label {} from #{}", label, number),
+            || issues.remove_label(number, label),
+        )
+        .await;
+        match res {
+            Err(octocrab::Error::GitHub { source, .. }) if source.status_code == 404 => {}
+            res => res?,
+        }
+    }
+    if !pending.comments.is_empty() {
+        github::comment(false, &issues, number, &pending.comments.join("\n\n")).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use figment::{Figment, providers::Serialized};
+    use serde_json::json;
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{method, path},
+    };
+
+    use super::*;
+    use crate::{Config, queue::Job};
+
+    fn job() -> Job {
+        Job {
+            delivery: "delivery".to_string(),
+            event: "issue_comment".to_string(),
+            body: "{}".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn failed_changes_become_dead_letters() {
+        let github = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/repositories/1/issues/2/labels"))
+            .respond_with(ResponseTemplate::new(422).set_body_json(json!({
+                "message": "Validation Failed",
+                "documentation_url": "https://docs.github.com/rest",
+            })))
+            .mount(&github)
+            .await;
+        let figment = Figment::from(Serialized::defaults(json!({
+            "github_token": "ghp_test",
+            "github_api_url": github.uri(),
+            "database_path": ":memory:",
+            "batch_windows": { "commands": 10 },
+        })));
+        let config = Config::from_figment(&figment).unwrap();
+        let state = AppState::from_config(&config).unwrap();
+        let client = Octocrab::builder()
+            .base_uri(github.uri())
+            .unwrap()
+            .personal_token("ghp_test".to_string())
+            .build()
+            .unwrap();
+
+        let labels = ["bug".to_string()];
+        let res = outbox::run(&state.db, &job(), "commands", false, async {
+            add_labels(&state, &client, RepositoryId(1), 2, &labels).await
+        })
+        .await;
+        res.unwrap();
+        let mut dead_letters = Vec::new();
+        for _ in 0..100 {
+            dead_letters = state.db.dead_letters().unwrap();
+            if !dead_letters.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(dead_letters.len(), 1);
+        assert_eq!(dead_letters[0].handler, "commands");
+        assert_eq!(dead_letters[0].job.delivery, "delivery");
+    }
+
+    #[test]
+    fn changes_cut_short_become_dead_letters() {
+        let db = Db::open(":memory:").unwrap();
+        db.add_batched(&job(), "commands").unwrap();
+        Batches::new(&HashMap::new(), db.clone()).unwrap();
+        let dead_letters = db.dead_letters().unwrap();
+        assert_eq!(dead_letters.len(), 1);
+        assert_eq!(dead_letters[0].handler, "commands");
+        // Only once.
+        Batches::new(&HashMap::new(), db.clone()).unwrap();
+        assert_eq!(db.dead_letters().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn the_last_label_change_wins() {
+        let mut pending = Pending::default();
+        pending.add_labels(&["bug".to_string(), "client".to_string()]);
+        pending.remove_label("bug");
+        pending.remove_label("triage-needed");
+        pending.add_labels(&["triage-needed".to_string()]);
+        assert_eq!(
+            pending.add,
+            BTreeSet::from(["client".to_string(), "triage-needed".to_string()])
+        );
+        assert_eq!(pending.remove, BTreeSet::from(["bug".to_string()]));
+    }
+}
@@ -0,0 +1,202 @@
+use std::collections::BTreeMap;
+
+use axum::{
+    Json,
+    extract::State,
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use octocrab::params::checks::{CheckRunConclusion, CheckRunOutput, CheckRunStatus};
+use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
+use tracing::warn;
+
+use crate::{AppState, github, handlers::Error};
+
+const CHECK_NAME: &str = "performance";
+
+/// The benchmark results of a commit, as posted by CI.
+#[derive(Debug, Deserialize)]
+pub struct Results {
+    /// `owner/name`
+    pub repo: String,
+    pub sha: String,
+    pub branch: String,
+    /// Time per benchmark by name, lower is better. The unit is up to CI.
+    pub results: BTreeMap<String, f64>,
+}
+
+/// A benchmark compared to its baseline.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Comparison {
+    pub name: String,
+    pub value: f64,
+    /// Not set for new benchmarks.
+    pub baseline: Option<f64>,
+    /// How many percent slower than the baseline, negative if faster.
+    pub change: Option<f64>,
+    pub regressed: bool,
+}
+
+/// `POST /benchmarks`: stores the results, and fails the `performance` check run of the
+/// commit if a benchmark got slower than the tolerance allows. Results of the baseline branch
+/// become the new baselines instead.
+///
+/// Requests need `Authorization: Bearer <token>`, and the endpoint doesn't exist without one.
+pub async fn post_results(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(results): Json<Results>,
+) -> Response {
+    let Some(config) = &state.benchmarks else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let sent = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|x| x.to_str().ok())
+        .and_then(|x| x.strip_prefix("Bearer "));
+    // Compared in constant time, so the time taken doesn't tell how much of the token was right.
+    let valid = sent.is_some_and(|x| bool::from(x.as_bytes().ct_eq(config.token.as_bytes())));
+    if !valid {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    match report(&state, &results).await {
+        Ok(Some(comparisons)) => Json(comparisons).into_response(),
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            warn!("Failed to report benchmarks of {}: {}", results.sha, e);
+            (StatusCode::BAD_GATEWAY, e.to_string()).into_response()
+        }
+    }
+}
+
+/// Compares and stores the results, `None` if the app isn't installed on the repo.
+async fn report(state: &AppState, results: &Results) -> Result<Option<Vec<Comparison>>, Error> {
+    let config = state.benchmarks.as_ref().ok_or("benchmarks not enabled")?;
+    let Some((repo, installation)) = state.db.repo_by_name(&results.repo)? else {
+        return Ok(None);
+    };
+    let baselines = state.db.benchmark_baselines(repo, &config.branch)?;
+    state
+        .db
+        .add_benchmark_results(repo, &results.sha, &results.branch, &results.results)?;
+    let comparisons = compare(&results.results, &baselines, config.tolerance);
+    if results.branch == config.branch {
+        return Ok(Some(comparisons));
+    }
+
+    let regressions = comparisons.iter().filter(|x| x.regressed).count();
+    let (conclusion, summary) = if regressions == 0 {
+        (
+            CheckRunConclusion::Success,
+            format!(
+                "No benchmark is more than {}% slower than on {}.",
+                config.tolerance, config.branch
+            ),
+        )
+    } else {
+        (
+            CheckRunConclusion::Failure,
+            format!(
+                "{} of {} benchmarks are more than {}% slower than on {}.",
+                regressions,
+                comparisons.len(),
+                config.tolerance,
+                config.branch
+            ),
+        )
+    };
+    let mut text = format!(
+        "| Benchmark | {} | This commit | Change |\n|---|---|---|---|\n",
+        config.branch
+    );
+    for x in &comparisons {
+        text.push_str(&format!(
+            "| {}{} | {} | {} | {} |\n",
+            x.name,
+            if x.regressed { " ⚠️" } else { "" },
+            x.baseline.map(|x| x.to_string()).unwrap_or("-".to_string()),
+            x.value,
+            x.change
+                .map(|x| format!("{:+.1}%", x))
+                .unwrap_or("new".to_string()),
+        ));
+    }
+
+    let (owner, name) = results.repo.split_once('/').ok_or("invalid repo")?;
+    let client = state.installations.client(installation)?;
+    let checks = client.checks(owner, name);
+    github::mutate(
+        state.dry_run,
+        format!("report {} on {}: {}", CHECK_NAME, results.sha, summary),
+        || {
+            checks
+                .create_check_run(CHECK_NAME, &results.sha)
+                .status(CheckRunStatus::Completed)
+                .conclusion(conclusion)
+                .output(CheckRunOutput {
+                    title: CHECK_NAME.to_string(),
+                    summary: summary.clone(),
+                    text: Some(text.clone()),
+                    annotations: Vec::new(),
+                    images: Vec::new(),
+                })
+                .send()
+        },
+    )
+    .await?;
+    Ok(Some(comparisons))
+}
+
+/// Compares every result to its baseline, regressed if it's more than `tolerance` percent
+/// slower.
+fn compare(
+    results: &BTreeMap<String, f64>,
+    baselines: &BTreeMap<String, f64>,
+    tolerance: f64,
+) -> Vec<Comparison> {
+    results
+        .iter()
+        .map(|(name, value)| {
+            let baseline = baselines.get(name).copied();
+            let change = baseline
+                .filter(|x| *x > 0.0)
+                .map(|x| (value - x) / x * 100.0);
+            Comparison {
+                name: name.clone(),
+                value: *value,
+                baseline,
+                change,
+                regressed: change.is_some_and(|x| x > tolerance),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slower_benchmarks_regress() {
+        let baselines = BTreeMap::from([("render".to_string(), 100.0), ("load".to_string(), 50.0)]);
+        let results = BTreeMap::from([
+            ("load".to_string(), 52.0),
+            ("new".to_string(), 1.0),
+            ("render".to_string(), 110.0),
+        ]);
+        let regressed: Vec<_> = compare(&results, &baselines, 5.0)
+            .into_iter()
+            .map(|x| (x.name, x.regressed))
+            .collect();
+        assert_eq!(
+            regressed,
+            [
+                ("load".to_string(), false),
+                ("new".to_string(), false),
+                ("render".to_string(), true),
+            ]
+        );
+    }
+}
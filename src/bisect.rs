@@ -0,0 +1,126 @@
+use octocrab::Octocrab;
+use serde::{Deserialize, Serialize};
+
+use crate::{db::Db, github, handlers::Error};
+
+/// A bisection run with `!ddnetbot bisect` on an issue, between the last commit known to work
+/// and the first one known to be broken.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Session {
+    pub good: String,
+    pub bad: String,
+    /// The commit people were asked to test.
+    pub testing: String,
+}
+
+/// What to do next in a bisection.
+#[derive(Debug)]
+pub enum Step {
+    /// Test this commit, `remaining` commits are left and about `steps` more tests.
+    Test {
+        commit: Commit,
+        remaining: usize,
+        steps: u32,
+    },
+    /// This is the first broken commit.
+    Done(Commit),
+}
+
+#[derive(Debug, Deserialize)]
+struct Comparison {
+    total_commits: usize,
+    commits: Vec<Commit>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Commit {
+    pub sha: String,
+    pub html_url: String,
+    pub commit: CommitMessage,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CommitMessage {
+    pub message: String,
+}
+
+impl Commit {
+    /// The first line of the message.
+    pub fn title(&self) -> &str {
+        self.commit.message.lines().next().unwrap_or_default()
+    }
+}
+
+fn key(full_name: &str, number: u64) -> String {
+    format!("bisect:{}:{}", full_name.to_lowercase(), number)
+}
+
+/// The bisection on the issue, if one was started.
+pub fn session(db: &Db, full_name: &str, number: u64) -> Result<Option<Session>, Error> {
+    match db.get_kv(&key(full_name, number))? {
+        Some(x) => Ok(Some(serde_json::from_str(&x)?)),
+        None => Ok(None),
+    }
+}
+
+pub fn set_session(db: &Db, full_name: &str, number: u64, session: &Session) -> Result<(), Error> {
+    db.set_kv(&key(full_name, number), &serde_json::to_string(session)?)?;
+    Ok(())
+}
+
+pub fn end_session(db: &Db, full_name: &str, number: u64) -> Result<(), Error> {
+    db.remove_kv(&key(full_name, number))?;
+    Ok(())
+}
+
+/// The index of the commit to test among `total` commits after the good one, the last of them
+/// being the bad one. Only the first `listed` are known, GitHub lists at most 250.
+fn midpoint(total: usize, listed: usize) -> usize {
+    ((total - 1) / 2).min(listed.saturating_sub(1))
+}
+
+/// About how many tests are left to find the first broken one of `total` commits.
+fn steps(total: usize) -> u32 {
+    usize::BITS - (total.max(1) - 1).leading_zeros()
+}
+
+/// The next step between the good and the bad commit of the session.
+pub async fn next(client: &Octocrab, full_name: &str, session: &Session) -> Result<Step, Error> {
+    let (owner, name) = full_name
+        .split_once('/')
+        .ok_or("repository without owner")?;
+    let route = format!(
+        "/repos/{}/{}/compare/{}...{}",
+        owner, name, session.good, session.bad
+    );
+    let comparison: Comparison = github::retry(|| client.get(&route, None::<&()>)).await?;
+    let total = comparison.total_commits;
+    let mut listed = comparison.commits;
+    if total == 0 || listed.is_empty() {
+        return Err(format!("{} isn't after {}", session.bad, session.good).into());
+    }
+    if total == 1 {
+        return Ok(Step::Done(listed.remove(0)));
+    }
+    let index = midpoint(total, listed.len());
+    Ok(Step::Test {
+        commit: listed.swap_remove(index),
+        remaining: total,
+        steps: steps(total),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bisection_halves_the_range() {
+        assert_eq!(midpoint(2, 2), 0);
+        assert_eq!(midpoint(9, 9), 4);
+        assert_eq!(midpoint(1000, 250), 249);
+        assert_eq!(steps(1), 0);
+        assert_eq!(steps(2), 1);
+        assert_eq!(steps(9), 4);
+    }
+}
@@ -0,0 +1,134 @@
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use tokio::sync::Notify;
+use tracing::{debug, warn};
+
+use crate::handlers::Error;
+
+tokio::task_local! {
+    /// The delivery the running handler handles, and the handler.
+    static CURRENT: (Arc<Budget>, &'static str);
+}
+
+/// How many GitHub API calls each handler made, for `/metrics`.
+#[derive(Debug, Clone, Default)]
+pub struct ApiUsage {
+    calls: Arc<Mutex<BTreeMap<&'static str, u64>>>,
+}
+
+impl ApiUsage {
+    pub fn calls(&self) -> BTreeMap<&'static str, u64> {
+        self.calls.lock().unwrap().clone()
+    }
+}
+
+/// The GitHub API calls the handlers of one delivery may make together, so a handler stuck in
+/// a loop can't use up the rate limit of the installation.
+#[derive(Debug)]
+pub struct Budget {
+    delivery: String,
+    limit: usize,
+    calls: AtomicUsize,
+    exceeded: Notify,
+    usage: ApiUsage,
+}
+
+impl Budget {
+    pub fn new(limit: usize, delivery: &str, usage: &ApiUsage) -> Arc<Self> {
+        Arc::new(Self {
+            delivery: delivery.to_string(),
+            limit,
+            calls: AtomicUsize::new(0),
+            exceeded: Notify::new(),
+            usage: usage.clone(),
+        })
+    }
+
+    /// How many calls were made so far.
+    pub fn calls(&self) -> usize {
+        self.calls.load(Ordering::Relaxed)
+    }
+}
+
+/// Runs a handler on the budget, cancelling it with an error once it makes a call the budget
+/// has no room for.
+pub async fn run(
+    budget: &Arc<Budget>,
+    handler: &'static str,
+    fut: impl Future<Output = Result<(), Error>>,
+) -> Result<(), Error> {
+    CURRENT
+        .scope((budget.clone(), handler), async {
+            tokio::select! {
+                res = fut => res,
+                () = budget.exceeded.notified() => Err(format!(
+                    "used up the budget of {} GitHub API calls of the delivery",
+                    budget.limit
+                )
+                .into()),
+            }
+        })
+        .await
+}
+
+/// Counts a GitHub API call against the budget of the running handler, if there is one.
+/// Returns false if the call must not be made.
+pub fn record() -> bool {
+    CURRENT
+        .try_with(|(budget, handler)| {
+            *budget
+                .usage
+                .calls
+                .lock()
+                .unwrap()
+                .entry(handler)
+                .or_default() += 1;
+            let calls = budget.calls.fetch_add(1, Ordering::Relaxed) + 1;
+            debug!(
+                delivery = budget.delivery,
+                handler, calls, "GitHub API call"
+            );
+            if calls > budget.limit {
+                warn!(
+                    "Handler {} exceeded the budget of {} GitHub API calls",
+                    handler, budget.limit
+                );
+                budget.exceeded.notify_one();
+                return false;
+            }
+            true
+        })
+        // Background jobs have no budget.
+        .unwrap_or(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn handlers_over_budget_are_cancelled() {
+        let usage = ApiUsage::default();
+        let budget = Budget::new(2, "delivery", &usage);
+        let res = run(&budget, "greeter", async {
+            for _ in 0..3 {
+                if !record() {
+                    std::future::pending::<()>().await;
+                }
+            }
+            Ok(())
+        })
+        .await;
+        assert!(res.is_err());
+        assert_eq!(budget.calls(), 3);
+        assert_eq!(usage.calls()["greeter"], 3);
+        assert!(record());
+    }
+}
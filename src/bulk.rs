@@ -0,0 +1,158 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use octocrab::{Octocrab, Page};
+use serde::{Serialize, de::DeserializeOwned};
+use tracing::{info, warn};
+
+use crate::{AppState, github, handlers::Error};
+
+/// How many items are changed before the progress is saved.
+const CHUNK: usize = 20;
+/// The pause between two changes, GitHub asks for at least a second between mutating requests
+/// to stay clear of its secondary rate limits.
+const MIN_DELAY: Duration = Duration::from_secs(1);
+const MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// Where the bulk operations that ran since the start stand, for `/admin/bulk-operations`.
+#[derive(Debug, Clone, Default)]
+pub struct Operations {
+    progress: Arc<Mutex<BTreeMap<String, Progress>>>,
+}
+
+impl Operations {
+    pub fn progress(&self) -> BTreeMap<String, Progress> {
+        self.progress.lock().unwrap().clone()
+    }
+
+    fn update(&self, id: &str, f: impl FnOnce(&mut Progress)) {
+        if let Some(x) = self.progress.lock().unwrap().get_mut(id) {
+            f(x);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Progress {
+    pub started_at: DateTime<Utc>,
+    pub total: usize,
+    pub done: usize,
+    /// Items done before an interruption, skipped this time.
+    pub resumed: usize,
+    /// The current pause between two changes.
+    pub delay_ms: u64,
+    pub finished: bool,
+    pub error: Option<String>,
+}
+
+/// Every item of the pages starting at `page`, each page fetched through [`github::retry`].
+pub async fn pages<T: DeserializeOwned>(
+    client: &Octocrab,
+    mut page: Page<T>,
+) -> Result<Vec<T>, Error> {
+    let mut items = page.take_items();
+    while let Some(mut next) = github::retry(|| client.get_page::<T>(&page.next)).await? {
+        items.append(&mut next.take_items());
+        page = next;
+    }
+    Ok(items)
+}
+
+/// The pause after a change, doubled when GitHub pushed back with a secondary rate limit and
+/// slowly lowered again while it doesn't.
+fn next_delay(delay: Duration, limited: bool) -> Duration {
+    if limited {
+        (delay * 2).min(MAX_DELAY)
+    } else {
+        delay.mul_f64(0.9).max(MIN_DELAY)
+    }
+}
+
+/// Runs `f` on every item, pacing the changes to GitHub's secondary rate limits and saving the
+/// progress every [`CHUNK`] items. An operation with the same `id` that was interrupted, by a
+/// failure or a restart, resumes after the items it already did. Items are told apart by `key`.
+pub async fn run<T, K, F, Fut>(
+    state: &AppState,
+    id: &str,
+    items: &[T],
+    key: K,
+    mut f: F,
+) -> Result<(), Error>
+where
+    T: Clone,
+    K: Fn(&T) -> String,
+    F: FnMut(T) -> Fut,
+    Fut: Future<Output = Result<(), Error>>,
+{
+    let saved = format!("bulk:{}", id);
+    let mut done: BTreeSet<String> = match state.db.get_kv(&saved)? {
+        Some(x) => serde_json::from_str(&x)?,
+        None => BTreeSet::new(),
+    };
+    let todo: Vec<&T> = items.iter().filter(|x| !done.contains(&key(x))).collect();
+    if todo.len() < items.len() {
+        info!(
+            "Resuming {} after {} of {} items",
+            id,
+            items.len() - todo.len(),
+            items.len()
+        );
+    }
+    state.bulk.progress.lock().unwrap().insert(
+        id.to_string(),
+        Progress {
+            started_at: Utc::now(),
+            total: items.len(),
+            done: items.len() - todo.len(),
+            resumed: items.len() - todo.len(),
+            delay_ms: MIN_DELAY.as_millis() as u64,
+            finished: false,
+            error: None,
+        },
+    );
+
+    let mut delay = MIN_DELAY;
+    for chunk in todo.chunks(CHUNK) {
+        for item in chunk {
+            let limited = github::secondary_limits();
+            if let Err(e) = f((*item).clone()).await {
+                warn!("Bulk operation {} stopped: {}", id, e);
+                state.db.set_kv(&saved, &serde_json::to_string(&done)?)?;
+                state.bulk.update(id, |x| x.error = Some(e.to_string()));
+                return Err(e);
+            }
+            done.insert(key(item));
+            if !state.dry_run {
+                delay = next_delay(delay, github::secondary_limits() > limited);
+                tokio::time::sleep(delay).await;
+            }
+            state.bulk.update(id, |x| {
+                x.done += 1;
+                x.delay_ms = delay.as_millis() as u64;
+            });
+        }
+        state.db.set_kv(&saved, &serde_json::to_string(&done)?)?;
+    }
+    state.db.remove_kv(&saved)?;
+    state.bulk.update(id, |x| x.finished = true);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pacing_backs_off_and_recovers() {
+        let delay = next_delay(MIN_DELAY, true);
+        assert_eq!(delay, Duration::from_secs(2));
+        assert_eq!(next_delay(Duration::from_secs(50), true), MAX_DELAY);
+        assert!(next_delay(delay, false) < delay);
+        assert_eq!(next_delay(MIN_DELAY, false), MIN_DELAY);
+    }
+}
@@ -0,0 +1,263 @@
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::http::{HeaderMap, HeaderValue, StatusCode, header};
+use octocrab::{FromResponse, Octocrab};
+use serde::de::DeserializeOwned;
+
+/// Items per page of lists, the most GitHub allows.
+const PER_PAGE: usize = 100;
+
+#[derive(Debug, Clone)]
+struct Page<V> {
+    value: V,
+    etag: Option<HeaderValue>,
+}
+
+#[derive(Debug)]
+struct Entry<V> {
+    /// One for single resources.
+    pages: Vec<Page<V>>,
+    fetched: Instant,
+}
+
+#[derive(Debug)]
+struct Entries<K, V> {
+    map: HashMap<K, Entry<V>>,
+    /// Counts the invalidations, so a fetch that was running meanwhile doesn't bring back what
+    /// was invalidated.
+    generation: u64,
+}
+
+/// Caches GET responses for `ttl`, then revalidates them with `If-None-Match`.
+///
+/// Revalidations answered with `304 Not Modified` don't count against the rate limit. Lists are
+/// cached by page, each revalidated with its own ETag.
+#[derive(Debug)]
+pub struct EtagCache<K, V> {
+    entries: Arc<Mutex<Entries<K, V>>>,
+    ttl: Duration,
+}
+
+impl<K, V> Clone for EtagCache<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            entries: self.entries.clone(),
+            ttl: self.ttl,
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone, V: DeserializeOwned + Clone> EtagCache<K, V> {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            entries: Arc::new(Mutex::new(Entries {
+                map: HashMap::new(),
+                generation: 0,
+            })),
+            ttl,
+        }
+    }
+
+    /// Returns the cached value for `key`, fetching `route` if it's missing or stale.
+    pub async fn get(&self, client: &Octocrab, key: K, route: &str) -> octocrab::Result<V> {
+        let mut pages = self
+            .fetch(client, key, |_| route.to_string(), |_| true)
+            .await?;
+        Ok(pages.swap_remove(0).value)
+    }
+
+    /// Returns the cached pages for `key`, fetching the `route(n)` of every page from 0 until
+    /// `last` says one was the last if they're missing or stale.
+    async fn fetch(
+        &self,
+        client: &Octocrab,
+        key: K,
+        route: impl Fn(usize) -> String,
+        last: impl Fn(&V) -> bool,
+    ) -> octocrab::Result<Vec<Page<V>>> {
+        let (cached, generation) = {
+            let entries = self.entries.lock().unwrap();
+            match entries.map.get(&key) {
+                Some(entry) if entry.fetched.elapsed() < self.ttl => {
+                    return Ok(entry.pages.clone());
+                }
+                Some(entry) => (entry.pages.clone(), entries.generation),
+                None => (Vec::new(), entries.generation),
+            }
+        };
+
+        let mut pages = Vec::new();
+        loop {
+            // Only pages in hand are revalidated, so a 304 always has one to use even if the
+            // entry was invalidated meanwhile.
+            let cached = cached.get(pages.len());
+            let mut headers = HeaderMap::new();
+            if let Some(etag) = cached.and_then(|x| x.etag.clone()) {
+                headers.insert(header::IF_NONE_MATCH, etag);
+            }
+            let res = client
+                ._get_with_headers(route(pages.len()), Some(headers))
+                .await?;
+            let page = match cached {
+                Some(cached) if res.status() == StatusCode::NOT_MODIFIED => cached.clone(),
+                _ => {
+                    let res = octocrab::map_github_error(res).await?;
+                    let etag = res.headers().get(header::ETAG).cloned();
+                    Page {
+                        value: V::from_response(res).await?,
+                        etag,
+                    }
+                }
+            };
+            let done = last(&page.value);
+            pages.push(page);
+            if done {
+                break;
+            }
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        if entries.generation == generation {
+            entries.map.insert(
+                key,
+                Entry {
+                    pages: pages.clone(),
+                    fetched: Instant::now(),
+                },
+            );
+        }
+        Ok(pages)
+    }
+
+    pub fn invalidate(&self, key: &K) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.map.remove(key);
+        entries.generation += 1;
+    }
+}
+
+impl<K: Eq + Hash + Clone, T: DeserializeOwned + Clone> EtagCache<K, Vec<T>> {
+    /// Returns the cached list for `key`, fetching every page of `route` if it's missing or
+    /// stale.
+    pub async fn get_all(
+        &self,
+        client: &Octocrab,
+        key: K,
+        route: &str,
+    ) -> octocrab::Result<Vec<T>> {
+        let separator = if route.contains('?') { '&' } else { '?' };
+        let route = |page: usize| {
+            format!(
+                "{}{}per_page={}&page={}",
+                route,
+                separator,
+                PER_PAGE,
+                page + 1
+            )
+        };
+        let pages = self
+            .fetch(client, key, route, |x: &Vec<T>| x.len() < PER_PAGE)
+            .await?;
+        Ok(pages.into_iter().flat_map(|x| x.value).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{header, method, path, query_param},
+    };
+
+    use super::*;
+
+    fn client(github: &MockServer) -> Octocrab {
+        Octocrab::builder()
+            .base_uri(github.uri())
+            .unwrap()
+            .personal_token("ghp_test".to_string())
+            .build()
+            .unwrap()
+    }
+
+    fn labels(names: impl IntoIterator<Item = String>) -> serde_json::Value {
+        json!(
+            names
+                .into_iter()
+                .map(|x| json!({ "name": x }))
+                .collect::<Vec<_>>()
+        )
+    }
+
+    #[tokio::test]
+    async fn not_modified_lists_are_reused() {
+        let github = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repositories/1/labels"))
+            .and(header("If-None-Match", r#""v1""#))
+            .respond_with(ResponseTemplate::new(304))
+            .with_priority(1)
+            .expect(1)
+            .mount(&github)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repositories/1/labels"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(labels(["bug".to_string()]))
+                    .insert_header("ETag", r#""v1""#),
+            )
+            .expect(2)
+            .mount(&github)
+            .await;
+
+        let client = client(&github);
+        let cache: EtagCache<u64, Vec<serde_json::Value>> = EtagCache::new(Duration::ZERO);
+        for _ in 0..2 {
+            let labels = cache.get_all(&client, 1, "/repositories/1/labels").await;
+            assert_eq!(labels.unwrap(), [json!({ "name": "bug" })]);
+        }
+        // Invalidated ones are fetched again without an ETag.
+        cache.invalidate(&1);
+        cache
+            .get_all(&client, 1, "/repositories/1/labels")
+            .await
+            .unwrap();
+        assert!(cache.entries.lock().unwrap().map.contains_key(&1));
+    }
+
+    #[tokio::test]
+    async fn every_page_is_fetched() {
+        let github = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repositories/1/labels"))
+            .and(query_param("page", "2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(labels(["last".to_string()])))
+            .with_priority(1)
+            .mount(&github)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repositories/1/labels"))
+            .and(query_param("page", "1"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(labels((0..PER_PAGE).map(|x| x.to_string()))),
+            )
+            .mount(&github)
+            .await;
+
+        let cache: EtagCache<u64, Vec<serde_json::Value>> = EtagCache::new(Duration::ZERO);
+        let labels = cache
+            .get_all(&client(&github), 1, "/repositories/1/labels")
+            .await
+            .unwrap();
+        assert_eq!(labels.len(), PER_PAGE + 1);
+        assert_eq!(labels[PER_PAGE], json!({ "name": "last" }));
+    }
+}
@@ -0,0 +1,157 @@
+use std::{error::Error, path::PathBuf};
+
+use clap::{Parser, Subcommand};
+
+use tracing::{Instrument, info, warn};
+
+use crate::{
+    AppState,
+    config::Config,
+    db::Db,
+    handlers, labels, replay,
+    state::{Rules, app_key},
+};
+
+#[derive(Debug, Parser)]
+#[command(version, about = "A DDNet github issue/pr bot.")]
+pub struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Run the webhook server (the default).
+    Serve,
+    /// Validate the configuration and print the effective settings.
+    CheckConfig,
+    /// Feed saved deliveries through the handlers, e.g. to debug missed or failed ones.
+    Replay {
+        /// Recorded deliveries (`{"headers": {...}, "body": {...}}`), or bare bodies with `--event`.
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// The event name of bare bodies, as in the `X-GitHub-Event` header.
+        #[arg(long)]
+        event: Option<String>,
+        /// Only log the changes that would be made on GitHub.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Create missing labels and fix the colors and descriptions of the others in every repo,
+    /// as defined in the config, and list the labels that aren't defined.
+    SyncLabels {
+        /// Only log the changes that would be made on GitHub.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// List the deliveries handlers failed on.
+    DeadLetters,
+    /// Run the handlers of dead letters again, e.g. after fixing what made them fail.
+    Redrive {
+        /// The dead letters to run, as listed by `dead-letters`.
+        #[arg(required_unless_present = "all")]
+        ids: Vec<i64>,
+        /// Run every dead letter.
+        #[arg(long, conflicts_with = "ids")]
+        all: bool,
+    },
+}
+
+impl Cli {
+    pub async fn run(self) -> Result<(), Box<dyn Error>> {
+        dotenvy::dotenv().ok();
+        let config = match Config::load() {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        };
+
+        match self.command.unwrap_or(Command::Serve) {
+            Command::Serve => crate::run(config).await?,
+            Command::CheckConfig => {
+                if config.github_token.is_none() {
+                    app_key(&config)?;
+                }
+                Rules::from_config(&config)?;
+                println!("{:#?}", config.redacted());
+            }
+            Command::Replay {
+                files,
+                event,
+                dry_run,
+            } => {
+                crate::init_logging(config.log_format);
+                let state = AppState {
+                    dry_run: dry_run || config.dry_run,
+                    ..AppState::from_config(&config)?
+                };
+                replay::replay(state, &files, event.as_deref()).await;
+            }
+            Command::SyncLabels { dry_run } => {
+                crate::init_logging(config.log_format);
+                let state = AppState {
+                    dry_run: dry_run || config.dry_run,
+                    ..AppState::from_config(&config)?
+                };
+                for (repo, report) in labels::sync_all(&state).await.map_err(|e| e.to_string())? {
+                    println!(
+                        "{}: created {:?}, updated {:?}, unknown {:?}",
+                        repo, report.created, report.updated, report.unknown
+                    );
+                }
+            }
+            Command::DeadLetters => {
+                let db = Db::open(&config.database_path)?;
+                for letter in db.dead_letters()? {
+                    println!(
+                        "{}\t{}\t{}\t{} ({})\t{}",
+                        letter.id,
+                        letter.created_at,
+                        letter.handler,
+                        letter.job.event,
+                        letter.job.delivery,
+                        letter.error
+                    );
+                }
+            }
+            Command::Redrive { ids, all } => {
+                crate::init_logging(config.log_format);
+                let state = AppState::from_config(&config)?;
+                let letters = if all {
+                    state.db.dead_letters()?
+                } else {
+                    let mut letters = Vec::new();
+                    for id in ids {
+                        letters.push(
+                            state
+                                .db
+                                .dead_letter(id)?
+                                .ok_or_else(|| format!("no dead letter {}", id))?,
+                        );
+                    }
+                    letters
+                };
+
+                let mut failed = 0;
+                for letter in &letters {
+                    let span = handlers::delivery_span(&letter.job.delivery, &letter.job.event);
+                    match handlers::redrive(&state, letter).instrument(span).await {
+                        Ok(()) => info!("Redrove dead letter {}", letter.id),
+                        Err(e) => {
+                            warn!("Dead letter {} failed again: {}", letter.id, e);
+                            failed += 1;
+                        }
+                    }
+                }
+                if failed > 0 {
+                    return Err(
+                        format!("{} of {} dead letters failed", failed, letters.len()).into(),
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,1901 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+};
+
+use async_trait::async_trait;
+use chrono::Utc;
+use octocrab::models::{
+    self,
+    webhook_events::{
+        WebhookEvent, WebhookEventPayload, WebhookEventType,
+        payload::{IssueCommentWebhookEventAction, IssueCommentWebhookEventPayload},
+    },
+};
+use rand::seq::IndexedRandom;
+use serde_json::json;
+use tracing::{Instrument, info, info_span, warn};
+
+use crate::{
+    Config, batch,
+    bisect::{self, Session, Step},
+    config::{CommandPermission, SecurityAlertsConfig, SummaryLlmConfig},
+    db::{Freeze, Reminder},
+    freeze, github,
+    handlers::{
+        ADOPTABLE_LABEL, Adoptions, Area, Cla, Context, Error, EventHandler, Faq, Protocol, Todos,
+        escalate_report, label_definitions,
+    },
+    labels, milestones, reminders, reviews,
+    summary::{self, Post},
+    triage, usage,
+    workflow::{self, Transition},
+};
+
+/// The commands anyone may run on the issues and PRs of others.
+const OPEN_COMMANDS: &[&str] = &[
+    "adopt",
+    "bisect",
+    "claim",
+    "next",
+    "subscribe",
+    "summarize",
+    "unclaim",
+    "unsubscribe",
+];
+
+/// The names of every command, see [`Command::parse`]. Usage is only recorded for these.
+pub const NAMES: &[&str] = &[
+    "ack-protocol",
+    "adopt",
+    "author",
+    "bisect",
+    "cla",
+    "claim",
+    "delegate",
+    "draft",
+    "follow-up",
+    "freeze",
+    "hide",
+    "label",
+    "milestone-status",
+    "next",
+    "ping-team",
+    "port-to",
+    "ready",
+    "relabel",
+    "remind",
+    "reviewers",
+    "roulette",
+    "search",
+    "security",
+    "snooze",
+    "solved",
+    "stats",
+    "status",
+    "subscribe",
+    "summarize",
+    "todo-issues",
+    "unclaim",
+    "undelegate",
+    "unfreeze",
+    "unsubscribe",
+];
+
+/// How many issues `!ddnetbot search` lists.
+const SEARCH_RESULTS: u8 = 5;
+
+/// Runs the `!ddnetbot` commands in issue and PR comments.
+#[derive(Debug)]
+pub struct Commands {
+    /// Other names of commands, e.g. `take` for `claim`.
+    aliases: HashMap<String, String>,
+    /// Aliases of single repos by lowercase `owner/name`, over the ones of every repo.
+    repo_aliases: HashMap<String, HashMap<String, String>>,
+    /// Who may run a command, by command name.
+    permissions: HashMap<String, CommandPermission>,
+    /// Permissions of single repos by lowercase `owner/name`, over the ones of every repo.
+    repo_permissions: HashMap<String, HashMap<String, CommandPermission>>,
+    /// How many days the author of a PR has to be inactive before it can be adopted.
+    adopt_after_days: u64,
+    /// Whether `label` takes an unknown label to mean the only similar one.
+    label_autocorrect: bool,
+    /// Where `security` tells the maintainers.
+    security_alerts: SecurityAlertsConfig,
+    /// What `summarize` asks too, if anything.
+    summary_llm: Option<SummaryLlmConfig>,
+    http: reqwest::Client,
+}
+
+impl Commands {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            aliases: config.command_aliases.clone(),
+            repo_aliases: config
+                .repos
+                .iter()
+                .map(|(repo, x)| (repo.to_ascii_lowercase(), x.aliases.clone()))
+                .collect(),
+            permissions: config.command_permissions.clone(),
+            repo_permissions: config
+                .repos
+                .iter()
+                .map(|(repo, x)| (repo.to_ascii_lowercase(), x.permissions.clone()))
+                .collect(),
+            adopt_after_days: config.adopt_after_days,
+            label_autocorrect: config.label_autocorrect,
+            security_alerts: config.security_alerts.clone(),
+            summary_llm: config.summary_llm.clone(),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// Whether someone with `roles` may run the command, always if it has no permission.
+    async fn permitted(
+        &self,
+        ctx: &Context,
+        repo: &models::Repository,
+        command: &str,
+        roles: &[&str],
+        login: &str,
+    ) -> Result<bool, Error> {
+        let full_name = repo.full_name.as_deref().unwrap_or_default();
+        let Some(permission) = self
+            .repo_permissions
+            .get(&full_name.to_ascii_lowercase())
+            .and_then(|x| x.get(command))
+            .or_else(|| self.permissions.get(command))
+        else {
+            return Ok(true);
+        };
+        if permission.allows(roles, login) {
+            return Ok(true);
+        }
+        let Some(owner) = &repo.owner else {
+            return Ok(false);
+        };
+        for team in &permission.teams {
+            let roles = &ctx.state.roles;
+            if roles
+                .in_team(&ctx.client, ctx.installation, &owner.login, team, login)
+                .await?
+            {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// The command `line` runs, with an alias at its start replaced by the command.
+    fn resolve<'a>(&self, repo: &str, line: &'a str) -> Cow<'a, str> {
+        let (name, args) = line.split_once(' ').unwrap_or((line, ""));
+        let command = self
+            .repo_aliases
+            .get(&repo.to_ascii_lowercase())
+            .and_then(|x| x.get(name))
+            .or_else(|| self.aliases.get(name));
+        match command {
+            Some(command) => Cow::Owned(format!("{} {}", command, args)),
+            None => Cow::Borrowed(line),
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for Commands {
+    fn name(&self) -> &'static str {
+        "commands"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::IssueComment, WebhookEventType::Label]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let Some(repo) = &event.repository else {
+            return Ok(());
+        };
+        match &event.specific {
+            // The label command validates against the cached repo labels.
+            WebhookEventPayload::Label(_) => {
+                ctx.state.repo_labels.invalidate(&repo.id);
+                Ok(())
+            }
+            WebhookEventPayload::IssueComment(payload) => {
+                handle_comment(self, ctx, repo, payload).await
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The commands of a comment, without the `!ddnetbot`. Lines in fenced code blocks and quotes
+/// are skipped, they're usually docs or someone else's comment.
+fn command_lines(body: &str) -> Vec<&str> {
+    let mut lines = Vec::new();
+    let mut fence: Option<&str> = None;
+    for line in body.lines() {
+        let trimmed = line.trim_start();
+        let marker = ["```", "~~~"].into_iter().find(|x| trimmed.starts_with(x));
+        match (fence, marker) {
+            (None, Some(marker)) => fence = Some(marker),
+            (Some(open), Some(marker)) if open == marker => fence = None,
+            (None, None) => {
+                if let Some(command) = line.strip_prefix("!ddnetbot") {
+                    lines.push(command.trim_start());
+                }
+            }
+            _ => {}
+        }
+    }
+    lines
+}
+
+/// The commands that aren't in `previous`, a command that was there twice and is there three
+/// times now runs once.
+fn new_commands<'a>(commands: Vec<&'a str>, mut previous: Vec<&str>) -> Vec<&'a str> {
+    commands
+        .into_iter()
+        .filter(|x| match previous.iter().position(|p| p == x) {
+            Some(i) => {
+                previous.swap_remove(i);
+                false
+            }
+            None => true,
+        })
+        .collect()
+}
+
+/// A command of a comment, parsed from the line after `!ddnetbot`.
+#[derive(Debug, PartialEq)]
+enum Command<'a> {
+    Claim,
+    Unclaim,
+    Ready,
+    Author,
+    Delegate(&'a str),
+    Undelegate(&'a str),
+    /// The stats of the user, or of the commenter without one.
+    Stats(Option<&'a str>),
+    Draft,
+    AcceptCla,
+    AckProtocol,
+    Hide {
+        url: &'a str,
+        classifier: &'static str,
+    },
+    Relabel {
+        old: &'a str,
+        new: &'a str,
+    },
+    /// Opens the issue in another repo of the org, `repo` with or without the org.
+    PortTo {
+        repo: &'a str,
+        close: bool,
+    },
+    FollowUp(&'a str),
+    Adopt,
+    TodoIssues,
+    Solved,
+    Remind {
+        after: chrono::Duration,
+        message: &'a str,
+    },
+    Snooze(chrono::Duration),
+    Search(&'a str),
+    PingTeam(&'a str),
+    Roulette,
+    Freeze {
+        branch: &'a str,
+        until: &'a str,
+        exempt_labels: Vec<&'a str>,
+    },
+    Unfreeze(&'a str),
+    Security,
+    Status,
+    /// The status of the milestone, or of the upcoming one without a title.
+    MilestoneStatus(Option<&'a str>),
+    BisectStart {
+        good: &'a str,
+        bad: &'a str,
+    },
+    /// The result for the commit being tested, if its SHA starts with `sha`.
+    BisectResult {
+        good: bool,
+        sha: Option<&'a str>,
+    },
+    Summarize,
+    Next,
+    Subscribe(Vec<&'a str>),
+    Unsubscribe(Vec<&'a str>),
+    Reviewers,
+    /// The `+name` and `-name` items, see [`label_args`].
+    Label(&'a str),
+}
+
+impl<'a> Command<'a> {
+    /// The command `line` runs, if it's one and has the arguments it needs.
+    fn parse(line: &'a str) -> Option<Self> {
+        fn non_empty(x: &str) -> Option<&str> {
+            (!x.is_empty()).then_some(x)
+        }
+
+        let (name, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let args = args.trim();
+        let user = args.trim_start_matches('@');
+        let mut words = args.split_ascii_whitespace();
+        Some(match name {
+            "claim" => Self::Claim,
+            "unclaim" => Self::Unclaim,
+            "ready" => Self::Ready,
+            "author" => Self::Author,
+            "delegate" => Self::Delegate(non_empty(user)?),
+            "undelegate" => Self::Undelegate(non_empty(user)?),
+            "stats" => Self::Stats(non_empty(user)),
+            "draft" => Self::Draft,
+            "cla" if args == "accept" => Self::AcceptCla,
+            "ack-protocol" => Self::AckProtocol,
+            "hide" => {
+                let (url, reason) = args.split_once(' ').unwrap_or((args, ""));
+                Self::Hide {
+                    url: non_empty(url)?,
+                    classifier: hide_classifier(reason.trim())?,
+                }
+            }
+            "relabel" => Self::Relabel {
+                old: words.next()?,
+                new: words.next()?,
+            },
+            "port-to" => Self::PortTo {
+                repo: words.next()?,
+                close: words.next() == Some("close"),
+            },
+            "follow-up" => Self::FollowUp(non_empty(args)?),
+            "adopt" => Self::Adopt,
+            "todo-issues" => Self::TodoIssues,
+            "solved" => Self::Solved,
+            "remind" => {
+                let (duration, message) = args.split_once(' ').unwrap_or((args, ""));
+                Self::Remind {
+                    after: reminders::parse_duration(duration)?,
+                    message: message.trim(),
+                }
+            }
+            "snooze" => Self::Snooze(reminders::parse_duration(args)?),
+            "search" => Self::Search(non_empty(args)?),
+            "ping-team" => Self::PingTeam(non_empty(user)?),
+            "roulette" => Self::Roulette,
+            "freeze" => Self::Freeze {
+                branch: words.next()?,
+                until: words.next()?,
+                exempt_labels: words.collect(),
+            },
+            "unfreeze" => Self::Unfreeze(non_empty(args)?),
+            "security" => Self::Security,
+            "status" if args.is_empty() => Self::Status,
+            "milestone-status" => Self::MilestoneStatus(non_empty(args)),
+            "bisect" => match (words.next(), words.next(), words.next()) {
+                (Some("start"), Some(good), Some(bad)) => Self::BisectStart { good, bad },
+                (Some(result @ ("good" | "bad")), sha, None) => Self::BisectResult {
+                    good: result == "good",
+                    sha,
+                },
+                _ => return None,
+            },
+            "summarize" => Self::Summarize,
+            "next" => Self::Next,
+            "subscribe" => Self::Subscribe(words.collect()),
+            "unsubscribe" => Self::Unsubscribe(words.collect()),
+            "reviewers" => Self::Reviewers,
+            "label" => Self::Label(args),
+            _ => return None,
+        })
+    }
+
+    /// Whether the command only changes labels or assignees, see [`Invocation::apply_batch`].
+    fn batchable(&self) -> bool {
+        matches!(
+            self,
+            Self::Claim | Self::Unclaim | Self::Ready | Self::Author | Self::Label(_)
+        )
+    }
+}
+
+async fn handle_comment(
+    commands: &Commands,
+    ctx: &Context,
+    repo: &models::Repository,
+    payload: &IssueCommentWebhookEventPayload,
+) -> Result<(), Error> {
+    let state = &ctx.state;
+    let client = &ctx.client;
+    // Roles are only looked up for comments with commands.
+    let Some(body) = payload
+        .comment
+        .body
+        .as_deref()
+        .filter(|x| !command_lines(x).is_empty())
+    else {
+        return Ok(());
+    };
+    let privilege_level = state
+        .roles
+        .role(
+            client,
+            ctx.installation,
+            repo,
+            &payload.comment.user.login,
+            &payload.comment.author_association,
+        )
+        .await?
+        .privilege_level();
+    // Delegates run commands on their issue or PR like collaborators, but can't delegate.
+    let delegate = privilege_level == 0
+        && state
+            .db
+            .is_delegate(repo.id, payload.issue.number, &payload.comment.user.login)?;
+    let privilege_level = if delegate { 1 } else { privilege_level };
+
+    // Without a role, only some commands run on the issues and PRs of others.
+    let outsider = privilege_level == 0 && payload.comment.user.id != payload.issue.user.id;
+
+    info!("comment: {:?}", body);
+    let full_name = repo.full_name.as_deref().unwrap_or_default();
+
+    // Only the commands that didn't run for the comment yet run, so an edited comment runs
+    // e.g. the one whose typo was fixed, and a redelivered one none.
+    let ran_key = format!("commands_ran:{}:{}", repo.id, payload.comment.id);
+    let ran: Option<Vec<String>> = state
+        .db
+        .get_kv(&ran_key)?
+        .and_then(|x| serde_json::from_str(&x).ok());
+    let mut previous: Vec<String> = match (ran, &payload.action) {
+        (
+            Some(ran),
+            IssueCommentWebhookEventAction::Created | IssueCommentWebhookEventAction::Edited,
+        ) => ran,
+        (None, IssueCommentWebhookEventAction::Created) => Vec::new(),
+        // Comments from before the commands that ran were recorded.
+        (None, IssueCommentWebhookEventAction::Edited) => payload
+            .changes
+            .as_ref()
+            .map(|x| command_lines(&x.body.from))
+            .unwrap_or_default()
+            .into_iter()
+            .map(String::from)
+            .collect(),
+        _ => return Ok(()),
+    };
+    let mut roles = Vec::new();
+    if payload.comment.user.id == payload.issue.user.id {
+        roles.push("author");
+    }
+    if delegate {
+        roles.push("delegate");
+    } else if privilege_level >= 1 {
+        roles.push("collaborator");
+    }
+    if privilege_level == 2 {
+        roles.push("member");
+    }
+    // Commands the commenter isn't permitted to run are dropped before any of them runs.
+    let login = &payload.comment.user.login;
+    let mut lines: Vec<(&str, Cow<str>)> = Vec::new();
+    let new = new_commands(
+        command_lines(body),
+        previous.iter().map(String::as_str).collect(),
+    );
+    let mut record_ran = |line: &str| {
+        previous.push(line.to_string());
+        state
+            .db
+            .set_kv(&ran_key, &serde_json::to_string(&previous)?)?;
+        Ok::<_, Error>(())
+    };
+    for raw in new {
+        let line = commands.resolve(full_name, raw);
+        let command = line.split_ascii_whitespace().next().unwrap_or_default();
+        if outsider && !OPEN_COMMANDS.contains(&command) {
+            continue;
+        }
+        if commands
+            .permitted(ctx, repo, command, &roles, login)
+            .await?
+        {
+            lines.push((raw, line));
+        } else {
+            info!("{} is not permitted to run {}", login, command);
+        }
+    }
+
+    let invocation = Invocation {
+        commands,
+        ctx,
+        repo,
+        payload,
+        full_name,
+        privilege_level,
+        delegate,
+        outsider,
+    };
+    let parsed: Vec<(&str, &str, Option<Command>)> = lines
+        .iter()
+        .map(|(raw, line)| (*raw, line.as_ref(), Command::parse(line)))
+        .collect();
+    let batchable = |x: &Option<Command>| x.as_ref().is_some_and(Command::batchable);
+
+    // Several label and assignee commands are checked first and then applied together.
+    let batch: Vec<&str> = parsed
+        .iter()
+        .filter(|(_, _, x)| batchable(x))
+        .map(|(_, line, _)| *line)
+        .collect();
+    let batched = batch.len() > 1;
+    if batched {
+        if !invocation.within_rate_limit().await {
+            return Ok(());
+        }
+        let span = info_span!("commands", user = login.as_str());
+        let res = invocation.apply_batch(&batch).instrument(span).await;
+        for line in &batch {
+            usage::record(&state.db, full_name, line, login, res.is_ok());
+        }
+        res?;
+        for (raw, _, command) in &parsed {
+            if batchable(command) {
+                record_ran(raw)?;
+            }
+        }
+    }
+
+    for (raw, line, command) in parsed {
+        if batched && batchable(&command) {
+            continue;
+        }
+        if !invocation.within_rate_limit().await {
+            break;
+        }
+        let name = line.split_ascii_whitespace().next().unwrap_or_default();
+        let span = info_span!("command", command = name, user = login.as_str());
+        let res = match command {
+            Some(command) => invocation.run(line, command).instrument(span).await,
+            None => Ok(false),
+        };
+        // Lines that aren't commands and commands that don't apply here aren't uses.
+        if !matches!(res, Ok(false)) {
+            usage::record(&state.db, full_name, line, login, res.is_ok());
+        }
+        res?;
+        record_ran(raw)?;
+    }
+    Ok(())
+}
+
+/// A comment running commands, with who wrote it.
+struct Invocation<'a> {
+    commands: &'a Commands,
+    ctx: &'a Context,
+    repo: &'a models::Repository,
+    payload: &'a IssueCommentWebhookEventPayload,
+    full_name: &'a str,
+    /// 0 without a role, 1 for collaborators and delegates, 2 for members.
+    privilege_level: u8,
+    /// Whether the commenter runs commands as a delegate of the issue or PR.
+    delegate: bool,
+    /// Whether the commenter has no role and isn't the author.
+    outsider: bool,
+}
+
+impl<'a> Invocation<'a> {
+    /// The issue or PR the comment is on.
+    fn number(&self) -> u64 {
+        self.payload.issue.number
+    }
+
+    /// The commenter.
+    fn user(&self) -> &'a str {
+        &self.payload.comment.user.login
+    }
+
+    fn is_pr(&self) -> bool {
+        self.payload.issue.pull_request.is_some()
+    }
+
+    fn owner(&self) -> Result<&'a str, Error> {
+        let owner = self.repo.owner.as_ref().ok_or("repository without owner")?;
+        Ok(&owner.login)
+    }
+
+    /// The PR the comment is on.
+    async fn pull(&self) -> Result<models::pulls::PullRequest, Error> {
+        let pulls = self.ctx.client.pulls(self.owner()?, &self.repo.name);
+        Ok(github::retry(|| pulls.get(self.number())).await?)
+    }
+
+    fn audit(&self, action: &str, detail: &str) -> Result<(), Error> {
+        self.ctx.state.db.audit(
+            self.full_name,
+            Some(self.number()),
+            self.user(),
+            action,
+            detail,
+        )?;
+        Ok(())
+    }
+
+    /// Replies on the issue or PR with the template. A reply that can't be rendered or posted
+    /// is only logged, what the command changed stays changed.
+    async fn reply(&self, template: &str, context: serde_json::Value) {
+        let number = self.number();
+        let res = match self
+            .ctx
+            .rules
+            .templates
+            .render(self.full_name, template, context)
+        {
+            Ok(comment) => {
+                let (state, client) = (&self.ctx.state, &self.ctx.client);
+                batch::comment(state, client, self.repo.id, number, &comment).await
+            }
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = res {
+            warn!("Failed to reply {} on #{}: {}", template, number, e);
+        }
+    }
+
+    /// Whether the commenter may run another command. People without a role are limited, and
+    /// told how long to wait.
+    async fn within_rate_limit(&self) -> bool {
+        if self.privilege_level > 0 {
+            return true;
+        }
+        let Err(wait) = self
+            .ctx
+            .state
+            .command_limiter
+            .check(self.payload.comment.user.id)
+        else {
+            return true;
+        };
+        let minutes = wait.as_secs().div_ceil(60);
+        self.reply(
+            "rate_limited",
+            json!({ "user": self.user(), "minutes": minutes }),
+        )
+        .await;
+        false
+    }
+
+    /// The label of the issue only collaborators may claim it with, if it has one.
+    fn maintainer_only_label(&self) -> Option<&'a str> {
+        let labels = &self.ctx.state.maintainer_only_labels;
+        self.payload
+            .issue
+            .labels
+            .iter()
+            .find(|x| labels.contains(&x.name))
+            .map(|x| x.name.as_str())
+    }
+
+    async fn assign(&self, login: &str, assign: bool) -> Result<(), Error> {
+        let issues = self.ctx.client.issues_by_id(self.repo.id);
+        let (number, assignees) = (self.number(), [login]);
+        let dry_run = self.ctx.state.dry_run;
+        if assign {
+            github::mutate(
+                dry_run,
+                format!("assign {:?} to #{}", assignees, number),
+                || issues.add_assignees(number, &assignees),
+            )
+            .await?;
+        } else {
+            github::mutate(
+                dry_run,
+                format!("unassign {:?} from #{}", assignees, number),
+                || issues.remove_assignees(number, &assignees),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        let issues = self.ctx.client.issues_by_id(self.repo.id);
+        let number = self.number();
+        github::mutate(self.ctx.state.dry_run, format!("close #{}", number), || {
+            issues
+                .update(number)
+                .state(models::IssueState::Closed)
+                .send()
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Runs a command that isn't applied in a batch. `line` is what the command was parsed from.
+    /// `false` if the command doesn't apply here or the commenter doesn't have the role for it.
+    async fn run(&self, line: &str, command: Command<'_>) -> Result<bool, Error> {
+        let state = &self.ctx.state;
+        let client = &self.ctx.client;
+        let (repo, full_name, number) = (self.repo, self.full_name, self.number());
+        let args = line
+            .split_once(char::is_whitespace)
+            .map_or("", |x| x.1.trim());
+        let res = match command {
+            Command::Claim => {
+                if self.privilege_level == 0
+                    && let Some(label) = self.maintainer_only_label()
+                {
+                    self.reply(
+                        "claim_refused",
+                        json!({ "user": self.user(), "label": label }),
+                    )
+                    .await;
+                    return Ok(true);
+                }
+                self.assign(self.user(), true).await?;
+                self.audit("claim", "")
+            }
+            Command::Unclaim => {
+                self.assign(self.user(), false).await?;
+                self.audit("unclaim", "")
+            }
+            Command::Ready => {
+                if self.is_pr() && self.pull().await?.draft == Some(true) {
+                    self.reply("draft_not_ready", json!({ "user": self.user() }))
+                        .await;
+                    return Ok(true);
+                }
+                if self.transition("ready", Transition::RequestReviews).await? {
+                    self.audit("ready", "")?;
+                }
+                Ok(())
+            }
+            Command::Author => {
+                if self
+                    .transition("author", Transition::RequestChanges)
+                    .await?
+                {
+                    self.audit("author", "")?;
+                }
+                Ok(())
+            }
+            Command::Delegate(user) if self.privilege_level > 0 && !self.delegate => {
+                state.db.add_delegate(repo.id, number, user, self.user())?;
+                self.audit("delegate", user)
+            }
+            Command::Undelegate(user) if self.privilege_level > 0 && !self.delegate => {
+                state.db.remove_delegate(repo.id, number, user)?;
+                self.audit("undelegate", user)
+            }
+            Command::Stats(user) => {
+                let user = user.unwrap_or(self.user());
+                let stats = state.db.user_stats(user)?;
+                self.reply(
+                    "stats",
+                    json!({
+                        "user": user,
+                        "merged_prs": stats.merged_prs,
+                        "reviews": stats.reviews,
+                        "triage": stats.triage,
+                    }),
+                )
+                .await;
+                Ok(())
+            }
+            Command::Draft if self.is_pr() => self.draft().await,
+            Command::AcceptCla if state.cla.is_some() => {
+                state.db.accept_cla(self.user())?;
+                self.audit("cla", "accept")?;
+                // Turn the check green right away on the author's PR.
+                if self.is_pr() && self.payload.comment.user.id == self.payload.issue.user.id {
+                    let pr = self.pull().await?;
+                    Cla::check(self.ctx, repo, number, self.user(), &pr.head.sha).await?;
+                }
+                Ok(())
+            }
+            Command::AckProtocol if self.is_pr() && self.privilege_level == 2 => {
+                state.db.set_kv(&Protocol::ack_key(repo.id, number), "1")?;
+                self.audit("ack-protocol", "")
+            }
+            Command::Hide { url, classifier } if self.privilege_level == 2 => {
+                self.hide(url, classifier).await
+            }
+            Command::Relabel { old, new } if self.privilege_level == 2 => {
+                self.relabel(old, new).await
+            }
+            Command::PortTo { repo, close } if self.privilege_level > 0 && !self.is_pr() => {
+                self.port_to(repo, close).await
+            }
+            Command::FollowUp(title) if self.is_pr() => self.follow_up(title).await,
+            Command::Adopt if self.is_pr() => self.adopt().await,
+            Command::TodoIssues if self.is_pr() => {
+                let is_author = self.payload.issue.user.login == self.user();
+                if self.privilege_level == 0 && !is_author {
+                    return Ok(true);
+                }
+                let pull = self.pull().await?;
+                let count = Todos::open_issues(self.ctx, repo, number, &pull.head.sha).await?;
+                self.reply("todo_issues", json!({ "count": count })).await;
+                self.audit("todo-issues", &count.to_string())
+            }
+            // The author confirms that the FAQ answer helped.
+            Command::Solved if !self.is_pr() => {
+                if state
+                    .db
+                    .get_kv(&Faq::answered_key(repo.id, number))?
+                    .is_none()
+                {
+                    return Ok(true);
+                }
+                self.close().await?;
+                self.audit("solved", "")
+            }
+            Command::Remind { after, message } => {
+                state.db.add_reminder(&Reminder {
+                    id: 0,
+                    installation: self.ctx.installation,
+                    repo: repo.id,
+                    full_name: full_name.to_string(),
+                    number,
+                    login: self.user().to_string(),
+                    message: message.to_string(),
+                    due_at: Utc::now() + after,
+                    label: None,
+                })?;
+                self.audit("remind", args)
+            }
+            // Takes the issue out of triage for a while, the reminder loop puts it back.
+            Command::Snooze(after) if self.privilege_level > 0 && !self.is_pr() => {
+                let label = &state.snooze_label;
+                if !self.payload.issue.labels.iter().any(|x| &x.name == label) {
+                    return Ok(true);
+                }
+                batch::remove_label(state, client, repo.id, number, label).await?;
+                state.db.add_reminder(&Reminder {
+                    id: 0,
+                    installation: self.ctx.installation,
+                    repo: repo.id,
+                    full_name: full_name.to_string(),
+                    number,
+                    login: self.user().to_string(),
+                    message: String::new(),
+                    due_at: Utc::now() + after,
+                    label: Some(label.clone()),
+                })?;
+                self.audit("snooze", args)
+            }
+            Command::Search(terms) => self.search(terms).await,
+            // Mentions of teams by the app don't notify their members.
+            Command::PingTeam(team) if self.privilege_level > 0 => self.ping_team(team).await,
+            // For code without clear owners, the review rules cover the rest.
+            Command::Roulette if self.is_pr() => self.roulette().await,
+            Command::Freeze {
+                branch,
+                until,
+                exempt_labels,
+            } if self.privilege_level == 2 => {
+                let Some(until) = freeze::parse_until(until, Utc::now()) else {
+                    return Ok(true);
+                };
+                let freeze = Freeze {
+                    full_name: full_name.to_string(),
+                    branch: branch.to_string(),
+                    until,
+                    exempt_labels: exempt_labels.into_iter().map(String::from).collect(),
+                };
+                state.db.set_freeze(&freeze, self.user())?;
+                self.reply(
+                    "branch_frozen",
+                    json!({
+                        "branch": branch,
+                        "until": until.format("%Y-%m-%d %H:%M UTC").to_string(),
+                        "exempt": freeze.exempt_labels,
+                    }),
+                )
+                .await;
+                self.audit("freeze", args)
+            }
+            Command::Unfreeze(branch) if self.privilege_level == 2 => {
+                let Some(frozen) = state.db.freeze(full_name, branch)? else {
+                    return Ok(true);
+                };
+                freeze::lift(state, client, &frozen).await?;
+                self.reply("branch_frozen", json!({ "branch": branch }))
+                    .await;
+                self.audit("unfreeze", branch)
+            }
+            Command::Security if !self.is_pr() && repo.private != Some(true) => {
+                escalate_report(
+                    self.ctx,
+                    &self.commands.security_alerts,
+                    &self.commands.http,
+                    repo,
+                    &self.payload.issue,
+                    *self.payload.comment.id,
+                    self.privilege_level > 0,
+                )
+                .await?;
+                self.audit("security", "")
+            }
+            Command::Status => {
+                self.audit("status", "")?;
+                let status = bot_status(self.ctx, full_name).await?;
+                self.reply("bot_status", status).await;
+                Ok(())
+            }
+            Command::MilestoneStatus(title) => self.milestone_status(title).await,
+            Command::BisectStart { good, bad } if !self.outsider => {
+                let session = Session {
+                    good: good.to_string(),
+                    bad: bad.to_string(),
+                    testing: String::new(),
+                };
+                self.bisect(line, session).await
+            }
+            Command::BisectResult { good, sha } => {
+                let Some(mut session) = bisect::session(&state.db, full_name, number)? else {
+                    return Ok(true);
+                };
+                // A result for a commit tested earlier came too late.
+                if sha.is_some_and(|x| !session.testing.starts_with(x)) {
+                    return Ok(true);
+                }
+                if good {
+                    session.good = session.testing.clone();
+                } else {
+                    session.bad = session.testing.clone();
+                }
+                self.bisect(line, session).await
+            }
+            Command::Summarize => self.summarize().await,
+            Command::Next => {
+                let areas = triage::areas(&state.triage_areas, self.user());
+                let issues: Vec<_> = triage::next_issues(client, full_name, areas)
+                    .await?
+                    .iter()
+                    .map(|x| json!({ "number": x.number, "title": x.title }))
+                    .collect();
+                self.reply(
+                    "next_issues",
+                    json!({ "user": self.user(), "areas": areas, "issues": issues }),
+                )
+                .await;
+                Ok(())
+            }
+            Command::Subscribe(areas) => self.subscribe(true, &areas).await,
+            Command::Unsubscribe(areas) => self.subscribe(false, &areas).await,
+            Command::Reviewers => {
+                let reviewers = reviews::workload(&state.db, state.max_review_load)?;
+                self.reply("reviewers", json!({ "reviewers": reviewers }))
+                    .await;
+                Ok(())
+            }
+            Command::Label(_) => self.apply_batch(&[line]).await,
+            // Commands for PRs on issues and the like, or without the role they need.
+            _ => return Ok(false),
+        };
+        res.map(|()| true)
+    }
+
+    /// Converts the PR to a draft. The `drafts` handler swaps the labels when GitHub reports
+    /// the conversion.
+    async fn draft(&self) -> Result<(), Error> {
+        let pr = self.pull().await?;
+        if pr.draft == Some(true) {
+            return Ok(());
+        }
+        let node_id = pr.node_id.ok_or("pull request without node id")?;
+        github::graphql_mutation(
+            self.ctx.state.dry_run,
+            &self.ctx.client,
+            format!("convert #{} to a draft", self.number()),
+            "mutation($id: ID!) { \
+                convertPullRequestToDraft(input: { pullRequestId: $id }) { \
+                clientMutationId } }",
+            json!({ "id": node_id }),
+        )
+        .await?;
+        self.audit("draft", "")
+    }
+
+    /// Minimizes the comment `url` links to.
+    async fn hide(&self, url: &str, classifier: &str) -> Result<(), Error> {
+        let client = &self.ctx.client;
+        let Some(route) = comment_route(url, self.full_name) else {
+            return Ok(());
+        };
+        let comment: serde_json::Value = github::retry(|| client.get(&route, None::<&()>)).await?;
+        let node_id = comment["node_id"].as_str().unwrap_or_default();
+        github::minimize_comment(self.ctx.state.dry_run, client, node_id, classifier).await?;
+        self.audit("hide", &format!("{} {}", url, classifier))
+    }
+
+    /// Opens the issue again in `target`, only a repo of the same org, the installation can't
+    /// see others anyway.
+    async fn port_to(&self, target: &str, close: bool) -> Result<(), Error> {
+        let state = &self.ctx.state;
+        let issue = &self.payload.issue;
+        let owner = self.owner()?;
+        let target = target
+            .strip_prefix(&format!("{}/", owner))
+            .unwrap_or(target);
+        if target.contains('/') || target == self.repo.name {
+            return Ok(());
+        }
+
+        let body = self.ctx.rules.templates.render(
+            self.full_name,
+            "ported_issue",
+            json!({
+                "author": issue.user.login,
+                "url": issue.html_url,
+                "body": issue.body.as_deref().unwrap_or_default(),
+            }),
+        )?;
+        let target_issues = self.ctx.client.issues(owner, target);
+        let ported = github::create_issue(
+            state.dry_run,
+            &target_issues,
+            &issue.title,
+            &body,
+            Vec::new(),
+            Vec::new(),
+        )
+        .await?;
+        let url = match ported {
+            Some(ported) => ported.html_url.to_string(),
+            None => format!("https://github.com/{}/{}/issues", owner, target),
+        };
+        self.reply("ported", json!({ "url": url })).await;
+        if close {
+            self.close().await?;
+        }
+        self.audit("port-to", target)
+    }
+
+    /// Moves a label in the background: it can be on more issues than the API calls and the time
+    /// a delivery may take allow. Where it stands is shown in `/admin/bulk-operations`.
+    async fn relabel(&self, old: &str, new: &str) -> Result<(), Error> {
+        let (state, client) = (self.ctx.state.clone(), self.ctx.client.clone());
+        let rules = self.ctx.rules.clone();
+        let (repo, full_name, number) = (self.repo.id, self.full_name.to_string(), self.number());
+        let (old, new) = (old.to_string(), new.to_string());
+        let context = json!({ "old": old, "new": new });
+        let detail = format!("{} {}", old, new);
+        tokio::spawn(
+            async move {
+                let count = match labels::relabel(&state, &client, repo, &old, &new).await {
+                    Ok(x) => x,
+                    Err(e) => {
+                        warn!("Failed to move label {} to {}: {}", old, new, e);
+                        return;
+                    }
+                };
+                let context = json!({ "old": old, "new": new, "count": count });
+                let res = match rules.templates.render(&full_name, "relabeled", context) {
+                    Ok(body) => {
+                        let issues = client.issues_by_id(repo);
+                        github::comment(state.dry_run, &issues, number, &body)
+                            .await
+                            .map_err(Error::from)
+                    }
+                    Err(e) => Err(e.into()),
+                };
+                if let Err(e) = res {
+                    warn!("Failed to reply relabeled on #{}: {}", number, e);
+                }
+            }
+            .in_current_span(),
+        );
+        self.reply("relabel_started", context).await;
+        self.audit("relabel", &detail)
+    }
+
+    /// Opens an issue for something left for later in the PR, assigned to the commenter.
+    async fn follow_up(&self, title: &str) -> Result<(), Error> {
+        let payload = self.payload;
+        // The topic labels of the PR, not the ones of the bot's workflow.
+        let workflow = label_definitions(&[]);
+        let labels: Vec<String> = payload
+            .issue
+            .labels
+            .iter()
+            .map(|x| x.name.clone())
+            .filter(|x| !workflow.iter().any(|w| &w.name == x))
+            .collect();
+        let body = self.ctx.rules.templates.render(
+            self.full_name,
+            "follow_up_issue",
+            json!({
+                "user": self.user(),
+                "pr": payload.issue.html_url,
+                "comment": payload.comment.html_url,
+            }),
+        )?;
+        let repo_issues = self.ctx.client.issues(self.owner()?, &self.repo.name);
+        let issue = github::create_issue(
+            self.ctx.state.dry_run,
+            &repo_issues,
+            title,
+            &body,
+            labels,
+            vec![self.user().to_string()],
+        )
+        .await?;
+        let url = match issue {
+            Some(issue) => issue.html_url.to_string(),
+            None => payload.issue.html_url.to_string(),
+        };
+        self.reply("follow_up", json!({ "url": url })).await;
+        self.audit("follow-up", title)
+    }
+
+    /// Hands the PR of an inactive author to the commenter.
+    async fn adopt(&self) -> Result<(), Error> {
+        let (state, client) = (&self.ctx.state, &self.ctx.client);
+        let (repo, number) = (self.repo, self.number());
+        let adopter = self.user();
+        let author = self.payload.issue.user.login.as_str();
+        if adopter == author || self.payload.issue.state != models::IssueState::Open {
+            return Ok(());
+        }
+        let key = Adoptions::adopted_key(repo.id, number);
+        let error = if let Some(adopted_by) = state.db.get_kv(&key)? {
+            Some(format!(
+                "`adopt`: this PR was adopted by @{} already",
+                adopted_by
+            ))
+        } else {
+            let inactive = inactive_days(self.ctx, repo, &self.payload.issue).await?;
+            let after = self.commands.adopt_after_days;
+            (inactive < after as i64).then(|| {
+                format!(
+                    "`adopt`: @{} was active {} days ago, PRs can be adopted after {} days",
+                    author, inactive, after
+                )
+            })
+        };
+        if let Some(error) = error {
+            self.reply(
+                "commands_rejected",
+                json!({ "user": adopter, "errors": [error] }),
+            )
+            .await;
+            return Ok(());
+        }
+
+        let pull = self.pull().await?;
+        let labels = [ADOPTABLE_LABEL.to_string()];
+        batch::add_labels(state, client, repo.id, number, &labels).await?;
+        self.assign(adopter, true).await?;
+        self.reply(
+            "adoption",
+            json!({
+                "adopter": adopter,
+                "author": author,
+                "pr": number,
+                "branch": pull.head.ref_field,
+            }),
+        )
+        .await;
+        state.db.set_kv(&key, adopter)?;
+        self.audit("adopt", "")
+    }
+
+    /// Lists the issues of the repo matching `terms`, except the one the comment is on.
+    async fn search(&self, terms: &str) -> Result<(), Error> {
+        let query = format!("repo:{} is:issue {}", self.full_name, terms);
+        let page = github::retry(|| {
+            self.ctx
+                .client
+                .search()
+                .issues_and_pull_requests(&query)
+                .per_page(SEARCH_RESULTS + 1)
+                .send()
+        })
+        .await?;
+        let results: Vec<_> = page
+            .items
+            .iter()
+            .filter(|x| x.number != self.number())
+            .take(SEARCH_RESULTS.into())
+            .map(|x| {
+                json!({
+                    "number": x.number,
+                    "title": x.title,
+                    "url": x.html_url,
+                    "state": if x.state == models::IssueState::Open { "open" } else { "closed" },
+                })
+            })
+            .collect();
+        self.reply(
+            "search_results",
+            json!({ "terms": terms, "results": results }),
+        )
+        .await;
+        Ok(())
+    }
+
+    /// Mentions the members of a team the commenter may ping.
+    async fn ping_team(&self, team: &str) -> Result<(), Error> {
+        let owner = self.owner()?;
+        let team = team.strip_prefix(&format!("{}/", owner)).unwrap_or(team);
+        if !self
+            .ctx
+            .state
+            .mentionable_teams
+            .iter()
+            .any(|x| x.eq_ignore_ascii_case(team))
+        {
+            return Ok(());
+        }
+        let (state, client) = (&self.ctx.state, &self.ctx.client);
+        let key = (owner.to_lowercase(), team.to_lowercase());
+        let route = format!("/orgs/{}/teams/{}/members", owner, team);
+        let members =
+            github::retry(|| state.team_members.get_all(client, key.clone(), &route)).await?;
+        let members: Vec<&str> = members
+            .iter()
+            .map(|x| x.login.as_str())
+            .filter(|x| *x != self.user())
+            .collect();
+        if members.is_empty() {
+            return Ok(());
+        }
+        self.reply(
+            "team_ping",
+            json!({ "user": self.user(), "team": team, "members": members }),
+        )
+        .await;
+        self.audit("ping-team", team)
+    }
+
+    /// Requests a review from someone of the review pool who isn't overloaded.
+    async fn roulette(&self) -> Result<(), Error> {
+        let state = &self.ctx.state;
+        let number = self.number();
+        let overloaded: HashSet<String> = reviews::workload(&state.db, state.max_review_load)?
+            .into_iter()
+            .filter(|x| x.overloaded)
+            .map(|x| x.login.to_lowercase())
+            .collect();
+        let eligible: Vec<&String> = state
+            .review_pool
+            .iter()
+            .filter(|x| !x.eq_ignore_ascii_case(&self.payload.issue.user.login))
+            .filter(|x| !overloaded.contains(&x.to_lowercase()))
+            .collect();
+        let Some(reviewer) = eligible.choose(&mut rand::rng()) else {
+            self.reply("roulette_empty", json!({ "user": self.user() }))
+                .await;
+            return Ok(());
+        };
+        let pulls = self.ctx.client.pulls(self.owner()?, &self.repo.name);
+        github::mutate(
+            state.dry_run,
+            format!("request review from {} on #{}", reviewer, number),
+            || pulls.request_reviews(number, vec![reviewer.to_string()], Vec::new()),
+        )
+        .await?;
+        self.reply("roulette", json!({ "reviewer": reviewer }))
+            .await;
+        self.audit("roulette", reviewer)
+    }
+
+    /// The progress of the milestone and what blocks it.
+    async fn milestone_status(&self, title: Option<&str>) -> Result<(), Error> {
+        let client = &self.ctx.client;
+        let milestones = milestones::open_milestones(client, self.full_name).await?;
+        let milestone = match title {
+            Some(title) => milestones.iter().find(|x| x.title == title),
+            None => milestones::upcoming(&milestones),
+        };
+        let Some(milestone) = milestone else {
+            return Ok(());
+        };
+        let blockers: Vec<_> = milestones::blockers(client, self.full_name, milestone)
+            .await?
+            .iter()
+            .map(|x| json!({ "number": x.number, "title": x.title }))
+            .collect();
+        self.reply(
+            "milestone_status",
+            json!({
+                "milestone": milestone.title,
+                "url": milestone.html_url,
+                "due": milestone.due_on.map(|x| x.format("%Y-%m-%d").to_string()),
+                "open": milestone.open_issues,
+                "closed": milestone.closed_issues,
+                "blockers": blockers,
+            }),
+        )
+        .await;
+        Ok(())
+    }
+
+    /// Asks for the next commit of the bisection to be tested, or tells the one it ended at.
+    async fn bisect(&self, line: &str, session: Session) -> Result<(), Error> {
+        let db = &self.ctx.state.db;
+        let (full_name, number) = (self.full_name, self.number());
+        match bisect::next(&self.ctx.client, full_name, &session).await? {
+            Step::Test {
+                commit,
+                remaining,
+                steps,
+            } => {
+                let session = Session {
+                    testing: commit.sha.clone(),
+                    ..session
+                };
+                bisect::set_session(db, full_name, number, &session)?;
+                self.reply(
+                    "bisect_step",
+                    json!({
+                        "commit": commit.sha,
+                        "title": commit.title(),
+                        "url": commit.html_url,
+                        "good": session.good,
+                        "bad": session.bad,
+                        "remaining": remaining,
+                        "steps": steps,
+                    }),
+                )
+                .await;
+            }
+            Step::Done(commit) => {
+                bisect::end_session(db, full_name, number)?;
+                self.reply(
+                    "bisect_done",
+                    json!({
+                        "commit": commit.sha,
+                        "title": commit.title(),
+                        "url": commit.html_url,
+                        "good": session.good,
+                    }),
+                )
+                .await;
+            }
+        }
+        self.audit("bisect", line)
+    }
+
+    /// Summarizes the discussion, with the configured LLM too if there's one.
+    async fn summarize(&self) -> Result<(), Error> {
+        let client = &self.ctx.client;
+        let issue = &self.payload.issue;
+        let issues = client.issues_by_id(self.repo.id);
+        let page =
+            github::retry(|| issues.list_comments(issue.number).per_page(100).send()).await?;
+        let comments = client.all_pages(page).await?;
+        let mut posts = vec![Post {
+            login: &issue.user.login,
+            body: issue.body.as_deref().unwrap_or_default(),
+            url: issue.html_url.as_str(),
+        }];
+        posts.extend(
+            comments
+                .iter()
+                .filter(|x| x.user.r#type != "Bot")
+                .map(|x| Post {
+                    login: &x.user.login,
+                    body: x.body.as_deref().unwrap_or_default(),
+                    url: x.html_url.as_str(),
+                }),
+        );
+        let summary = summary::summarize(&posts);
+        let llm = match &self.commands.summary_llm {
+            Some(config) => summary::llm_summary(&self.commands.http, config, &issue.title, &posts)
+                .await
+                .inspect_err(|e| warn!("Failed to summarize: {}", e))
+                .ok(),
+            None => None,
+        };
+        self.reply(
+            "thread_summary",
+            json!({
+                "comments": summary.comments,
+                "participants": summary.participants,
+                "decisions": summary.decisions,
+                "references": summary.references,
+                "llm": llm,
+            }),
+        )
+        .await;
+        Ok(())
+    }
+
+    /// Subscribes the commenter to the areas, or unsubscribes them, from every area without
+    /// any. `--digest` and `--immediate` choose how subscriptions are delivered.
+    async fn subscribe(&self, subscribe: bool, args: &[&str]) -> Result<(), Error> {
+        let db = &self.ctx.state.db;
+        let (full_name, login) = (self.full_name, self.user());
+        if !subscribe && args.is_empty() {
+            db.remove_subscriptions(full_name, login, None)?;
+        }
+        for arg in args {
+            let area = Area::parse(arg);
+            match *arg {
+                "--digest" | "--immediate" if subscribe => {
+                    db.set_subscription_digest(full_name, login, *arg == "--digest")?;
+                }
+                _ if subscribe => {
+                    let (kind, value) = area.parts();
+                    db.add_subscription(full_name, login, kind, value)?;
+                }
+                _ => {
+                    db.remove_subscriptions(full_name, login, Some(area.parts()))?;
+                }
+            }
+        }
+        let areas: Vec<String> = db
+            .subscriptions(full_name, Some(login))?
+            .iter()
+            .map(|x| format!("{}:{}", x.kind, x.value))
+            .collect();
+        self.reply(
+            "subscribed",
+            json!({
+                "user": login,
+                "areas": areas,
+                "digest": db.subscription_digest(full_name, login)?,
+            }),
+        )
+        .await;
+        let command = if subscribe {
+            "subscribe"
+        } else {
+            "unsubscribe"
+        };
+        self.audit(command, &args.join(" "))
+    }
+
+    /// Moves the PR to another workflow state for `command`, or replies why it can't. Returns
+    /// whether it moved.
+    async fn transition(&self, command: &str, transition: Transition) -> Result<bool, Error> {
+        let number = self.number();
+        let issues = self.ctx.client.issues_by_id(self.repo.id);
+        let before: HashSet<String> = github::retry(|| issues.list_labels_for_issue(number).send())
+            .await?
+            .into_iter()
+            .map(|x| x.name)
+            .collect();
+        let mut after = before.clone();
+        let res = match self.payload.issue.pull_request {
+            Some(_) => workflow::apply(&mut after, transition),
+            None => Err(PR_ONLY),
+        };
+        if let Err(e) = res {
+            self.reply(
+                "commands_rejected",
+                json!({
+                    "user": self.user(),
+                    "errors": [format!("`{}`: {}", command, e)],
+                }),
+            )
+            .await;
+            return Ok(false);
+        }
+        let (state, client) = (&self.ctx.state, &self.ctx.client);
+        workflow::sync(state, client, self.repo.id, number, &before, &after).await?;
+        Ok(true)
+    }
+
+    /// Applies the label and assignee commands of one comment with one call each. Every
+    /// command is checked first, and if one of them can't be applied, nothing is and the
+    /// problems are replied.
+    async fn apply_batch(&self, lines: &[&str]) -> Result<(), Error> {
+        let state = &self.ctx.state;
+        let client = &self.ctx.client;
+        let repo = self.repo;
+        let number = self.number();
+        let user = self.user();
+        let issues = client.issues_by_id(repo.id);
+
+        let route = format!("/repositories/{}/labels", repo.id);
+        let repo_labels =
+            github::retry(|| state.repo_labels.get_all(client, repo.id, &route)).await?;
+        let repo_labels: HashSet<String> = repo_labels.into_iter().map(|x| x.name).collect();
+        let current = github::retry(|| issues.list_labels_for_issue(number).send()).await?;
+        let current: HashSet<String> = current.into_iter().map(|x| x.name).collect();
+        let commands: Vec<Command> = lines.iter().filter_map(|x| Command::parse(x)).collect();
+        let draft = if self.is_pr() && commands.contains(&Command::Ready) {
+            self.pull().await?.draft == Some(true)
+        } else {
+            false
+        };
+
+        let mut labels = current.clone();
+        // Claimed or unclaimed, the last command wins.
+        let mut claim = None;
+        let mut errors = Vec::new();
+        // Labels of `label` that were already on or off, with why.
+        let mut skipped = Vec::new();
+        for command in &commands {
+            match command {
+                Command::Claim => match self.maintainer_only_label() {
+                    Some(label) if self.privilege_level == 0 => errors.push(format!(
+                        "`claim`: issues labeled `{}` can only be claimed by collaborators",
+                        label
+                    )),
+                    _ => claim = Some(true),
+                },
+                Command::Unclaim => claim = Some(false),
+                Command::Ready if !self.is_pr() => errors.push(format!("`ready`: {}", PR_ONLY)),
+                Command::Author if !self.is_pr() => errors.push(format!("`author`: {}", PR_ONLY)),
+                Command::Ready if draft => errors
+                    .push("`ready`: the PR is a draft, mark it as ready for review".to_string()),
+                Command::Ready => {
+                    if let Err(e) = workflow::apply(&mut labels, Transition::RequestReviews) {
+                        errors.push(format!("`ready`: {}", e));
+                    }
+                }
+                Command::Author => {
+                    if let Err(e) = workflow::apply(&mut labels, Transition::RequestChanges) {
+                        errors.push(format!("`author`: {}", e));
+                    }
+                }
+                Command::Label(args) => {
+                    for label in &label_args(args) {
+                        let (add, name) = match (label.strip_prefix('+'), label.strip_prefix('-')) {
+                            (Some(name), _) => (true, name),
+                            (_, Some(name)) => (false, name),
+                            _ => {
+                                errors.push(format!("`label`: `{}` needs a `+` or `-`", label));
+                                continue;
+                            }
+                        };
+                        let autocorrect = self.commands.label_autocorrect;
+                        let name = match known_label(name, &repo_labels, autocorrect) {
+                            Ok(x) => x,
+                            Err(e) => {
+                                errors.push(e);
+                                continue;
+                            }
+                        };
+                        if add == labels.contains(&name) {
+                            let reason = if add {
+                                "was already there"
+                            } else {
+                                "wasn't there"
+                            };
+                            skipped.push(json!({ "label": name, "reason": reason }));
+                        } else if let Some(to) = workflow::State::from_label(&name)
+                            && add
+                        {
+                            if let Err(e) = workflow::apply(&mut labels, to.requested_by()) {
+                                errors.push(format!("`label`: `{}`, {}", name, e));
+                            }
+                        } else if add {
+                            labels.insert(name);
+                        } else {
+                            labels.remove(&name);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if !errors.is_empty() {
+            self.reply(
+                "commands_rejected",
+                json!({ "user": user, "errors": errors }),
+            )
+            .await;
+            return Ok(());
+        }
+
+        let mut added: Vec<&String> = labels.difference(&current).collect();
+        let mut removed: Vec<&String> = current.difference(&labels).collect();
+        added.sort_unstable();
+        removed.sort_unstable();
+        // Only the changes are sent, so labels someone else changed meanwhile stay as they are.
+        if !added.is_empty() {
+            let labels: Vec<String> = added.iter().map(|x| x.to_string()).collect();
+            batch::add_labels(state, client, repo.id, number, &labels).await?;
+        }
+        for label in &removed {
+            batch::remove_label(state, client, repo.id, number, label).await?;
+        }
+        // Also the labels other commands changed, e.g. the workflow label `ready` took off.
+        if commands.iter().any(|x| matches!(x, Command::Label(_))) {
+            self.reply(
+                "labels_changed",
+                json!({ "user": user, "added": added, "removed": removed, "skipped": skipped }),
+            )
+            .await;
+        }
+        if let Some(assign) = claim {
+            self.assign(user, assign).await?;
+        }
+        for line in lines {
+            let (command, args) = line.split_once(' ').unwrap_or((line, ""));
+            self.audit(command, args.trim())?;
+        }
+        Ok(())
+    }
+}
+
+/// Days since the author of the PR last commented on it or committed to it.
+async fn inactive_days(
+    ctx: &Context,
+    repo: &models::Repository,
+    issue: &models::issues::Issue,
+) -> Result<i64, Error> {
+    let issues = ctx.client.issues_by_id(repo.id);
+    let page = github::retry(|| issues.list_comments(issue.number).per_page(100).send()).await?;
+    let comments = ctx.client.all_pages(page).await?;
+    let commits = github::pr_commits(&ctx.client, repo, issue.number).await?;
+    let last = comments
+        .iter()
+        .filter(|x| x.user.id == issue.user.id)
+        .map(|x| x.created_at)
+        .chain(
+            commits
+                .iter()
+                .filter_map(|x| x.commit.author.as_ref()?.date),
+        )
+        .fold(issue.created_at, |a, b| a.max(b));
+    Ok((Utc::now() - last).num_days())
+}
+
+/// The `+name` and `-name` items of `label`, split on whitespace, or on commas if there are
+/// any so names can have spaces. Quotes keep a name with spaces together too, e.g.
+/// `+"good first issue"`.
+fn label_args(args: &str) -> Vec<String> {
+    let mut quoted = false;
+    let by_comma = args.chars().any(|x| {
+        quoted ^= x == '"';
+        x == ',' && !quoted
+    });
+
+    let mut items = Vec::new();
+    let mut item = String::new();
+    let mut push = |item: &mut String| {
+        if !item.trim().is_empty() {
+            items.push(item.trim().to_string());
+        }
+        item.clear();
+    };
+    quoted = false;
+    for x in args.chars() {
+        match x {
+            '"' => quoted = !quoted,
+            ',' if !quoted => push(&mut item),
+            x if x.is_whitespace() && !quoted && !by_comma => push(&mut item),
+            x => item.push(x),
+        }
+    }
+    push(&mut item);
+    items
+}
+
+/// The label of the repo `name` means, or why there's none. With `autocorrect`, a label that
+/// doesn't exist is taken to mean the only similar one.
+fn known_label(
+    name: &str,
+    repo_labels: &HashSet<String>,
+    autocorrect: bool,
+) -> Result<String, String> {
+    if repo_labels.contains(name) {
+        return Ok(name.to_string());
+    }
+    match labels::similar(name, repo_labels).as_slice() {
+        [only] if autocorrect => Ok(only.to_string()),
+        [] => Err(format!("`label`: there is no label `{}`", name)),
+        similar => Err(format!(
+            "`label`: there is no label `{}`, did you mean {}?",
+            name,
+            similar
+                .iter()
+                .map(|x| format!("`{}`", x))
+                .collect::<Vec<_>>()
+                .join(" or ")
+        )),
+    }
+}
+
+/// The classifier `hide` minimizes a comment with, by the reason given, off-topic by default.
+fn hide_classifier(reason: &str) -> Option<&'static str> {
+    match reason.to_ascii_lowercase().as_str() {
+        "" | "off-topic" => Some("OFF_TOPIC"),
+        "spam" => Some("SPAM"),
+        "abuse" => Some("ABUSE"),
+        "outdated" => Some("OUTDATED"),
+        "duplicate" => Some("DUPLICATE"),
+        "resolved" => Some("RESOLVED"),
+        _ => None,
+    }
+}
+
+/// The API route of the comment a link points to, if it's in the repo: a comment on an issue
+/// or PR (`#issuecomment-123`) or a review comment (`#discussion_r123`).
+fn comment_route(url: &str, full_name: &str) -> Option<String> {
+    let (path, anchor) = url.strip_prefix("https://github.com/")?.split_once('#')?;
+    let mut path = path.splitn(3, '/');
+    let repo = format!("{}/{}", path.next()?, path.next()?);
+    if !repo.eq_ignore_ascii_case(full_name) {
+        return None;
+    }
+    let (kind, id) = match anchor.split_once(['-', '_']) {
+        Some(("issuecomment", id)) => ("issues", id),
+        Some(("discussion", id)) => ("pulls", id.strip_prefix('r')?),
+        _ => return None,
+    };
+    let id: u64 = id.parse().ok()?;
+    Some(format!("/repos/{}/{}/comments/{}", repo, kind, id))
+}
+
+/// What `!ddnetbot status` replies: the version and uptime of the bot, how far behind it is
+/// and what runs for the repo.
+async fn bot_status(ctx: &Context, full_name: &str) -> Result<serde_json::Value, Error> {
+    let state = &ctx.state;
+    let queue = match &state.queue {
+        Some(queue) => Some(queue.waiting().await?),
+        None => None,
+    };
+    let last_handled = *state.last_handled.lock().unwrap();
+    let features: Vec<_> = ctx
+        .rules
+        .handlers
+        .features(full_name)
+        .into_iter()
+        .map(|(name, enabled)| json!({ "name": name, "enabled": enabled }))
+        .collect();
+    Ok(json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "uptime": uptime(Utc::now() - state.started_at),
+        "queue": queue,
+        "last_handled": last_handled.map(|x| x.format("%Y-%m-%d %H:%M:%S UTC").to_string()),
+        "repo": full_name,
+        "features": features,
+        "disabled": ctx.rules.handlers.disabled(full_name),
+    }))
+}
+
+/// `3d 4h 12m`, without the larger units that are zero.
+fn uptime(duration: chrono::Duration) -> String {
+    let minutes = duration.num_minutes();
+    let (days, hours, minutes) = (minutes / (24 * 60), minutes / 60 % 24, minutes % 60);
+    if days > 0 {
+        format!("{}d {}h {}m", days, hours, minutes)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+/// Why `ready` and `author` don't do anything on issues.
+const PR_ONLY: &str = "only PRs wait for reviews or their author, this is an issue";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_in_code_and_quotes_are_skipped() {
+        let body = "!ddnetbot claim\n\
+> !ddnetbot close\n\
+```\n!ddnetbot label bug\n~~~\n!ddnetbot ready\n```\n\
+~~~md\n!ddnetbot roulette\n~~~\n\
+!ddnetbot   label  good-first-issue";
+        assert_eq!(command_lines(body), ["claim", "label  good-first-issue"]);
+    }
+
+    #[test]
+    fn hide_takes_links_to_comments_of_the_repo() {
+        assert_eq!(
+            comment_route(
+                "https://github.com/ddnet/ddnet/issues/9877#issuecomment-123",
+                "ddnet/ddnet"
+            )
+            .as_deref(),
+            Some("/repos/ddnet/ddnet/issues/comments/123")
+        );
+        assert_eq!(
+            comment_route(
+                "https://github.com/ddnet/ddnet/pull/1#discussion_r456",
+                "DDNet/ddnet"
+            )
+            .as_deref(),
+            Some("/repos/ddnet/ddnet/pulls/comments/456")
+        );
+        assert_eq!(
+            comment_route(
+                "https://github.com/other/ddnet/issues/1#issuecomment-123",
+                "ddnet/ddnet"
+            ),
+            None
+        );
+        assert_eq!(hide_classifier(""), Some("OFF_TOPIC"));
+        assert_eq!(hide_classifier("Spam"), Some("SPAM"));
+        assert_eq!(hide_classifier("rude"), None);
+    }
+
+    #[test]
+    fn commands_need_their_arguments() {
+        assert_eq!(Command::parse("claim"), Some(Command::Claim));
+        assert_eq!(
+            Command::parse("delegate @heinrich5991"),
+            Some(Command::Delegate("heinrich5991"))
+        );
+        assert_eq!(Command::parse("delegate @"), None);
+        assert_eq!(Command::parse("stats"), Some(Command::Stats(None)));
+        assert_eq!(
+            Command::parse("port-to ddnet-web  close"),
+            Some(Command::PortTo {
+                repo: "ddnet-web",
+                close: true
+            })
+        );
+        assert_eq!(Command::parse("relabel bug"), None);
+        assert_eq!(
+            Command::parse("hide https://github.com/ddnet/ddnet/issues/1#issuecomment-2 spam"),
+            Some(Command::Hide {
+                url: "https://github.com/ddnet/ddnet/issues/1#issuecomment-2",
+                classifier: "SPAM"
+            })
+        );
+        assert_eq!(Command::parse("hide https://github.com/ rude"), None);
+        assert_eq!(
+            Command::parse("bisect bad abc123"),
+            Some(Command::BisectResult {
+                good: false,
+                sha: Some("abc123")
+            })
+        );
+        assert_eq!(Command::parse("bisect start v18.0"), None);
+        assert_eq!(Command::parse("remind soon ping"), None);
+        assert_eq!(Command::parse("status please"), None);
+        assert_eq!(Command::parse("claimed"), None);
+        assert!(Command::parse("label +bug").is_some_and(|x| x.batchable()));
+        assert!(!Command::parse("subscribe map").is_some_and(|x| x.batchable()));
+    }
+
+    #[test]
+    fn label_names_can_have_spaces() {
+        assert_eq!(label_args(" +bug  -crash"), ["+bug", "-crash"]);
+        assert_eq!(
+            label_args("+\"good first issue\" \"-needs info\" +bug"),
+            ["+good first issue", "-needs info", "+bug"]
+        );
+        assert_eq!(
+            label_args("+good first issue, -bug,"),
+            ["+good first issue", "-bug"]
+        );
+        assert_eq!(label_args("+\"a, b\" +c"), ["+a, b", "+c"]);
+    }
+
+    #[test]
+    fn edited_comments_run_only_new_commands() {
+        assert_eq!(
+            new_commands(
+                vec!["claim", "label bug", "label bug", "ready"],
+                vec!["label bug", "lable crash", "claim"]
+            ),
+            ["label bug", "ready"]
+        );
+    }
+
+    #[test]
+    fn permissions_match_roles_and_users() {
+        let permission = CommandPermission {
+            roles: vec!["member".to_string()],
+            teams: vec!["map-testers".to_string()],
+            users: vec!["Patiga".to_string()],
+        };
+        assert!(permission.allows(&["collaborator", "member"], "someone"));
+        assert!(permission.allows(&["author"], "patiga"));
+        assert!(!permission.allows(&["author", "collaborator"], "someone"));
+    }
+
+    #[test]
+    fn uptime_leaves_out_empty_units() {
+        assert_eq!(uptime(chrono::Duration::seconds(59)), "0m");
+        assert_eq!(uptime(chrono::Duration::minutes(61)), "1h 1m");
+        assert_eq!(uptime(chrono::Duration::hours(76)), "3d 4h 0m");
+    }
+}
@@ -0,0 +1,1537 @@
+use std::{collections::HashMap, fmt, path::PathBuf, time::Duration};
+
+use chrono::{DateTime, Timelike, Utc};
+use figment::{
+    Figment,
+    providers::{Env, Format, Serialized, Toml},
+};
+use serde::de::DeserializeOwned;
+
+use crate::{db::ReviewRequest, feed::TRIAGE_LABEL};
+
+/// Everything the bot is configured with.
+///
+/// Values are read from the TOML file named by `DDBOT_CONFIG` (default `ddbot.toml`) and then
+/// from the environment, which takes precedence. Keys are the lowercase environment variable
+/// names, e.g. `github_app_id = 1234` in the file or `GITHUB_APP_ID=1234` in the environment.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Set unless `github_token` is.
+    pub github_app_id: Option<u64>,
+    /// Base URL of the GitHub REST API, overridden by tests to point at a mock server.
+    pub github_api_url: String,
+    /// Set unless `github_token` is.
+    pub app_private_key_path: Option<PathBuf>,
+    /// A personal access token used instead of a GitHub App, for local development.
+    pub github_token: Option<String>,
+    pub bind_address: String,
+    pub port: u16,
+    pub webhook_path: String,
+    pub tls_cert_path: Option<PathBuf>,
+    pub tls_key_path: Option<PathBuf>,
+    pub unix_socket_path: Option<PathBuf>,
+    /// Octal permissions of the unix socket.
+    pub unix_socket_mode: u32,
+    /// The secret of the webhook, deliveries without a valid `X-Hub-Signature-256` are refused
+    /// when set.
+    pub webhook_secret: Option<String>,
+    /// Whitespace separated `url` or `url|event,event`.
+    pub forward_targets: String,
+    pub forward_secret: Option<String>,
+    pub feed_refresh_secs: u64,
+    /// `(owner, name)` of the repo holding the triage digest.
+    pub digest_repo: Option<(String, String)>,
+    pub digest_refresh_secs: u64,
+    /// `(owner/name, number)` of the issue the monthly command usage is commented on.
+    pub command_report_issue: Option<(String, u64)>,
+    /// `(owner/name, number)` of the issue subscription digests are commented on, for the people
+    /// without a Discord webhook.
+    pub subscription_digest_issue: Option<(String, u64)>,
+    /// How often subscription digests are sent.
+    pub subscription_digest_secs: u64,
+    /// How often every open PR is checked for conflicts.
+    pub conflict_check_secs: u64,
+    /// How often the progress comments on tracking issues are updated.
+    pub tracking_progress_secs: u64,
+    /// How often issues waiting for triage are labeled by how long they've waited.
+    pub triage_aging_secs: u64,
+    /// How often labels are checked for problems, see [`crate::hygiene`].
+    pub label_hygiene_secs: u64,
+    /// `(owner/name, number)` of the issue label problems are commented on.
+    pub label_hygiene_issue: Option<(String, u64)>,
+    /// Whether issues and PRs with contradicting workflow labels are fixed.
+    pub label_hygiene_autofix: bool,
+    /// How often the open and closed counts of milestones are recorded.
+    pub milestone_snapshot_secs: u64,
+    /// How often merged PRs and their reviews are imported into the stats.
+    pub stats_sync_secs: u64,
+    /// How often the repos the app is installed on are listed, see [`crate::repos`].
+    pub repo_sync_secs: u64,
+    /// How long the handlers of a delivery may take together before they're given up on.
+    pub event_timeout_secs: u64,
+    pub database_path: PathBuf,
+    pub rate_limit_threshold: usize,
+    pub max_workers: usize,
+    pub max_workers_per_installation: usize,
+    pub command_rate_limit: usize,
+    pub command_rate_window_secs: u64,
+    pub max_body_bytes: usize,
+    /// GitHub API calls the handlers of a delivery may make together before they're cancelled.
+    pub max_api_calls_per_event: usize,
+    /// Reviewers with more pending review requests than this are shown as overloaded.
+    pub max_review_load: u64,
+    /// How many days before the due date of a milestone its idle `blocker` issues are pinged
+    /// about and lead the triage digest.
+    pub blocker_ping_days: u64,
+    /// Hours new issues may wait for a first response by someone other than their author, and
+    /// for triage. Issues waiting longer are listed in the triage digest.
+    pub slo_first_response_hours: Option<u64>,
+    pub slo_triage_hours: Option<u64>,
+    /// When digests, review reminders and release announcements wait, only settable in the
+    /// config file.
+    pub quiet_hours: Option<QuietHours>,
+    pub log_format: LogFormat,
+    /// Directory every received delivery is recorded to, for test fixtures and replays.
+    pub capture_dir: Option<PathBuf>,
+    /// Only log the API calls that would change something on GitHub.
+    pub dry_run: bool,
+    /// Queue deliveries in Redis and handle them in the background, shared by every replica.
+    pub redis_url: Option<String>,
+    /// Queue deliveries in the database and handle them in the background.
+    pub disk_queue: bool,
+    /// Require every commit of a PR to be signed off by its author.
+    pub dco: bool,
+    /// Sum up what the bot did for a PR that was opened or pushed to in a `ddbot` check run.
+    pub summary_check: bool,
+    /// Open an issue explaining the commands and the per-repo config on repos the app is newly
+    /// installed on.
+    pub setup_issue: bool,
+    /// Enables the admin API under `/admin`, requests must send it as a bearer token.
+    pub admin_token: Option<String>,
+    /// Lets members of teams log in with GitHub to use `/admin` and the dashboard, which is
+    /// public without it. Only settable in the config file.
+    pub login: Option<LoginConfig>,
+    /// Enables `/benchmarks` for CI to post benchmark results to, only settable in the config
+    /// file.
+    pub benchmarks: Option<BenchmarksConfig>,
+    /// Callers of `/triggers/<name>` by name, e.g. the release pipeline. Only settable in the
+    /// config file.
+    pub triggers: HashMap<String, TriggerCaller>,
+    /// The language model `!ddnetbot summarize` asks too, with the `llm-summary` feature. Only
+    /// settable in the config file.
+    pub summary_llm: Option<SummaryLlmConfig>,
+    /// Automation scripts, only settable in the config file.
+    pub scripts: Vec<ScriptConfig>,
+    /// WebAssembly plugins, only settable in the config file.
+    pub plugins: Vec<PluginConfig>,
+    /// Overrides of the comment templates by name, only settable in the config file.
+    pub templates: HashMap<String, String>,
+    /// Directory with a `<language>.toml` file of translated templates per language.
+    pub translations_dir: Option<PathBuf>,
+    /// Language of the bot's comments by `owner/name` of the repo, English if not listed.
+    pub languages: HashMap<String, String>,
+    /// Review checklist items by the paths they're about, only settable in the config file.
+    pub checklist: Vec<ChecklistItem>,
+    /// What makes an issue look beginner-friendly, only settable in the config file.
+    pub good_first_issues: GoodFirstIssuesConfig,
+    /// Who reviews changes to which paths, only settable in the config file.
+    pub review_rules: Vec<ReviewRule>,
+    /// How reviewers are picked from the history of the changed files, not at all if unset. Only
+    /// settable in the config file.
+    pub blame_reviewers: Option<BlameReviewersConfig>,
+    /// Logins `!ddnetbot roulette` picks a reviewer from, only settable in the config file.
+    pub review_pool: Vec<String>,
+    /// How long reviewers have before they're reminded, only settable in the config file.
+    pub review_sla: ReviewSlaConfig,
+    /// How crash and regression reports are acknowledged, only settable in the config file.
+    pub acknowledgements: AcknowledgementsConfig,
+    /// Where to raise alerts about direct pushes to sensitive paths, only settable in the config
+    /// file.
+    pub protected_paths: ProtectedPathsConfig,
+    /// Rules for the commit messages of PRs, not checked if unset. Only settable in the config
+    /// file.
+    pub commit_lint: Option<CommitLintConfig>,
+    /// The CLA PR authors have to accept, not required if unset. Only settable in the config
+    /// file.
+    pub cla: Option<ClaConfig>,
+    /// The header new source files need, not checked if unset. Only settable in the config file.
+    pub license_header: Option<LicenseHeaderConfig>,
+    /// Which issues are gameplay bugs that need a demo, not asked for if unset. Only settable in
+    /// the config file.
+    pub demo_request: Option<DemoRequestConfig>,
+    /// How PRs of dependency update bots are handled, only settable in the config file.
+    pub dependency_updates: Option<DependencyUpdatesConfig>,
+    /// Where security alerts are forwarded to, only settable in the config file.
+    pub security_alerts: SecurityAlertsConfig,
+    /// How discussions are triaged, only settable in the config file.
+    pub discussions: DiscussionsConfig,
+    /// Where published releases are announced, only settable in the config file.
+    pub releases: ReleasesConfig,
+    /// The project board triaged issues are added to, only settable in the config file.
+    pub project: Option<ProjectConfig>,
+    /// Canonical labels of every repo, on top of the ones the bot needs. Only settable in the
+    /// config file.
+    pub labels: Vec<LabelDefinition>,
+    /// Keywords of issues that belong in another repo, only settable in the config file.
+    pub routing: Vec<RoutingRule>,
+    /// Canned answers to frequently asked questions, only settable in the config file.
+    pub faq: Vec<FaqEntry>,
+    /// Documentation pages by the paths they describe, only settable in the config file.
+    pub docs: Vec<DocLink>,
+    /// The format of image assets added by PRs, only settable in the config file.
+    pub assets: Vec<AssetRule>,
+    /// How changed console settings are found in PRs, only settable in the config file.
+    pub settings: SettingsConfig,
+    /// Which added comments PRs get issues for, only settable in the config file.
+    pub todos: TodosConfig,
+    /// Files defining the network protocol, e.g. `src/engine/shared/protocol.h`. Only settable
+    /// in the config file.
+    pub protocol_paths: Vec<String>,
+    /// PRs touching these paths need screenshots, e.g. UI or rendering code. Only settable in
+    /// the config file.
+    pub screenshot_paths: Vec<String>,
+    /// Labels of PRs that fix a bug and have to link its issue, besides those titled "Fix ...".
+    /// Only settable in the config file.
+    pub bugfix_labels: Vec<String>,
+    /// The template explaining a label by label, posted when people add it. Only settable in
+    /// the config file.
+    pub label_help: HashMap<String, String>,
+    /// Public headers whose removed or changed declarations are suspected API breaks, only
+    /// settable in the config file.
+    pub api_headers: Vec<String>,
+    /// Issues with one of these labels can only be claimed by collaborators.
+    pub maintainer_only_labels: Vec<String>,
+    /// How many days the author of a PR has to be inactive before someone can adopt it with
+    /// `!ddnetbot adopt`.
+    pub adopt_after_days: u64,
+    /// Whether `!ddnetbot label` takes a label that doesn't exist to mean the only similar one,
+    /// instead of suggesting it.
+    pub label_autocorrect: bool,
+    /// The labels of the areas people triage by login, for `!ddnetbot next`. Only settable in
+    /// the config file.
+    pub triage_areas: HashMap<String, Vec<String>>,
+    /// Discord webhooks subscription digests are posted to by login, e.g. of a private channel.
+    /// Only settable in the config file.
+    pub subscription_webhooks: HashMap<String, String>,
+    /// Other names of `!ddnetbot` commands, e.g. `take` for `claim`. Only settable in the config
+    /// file.
+    pub command_aliases: HashMap<String, String>,
+    /// Who may run a command besides its own checks, by command name, e.g. only a team may run
+    /// `map`. Only settable in the config file.
+    pub command_permissions: HashMap<String, CommandPermission>,
+    /// Slugs of the teams collaborators can mention with `!ddnetbot ping-team`.
+    pub mentionable_teams: Vec<String>,
+    /// The triage label `!ddnetbot snooze` takes off issues for a while.
+    pub snooze_label: String,
+    /// Issues reporting an older version are asked to upgrade and closed.
+    pub min_supported_version: Option<String>,
+    /// The issue form field holding the version bugs were found on.
+    pub version_field: String,
+    /// The repos the bot acts on by `owner/name`, every repo it's installed on if empty.
+    /// Only settable in the config file.
+    pub repos: HashMap<String, RepoConfig>,
+    /// How many milliseconds the label and comment changes of handlers to the same issue or PR
+    /// are collected before they're made together, by handler name. Only settable in the config
+    /// file.
+    pub batch_windows: HashMap<String, u64>,
+    /// Names of the handlers that run without changing anything on GitHub, what they would have
+    /// done is logged and recorded. Only settable in the config file.
+    pub shadow_handlers: Vec<String>,
+    /// How much of the traffic handlers get by name, all of it if not listed. Only settable in
+    /// the config file.
+    pub rollout: HashMap<String, RolloutConfig>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct RepoConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Names of the handlers that don't run for the repo, e.g. `labeler`.
+    #[serde(default)]
+    pub disabled: Vec<String>,
+    /// Command aliases of the repo, over the `command_aliases` of every repo.
+    #[serde(default)]
+    pub aliases: HashMap<String, String>,
+    /// Command permissions of the repo, over the `command_permissions` of every repo.
+    #[serde(default)]
+    pub permissions: HashMap<String, CommandPermission>,
+    /// Groups of handlers turned on or off by name, e.g. `triage = false`, see
+    /// [`crate::handlers::Registry`]. Unlisted features are on.
+    #[serde(default)]
+    pub features: HashMap<String, bool>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// The deliveries a handler being rolled out runs for, see [`crate::handlers::Registry`].
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct RolloutConfig {
+    /// Share of the deliveries, from 0 to 100.
+    #[serde(default = "default_rollout_percent")]
+    pub percent: u8,
+    /// `owner/name` of the repos, every repo if empty.
+    #[serde(default)]
+    pub repos: Vec<String>,
+}
+
+fn default_rollout_percent() -> u8 {
+    100
+}
+
+/// Who may run a command, anyone matching one of the lists.
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+pub struct CommandPermission {
+    /// `member` (of the org), `collaborator` (and members), `delegate` or `author` (of the issue
+    /// or PR).
+    #[serde(default)]
+    pub roles: Vec<String>,
+    /// Slugs of teams of the org owning the repo.
+    #[serde(default)]
+    pub teams: Vec<String>,
+    #[serde(default)]
+    pub users: Vec<String>,
+}
+
+impl CommandPermission {
+    /// Whether someone with one of `roles` or named `login` may run the command, without
+    /// looking at teams.
+    pub fn allows(&self, roles: &[&str], login: &str) -> bool {
+        self.roles
+            .iter()
+            .any(|x| roles.iter().any(|r| x.eq_ignore_ascii_case(r)))
+            || self.users.iter().any(|x| x.eq_ignore_ascii_case(login))
+    }
+}
+
+/// An item of the review checklist, posted on PRs that change a file under one of `paths`.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct ChecklistItem {
+    pub paths: Vec<String>,
+    pub item: String,
+}
+
+/// Signs of a beginner-friendly issue, see [`crate::handlers::FirstIssues`].
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct GoodFirstIssuesConfig {
+    /// Words in the title or body hinting at a small change.
+    #[serde(default = "default_keywords")]
+    pub keywords: Vec<String>,
+    /// Labels of issues that are usually small.
+    #[serde(default)]
+    pub labels: Vec<String>,
+}
+
+impl Default for GoodFirstIssuesConfig {
+    fn default() -> Self {
+        Self {
+            keywords: default_keywords(),
+            labels: Vec::new(),
+        }
+    }
+}
+
+fn default_keywords() -> Vec<String> {
+    [
+        "typo",
+        "spelling",
+        "wording",
+        "tooltip",
+        "translation",
+        "documentation",
+    ]
+    .map(String::from)
+    .to_vec()
+}
+
+/// What commit messages of PRs have to look like, see [`crate::handlers::CommitLint`].
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct CommitLintConfig {
+    #[serde(default = "default_max_subject_len")]
+    pub max_subject_len: usize,
+    /// Components subjects must start with, as in `client: Fix the menus`. Not required if
+    /// empty.
+    #[serde(default)]
+    pub components: Vec<String>,
+}
+
+fn default_max_subject_len() -> usize {
+    72
+}
+
+/// The CLA PR authors have to accept, see [`crate::handlers::Cla`].
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct ClaConfig {
+    /// URL of the CLA text.
+    pub document: String,
+    /// Users who accepted the CLA outside of the bot, by login.
+    #[serde(default)]
+    pub signatories: Vec<String>,
+}
+
+/// The OAuth app people log in with, see [`crate::login`].
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct LoginConfig {
+    /// Of an OAuth app with `<url of the bot>/login/callback` as callback URL.
+    pub client_id: String,
+    pub client_secret: String,
+    /// Who may log in, as `org/slug`.
+    pub teams: Vec<String>,
+    /// Where people log in, e.g. the URL of a GitHub Enterprise server.
+    #[serde(default = "default_github_url")]
+    pub github_url: String,
+}
+
+fn default_github_url() -> String {
+    "https://github.com".to_string()
+}
+
+/// An OpenAI compatible chat completions API, see [`crate::summary::llm_summary`].
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct SummaryLlmConfig {
+    /// e.g. `https://api.openai.com/v1/chat/completions`.
+    pub url: String,
+    pub api_key: String,
+    pub model: String,
+    /// How much of the thread is sent, longer threads are cut.
+    #[serde(default = "default_summary_max_chars")]
+    pub max_chars: usize,
+}
+
+fn default_summary_max_chars() -> usize {
+    100_000
+}
+
+/// How benchmark results posted by CI are compared, see [`crate::benchmarks`].
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct BenchmarksConfig {
+    /// Requests must send it as a bearer token.
+    pub token: String,
+    /// How many percent slower than the baseline a benchmark may get.
+    #[serde(default = "default_benchmark_tolerance")]
+    pub tolerance: f64,
+    /// The results of this branch are the baselines.
+    #[serde(default = "default_benchmark_branch")]
+    pub branch: String,
+}
+
+/// A caller of `/triggers/<name>`, see [`crate::triggers`].
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct TriggerCaller {
+    /// Requests are signed with it like GitHub signs webhooks, in `X-Signature-256`.
+    pub secret: String,
+    /// The actions the caller may trigger, e.g. `release-checklist`.
+    pub scopes: Vec<String>,
+    /// `owner/name` of the repos it may act on, every repo if empty.
+    #[serde(default)]
+    pub repos: Vec<String>,
+}
+
+impl TriggerCaller {
+    pub fn allows(&self, action: &str, repo: &str) -> bool {
+        self.scopes.iter().any(|x| x == action)
+            && (self.repos.is_empty() || self.repos.iter().any(|x| x.eq_ignore_ascii_case(repo)))
+    }
+}
+
+fn default_benchmark_tolerance() -> f64 {
+    5.0
+}
+
+fn default_benchmark_branch() -> String {
+    "master".to_string()
+}
+
+/// The header new source files must start with, see [`crate::handlers::LicenseHeader`].
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct LicenseHeaderConfig {
+    pub header: String,
+    /// Extensions of the files that need the header.
+    #[serde(default = "default_license_extensions")]
+    pub extensions: Vec<String>,
+}
+
+fn default_license_extensions() -> Vec<String> {
+    ["cpp", "h", "rs"].map(String::from).to_vec()
+}
+
+/// Images under `paths`, e.g. skins, and the format they must have, see
+/// [`crate::handlers::Assets`].
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct AssetRule {
+    pub paths: Vec<String>,
+    /// The size in pixels as `[width, height]`.
+    pub size: [u32; 2],
+    /// Allow multiples of the size with the same aspect ratio, e.g. HD skins.
+    #[serde(default)]
+    pub multiples: bool,
+    /// Require 8-bit RGBA, no palette or grayscale images.
+    #[serde(default = "default_enabled")]
+    pub rgba: bool,
+}
+
+/// How PRs changing console settings are found, see [`crate::handlers::Settings`].
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct SettingsConfig {
+    /// Text of added lines that declare a setting, e.g. `MACRO_CONFIG_INT(`.
+    #[serde(default = "default_setting_patterns")]
+    pub patterns: Vec<String>,
+    /// The settings documentation the author is pointed to.
+    #[serde(default)]
+    pub docs: Option<String>,
+}
+
+impl Default for SettingsConfig {
+    fn default() -> Self {
+        Self {
+            patterns: default_setting_patterns(),
+            docs: None,
+        }
+    }
+}
+
+fn default_setting_patterns() -> Vec<String> {
+    [
+        "MACRO_CONFIG_INT(",
+        "MACRO_CONFIG_STR(",
+        "MACRO_CONFIG_COL(",
+    ]
+    .map(String::from)
+    .to_vec()
+}
+
+/// The TODO comments PRs add that issues are opened for, see [`crate::handlers::Todos`].
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct TodosConfig {
+    /// Text of added lines that's worth an issue, nothing is listed if empty.
+    #[serde(default = "default_todo_markers")]
+    pub markers: Vec<String>,
+    /// Open the issues when the PR is merged instead of on `!ddnetbot todo-issues`.
+    #[serde(default)]
+    pub auto: bool,
+}
+
+impl Default for TodosConfig {
+    fn default() -> Self {
+        Self {
+            markers: default_todo_markers(),
+            auto: false,
+        }
+    }
+}
+
+fn default_todo_markers() -> Vec<String> {
+    ["TODO", "FIXME"].map(String::from).to_vec()
+}
+
+/// Issues that need a demo to be reproduced, see [`crate::handlers::Demos`].
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct DemoRequestConfig {
+    /// Labels of gameplay or physics bugs, e.g. "bug: physics".
+    #[serde(default)]
+    pub labels: Vec<String>,
+    /// Words in the title or body of an issue hinting at one, e.g. "teleporter".
+    #[serde(default)]
+    pub keywords: Vec<String>,
+}
+
+/// What to do with the PRs of dependency update bots, see
+/// [`crate::handlers::DependencyUpdates`].
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct DependencyUpdatesConfig {
+    /// Logins of the bots.
+    #[serde(default = "default_dependency_bots")]
+    pub bots: Vec<String>,
+    #[serde(default = "default_dependency_label")]
+    pub label: String,
+    /// Approve patch-level updates.
+    #[serde(default)]
+    pub auto_approve: bool,
+    /// Enable auto-merge on patch-level updates, so they're merged once CI passes.
+    #[serde(default)]
+    pub auto_merge: bool,
+    /// Only these dependencies are approved and merged automatically, all if empty.
+    #[serde(default)]
+    pub allow: Vec<String>,
+    /// These dependencies are never approved or merged automatically.
+    #[serde(default)]
+    pub deny: Vec<String>,
+}
+
+fn default_dependency_bots() -> Vec<String> {
+    ["dependabot[bot]", "renovate[bot]"]
+        .map(String::from)
+        .to_vec()
+}
+
+fn default_dependency_label() -> String {
+    "dependencies".to_string()
+}
+
+/// Paths of the default branch that should only change through PRs.
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+pub struct ProtectedPathsConfig {
+    #[serde(default)]
+    pub paths: Vec<String>,
+    /// Number of the tracking issue alerts are commented on, in the pushed repo.
+    pub issue: Option<u64>,
+    /// Discord webhook URL alerts are posted to.
+    pub discord_webhook: Option<String>,
+}
+
+/// How discussions are triaged, see [`crate::handlers::Discussions`].
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct DiscussionsConfig {
+    /// Labels added to discussions by category name.
+    #[serde(default)]
+    pub category_labels: HashMap<String, Vec<String>>,
+    /// Words in the title or body of a new discussion hinting at a bug report.
+    #[serde(default = "default_bug_keywords")]
+    pub bug_keywords: Vec<String>,
+    /// Categories where no bug report suggestions are made, e.g. "Q&A".
+    #[serde(default)]
+    pub ignored_categories: Vec<String>,
+}
+
+impl Default for DiscussionsConfig {
+    fn default() -> Self {
+        Self {
+            category_labels: HashMap::new(),
+            bug_keywords: default_bug_keywords(),
+            ignored_categories: Vec::new(),
+        }
+    }
+}
+
+fn default_bug_keywords() -> Vec<String> {
+    ["crash", "bug", "broken", "doesn't work", "error"]
+        .map(String::from)
+        .to_vec()
+}
+
+/// A documentation page that may need updating when `paths` change, see
+/// [`crate::handlers::Docs`].
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct DocLink {
+    pub paths: Vec<String>,
+    pub title: String,
+    pub url: String,
+}
+
+/// A canned answer, see [`crate::handlers::Faq`].
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct FaqEntry {
+    /// Phrases of the question, e.g. "host a server".
+    pub patterns: Vec<String>,
+    /// The answer or a link to it, in Markdown.
+    pub answer: String,
+}
+
+/// Issues that belong in `repo`, see [`crate::handlers::Routing`].
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct RoutingRule {
+    /// Words in the title or body, e.g. "skin database".
+    pub keywords: Vec<String>,
+    /// `owner/name` of the repo.
+    pub repo: String,
+    /// Transfer the issue there instead of pointing the author to it. Needs the bot on both
+    /// repos.
+    #[serde(default)]
+    pub transfer: bool,
+}
+
+/// A label every repo should have, see [`crate::labels::sync`].
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct LabelDefinition {
+    pub name: String,
+    /// Hex color without `#`, e.g. `d73a4a`.
+    pub color: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+/// A Projects (v2) board, see [`crate::projects`].
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct ProjectConfig {
+    /// Node ID of the project, e.g. `PVT_kwDOAbc`.
+    pub id: String,
+    #[serde(default)]
+    pub fields: Vec<ProjectField>,
+}
+
+/// A single select field of the board, set from labels.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct ProjectField {
+    pub name: String,
+    /// Option of the field by label, the first label of the issue found here wins.
+    pub labels: HashMap<String, String>,
+}
+
+/// Where releases are announced, see [`crate::handlers::Releases`].
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+pub struct ReleasesConfig {
+    /// Discord webhook URLs.
+    #[serde(default)]
+    pub discord_webhooks: Vec<String>,
+    #[serde(default)]
+    pub matrix: Vec<MatrixRoom>,
+    /// Also announce prereleases.
+    #[serde(default)]
+    pub prereleases: bool,
+    /// Open and pin a "Known issues" issue per release.
+    #[serde(default)]
+    pub known_issues: bool,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct MatrixRoom {
+    /// e.g. `https://matrix.org`.
+    pub homeserver: String,
+    /// e.g. `!abcdef:matrix.org`.
+    pub room_id: String,
+    /// Token of the account posting the announcements.
+    pub access_token: String,
+}
+
+/// Private places security alerts go to, see [`crate::handlers::SecurityAlerts`].
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+pub struct SecurityAlertsConfig {
+    /// Discord webhook URL of a private channel.
+    pub discord_webhook: Option<String>,
+    /// Private repo (`owner/name`) a confidential issue is opened in per alert. The bot has to
+    /// be installed on it with the same installation as the alerting repos.
+    pub repo: Option<String>,
+    /// Whether issues moved here with `!ddnetbot security` are locked until they're assessed.
+    #[serde(default)]
+    pub lock_escalated: bool,
+}
+
+/// Reviewers requested on PRs that change a file under one of `paths`.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct ReviewRule {
+    pub paths: Vec<String>,
+    /// Logins.
+    #[serde(default)]
+    pub reviewers: Vec<String>,
+    /// Team slugs.
+    #[serde(default)]
+    pub teams: Vec<String>,
+}
+
+/// Reviewers picked by their recent commits to the files a PR changes, see
+/// [`crate::handlers::BlameReviewers`].
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct BlameReviewersConfig {
+    /// How many reviewers per PR at most.
+    #[serde(default = "default_blame_max")]
+    pub max: usize,
+    /// Only commits of the last days count.
+    #[serde(default = "default_blame_days")]
+    pub days: u64,
+    /// Request their reviews instead of suggesting them in a comment.
+    #[serde(default)]
+    pub request: bool,
+}
+
+fn default_blame_max() -> usize {
+    2
+}
+
+fn default_blame_days() -> u64 {
+    365
+}
+
+/// How many days a requested review may take, see [`crate::reviews::reminder_loop`].
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct ReviewSlaConfig {
+    #[serde(default = "default_review_days")]
+    pub days: u64,
+    /// Days by team slug, for reviews requested from a team.
+    #[serde(default)]
+    pub teams: HashMap<String, u64>,
+}
+
+/// See [`crate::handlers::Acknowledgements`].
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct AcknowledgementsConfig {
+    /// Issues getting one of these labels are acknowledged.
+    #[serde(default = "default_acknowledged_labels")]
+    pub labels: Vec<String>,
+    /// How many hours someone has to answer an acknowledged report before `ping` is mentioned.
+    #[serde(default = "default_response_hours")]
+    pub hours: u64,
+    /// Who is mentioned on reports nobody answered in time, e.g. `@ddnet/maintainers`. Nobody is
+    /// pinged if empty.
+    #[serde(default)]
+    pub ping: Vec<String>,
+}
+
+impl Default for AcknowledgementsConfig {
+    fn default() -> Self {
+        Self {
+            labels: default_acknowledged_labels(),
+            hours: default_response_hours(),
+            ping: Vec::new(),
+        }
+    }
+}
+
+fn default_acknowledged_labels() -> Vec<String> {
+    ["crash", "regression"].map(String::from).to_vec()
+}
+
+fn default_response_hours() -> u64 {
+    48
+}
+
+impl Default for ReviewSlaConfig {
+    fn default() -> Self {
+        Self {
+            days: default_review_days(),
+            teams: HashMap::new(),
+        }
+    }
+}
+
+impl ReviewSlaConfig {
+    pub fn days(&self, request: &ReviewRequest) -> u64 {
+        match self.teams.get(&request.reviewer) {
+            Some(days) if request.team => *days,
+            _ => self.days,
+        }
+    }
+}
+
+/// Hours of the day in UTC, from `start` up to `end`, during which non-urgent notifications are
+/// held back. They may wrap around midnight, e.g. from 22 to 7.
+#[derive(Debug, Clone, Copy, serde::Deserialize)]
+pub struct QuietHours {
+    pub start: u32,
+    pub end: u32,
+}
+
+impl QuietHours {
+    pub fn contains(&self, now: DateTime<Utc>) -> bool {
+        let hour = now.hour();
+        if self.start <= self.end {
+            (self.start..self.end).contains(&hour)
+        } else {
+            hour >= self.start || hour < self.end
+        }
+    }
+
+    /// How long until the quiet hours are over, zero outside of them.
+    pub fn remaining(&self, now: DateTime<Utc>) -> Duration {
+        if !self.contains(now) {
+            return Duration::ZERO;
+        }
+        let hours = (self.end + 24 - now.hour()) % 24;
+        let elapsed = u64::from(now.minute() * 60 + now.second());
+        Duration::from_secs(u64::from(hours) * 60 * 60 - elapsed)
+    }
+}
+
+fn default_review_days() -> u64 {
+    7
+}
+
+/// A rhai script run on events of one repo.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct ScriptConfig {
+    /// `owner/name` of the repo.
+    pub repo: String,
+    /// Event names (as in `X-GitHub-Event`) the script runs on.
+    pub events: Vec<String>,
+    pub file: PathBuf,
+}
+
+/// A WebAssembly plugin run on events of the given repos, or of every repo.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct PluginConfig {
+    /// `owner/name` of the repos, all of them if empty.
+    #[serde(default)]
+    pub repos: Vec<String>,
+    /// Event names (as in `X-GitHub-Event`) the plugin runs on.
+    pub events: Vec<String>,
+    pub file: PathBuf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+/// Settings read from the environment as written, `Env` would parse e.g. a secret of `0123`
+/// as a number.
+const STRING_KEYS: &[&str] = &[
+    "app_private_key_path",
+    "github_token",
+    "github_api_url",
+    "bind_address",
+    "webhook_path",
+    "tls_cert_path",
+    "tls_key_path",
+    "unix_socket_path",
+    "unix_socket_mode",
+    "webhook_secret",
+    "forward_targets",
+    "forward_secret",
+    "digest_repo",
+    "command_report_issue",
+    "subscription_digest_issue",
+    "label_hygiene_issue",
+    "database_path",
+    "log_format",
+    "capture_dir",
+    "translations_dir",
+    "redis_url",
+    "admin_token",
+    "snooze_label",
+    "min_supported_version",
+    "version_field",
+];
+
+/// Every missing or invalid setting found while loading the config.
+#[derive(Debug)]
+pub struct ConfigError(pub Vec<String>);
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "invalid configuration:")?;
+        for error in &self.0 {
+            writeln!(f, "  - {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// The URL without the user and password, e.g. `redis://<redacted>@host` for
+/// `redis://:pass@host`.
+fn redact_userinfo(url: &str) -> String {
+    let start = url.find("://").map_or(0, |x| x + 3);
+    let authority = url[start..]
+        .find('/')
+        .map_or(&url[start..], |end| &url[start..start + end]);
+    match authority.rfind('@') {
+        Some(at) => format!("{}<redacted>{}", &url[..start], &url[start + at..]),
+        None => url.to_string(),
+    }
+}
+
+impl Config {
+    /// The config file, `DDBOT_CONFIG` or `ddbot.toml`.
+    pub fn path() -> PathBuf {
+        std::env::var("DDBOT_CONFIG")
+            .unwrap_or("ddbot.toml".to_string())
+            .into()
+    }
+
+    pub fn load() -> Result<Self, ConfigError> {
+        let path = Self::path();
+        let strings: HashMap<&str, String> = STRING_KEYS
+            .iter()
+            .filter_map(|key| Some((*key, std::env::var(key.to_ascii_uppercase()).ok()?)))
+            .collect();
+        let figment = Figment::new()
+            .merge(Toml::file(path))
+            .merge(Env::raw().ignore(STRING_KEYS))
+            .merge(Serialized::defaults(strings));
+        Self::from_figment(&figment)
+    }
+
+    /// A copy that is safe to print.
+    pub fn redacted(&self) -> Self {
+        Self {
+            webhook_secret: self
+                .webhook_secret
+                .as_ref()
+                .map(|_| "<redacted>".to_string()),
+            forward_secret: self
+                .forward_secret
+                .as_ref()
+                .map(|_| "<redacted>".to_string()),
+            github_token: self.github_token.as_ref().map(|_| "<redacted>".to_string()),
+            redis_url: self.redis_url.as_deref().map(redact_userinfo),
+            admin_token: self.admin_token.as_ref().map(|_| "<redacted>".to_string()),
+            login: self.login.as_ref().map(|x| LoginConfig {
+                client_secret: "<redacted>".to_string(),
+                ..x.clone()
+            }),
+            subscription_webhooks: self
+                .subscription_webhooks
+                .keys()
+                .map(|x| (x.clone(), "<redacted>".to_string()))
+                .collect(),
+            summary_llm: self.summary_llm.as_ref().map(|x| SummaryLlmConfig {
+                api_key: "<redacted>".to_string(),
+                ..x.clone()
+            }),
+            benchmarks: self.benchmarks.as_ref().map(|x| BenchmarksConfig {
+                token: "<redacted>".to_string(),
+                ..x.clone()
+            }),
+            triggers: self
+                .triggers
+                .iter()
+                .map(|(name, x)| {
+                    let caller = TriggerCaller {
+                        secret: "<redacted>".to_string(),
+                        ..x.clone()
+                    };
+                    (name.clone(), caller)
+                })
+                .collect(),
+            protected_paths: ProtectedPathsConfig {
+                discord_webhook: self
+                    .protected_paths
+                    .discord_webhook
+                    .as_ref()
+                    .map(|_| "<redacted>".to_string()),
+                ..self.protected_paths.clone()
+            },
+            releases: ReleasesConfig {
+                discord_webhooks: self
+                    .releases
+                    .discord_webhooks
+                    .iter()
+                    .map(|_| "<redacted>".to_string())
+                    .collect(),
+                matrix: self
+                    .releases
+                    .matrix
+                    .iter()
+                    .map(|x| MatrixRoom {
+                        access_token: "<redacted>".to_string(),
+                        ..x.clone()
+                    })
+                    .collect(),
+                ..self.releases.clone()
+            },
+            security_alerts: SecurityAlertsConfig {
+                discord_webhook: self
+                    .security_alerts
+                    .discord_webhook
+                    .as_ref()
+                    .map(|_| "<redacted>".to_string()),
+                ..self.security_alerts.clone()
+            },
+            ..self.clone()
+        }
+    }
+
+    pub fn from_figment(figment: &Figment) -> Result<Self, ConfigError> {
+        let mut loader = Loader {
+            figment,
+            errors: Vec::new(),
+        };
+
+        let github_token: Option<String> = loader.optional("github_token");
+        let (github_app_id, app_private_key_path) = if github_token.is_some() {
+            (
+                loader.optional("github_app_id"),
+                loader.optional("app_private_key_path"),
+            )
+        } else {
+            (
+                loader.required("github_app_id"),
+                loader.required("app_private_key_path"),
+            )
+        };
+        let github_api_url: String =
+            loader.or("github_api_url", "https://api.github.com".to_string());
+        let bind_address = loader.or("bind_address", "0.0.0.0".to_string());
+        let port = loader.or("port", 3000);
+        let webhook_path: String = loader.or("webhook_path", "/".to_string());
+        let tls_cert_path = loader.optional("tls_cert_path");
+        let tls_key_path = loader.optional("tls_key_path");
+        let unix_socket_path = loader.optional("unix_socket_path");
+        let unix_socket_mode: String = loader.or("unix_socket_mode", "660".to_string());
+        let webhook_secret: Option<String> = loader.optional("webhook_secret");
+        let forward_targets: String = loader.or("forward_targets", String::new());
+        let forward_secret = loader.optional("forward_secret");
+        let feed_refresh_secs = loader.or("feed_refresh_secs", 600);
+        let digest_repo: Option<String> = loader.optional("digest_repo");
+        let command_report_issue: Option<String> = loader.optional("command_report_issue");
+        let subscription_digest_issue: Option<String> =
+            loader.optional("subscription_digest_issue");
+        let subscription_digest_secs = loader.or("subscription_digest_secs", 24 * 60 * 60);
+        let digest_refresh_secs = loader.or("digest_refresh_secs", 6 * 60 * 60);
+        let conflict_check_secs = loader.or("conflict_check_secs", 30 * 60);
+        let tracking_progress_secs = loader.or("tracking_progress_secs", 60 * 60);
+        let triage_aging_secs = loader.or("triage_aging_secs", 6 * 60 * 60);
+        let milestone_snapshot_secs = loader.or("milestone_snapshot_secs", 6 * 60 * 60);
+        let stats_sync_secs = loader.or("stats_sync_secs", 6 * 60 * 60);
+        let repo_sync_secs = loader.or("repo_sync_secs", 60 * 60);
+        let label_hygiene_secs = loader.or("label_hygiene_secs", 7 * 24 * 60 * 60);
+        let label_hygiene_issue: Option<String> = loader.optional("label_hygiene_issue");
+        let label_hygiene_autofix = loader.or("label_hygiene_autofix", false);
+        let event_timeout_secs = loader.or("event_timeout_secs", 5 * 60);
+        let database_path = loader.or("database_path", PathBuf::from("ddbot.db"));
+        let rate_limit_threshold = loader.or("rate_limit_threshold", 500);
+        let max_workers = loader.or("max_workers", 16);
+        let max_workers_per_installation = loader.or("max_workers_per_installation", 4);
+        let command_rate_limit = loader.or("command_rate_limit", 5);
+        let command_rate_window_secs = loader.or("command_rate_window_secs", 600);
+        // GitHub caps webhook payloads at 25 MB.
+        let max_body_bytes = loader.or("max_body_bytes", 25 * 1024 * 1024);
+        let max_api_calls_per_event = loader.or("max_api_calls_per_event", 200);
+        let max_review_load = loader.or("max_review_load", 5);
+        let blocker_ping_days = loader.or("blocker_ping_days", 7);
+        let slo_first_response_hours: Option<u64> = loader.optional("slo_first_response_hours");
+        let slo_triage_hours: Option<u64> = loader.optional("slo_triage_hours");
+        let quiet_hours: Option<QuietHours> = loader.optional("quiet_hours");
+        let log_format = loader.or("log_format", LogFormat::Text);
+        let capture_dir: Option<PathBuf> = loader.optional("capture_dir");
+        let dry_run = loader.or("dry_run", false);
+        let redis_url: Option<String> = loader.optional("redis_url");
+        let disk_queue = loader.or("disk_queue", false);
+        let dco = loader.or("dco", false);
+        let summary_check = loader.or("summary_check", false);
+        let setup_issue = loader.or("setup_issue", true);
+        let admin_token: Option<String> = loader.optional("admin_token");
+        let login: Option<LoginConfig> = loader.optional("login");
+        let benchmarks: Option<BenchmarksConfig> = loader.optional("benchmarks");
+        let triggers: HashMap<String, TriggerCaller> = loader.or("triggers", HashMap::new());
+        let batch_windows: HashMap<String, u64> = loader.or("batch_windows", HashMap::new());
+        let summary_llm: Option<SummaryLlmConfig> = loader.optional("summary_llm");
+        let scripts: Vec<ScriptConfig> = loader.or("scripts", Vec::new());
+        let plugins: Vec<PluginConfig> = loader.or("plugins", Vec::new());
+        let templates = loader.or("templates", HashMap::new());
+        let translations_dir: Option<PathBuf> = loader.optional("translations_dir");
+        let languages = loader.or("languages", HashMap::new());
+        let checklist = loader.or("checklist", Vec::new());
+        let good_first_issues = loader.or("good_first_issues", GoodFirstIssuesConfig::default());
+        let review_rules = loader.or("review_rules", Vec::new());
+        let blame_reviewers: Option<BlameReviewersConfig> = loader.optional("blame_reviewers");
+        let review_pool: Vec<String> = loader.or("review_pool", Vec::new());
+        let review_sla = loader.or("review_sla", ReviewSlaConfig::default());
+        let acknowledgements = loader.or("acknowledgements", AcknowledgementsConfig::default());
+        let protected_paths = loader.or("protected_paths", ProtectedPathsConfig::default());
+        let commit_lint: Option<CommitLintConfig> = loader.optional("commit_lint");
+        let cla: Option<ClaConfig> = loader.optional("cla");
+        let license_header: Option<LicenseHeaderConfig> = loader.optional("license_header");
+        let demo_request: Option<DemoRequestConfig> = loader.optional("demo_request");
+        let dependency_updates: Option<DependencyUpdatesConfig> =
+            loader.optional("dependency_updates");
+        let discussions = loader.or("discussions", DiscussionsConfig::default());
+        let releases = loader.or("releases", ReleasesConfig::default());
+        let project: Option<ProjectConfig> = loader.optional("project");
+        let labels: Vec<LabelDefinition> = loader.or("labels", Vec::new());
+        let routing: Vec<RoutingRule> = loader.or("routing", Vec::new());
+        let faq: Vec<FaqEntry> = loader.or("faq", Vec::new());
+        let docs: Vec<DocLink> = loader.or("docs", Vec::new());
+        let assets: Vec<AssetRule> = loader.or("assets", Vec::new());
+        let settings = loader.or("settings", SettingsConfig::default());
+        let todos = loader.or("todos", TodosConfig::default());
+        let protocol_paths: Vec<String> = loader.or("protocol_paths", Vec::new());
+        let screenshot_paths: Vec<String> = loader.or("screenshot_paths", Vec::new());
+        let bugfix_labels: Vec<String> = loader.or("bugfix_labels", vec!["bug".to_string()]);
+        let api_headers: Vec<String> = loader.or("api_headers", Vec::new());
+        let label_help: HashMap<String, String> = loader.or(
+            "label_help",
+            [
+                ("needs-rebase", "help_needs_rebase"),
+                ("needs-changelog", "help_needs_changelog"),
+                ("needs-screenshot", "help_needs_screenshot"),
+            ]
+            .into_iter()
+            .map(|(label, template)| (label.to_string(), template.to_string()))
+            .collect(),
+        );
+        let security_alerts: SecurityAlertsConfig =
+            loader.or("security_alerts", SecurityAlertsConfig::default());
+        let maintainer_only_labels = loader.or(
+            "maintainer_only_labels",
+            vec!["maintainer-only".to_string(), "blocked".to_string()],
+        );
+        let adopt_after_days = loader.or("adopt_after_days", 30);
+        let label_autocorrect = loader.or("label_autocorrect", false);
+        let triage_areas: HashMap<String, Vec<String>> = loader.or("triage_areas", HashMap::new());
+        let subscription_webhooks: HashMap<String, String> =
+            loader.or("subscription_webhooks", HashMap::new());
+        let command_aliases: HashMap<String, String> = loader.or("command_aliases", HashMap::new());
+        let command_permissions: HashMap<String, CommandPermission> =
+            loader.or("command_permissions", HashMap::new());
+        let mentionable_teams: Vec<String> = loader.or("mentionable_teams", Vec::new());
+        let snooze_label = loader.or("snooze_label", TRIAGE_LABEL.to_string());
+        let min_supported_version: Option<String> = loader.optional("min_supported_version");
+        let version_field = loader.or("version_field", "Version".to_string());
+        let repos: HashMap<String, RepoConfig> = loader.or("repos", HashMap::new());
+        let shadow_handlers: Vec<String> = loader.or("shadow_handlers", Vec::new());
+        let rollout: HashMap<String, RolloutConfig> = loader.or("rollout", HashMap::new());
+
+        if let Some(dir) = &capture_dir
+            && !dir.is_dir()
+        {
+            loader.invalid("capture_dir", "must be an existing directory");
+        }
+        if let Some(dir) = &translations_dir
+            && !dir.is_dir()
+        {
+            loader.invalid("translations_dir", "must be an existing directory");
+        }
+        if github_api_url.parse::<axum::http::Uri>().is_err() {
+            loader.invalid("github_api_url", "must be a URL");
+        }
+        for script in &scripts {
+            if !script.file.is_file() {
+                loader.invalid(
+                    "scripts",
+                    &format!("{} doesn't exist", script.file.display()),
+                );
+            }
+        }
+        for plugin in &plugins {
+            if !plugin.file.is_file() {
+                loader.invalid(
+                    "plugins",
+                    &format!("{} doesn't exist", plugin.file.display()),
+                );
+            }
+        }
+        for repo in repos.keys() {
+            if !repo.contains('/') {
+                loader.invalid("repos", &format!("{} isn't in the owner/name format", repo));
+            }
+        }
+        if redis_url.is_some() && disk_queue {
+            loader.invalid("disk_queue", "can't be used together with REDIS_URL");
+        }
+        // Anyone could get forged deliveries signed with the forward secret otherwise.
+        if !forward_targets.trim().is_empty() && webhook_secret.is_none() {
+            loader.invalid(
+                "forward_targets",
+                "needs WEBHOOK_SECRET, only deliveries from GitHub are forwarded",
+            );
+        }
+        if !webhook_path.starts_with('/') {
+            loader.invalid("webhook_path", "must start with /");
+        }
+        if tls_cert_path.is_some() != tls_key_path.is_some() {
+            loader.invalid("tls_cert_path", "must be set together with TLS_KEY_PATH");
+        }
+        let unix_socket_mode = u32::from_str_radix(&unix_socket_mode, 8).unwrap_or_else(|_| {
+            loader.invalid("unix_socket_mode", "must be an octal mode like 660");
+            0
+        });
+        let digest_repo = digest_repo.and_then(|x| match x.split_once('/') {
+            Some((owner, name)) => Some((owner.to_string(), name.to_string())),
+            None => {
+                loader.invalid("digest_repo", "must be in the owner/name format");
+                None
+            }
+        });
+        let mut issue_ref = |key, x: Option<String>| {
+            let x = x?;
+            match x
+                .split_once('#')
+                .map(|(repo, number)| (repo, number.parse()))
+            {
+                Some((repo, Ok(number))) if repo.contains('/') => Some((repo.to_string(), number)),
+                _ => {
+                    loader.invalid(key, "must be in the owner/name#number format");
+                    None
+                }
+            }
+        };
+        let command_report_issue = issue_ref("command_report_issue", command_report_issue);
+        let subscription_digest_issue =
+            issue_ref("subscription_digest_issue", subscription_digest_issue);
+        let label_hygiene_issue = issue_ref("label_hygiene_issue", label_hygiene_issue);
+        for (key, value) in [
+            ("feed_refresh_secs", feed_refresh_secs),
+            ("digest_refresh_secs", digest_refresh_secs),
+            ("conflict_check_secs", conflict_check_secs),
+            ("tracking_progress_secs", tracking_progress_secs),
+            ("triage_aging_secs", triage_aging_secs),
+            ("milestone_snapshot_secs", milestone_snapshot_secs),
+            ("stats_sync_secs", stats_sync_secs),
+            ("repo_sync_secs", repo_sync_secs),
+            ("label_hygiene_secs", label_hygiene_secs),
+            ("subscription_digest_secs", subscription_digest_secs),
+            ("event_timeout_secs", event_timeout_secs),
+            ("command_rate_window_secs", command_rate_window_secs),
+        ] {
+            if value == 0 {
+                loader.invalid(key, "must be greater than 0");
+            }
+        }
+        if let Some(quiet_hours) = quiet_hours
+            && (quiet_hours.start >= 24
+                || quiet_hours.end >= 24
+                || quiet_hours.start == quiet_hours.end)
+        {
+            loader.invalid(
+                "quiet_hours",
+                "start and end must be different hours from 0 to 23",
+            );
+        }
+        for (key, value) in [
+            ("max_workers", max_workers),
+            ("max_workers_per_installation", max_workers_per_installation),
+        ] {
+            if value == 0 {
+                loader.invalid(key, "must be greater than 0");
+            }
+        }
+
+        if !loader.errors.is_empty() {
+            return Err(ConfigError(loader.errors));
+        }
+
+        Ok(Self {
+            github_app_id,
+            app_private_key_path,
+            github_token,
+            github_api_url,
+            bind_address,
+            port,
+            webhook_path,
+            tls_cert_path,
+            tls_key_path,
+            unix_socket_path,
+            unix_socket_mode,
+            webhook_secret,
+            forward_targets,
+            forward_secret,
+            feed_refresh_secs,
+            digest_repo,
+            digest_refresh_secs,
+            command_report_issue,
+            subscription_digest_issue,
+            subscription_digest_secs,
+            conflict_check_secs,
+            tracking_progress_secs,
+            triage_aging_secs,
+            milestone_snapshot_secs,
+            stats_sync_secs,
+            repo_sync_secs,
+            label_hygiene_secs,
+            label_hygiene_issue,
+            label_hygiene_autofix,
+            event_timeout_secs,
+            database_path,
+            rate_limit_threshold,
+            max_workers,
+            max_workers_per_installation,
+            command_rate_limit,
+            command_rate_window_secs,
+            max_body_bytes,
+            max_api_calls_per_event,
+            max_review_load,
+            blocker_ping_days,
+            slo_first_response_hours,
+            slo_triage_hours,
+            quiet_hours,
+            log_format,
+            capture_dir,
+            dry_run,
+            redis_url,
+            disk_queue,
+            dco,
+            summary_check,
+            setup_issue,
+            admin_token,
+            login,
+            benchmarks,
+            triggers,
+            batch_windows,
+            summary_llm,
+            scripts,
+            plugins,
+            templates,
+            translations_dir,
+            languages,
+            checklist,
+            good_first_issues,
+            review_rules,
+            blame_reviewers,
+            review_pool,
+            review_sla,
+            acknowledgements,
+            protected_paths,
+            commit_lint,
+            cla,
+            license_header,
+            demo_request,
+            dependency_updates,
+            security_alerts,
+            discussions,
+            releases,
+            project,
+            labels,
+            routing,
+            faq,
+            docs,
+            assets,
+            settings,
+            todos,
+            protocol_paths,
+            screenshot_paths,
+            bugfix_labels,
+            label_help,
+            api_headers,
+            maintainer_only_labels,
+            adopt_after_days,
+            label_autocorrect,
+            triage_areas,
+            subscription_webhooks,
+            command_aliases,
+            command_permissions,
+            mentionable_teams,
+            snooze_label,
+            min_supported_version,
+            version_field,
+            repos,
+            shadow_handlers,
+            rollout,
+        })
+    }
+}
+
+/// Extracts settings one at a time so every problem is reported, not just the first one.
+struct Loader<'a> {
+    figment: &'a Figment,
+    errors: Vec<String>,
+}
+
+impl Loader<'_> {
+    fn required<T: DeserializeOwned>(&mut self, key: &str) -> Option<T> {
+        if !self.figment.contains(key) {
+            self.errors
+                .push(format!("{}: missing", key.to_ascii_uppercase()));
+            return None;
+        }
+        self.optional(key)
+    }
+
+    fn optional<T: DeserializeOwned>(&mut self, key: &str) -> Option<T> {
+        if !self.figment.contains(key) {
+            return None;
+        }
+        match self.figment.extract_inner(key) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                self.invalid(key, &e.kind.to_string());
+                None
+            }
+        }
+    }
+
+    fn or<T: DeserializeOwned>(&mut self, key: &str, default: T) -> T {
+        self.optional(key).unwrap_or(default)
+    }
+
+    fn invalid(&mut self, key: &str, reason: &str) {
+        self.errors
+            .push(format!("{}: {}", key.to_ascii_uppercase(), reason));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn redacted_config_has_no_secrets() {
+        // Every field holding a secret. New ones have to be added here and to `redacted`, the
+        // test can't find them by itself.
+        let figment = Figment::from(Serialized::defaults(json!({
+            "github_token": "hunter2-github-token",
+            "database_path": ":memory:",
+            "webhook_secret": "hunter2-webhook",
+            "forward_secret": "hunter2-forward",
+            "admin_token": "hunter2-admin",
+            "redis_url": "redis://:hunter2-redis@localhost:6379/0",
+            "login": {
+                "client_id": "id",
+                "client_secret": "hunter2-login",
+                "teams": ["ddnet/maintainers"],
+            },
+            "subscription_webhooks": { "heinrich5991": "https://example.org/hunter2-subscription" },
+            "summary_llm": {
+                "url": "https://api.example.org/v1/chat/completions",
+                "api_key": "hunter2-llm",
+                "model": "model",
+            },
+            "benchmarks": { "token": "hunter2-benchmarks" },
+            "triggers": { "release": { "secret": "hunter2-trigger", "scopes": [] } },
+            "protected_paths": { "discord_webhook": "https://discord.com/api/webhooks/hunter2-paths" },
+            "releases": {
+                "discord_webhooks": ["https://discord.com/api/webhooks/hunter2-releases"],
+                "matrix": [{
+                    "homeserver": "https://matrix.org",
+                    "room_id": "!abc:matrix.org",
+                    "access_token": "hunter2-matrix",
+                }],
+            },
+            "security_alerts": { "discord_webhook": "https://discord.com/api/webhooks/hunter2-security" },
+        })));
+        let config = Config::from_figment(&figment).unwrap();
+        assert!(format!("{:?}", config).contains("hunter2-redis"));
+        let printed = format!("{:?}", config.redacted());
+        assert!(!printed.contains("hunter2"), "{}", printed);
+        assert_eq!(
+            config.redacted().redis_url.as_deref(),
+            Some("redis://<redacted>@localhost:6379/0")
+        );
+        assert_eq!(redact_userinfo("redis://localhost"), "redis://localhost");
+    }
+
+    #[test]
+    fn every_problem_is_reported() {
+        let figment = Figment::from(Serialized::defaults(json!({
+            "port": "not a port",
+            "feed_refresh_secs": 0,
+            "forward_targets": "https://staging.example/",
+        })));
+        let ConfigError(errors) = Config::from_figment(&figment).unwrap_err();
+        for key in [
+            "GITHUB_APP_ID",
+            "APP_PRIVATE_KEY_PATH",
+            "PORT",
+            "FEED_REFRESH_SECS",
+            "FORWARD_TARGETS",
+        ] {
+            assert!(
+                errors.iter().any(|x| x.starts_with(&format!("{}: ", key))),
+                "{:?}",
+                errors
+            );
+        }
+    }
+
+    #[test]
+    // The error type is figment's.
+    #[allow(clippy::result_large_err)]
+    fn environment_overrides_the_file_as_written() {
+        figment::Jail::expect_with(|jail| {
+            jail.create_file(
+                "ddbot.toml",
+                r#"
+                github_token = "from-file"
+                admin_token = "from-file"
+                database_path = ":memory:"
+                port = 4000
+                "#,
+            )?;
+            jail.set_env("DDBOT_CONFIG", "ddbot.toml");
+            jail.set_env("GITHUB_TOKEN", "0123");
+            jail.set_env("ADMIN_TOKEN", "true");
+            jail.set_env("PORT", "5000");
+            let config = Config::load().map_err(|e| e.to_string())?;
+            // Strings that look like numbers or booleans stay strings.
+            assert_eq!(config.github_token.as_deref(), Some("0123"));
+            assert_eq!(config.admin_token.as_deref(), Some("true"));
+            assert_eq!(config.port, 5000);
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,205 @@
+use std::{collections::HashSet, time::Duration};
+
+use octocrab::{Octocrab, models::Repository, params};
+use serde_json::json;
+use tracing::{info, warn};
+
+use crate::{
+    AppState, batch, bulk, github,
+    handlers::Error,
+    templates::Templates,
+    workflow::{self, Transition},
+};
+
+pub const NEEDS_REBASE_LABEL: &str = "needs-rebase";
+
+/// How often GitHub is asked whether a PR is mergeable before giving up, it computes it in the
+/// background after a push.
+const MERGEABLE_ATTEMPTS: u32 = 6;
+const MERGEABLE_DELAY: Duration = Duration::from_secs(10);
+
+/// Periodically checks every open PR for conflicts, so `needs-rebase` comes and goes with them
+/// even when no delivery says so.
+pub async fn poll_loop(state: AppState, period: Duration) {
+    let mut interval = tokio::time::interval(period);
+
+    loop {
+        interval.tick().await;
+        let repos = match github::installed_repos(&state.installations).await {
+            Ok(x) => x,
+            Err(e) => {
+                warn!("Failed to list repos for the conflict check: {}", e);
+                continue;
+            }
+        };
+        for (installation, client, repo) in repos {
+            let rules = state.rules();
+            if !rules
+                .handlers
+                .enabled("conflicts", repo.full_name.as_deref())
+            {
+                continue;
+            }
+            state
+                .rate_limits
+                .wait_for_budget(installation, "conflict check")
+                .await;
+            if let Err(e) = poll_repo(&state, &rules.templates, &client, &repo).await {
+                warn!(
+                    "Failed to check the PRs of {} for conflicts: {}",
+                    repo.full_name.as_deref().unwrap_or_default(),
+                    e
+                );
+            }
+        }
+    }
+}
+
+async fn poll_repo(
+    state: &AppState,
+    templates: &Templates,
+    client: &Octocrab,
+    repo: &Repository,
+) -> Result<(), Error> {
+    let owner = repo.owner.as_ref().ok_or("repository without owner")?;
+    let pulls = client.pulls(&owner.login, &repo.name);
+    let page =
+        github::retry(|| pulls.list().state(params::State::Open).per_page(100).send()).await?;
+    // The first request makes GitHub compute what's unknown, the next round picks it up.
+    for pr in bulk::pages(client, page).await? {
+        if let Err(e) = reconcile(state, templates, client, repo, pr.number).await {
+            warn!(
+                "Failed to check #{} of {} for conflicts: {}",
+                pr.number,
+                repo.full_name.as_deref().unwrap_or_default(),
+                e
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Checks the PRs like [`reconcile`], asking GitHub again every [`MERGEABLE_DELAY`] about the
+/// ones it didn't find out about yet, up to [`MERGEABLE_ATTEMPTS`] times for all of them
+/// together. A PR failing doesn't stop the others, the errors are returned together.
+pub async fn reconcile_all(
+    state: &AppState,
+    templates: &Templates,
+    client: &Octocrab,
+    repo: &Repository,
+    mut numbers: Vec<u64>,
+) -> Result<(), Error> {
+    let mut errors = Vec::new();
+    for attempt in 1..=MERGEABLE_ATTEMPTS {
+        if numbers.is_empty() {
+            break;
+        }
+        if attempt > 1 {
+            tokio::time::sleep(MERGEABLE_DELAY).await;
+        }
+        let mut unknown = Vec::new();
+        for number in numbers {
+            match reconcile(state, templates, client, repo, number).await {
+                Ok(true) => {}
+                Ok(false) => unknown.push(number),
+                Err(e) => errors.push(format!("#{}: {}", number, e)),
+            }
+        }
+        numbers = unknown;
+    }
+    if !errors.is_empty() {
+        return Err(format!("failed to check PRs for conflicts: {}", errors.join(", ")).into());
+    }
+    Ok(())
+}
+
+/// Moves the PR to `needs-rebase` and tells the author if it conflicts, or back to
+/// `waiting-for-reviews` if the conflict is gone and marks the comments telling the author as
+/// resolved. The author is told once per conflict, while the label is on.
+///
+/// The PR is fetched again, listed PRs don't say whether they can be merged. Returns false if
+/// GitHub didn't find out yet, it does in the background after a push.
+pub async fn reconcile(
+    state: &AppState,
+    templates: &Templates,
+    client: &Octocrab,
+    repo: &Repository,
+    number: u64,
+) -> Result<bool, Error> {
+    let owner = repo.owner.as_ref().ok_or("repository without owner")?;
+    let pulls = client.pulls(&owner.login, &repo.name);
+    let pr = github::retry(|| pulls.get(number)).await?;
+    let before: HashSet<String> = pr.labels.iter().flatten().map(|x| x.name.clone()).collect();
+    let labeled = before.contains(NEEDS_REBASE_LABEL);
+
+    match pr.mergeable {
+        None => return Ok(false),
+        Some(false) if !labeled => {
+            info!("#{} conflicts with {}", pr.number, pr.base.ref_field);
+            let mut after = before.clone();
+            workflow::apply(&mut after, Transition::Conflict)?;
+            workflow::sync(state, client, repo.id, pr.number, &before, &after).await?;
+
+            let comment = templates.render(
+                repo.full_name.as_deref().unwrap_or_default(),
+                "needs_rebase",
+                json!({
+                    "author": pr.user.as_ref().map(|x| x.login.as_str()).unwrap_or_default(),
+                    "base": pr.base.ref_field,
+                }),
+            )?;
+            let comment = format!("{}\n{}", comment, help_marker(NEEDS_REBASE_LABEL));
+            batch::comment(state, client, repo.id, pr.number, &comment).await?;
+        }
+        Some(true) if labeled => {
+            info!("#{} doesn't conflict anymore", pr.number);
+            let mut after = before.clone();
+            if pr.draft == Some(true) {
+                after.remove(NEEDS_REBASE_LABEL);
+            } else {
+                workflow::apply(&mut after, Transition::Rebase)?;
+            }
+            workflow::sync(state, client, repo.id, pr.number, &before, &after).await?;
+            resolve_help(state, client, repo, pr.number, NEEDS_REBASE_LABEL).await?;
+        }
+        _ => {}
+    }
+    Ok(true)
+}
+
+/// Hidden in the comments explaining a label, see [`resolve_help`].
+pub fn help_marker(label: &str) -> String {
+    format!("<!-- ddbot:help:{} -->", label)
+}
+
+/// Minimizes the comments of the bot explaining `label` as resolved, once it's off the issue or
+/// PR.
+pub async fn resolve_help(
+    state: &AppState,
+    client: &Octocrab,
+    repo: &Repository,
+    number: u64,
+    label: &str,
+) -> Result<(), Error> {
+    let issues = client.issues_by_id(repo.id);
+    let page = github::retry(|| issues.list_comments(number).per_page(100).send()).await?;
+    let marker = help_marker(label);
+    for comment in bulk::pages(client, page).await? {
+        if !comment.body.as_deref().is_some_and(|x| x.contains(&marker)) {
+            continue;
+        }
+        // Anyone can quote the marker, and the REST API doesn't tell whether it's hidden.
+        let res = github::graphql(
+            client,
+            "query($id: ID!) { node(id: $id) { ... on IssueComment { viewerDidAuthor isMinimized } } }",
+            json!({ "id": comment.node_id }),
+        )
+        .await?;
+        let node = &res["data"]["node"];
+        if node["viewerDidAuthor"] != true || node["isMinimized"] == true {
+            continue;
+        }
+        github::minimize_comment(state.dry_run, client, &comment.node_id, "RESOLVED").await?;
+    }
+    Ok(())
+}
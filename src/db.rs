@@ -0,0 +1,1961 @@
+use std::{
+    collections::BTreeMap,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, Utc};
+use octocrab::models::{InstallationId, RepositoryId};
+use rusqlite::{Connection, OptionalExtension, params};
+
+use crate::queue::Job;
+
+/// Schema migrations, applied in order and tracked with `PRAGMA user_version`.
+const MIGRATIONS: &[&str] = &[
+    // 1: audit log of every action taken on behalf of a user, and contributions per user.
+    "CREATE TABLE audit_log (
+        id INTEGER PRIMARY KEY,
+        created_at TEXT NOT NULL,
+        repo TEXT NOT NULL,
+        number INTEGER,
+        actor TEXT NOT NULL,
+        action TEXT NOT NULL,
+        detail TEXT NOT NULL DEFAULT ''
+    );
+    CREATE INDEX audit_log_actor ON audit_log (actor);
+    CREATE TABLE contributions (
+        repo TEXT NOT NULL,
+        number INTEGER NOT NULL,
+        login TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        UNIQUE (repo, number, login, kind)
+    );
+    CREATE TABLE kv (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );",
+    // 2: the status comment of each PR and its sections as a JSON object.
+    "CREATE TABLE status_comments (
+        repo_id INTEGER NOT NULL,
+        number INTEGER NOT NULL,
+        comment_id INTEGER,
+        sections TEXT NOT NULL,
+        PRIMARY KEY (repo_id, number)
+    );",
+    // 3: the repos the app is installed on.
+    "CREATE TABLE repos (
+        repo_id INTEGER PRIMARY KEY,
+        installation_id INTEGER NOT NULL,
+        full_name TEXT NOT NULL
+    );",
+    // 4: deliveries waiting to be handled, see `crate::queue::DiskQueue`.
+    "CREATE TABLE queue (
+        id INTEGER PRIMARY KEY,
+        delivery TEXT NOT NULL UNIQUE,
+        job TEXT NOT NULL,
+        taken INTEGER NOT NULL DEFAULT 0
+    );",
+    // 5: deliveries a handler failed on, kept to be run again, see `crate::handlers::redrive`.
+    "CREATE TABLE dead_letters (
+        id INTEGER PRIMARY KEY,
+        created_at TEXT NOT NULL,
+        handler TEXT NOT NULL,
+        error TEXT NOT NULL,
+        job TEXT NOT NULL
+    );",
+    // 6: users allowed to run collaborator commands on one issue or PR, by lowercase login.
+    "CREATE TABLE delegations (
+        repo_id INTEGER NOT NULL,
+        number INTEGER NOT NULL,
+        login TEXT NOT NULL,
+        granted_by TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        PRIMARY KEY (repo_id, number, login)
+    );",
+    // 7: pending review requests, `reviewer` is a login or a team slug.
+    "CREATE TABLE review_requests (
+        repo_id INTEGER NOT NULL,
+        number INTEGER NOT NULL,
+        reviewer TEXT NOT NULL,
+        team INTEGER NOT NULL,
+        installation_id INTEGER NOT NULL,
+        full_name TEXT NOT NULL,
+        requested_at TEXT NOT NULL,
+        reminded INTEGER NOT NULL DEFAULT 0,
+        PRIMARY KEY (repo_id, number, reviewer, team)
+    );",
+    // 8: users who accepted the CLA, by lowercase login.
+    "CREATE TABLE cla_signatures (
+        login TEXT PRIMARY KEY,
+        accepted_at TEXT NOT NULL
+    );",
+    // 9: issues and PRs in the task lists of tracking issues, `item` as a lowercase
+    // `owner/name#number`.
+    "CREATE TABLE tracked_items (
+        repo_id INTEGER NOT NULL,
+        number INTEGER NOT NULL,
+        item TEXT NOT NULL,
+        PRIMARY KEY (repo_id, number, item)
+    );
+    CREATE INDEX tracked_items_item ON tracked_items (item);",
+    // 10: Discord messages held back during the quiet hours, see `crate::quiet`.
+    "CREATE TABLE held_messages (
+        id INTEGER PRIMARY KEY,
+        created_at TEXT NOT NULL,
+        url TEXT NOT NULL,
+        content TEXT NOT NULL
+    );",
+    // 11: reminders set with `!ddnetbot remind`, see `crate::reminders`.
+    "CREATE TABLE reminders (
+        id INTEGER PRIMARY KEY,
+        installation_id INTEGER NOT NULL,
+        repo_id INTEGER NOT NULL,
+        full_name TEXT NOT NULL,
+        number INTEGER NOT NULL,
+        login TEXT NOT NULL,
+        message TEXT NOT NULL,
+        due_at TEXT NOT NULL
+    );
+    CREATE INDEX reminders_due_at ON reminders (due_at);",
+    // 12: the label a snoozed issue gets back, see `!ddnetbot snooze`.
+    "ALTER TABLE reminders ADD COLUMN label TEXT;",
+    // 13: benchmark results posted by CI, see `crate::benchmarks`.
+    "CREATE TABLE benchmarks (
+        id INTEGER PRIMARY KEY,
+        created_at TEXT NOT NULL,
+        repo_id INTEGER NOT NULL,
+        sha TEXT NOT NULL,
+        branch TEXT NOT NULL,
+        name TEXT NOT NULL,
+        value REAL NOT NULL
+    );
+    CREATE INDEX benchmarks_branch ON benchmarks (repo_id, branch, name);",
+    // 14: checks that failed and then passed on the same commit, see
+    // `crate::handlers::FlakyTests`.
+    "CREATE TABLE flaky_tests (
+        repo_id INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        issue_number INTEGER,
+        PRIMARY KEY (repo_id, name)
+    );
+    CREATE TABLE flaky_occurrences (
+        id INTEGER PRIMARY KEY,
+        created_at TEXT NOT NULL,
+        repo_id INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        sha TEXT NOT NULL,
+        pr INTEGER,
+        url TEXT NOT NULL
+    );
+    CREATE INDEX flaky_occurrences_name ON flaky_occurrences (repo_id, name);",
+    // 15: finished workflow jobs, see `crate::handlers::CiMetrics`.
+    "CREATE TABLE ci_jobs (
+        job_id INTEGER PRIMARY KEY,
+        full_name TEXT NOT NULL,
+        workflow TEXT NOT NULL,
+        name TEXT NOT NULL,
+        queued_secs REAL NOT NULL,
+        run_secs REAL NOT NULL,
+        completed_at TEXT NOT NULL
+    );
+    CREATE INDEX ci_jobs_completed_at ON ci_jobs (completed_at);",
+    // 16: changes handlers make on GitHub, see `crate::outbox`.
+    "CREATE TABLE effects (
+        key TEXT PRIMARY KEY,
+        started_at TEXT NOT NULL,
+        done INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE INDEX effects_started_at ON effects (started_at);",
+    // 17: commands run, see `crate::usage`.
+    "CREATE TABLE command_uses (
+        id INTEGER PRIMARY KEY,
+        created_at TEXT NOT NULL,
+        full_name TEXT NOT NULL,
+        command TEXT NOT NULL,
+        login TEXT NOT NULL,
+        ok INTEGER NOT NULL
+    );
+    CREATE INDEX command_uses_created_at ON command_uses (created_at);",
+    // 18: changes handlers in shadow mode would have made, see `crate::outbox`.
+    "CREATE TABLE shadow_actions (
+        id INTEGER PRIMARY KEY,
+        created_at TEXT NOT NULL,
+        delivery TEXT NOT NULL,
+        handler TEXT NOT NULL,
+        what TEXT NOT NULL
+    );
+    CREATE INDEX shadow_actions_handler ON shadow_actions (handler, id);",
+    // 19: areas people want to be cc'd on, see `crate::handlers::Subscriptions`.
+    "CREATE TABLE subscriptions (
+        full_name TEXT NOT NULL,
+        login TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        value TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        PRIMARY KEY (full_name, login, kind, value)
+    );",
+    // 20: who gets a digest instead of being cc'd, and what's waiting for it.
+    "CREATE TABLE subscription_preferences (
+        full_name TEXT NOT NULL,
+        login TEXT NOT NULL,
+        digest INTEGER NOT NULL,
+        PRIMARY KEY (full_name, login)
+    );
+    CREATE TABLE subscription_digest (
+        full_name TEXT NOT NULL,
+        login TEXT NOT NULL,
+        number INTEGER NOT NULL,
+        title TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        PRIMARY KEY (full_name, login, number)
+    );",
+    // 21: frozen release branches, see `crate::freeze`.
+    "CREATE TABLE freezes (
+        full_name TEXT NOT NULL,
+        branch TEXT NOT NULL,
+        until TEXT NOT NULL,
+        exempt_labels TEXT NOT NULL,
+        created_by TEXT NOT NULL,
+        PRIMARY KEY (full_name, branch)
+    );",
+    // 22: daily open and closed counts of milestones, see `crate::milestones`.
+    "CREATE TABLE milestone_counts (
+        full_name TEXT NOT NULL,
+        milestone INTEGER NOT NULL,
+        day TEXT NOT NULL,
+        open INTEGER NOT NULL,
+        closed INTEGER NOT NULL,
+        PRIMARY KEY (full_name, milestone, day)
+    );",
+    // 23: what PRs declare to depend on, see `crate::handlers::PrDependencies`.
+    "CREATE TABLE pr_dependencies (
+        full_name TEXT NOT NULL,
+        number INTEGER NOT NULL,
+        dependency INTEGER NOT NULL,
+        PRIMARY KEY (full_name, number, dependency)
+    );
+    CREATE INDEX pr_dependencies_dependency ON pr_dependencies (full_name, dependency);",
+    // 24: backtrace signatures of crash reports, see `crate::handlers::CrashSignatures`.
+    "CREATE TABLE crash_signatures (
+        full_name TEXT NOT NULL,
+        signature TEXT NOT NULL,
+        number INTEGER NOT NULL,
+        PRIMARY KEY (full_name, signature, number)
+    );",
+    // 25: repos the bot was set up on, see `crate::handlers::Setup`.
+    "CREATE TABLE onboarded_repos (
+        repo_id INTEGER PRIMARY KEY,
+        full_name TEXT NOT NULL,
+        setup_issue INTEGER,
+        onboarded_at TEXT NOT NULL
+    );",
+    // 26: when issues were opened, first answered and triaged, see `crate::slo`.
+    "CREATE TABLE issue_times (
+        full_name TEXT NOT NULL,
+        number INTEGER NOT NULL,
+        opened_at TEXT NOT NULL,
+        first_response_at TEXT,
+        triaged_at TEXT,
+        closed INTEGER NOT NULL DEFAULT 0,
+        PRIMARY KEY (full_name, number)
+    );",
+    // 27: deliveries whose batched changes weren't made yet, see `crate::batch`.
+    "CREATE TABLE batched (
+        id INTEGER PRIMARY KEY,
+        handler TEXT NOT NULL,
+        job TEXT NOT NULL
+    );",
+    // 28: acknowledged reports waiting for a response, see `crate::handlers::Acknowledgements`.
+    "CREATE TABLE response_timers (
+        repo_id INTEGER NOT NULL,
+        number INTEGER NOT NULL,
+        installation_id INTEGER NOT NULL,
+        full_name TEXT NOT NULL,
+        author TEXT NOT NULL,
+        label TEXT NOT NULL,
+        started_at TEXT NOT NULL,
+        due_at TEXT NOT NULL,
+        PRIMARY KEY (repo_id, number)
+    );",
+    // 29: what changes created on GitHub, see `crate::github::create_comment`.
+    "ALTER TABLE effects ADD COLUMN result TEXT;",
+];
+
+/// How long changes of handlers are remembered, longer than deliveries are retried. Changes of
+/// handlers in shadow mode are kept as long.
+const EFFECT_DAYS: i64 = 30;
+
+/// Actions from the audit log that count as triage work.
+const TRIAGE_ACTIONS: &[&str] = &["label", "ready", "author", "labeled"];
+
+#[derive(Debug, Clone)]
+pub struct Db {
+    conn: Arc<Mutex<Connection>>,
+}
+
+#[derive(Debug, Default, Clone, serde::Serialize)]
+pub struct UserStats {
+    pub merged_prs: u64,
+    pub reviews: u64,
+    pub triage: u64,
+}
+
+/// The status comment of a PR, see [`crate::status`].
+#[derive(Debug, Default, Clone)]
+pub struct StatusComment {
+    /// Not set until the comment was posted.
+    pub comment_id: Option<u64>,
+    pub sections: BTreeMap<String, String>,
+}
+
+/// A review someone or a team was asked for and didn't give yet.
+#[derive(Debug, Clone)]
+pub struct ReviewRequest {
+    pub installation: InstallationId,
+    pub repo: RepositoryId,
+    pub full_name: String,
+    pub number: u64,
+    /// A login, or a team slug if `team` is set.
+    pub reviewer: String,
+    pub team: bool,
+    pub requested_at: DateTime<Utc>,
+    /// Whether the reviewer was reminded that the review is overdue.
+    pub reminded: bool,
+}
+
+/// A reminder someone set on an issue or PR with `!ddnetbot remind`.
+#[derive(Debug, Clone)]
+pub struct Reminder {
+    /// Not set until the reminder is stored.
+    pub id: i64,
+    pub installation: InstallationId,
+    pub repo: RepositoryId,
+    pub full_name: String,
+    pub number: u64,
+    pub login: String,
+    pub message: String,
+    pub due_at: DateTime<Utc>,
+    /// The label to add back, for issues snoozed with `!ddnetbot snooze`.
+    pub label: Option<String>,
+}
+
+/// A crash or regression report that was acknowledged, the team is pinged if nobody answers it
+/// by `due_at`.
+#[derive(Debug, Clone)]
+pub struct ResponseTimer {
+    pub installation: InstallationId,
+    pub repo: RepositoryId,
+    pub full_name: String,
+    pub number: u64,
+    pub author: String,
+    /// The label the report was acknowledged for.
+    pub label: String,
+    pub started_at: DateTime<Utc>,
+    pub due_at: DateTime<Utc>,
+}
+
+/// A time a check failed and then passed on the same commit.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct FlakyOccurrence {
+    pub created_at: String,
+    pub sha: String,
+    pub pr: Option<u64>,
+    /// The check run that passed.
+    pub url: String,
+}
+
+/// A finished workflow job.
+#[derive(Debug, Clone)]
+pub struct CiJob {
+    pub id: u64,
+    pub full_name: String,
+    pub workflow: String,
+    pub name: String,
+    /// From being queued to starting on a runner.
+    pub queued_secs: f64,
+    pub run_secs: f64,
+    pub completed_at: DateTime<Utc>,
+}
+
+/// The jobs of the same name of a workflow, summed up.
+#[derive(Debug, Clone)]
+pub struct CiJobStats {
+    pub full_name: String,
+    pub workflow: String,
+    pub name: String,
+    pub runs: u64,
+    pub avg_queued_secs: f64,
+    pub max_queued_secs: f64,
+    pub avg_run_secs: f64,
+}
+
+/// When an issue was opened, first answered by someone other than its author and triaged.
+#[derive(Debug, Clone)]
+pub struct IssueTimes {
+    pub full_name: String,
+    pub number: u64,
+    pub opened_at: DateTime<Utc>,
+    pub first_response_at: Option<DateTime<Utc>>,
+    pub triaged_at: Option<DateTime<Utc>>,
+    pub closed: bool,
+}
+
+/// A change a handler in shadow mode would have made.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ShadowAction {
+    pub id: i64,
+    pub created_at: String,
+    pub delivery: String,
+    pub handler: String,
+    pub what: String,
+}
+
+/// How often a command was used in a while.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct CommandUsage {
+    pub command: String,
+    pub uses: u64,
+    pub failures: u64,
+    /// How many people used it.
+    pub users: u64,
+}
+
+/// How many issues and PRs of a milestone were open and closed on a day.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct MilestoneCount {
+    /// `YYYY-MM-DD`.
+    pub day: String,
+    pub open: u64,
+    pub closed: u64,
+}
+
+/// An area someone wants to be cc'd on in a repo.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subscription {
+    pub login: String,
+    /// `label` or `path`.
+    pub kind: String,
+    pub value: String,
+}
+
+/// A branch only PRs with one of `exempt_labels` may be merged into until `until`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Freeze {
+    pub full_name: String,
+    pub branch: String,
+    pub until: DateTime<Utc>,
+    pub exempt_labels: Vec<String>,
+}
+
+impl Freeze {
+    /// Whether a PR with these labels may still be merged.
+    pub fn exempts(&self, labels: &[&str]) -> bool {
+        self.exempt_labels
+            .iter()
+            .any(|x| labels.contains(&x.as_str()))
+    }
+}
+
+/// An issue or PR waiting for the next subscription digest of someone.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DigestItem {
+    pub full_name: String,
+    pub login: String,
+    pub number: u64,
+    pub title: String,
+}
+
+/// A delivery a handler failed on.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DeadLetter {
+    pub id: i64,
+    pub created_at: String,
+    pub handler: String,
+    /// The error of the last attempt.
+    pub error: String,
+    pub job: Job,
+}
+
+impl Db {
+    pub fn open(path: impl AsRef<Path>) -> rusqlite::Result<Self> {
+        let conn = Connection::open(path)?;
+        migrate(&conn)?;
+        let cutoff = (Utc::now() - chrono::Duration::days(EFFECT_DAYS)).to_rfc3339();
+        conn.execute("DELETE FROM effects WHERE started_at < ?1", [&cutoff])?;
+        conn.execute(
+            "DELETE FROM shadow_actions WHERE created_at < ?1",
+            [&cutoff],
+        )?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// Records an action in the audit log.
+    pub fn audit(
+        &self,
+        repo: &str,
+        number: Option<u64>,
+        actor: &str,
+        action: &str,
+        detail: &str,
+    ) -> rusqlite::Result<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO audit_log (created_at, repo, number, actor, action, detail)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![Utc::now().to_rfc3339(), repo, number, actor, action, detail],
+        )?;
+        Ok(())
+    }
+
+    /// Records a merged PR (`kind = "merged_pr"`) or review (`kind = "review"`), ignoring duplicates.
+    pub fn add_contribution(
+        &self,
+        repo: &str,
+        number: u64,
+        login: &str,
+        kind: &str,
+        created_at: chrono::DateTime<Utc>,
+    ) -> rusqlite::Result<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT OR IGNORE INTO contributions (repo, number, login, kind, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![repo, number, login, kind, created_at.to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    pub fn user_stats(&self, login: &str) -> rusqlite::Result<UserStats> {
+        Ok(self
+            .all_stats()?
+            .into_iter()
+            .find(|(x, _)| x.eq_ignore_ascii_case(login))
+            .map(|(_, stats)| stats)
+            .unwrap_or_default())
+    }
+
+    /// Returns the stats of every known user, keyed by login.
+    pub fn all_stats(&self) -> rusqlite::Result<BTreeMap<String, UserStats>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stats = BTreeMap::<String, UserStats>::new();
+
+        let mut stmt =
+            conn.prepare("SELECT login, kind, COUNT(*) FROM contributions GROUP BY login, kind")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, u64>(2)?,
+            ))
+        })?;
+        for row in rows {
+            let (login, kind, count) = row?;
+            let entry = stats.entry(login).or_default();
+            match kind.as_str() {
+                "merged_pr" => entry.merged_prs += count,
+                "review" => entry.reviews += count,
+                _ => {}
+            }
+        }
+
+        let placeholders = vec!["?"; TRIAGE_ACTIONS.len()].join(", ");
+        let mut stmt = conn.prepare(&format!(
+            "SELECT actor, COUNT(*) FROM audit_log WHERE action IN ({}) GROUP BY actor",
+            placeholders
+        ))?;
+        let rows = stmt.query_map(rusqlite::params_from_iter(TRIAGE_ACTIONS), |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, u64>(1)?))
+        })?;
+        for row in rows {
+            let (actor, count) = row?;
+            stats.entry(actor).or_default().triage += count;
+        }
+
+        Ok(stats)
+    }
+
+    pub fn status_comment(
+        &self,
+        repo: RepositoryId,
+        number: u64,
+    ) -> rusqlite::Result<Option<StatusComment>> {
+        self.conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT comment_id, sections FROM status_comments
+                 WHERE repo_id = ?1 AND number = ?2",
+                params![repo.0, number],
+                |row| {
+                    let sections: String = row.get(1)?;
+                    Ok(StatusComment {
+                        comment_id: row.get(0)?,
+                        sections: serde_json::from_str(&sections).unwrap_or_default(),
+                    })
+                },
+            )
+            .optional()
+    }
+
+    pub fn set_status_comment(
+        &self,
+        repo: RepositoryId,
+        number: u64,
+        status: &StatusComment,
+    ) -> rusqlite::Result<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO status_comments (repo_id, number, comment_id, sections)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (repo_id, number)
+             DO UPDATE SET comment_id = excluded.comment_id, sections = excluded.sections",
+            params![
+                repo.0,
+                number,
+                status.comment_id,
+                serde_json::to_string(&status.sections).unwrap()
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn add_delegate(
+        &self,
+        repo: RepositoryId,
+        number: u64,
+        login: &str,
+        granted_by: &str,
+    ) -> rusqlite::Result<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO delegations (repo_id, number, login, granted_by, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                repo.0,
+                number,
+                login.to_lowercase(),
+                granted_by,
+                Utc::now().to_rfc3339()
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn remove_delegate(
+        &self,
+        repo: RepositoryId,
+        number: u64,
+        login: &str,
+    ) -> rusqlite::Result<()> {
+        self.conn.lock().unwrap().execute(
+            "DELETE FROM delegations WHERE repo_id = ?1 AND number = ?2 AND login = ?3",
+            params![repo.0, number, login.to_lowercase()],
+        )?;
+        Ok(())
+    }
+
+    pub fn is_delegate(
+        &self,
+        repo: RepositoryId,
+        number: u64,
+        login: &str,
+    ) -> rusqlite::Result<bool> {
+        self.conn.lock().unwrap().query_row(
+            "SELECT EXISTS (SELECT 1 FROM delegations
+             WHERE repo_id = ?1 AND number = ?2 AND login = ?3)",
+            params![repo.0, number, login.to_lowercase()],
+            |row| row.get(0),
+        )
+    }
+
+    pub fn add_review_request(&self, request: &ReviewRequest) -> rusqlite::Result<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO review_requests
+             (repo_id, number, reviewer, team, installation_id, full_name, requested_at, reminded)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                request.repo.0,
+                request.number,
+                request.reviewer,
+                request.team,
+                request.installation.0,
+                request.full_name,
+                request.requested_at.to_rfc3339(),
+                request.reminded,
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn remove_review_request(
+        &self,
+        repo: RepositoryId,
+        number: u64,
+        reviewer: &str,
+        team: bool,
+    ) -> rusqlite::Result<()> {
+        self.conn.lock().unwrap().execute(
+            "DELETE FROM review_requests
+             WHERE repo_id = ?1 AND number = ?2 AND reviewer = ?3 AND team = ?4",
+            params![repo.0, number, reviewer, team],
+        )?;
+        Ok(())
+    }
+
+    /// Removes the review requests of a PR, all of them or only those of `reviewer` and of
+    /// teams, which are done when any member reviews.
+    pub fn remove_review_requests(
+        &self,
+        repo: RepositoryId,
+        number: u64,
+        reviewer: Option<&str>,
+    ) -> rusqlite::Result<()> {
+        self.conn.lock().unwrap().execute(
+            "DELETE FROM review_requests
+             WHERE repo_id = ?1 AND number = ?2 AND (?3 IS NULL OR reviewer = ?3 OR team = 1)",
+            params![repo.0, number, reviewer],
+        )?;
+        Ok(())
+    }
+
+    /// Every pending review request, of one PR if `pr` is set.
+    pub fn review_requests(
+        &self,
+        pr: Option<(RepositoryId, u64)>,
+    ) -> rusqlite::Result<Vec<ReviewRequest>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT installation_id, repo_id, full_name, number, reviewer, team, requested_at,
+                    reminded
+             FROM review_requests
+             WHERE ?1 IS NULL OR (repo_id = ?1 AND number = ?2)
+             ORDER BY requested_at",
+        )?;
+        stmt.query_map(params![pr.map(|x| x.0.0), pr.map(|x| x.1)], |row| {
+            let requested_at: String = row.get(6)?;
+            Ok(ReviewRequest {
+                installation: InstallationId(row.get(0)?),
+                repo: RepositoryId(row.get(1)?),
+                full_name: row.get(2)?,
+                number: row.get(3)?,
+                reviewer: row.get(4)?,
+                team: row.get(5)?,
+                requested_at: DateTime::parse_from_rfc3339(&requested_at)
+                    .unwrap()
+                    .with_timezone(&Utc),
+                reminded: row.get(7)?,
+            })
+        })?
+        .collect()
+    }
+
+    /// Records that the app was installed on a repo.
+    pub fn add_repo(
+        &self,
+        installation: InstallationId,
+        repo: RepositoryId,
+        full_name: &str,
+    ) -> rusqlite::Result<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO repos (repo_id, installation_id, full_name)
+             VALUES (?1, ?2, ?3)",
+            params![repo.0, installation.0, full_name],
+        )?;
+        Ok(())
+    }
+
+    /// The repo and installation of a repo the app is installed on, by `owner/name`.
+    pub fn repo_by_name(
+        &self,
+        full_name: &str,
+    ) -> rusqlite::Result<Option<(RepositoryId, InstallationId)>> {
+        self.conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT repo_id, installation_id FROM repos WHERE full_name = ?1 COLLATE NOCASE",
+                [full_name],
+                |row| Ok((RepositoryId(row.get(0)?), InstallationId(row.get(1)?))),
+            )
+            .optional()
+    }
+
+    /// Replaces the repos the app is installed on, e.g. with the ones listed by GitHub.
+    pub fn set_repos(
+        &self,
+        repos: &[(InstallationId, RepositoryId, String)],
+    ) -> rusqlite::Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM repos", [])?;
+        for (installation, repo, full_name) in repos {
+            tx.execute(
+                "INSERT OR REPLACE INTO repos (repo_id, installation_id, full_name)
+                 VALUES (?1, ?2, ?3)",
+                params![repo.0, installation.0, full_name],
+            )?;
+        }
+        tx.commit()
+    }
+
+    pub fn remove_repo(&self, repo: RepositoryId) -> rusqlite::Result<()> {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute("DELETE FROM repos WHERE repo_id = ?1", [repo.0])?;
+        Ok(())
+    }
+
+    pub fn remove_installation(&self, installation: InstallationId) -> rusqlite::Result<()> {
+        self.conn.lock().unwrap().execute(
+            "DELETE FROM repos WHERE installation_id = ?1",
+            [installation.0],
+        )?;
+        Ok(())
+    }
+
+    /// Queues a job, returns false if the delivery is already queued.
+    pub fn queue_push(&self, job: &Job) -> rusqlite::Result<bool> {
+        let changed = self.conn.lock().unwrap().execute(
+            "INSERT OR IGNORE INTO queue (delivery, job) VALUES (?1, ?2)",
+            params![job.delivery, serde_json::to_string(job).unwrap()],
+        )?;
+        Ok(changed == 1)
+    }
+
+    /// Marks the oldest job nobody took yet as taken and returns it.
+    pub fn queue_take(&self) -> rusqlite::Result<Option<(i64, String)>> {
+        self.conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "UPDATE queue SET taken = 1
+                 WHERE id = (SELECT id FROM queue WHERE taken = 0 ORDER BY id LIMIT 1)
+                 RETURNING id, job",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+    }
+
+    /// How many jobs wait for a worker.
+    pub fn queue_len(&self) -> rusqlite::Result<u64> {
+        self.conn.lock().unwrap().query_row(
+            "SELECT COUNT(*) FROM queue WHERE taken = 0",
+            [],
+            |row| row.get(0),
+        )
+    }
+
+    pub fn queue_remove(&self, id: i64) -> rusqlite::Result<()> {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute("DELETE FROM queue WHERE id = ?1", [id])?;
+        Ok(())
+    }
+
+    /// Puts every taken job back, returns how many there were.
+    pub fn release_queue(&self) -> rusqlite::Result<usize> {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute("UPDATE queue SET taken = 0 WHERE taken = 1", [])
+    }
+
+    pub fn add_dead_letter(&self, job: &Job, handler: &str, error: &str) -> rusqlite::Result<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO dead_letters (created_at, handler, error, job) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                Utc::now().to_rfc3339(),
+                handler,
+                error,
+                serde_json::to_string(job).unwrap()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Every dead letter, oldest first.
+    pub fn dead_letters(&self) -> rusqlite::Result<Vec<DeadLetter>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT id, created_at, handler, error, job FROM dead_letters ORDER BY id")?;
+        stmt.query_map([], dead_letter_from_row)?.collect()
+    }
+
+    pub fn dead_letter(&self, id: i64) -> rusqlite::Result<Option<DeadLetter>> {
+        self.conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT id, created_at, handler, error, job FROM dead_letters WHERE id = ?1",
+                [id],
+                dead_letter_from_row,
+            )
+            .optional()
+    }
+
+    pub fn set_dead_letter_error(&self, id: i64, error: &str) -> rusqlite::Result<()> {
+        self.conn.lock().unwrap().execute(
+            "UPDATE dead_letters SET error = ?2 WHERE id = ?1",
+            params![id, error],
+        )?;
+        Ok(())
+    }
+
+    pub fn remove_dead_letter(&self, id: i64) -> rusqlite::Result<()> {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute("DELETE FROM dead_letters WHERE id = ?1", [id])?;
+        Ok(())
+    }
+
+    /// Records that the handler batched changes for the delivery, until they're made.
+    pub fn add_batched(&self, job: &Job, handler: &str) -> rusqlite::Result<i64> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO batched (handler, job) VALUES (?1, ?2)",
+            params![handler, serde_json::to_string(job).unwrap()],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Forgets the batched changes of the deliveries, they were made.
+    pub fn remove_batched(&self, ids: &[i64]) -> rusqlite::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        for id in ids {
+            conn.execute("DELETE FROM batched WHERE id = ?1", [id])?;
+        }
+        Ok(())
+    }
+
+    /// Keeps the deliveries whose batched changes failed as dead letters of their handlers, so
+    /// they're made again on redrive. Without `ids`, every delivery still waiting is. Returns
+    /// how many there were.
+    pub fn dead_letter_batched(&self, ids: Option<&[i64]>, error: &str) -> rusqlite::Result<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let rows: Vec<(i64, String, String)> = tx
+            .prepare("SELECT id, handler, job FROM batched ORDER BY id")?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+        let now = Utc::now().to_rfc3339();
+        let mut count = 0;
+        for (id, handler, job) in rows {
+            if ids.is_some_and(|x| !x.contains(&id)) {
+                continue;
+            }
+            tx.execute(
+                "INSERT INTO dead_letters (created_at, handler, error, job) VALUES (?1, ?2, ?3, ?4)",
+                params![now, handler, error, job],
+            )?;
+            tx.execute("DELETE FROM batched WHERE id = ?1", [id])?;
+            count += 1;
+        }
+        tx.commit()?;
+        Ok(count)
+    }
+
+    pub fn accept_cla(&self, login: &str) -> rusqlite::Result<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT OR IGNORE INTO cla_signatures (login, accepted_at) VALUES (?1, ?2)",
+            params![login.to_lowercase(), Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    pub fn has_accepted_cla(&self, login: &str) -> rusqlite::Result<bool> {
+        self.conn.lock().unwrap().query_row(
+            "SELECT EXISTS (SELECT 1 FROM cla_signatures WHERE login = ?1)",
+            [login.to_lowercase()],
+            |row| row.get(0),
+        )
+    }
+
+    /// Records that the check `name` turned out to be flaky on `sha`.
+    pub fn add_flaky_occurrence(
+        &self,
+        repo: RepositoryId,
+        name: &str,
+        occurrence: &FlakyOccurrence,
+    ) -> rusqlite::Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT OR IGNORE INTO flaky_tests (repo_id, name) VALUES (?1, ?2)",
+            params![repo.0, name],
+        )?;
+        tx.execute(
+            "INSERT INTO flaky_occurrences (created_at, repo_id, name, sha, pr, url)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                occurrence.created_at,
+                repo.0,
+                name,
+                occurrence.sha,
+                occurrence.pr,
+                occurrence.url
+            ],
+        )?;
+        tx.commit()
+    }
+
+    /// Every time the check `name` was flaky, the oldest first.
+    pub fn flaky_occurrences(
+        &self,
+        repo: RepositoryId,
+        name: &str,
+    ) -> rusqlite::Result<Vec<FlakyOccurrence>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT created_at, sha, pr, url FROM flaky_occurrences
+             WHERE repo_id = ?1 AND name = ?2
+             ORDER BY id",
+        )?;
+        stmt.query_map(params![repo.0, name], |row| {
+            Ok(FlakyOccurrence {
+                created_at: row.get(0)?,
+                sha: row.get(1)?,
+                pr: row.get(2)?,
+                url: row.get(3)?,
+            })
+        })?
+        .collect()
+    }
+
+    /// The issue tracking the flaky check `name`, if there is one yet.
+    pub fn flaky_issue(&self, repo: RepositoryId, name: &str) -> rusqlite::Result<Option<u64>> {
+        self.conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT issue_number FROM flaky_tests WHERE repo_id = ?1 AND name = ?2",
+                params![repo.0, name],
+                |row| row.get(0),
+            )
+            .optional()
+            .map(Option::flatten)
+    }
+
+    pub fn set_flaky_issue(
+        &self,
+        repo: RepositoryId,
+        name: &str,
+        number: u64,
+    ) -> rusqlite::Result<()> {
+        self.conn.lock().unwrap().execute(
+            "UPDATE flaky_tests SET issue_number = ?3 WHERE repo_id = ?1 AND name = ?2",
+            params![repo.0, name, number],
+        )?;
+        Ok(())
+    }
+
+    /// Replaces the items recorded for a tracking issue.
+    pub fn set_tracked_items(
+        &self,
+        repo: RepositoryId,
+        number: u64,
+        items: &[String],
+    ) -> rusqlite::Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "DELETE FROM tracked_items WHERE repo_id = ?1 AND number = ?2",
+            params![repo.0, number],
+        )?;
+        for item in items {
+            tx.execute(
+                "INSERT OR IGNORE INTO tracked_items (repo_id, number, item) VALUES (?1, ?2, ?3)",
+                params![repo.0, number, item],
+            )?;
+        }
+        tx.commit()
+    }
+
+    /// The items of every tracking issue of the repo, by issue number and in task list order.
+    pub fn tracked_items(&self, repo: RepositoryId) -> rusqlite::Result<Vec<(u64, String)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT number, item FROM tracked_items WHERE repo_id = ?1 ORDER BY number, rowid",
+        )?;
+        stmt.query_map([repo.0], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect()
+    }
+
+    /// The tracking issues with the item in their task list.
+    pub fn tracking_issues(&self, item: &str) -> rusqlite::Result<Vec<(RepositoryId, u64)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT repo_id, number FROM tracked_items WHERE item = ?1")?;
+        stmt.query_map([item], |row| Ok((RepositoryId(row.get(0)?), row.get(1)?)))?
+            .collect()
+    }
+
+    pub fn add_reminder(&self, reminder: &Reminder) -> rusqlite::Result<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO reminders
+                (installation_id, repo_id, full_name, number, login, message, due_at, label)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                reminder.installation.0,
+                reminder.repo.0,
+                reminder.full_name,
+                reminder.number,
+                reminder.login,
+                reminder.message,
+                reminder.due_at.to_rfc3339(),
+                reminder.label,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// The reminders due at `now`, the oldest first.
+    pub fn due_reminders(&self, now: DateTime<Utc>) -> rusqlite::Result<Vec<Reminder>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, installation_id, repo_id, full_name, number, login, message, due_at, label
+             FROM reminders
+             WHERE due_at <= ?1
+             ORDER BY due_at",
+        )?;
+        stmt.query_map([now.to_rfc3339()], |row| {
+            let due_at: String = row.get(7)?;
+            Ok(Reminder {
+                id: row.get(0)?,
+                installation: InstallationId(row.get(1)?),
+                repo: RepositoryId(row.get(2)?),
+                full_name: row.get(3)?,
+                number: row.get(4)?,
+                login: row.get(5)?,
+                message: row.get(6)?,
+                due_at: DateTime::parse_from_rfc3339(&due_at)
+                    .unwrap()
+                    .with_timezone(&Utc),
+                label: row.get(8)?,
+            })
+        })?
+        .collect()
+    }
+
+    /// Starts the timer, unless the issue already has one.
+    pub fn add_response_timer(&self, timer: &ResponseTimer) -> rusqlite::Result<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT OR IGNORE INTO response_timers
+                (repo_id, number, installation_id, full_name, author, label, started_at, due_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                timer.repo.0,
+                timer.number,
+                timer.installation.0,
+                timer.full_name,
+                timer.author,
+                timer.label,
+                timer.started_at.to_rfc3339(),
+                timer.due_at.to_rfc3339(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// The response timers due at `now`, the oldest first.
+    pub fn due_response_timers(&self, now: DateTime<Utc>) -> rusqlite::Result<Vec<ResponseTimer>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT repo_id, number, installation_id, full_name, author, label, started_at, due_at
+             FROM response_timers
+             WHERE due_at <= ?1
+             ORDER BY due_at",
+        )?;
+        let time = |x: String| {
+            DateTime::parse_from_rfc3339(&x)
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        stmt.query_map([now.to_rfc3339()], |row| {
+            Ok(ResponseTimer {
+                repo: RepositoryId(row.get(0)?),
+                number: row.get(1)?,
+                installation: InstallationId(row.get(2)?),
+                full_name: row.get(3)?,
+                author: row.get(4)?,
+                label: row.get(5)?,
+                started_at: time(row.get(6)?),
+                due_at: time(row.get(7)?),
+            })
+        })?
+        .collect()
+    }
+
+    pub fn remove_response_timer(&self, repo: RepositoryId, number: u64) -> rusqlite::Result<()> {
+        self.conn.lock().unwrap().execute(
+            "DELETE FROM response_timers WHERE repo_id = ?1 AND number = ?2",
+            params![repo.0, number],
+        )?;
+        Ok(())
+    }
+
+    pub fn remove_reminder(&self, id: i64) -> rusqlite::Result<()> {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute("DELETE FROM reminders WHERE id = ?1", [id])?;
+        Ok(())
+    }
+
+    /// Records the benchmark results of a commit.
+    pub fn add_benchmark_results(
+        &self,
+        repo: RepositoryId,
+        sha: &str,
+        branch: &str,
+        results: &BTreeMap<String, f64>,
+    ) -> rusqlite::Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let now = Utc::now().to_rfc3339();
+        for (name, value) in results {
+            tx.execute(
+                "INSERT INTO benchmarks (created_at, repo_id, sha, branch, name, value)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![now, repo.0, sha, branch, name, value],
+            )?;
+        }
+        tx.commit()
+    }
+
+    /// The latest result of every benchmark on `branch`.
+    pub fn benchmark_baselines(
+        &self,
+        repo: RepositoryId,
+        branch: &str,
+    ) -> rusqlite::Result<BTreeMap<String, f64>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT name, value FROM benchmarks
+             WHERE id IN (
+                 SELECT MAX(id) FROM benchmarks WHERE repo_id = ?1 AND branch = ?2 GROUP BY name
+             )",
+        )?;
+        stmt.query_map(params![repo.0, branch], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?
+        .collect()
+    }
+
+    /// Records a finished workflow job, redeliveries replace it.
+    pub fn add_ci_job(&self, job: &CiJob) -> rusqlite::Result<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO ci_jobs
+                (job_id, full_name, workflow, name, queued_secs, run_secs, completed_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                job.id,
+                job.full_name,
+                job.workflow,
+                job.name,
+                job.queued_secs,
+                job.run_secs,
+                job.completed_at.to_rfc3339(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// The stats of the jobs completed since `since`, the longest queued first.
+    pub fn ci_job_stats(&self, since: DateTime<Utc>) -> rusqlite::Result<Vec<CiJobStats>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT full_name, workflow, name, COUNT(*), AVG(queued_secs), MAX(queued_secs),
+                 AVG(run_secs)
+             FROM ci_jobs
+             WHERE completed_at >= ?1
+             GROUP BY full_name, workflow, name
+             ORDER BY AVG(queued_secs) DESC",
+        )?;
+        stmt.query_map([since.to_rfc3339()], |row| {
+            Ok(CiJobStats {
+                full_name: row.get(0)?,
+                workflow: row.get(1)?,
+                name: row.get(2)?,
+                runs: row.get(3)?,
+                avg_queued_secs: row.get(4)?,
+                max_queued_secs: row.get(5)?,
+                avg_run_secs: row.get(6)?,
+            })
+        })?
+        .collect()
+    }
+
+    pub fn add_command_use(
+        &self,
+        full_name: &str,
+        command: &str,
+        login: &str,
+        ok: bool,
+    ) -> rusqlite::Result<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO command_uses (created_at, full_name, command, login, ok)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![Utc::now().to_rfc3339(), full_name, command, login, ok],
+        )?;
+        Ok(())
+    }
+
+    /// The usage of every command run from `since` until `until`, the most used first.
+    pub fn command_usage(
+        &self,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> rusqlite::Result<Vec<CommandUsage>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT command, COUNT(*), SUM(NOT ok), COUNT(DISTINCT login)
+             FROM command_uses
+             WHERE created_at >= ?1 AND created_at < ?2
+             GROUP BY command
+             ORDER BY COUNT(*) DESC, command",
+        )?;
+        stmt.query_map([since.to_rfc3339(), until.to_rfc3339()], |row| {
+            Ok(CommandUsage {
+                command: row.get(0)?,
+                uses: row.get(1)?,
+                failures: row.get(2)?,
+                users: row.get(3)?,
+            })
+        })?
+        .collect()
+    }
+
+    pub fn add_subscription(
+        &self,
+        full_name: &str,
+        login: &str,
+        kind: &str,
+        value: &str,
+    ) -> rusqlite::Result<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT OR IGNORE INTO subscriptions (full_name, login, kind, value, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                full_name.to_lowercase(),
+                login,
+                kind,
+                value,
+                Utc::now().to_rfc3339()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// The subscriptions of the repo, of one person if `login` is given.
+    pub fn subscriptions(
+        &self,
+        full_name: &str,
+        login: Option<&str>,
+    ) -> rusqlite::Result<Vec<Subscription>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT login, kind, value FROM subscriptions
+             WHERE full_name = ?1 AND (?2 IS NULL OR login = ?2)
+             ORDER BY login, kind, value",
+        )?;
+        stmt.query_map(params![full_name.to_lowercase(), login], |row| {
+            Ok(Subscription {
+                login: row.get(0)?,
+                kind: row.get(1)?,
+                value: row.get(2)?,
+            })
+        })?
+        .collect()
+    }
+
+    /// Removes a subscription, or all of them of the person without `kind` and `value`.
+    /// Returns how many were removed.
+    pub fn remove_subscriptions(
+        &self,
+        full_name: &str,
+        login: &str,
+        area: Option<(&str, &str)>,
+    ) -> rusqlite::Result<usize> {
+        let (kind, value) = area.unzip();
+        self.conn.lock().unwrap().execute(
+            "DELETE FROM subscriptions
+             WHERE full_name = ?1 AND login = ?2
+             AND (?3 IS NULL OR (kind = ?3 AND value = ?4))",
+            params![full_name.to_lowercase(), login, kind, value],
+        )
+    }
+
+    pub fn set_subscription_digest(
+        &self,
+        full_name: &str,
+        login: &str,
+        digest: bool,
+    ) -> rusqlite::Result<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO subscription_preferences (full_name, login, digest) VALUES (?1, ?2, ?3)
+             ON CONFLICT (full_name, login) DO UPDATE SET digest = ?3",
+            params![full_name.to_lowercase(), login, digest],
+        )?;
+        Ok(())
+    }
+
+    /// Whether the person wants a digest instead of being cc'd, they're cc'd by default.
+    pub fn subscription_digest(&self, full_name: &str, login: &str) -> rusqlite::Result<bool> {
+        let digest = self
+            .conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT digest FROM subscription_preferences WHERE full_name = ?1 AND login = ?2",
+                params![full_name.to_lowercase(), login],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(digest.unwrap_or(false))
+    }
+
+    pub fn add_digest_item(
+        &self,
+        full_name: &str,
+        login: &str,
+        number: u64,
+        title: &str,
+    ) -> rusqlite::Result<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT OR IGNORE INTO subscription_digest (full_name, login, number, title, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                full_name.to_lowercase(),
+                login,
+                number,
+                title,
+                Utc::now().to_rfc3339()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Everything waiting for a digest, by repo and person.
+    pub fn digest_items(&self) -> rusqlite::Result<Vec<DigestItem>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT full_name, login, number, title FROM subscription_digest
+             ORDER BY full_name, login, number",
+        )?;
+        stmt.query_map([], |row| {
+            Ok(DigestItem {
+                full_name: row.get(0)?,
+                login: row.get(1)?,
+                number: row.get(2)?,
+                title: row.get(3)?,
+            })
+        })?
+        .collect()
+    }
+
+    /// Removes the items sent in a digest of the person.
+    pub fn remove_digest_items(&self, full_name: &str, login: &str) -> rusqlite::Result<()> {
+        self.conn.lock().unwrap().execute(
+            "DELETE FROM subscription_digest WHERE full_name = ?1 AND login = ?2",
+            params![full_name.to_lowercase(), login],
+        )?;
+        Ok(())
+    }
+
+    /// Records the counts of the milestone today, over the ones recorded earlier today.
+    pub fn set_milestone_count(
+        &self,
+        full_name: &str,
+        milestone: i64,
+        open: u64,
+        closed: u64,
+    ) -> rusqlite::Result<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO milestone_counts (full_name, milestone, day, open, closed)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                full_name.to_lowercase(),
+                milestone,
+                Utc::now().format("%Y-%m-%d").to_string(),
+                open,
+                closed
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// The recorded counts of the milestone, the oldest first.
+    pub fn milestone_counts(
+        &self,
+        full_name: &str,
+        milestone: i64,
+    ) -> rusqlite::Result<Vec<MilestoneCount>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT day, open, closed FROM milestone_counts
+             WHERE full_name = ?1 AND milestone = ?2
+             ORDER BY day",
+        )?;
+        stmt.query_map(params![full_name.to_lowercase(), milestone], |row| {
+            Ok(MilestoneCount {
+                day: row.get(0)?,
+                open: row.get(1)?,
+                closed: row.get(2)?,
+            })
+        })?
+        .collect()
+    }
+
+    /// Freezes the branch, or changes an existing freeze of it.
+    pub fn set_freeze(&self, freeze: &Freeze, login: &str) -> rusqlite::Result<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO freezes (full_name, branch, until, exempt_labels, created_by)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                freeze.full_name.to_lowercase(),
+                freeze.branch,
+                freeze.until.to_rfc3339(),
+                freeze.exempt_labels.join("\n"),
+                login
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Returns whether the branch was frozen.
+    pub fn remove_freeze(&self, full_name: &str, branch: &str) -> rusqlite::Result<bool> {
+        let removed = self.conn.lock().unwrap().execute(
+            "DELETE FROM freezes WHERE full_name = ?1 AND branch = ?2",
+            params![full_name.to_lowercase(), branch],
+        )?;
+        Ok(removed > 0)
+    }
+
+    /// Every freeze, also the ones past their end that weren't lifted yet.
+    pub fn freezes(&self) -> rusqlite::Result<Vec<Freeze>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT full_name, branch, until, exempt_labels FROM freezes ORDER BY full_name, branch",
+        )?;
+        stmt.query_map([], |row| {
+            let until: String = row.get(2)?;
+            let exempt_labels: String = row.get(3)?;
+            Ok(Freeze {
+                full_name: row.get(0)?,
+                branch: row.get(1)?,
+                until: DateTime::parse_from_rfc3339(&until)
+                    .unwrap()
+                    .with_timezone(&Utc),
+                exempt_labels: exempt_labels
+                    .lines()
+                    .filter(|x| !x.is_empty())
+                    .map(String::from)
+                    .collect(),
+            })
+        })?
+        .collect()
+    }
+
+    /// The freeze of the branch, if it's frozen.
+    pub fn freeze(&self, full_name: &str, branch: &str) -> rusqlite::Result<Option<Freeze>> {
+        let full_name = full_name.to_lowercase();
+        Ok(self
+            .freezes()?
+            .into_iter()
+            .find(|x| x.full_name == full_name && x.branch == branch))
+    }
+
+    /// Records that the issue reports the crash with this signature.
+    pub fn add_crash_signature(
+        &self,
+        full_name: &str,
+        signature: &str,
+        number: u64,
+    ) -> rusqlite::Result<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT OR IGNORE INTO crash_signatures (full_name, signature, number)
+             VALUES (?1, ?2, ?3)",
+            params![full_name.to_lowercase(), signature, number],
+        )?;
+        Ok(())
+    }
+
+    /// The issues reporting the crash with this signature, the oldest first.
+    pub fn crash_reports(&self, full_name: &str, signature: &str) -> rusqlite::Result<Vec<u64>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT number FROM crash_signatures WHERE full_name = ?1 AND signature = ?2
+             ORDER BY number",
+        )?;
+        stmt.query_map(params![full_name.to_lowercase(), signature], |row| {
+            row.get(0)
+        })?
+        .collect()
+    }
+
+    /// Whether the bot was already set up on the repo, e.g. before it was uninstalled.
+    pub fn is_onboarded(&self, repo: RepositoryId) -> rusqlite::Result<bool> {
+        self.conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT 1 FROM onboarded_repos WHERE repo_id = ?1",
+                [repo.0],
+                |_| Ok(()),
+            )
+            .optional()
+            .map(|x| x.is_some())
+    }
+
+    /// Records that the bot was set up on the repo, with the number of the setup issue if one
+    /// was opened.
+    pub fn add_onboarded_repo(
+        &self,
+        repo: RepositoryId,
+        full_name: &str,
+        setup_issue: Option<u64>,
+    ) -> rusqlite::Result<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO onboarded_repos (repo_id, full_name, setup_issue, onboarded_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![repo.0, full_name, setup_issue, Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    pub fn add_issue_opened(
+        &self,
+        full_name: &str,
+        number: u64,
+        opened_at: DateTime<Utc>,
+    ) -> rusqlite::Result<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT OR IGNORE INTO issue_times (full_name, number, opened_at) VALUES (?1, ?2, ?3)",
+            params![full_name.to_lowercase(), number, opened_at.to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    /// Records the first response to the issue, later ones are ignored.
+    pub fn set_issue_responded(
+        &self,
+        full_name: &str,
+        number: u64,
+        at: DateTime<Utc>,
+    ) -> rusqlite::Result<()> {
+        self.conn.lock().unwrap().execute(
+            "UPDATE issue_times SET first_response_at = ?3
+             WHERE full_name = ?1 AND number = ?2 AND first_response_at IS NULL",
+            params![full_name.to_lowercase(), number, at.to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    /// Records when the issue was first triaged, later triage is ignored.
+    pub fn set_issue_triaged(
+        &self,
+        full_name: &str,
+        number: u64,
+        at: DateTime<Utc>,
+    ) -> rusqlite::Result<()> {
+        self.conn.lock().unwrap().execute(
+            "UPDATE issue_times SET triaged_at = ?3
+             WHERE full_name = ?1 AND number = ?2 AND triaged_at IS NULL",
+            params![full_name.to_lowercase(), number, at.to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    pub fn set_issue_closed(
+        &self,
+        full_name: &str,
+        number: u64,
+        closed: bool,
+    ) -> rusqlite::Result<()> {
+        self.conn.lock().unwrap().execute(
+            "UPDATE issue_times SET closed = ?3 WHERE full_name = ?1 AND number = ?2",
+            params![full_name.to_lowercase(), number, closed],
+        )?;
+        Ok(())
+    }
+
+    /// The times of the issues opened since `since` and of every open one.
+    pub fn issue_times(&self, since: DateTime<Utc>) -> rusqlite::Result<Vec<IssueTimes>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT full_name, number, opened_at, first_response_at, triaged_at, closed
+             FROM issue_times
+             WHERE opened_at >= ?1 OR closed = 0
+             ORDER BY opened_at",
+        )?;
+        let time = |x: String| {
+            DateTime::parse_from_rfc3339(&x)
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        stmt.query_map([since.to_rfc3339()], |row| {
+            Ok(IssueTimes {
+                full_name: row.get(0)?,
+                number: row.get(1)?,
+                opened_at: time(row.get(2)?),
+                first_response_at: row.get::<_, Option<String>>(3)?.map(time),
+                triaged_at: row.get::<_, Option<String>>(4)?.map(time),
+                closed: row.get(5)?,
+            })
+        })?
+        .collect()
+    }
+
+    /// Replaces what the PR depends on.
+    pub fn set_pr_dependencies(
+        &self,
+        full_name: &str,
+        number: u64,
+        dependencies: &[u64],
+    ) -> rusqlite::Result<()> {
+        let full_name = full_name.to_lowercase();
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "DELETE FROM pr_dependencies WHERE full_name = ?1 AND number = ?2",
+            params![full_name, number],
+        )?;
+        for dependency in dependencies {
+            tx.execute(
+                "INSERT OR IGNORE INTO pr_dependencies (full_name, number, dependency)
+                 VALUES (?1, ?2, ?3)",
+                params![full_name, number, dependency],
+            )?;
+        }
+        tx.commit()
+    }
+
+    /// The PRs depending on `dependency`.
+    pub fn pr_dependents(&self, full_name: &str, dependency: u64) -> rusqlite::Result<Vec<u64>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT number FROM pr_dependencies WHERE full_name = ?1 AND dependency = ?2
+             ORDER BY number",
+        )?;
+        stmt.query_map(params![full_name.to_lowercase(), dependency], |row| {
+            row.get(0)
+        })?
+        .collect()
+    }
+
+    pub fn add_shadow_action(
+        &self,
+        delivery: &str,
+        handler: &str,
+        what: &str,
+    ) -> rusqlite::Result<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO shadow_actions (created_at, delivery, handler, what)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![Utc::now().to_rfc3339(), delivery, handler, what],
+        )?;
+        Ok(())
+    }
+
+    /// The latest changes of handlers in shadow mode, of every handler if `handler` is `None`.
+    pub fn shadow_actions(
+        &self,
+        handler: Option<&str>,
+        limit: u32,
+    ) -> rusqlite::Result<Vec<ShadowAction>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, created_at, delivery, handler, what FROM shadow_actions
+             WHERE ?1 IS NULL OR handler = ?1
+             ORDER BY id DESC LIMIT ?2",
+        )?;
+        stmt.query_map(params![handler, limit], |row| {
+            Ok(ShadowAction {
+                id: row.get(0)?,
+                created_at: row.get(1)?,
+                delivery: row.get(2)?,
+                handler: row.get(3)?,
+                what: row.get(4)?,
+            })
+        })?
+        .collect()
+    }
+
+    /// When the change was started and whether it's done.
+    pub fn effect(&self, key: &str) -> rusqlite::Result<Option<(DateTime<Utc>, bool)>> {
+        self.conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT started_at, done FROM effects WHERE key = ?1",
+                [key],
+                |row| {
+                    let started_at: String = row.get(0)?;
+                    Ok((
+                        DateTime::parse_from_rfc3339(&started_at)
+                            .unwrap()
+                            .with_timezone(&Utc),
+                        row.get(1)?,
+                    ))
+                },
+            )
+            .optional()
+    }
+
+    pub fn begin_effect(&self, key: &str) -> rusqlite::Result<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT OR IGNORE INTO effects (key, started_at) VALUES (?1, ?2)",
+            params![key, Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    pub fn finish_effect(&self, key: &str) -> rusqlite::Result<()> {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute("UPDATE effects SET done = 1 WHERE key = ?1", [key])?;
+        Ok(())
+    }
+
+    /// Marks the change as done, keeping what it created.
+    pub fn finish_effect_with(&self, key: &str, result: &str) -> rusqlite::Result<()> {
+        self.conn.lock().unwrap().execute(
+            "UPDATE effects SET done = 1, result = ?2 WHERE key = ?1",
+            params![key, result],
+        )?;
+        Ok(())
+    }
+
+    /// What a change that is done created, see [`Db::finish_effect_with`].
+    pub fn effect_result(&self, key: &str) -> rusqlite::Result<Option<String>> {
+        self.conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT result FROM effects WHERE key = ?1 AND done = 1",
+                [key],
+                |row| row.get(0),
+            )
+            .optional()
+            .map(Option::flatten)
+    }
+
+    pub fn hold_message(&self, url: &str, content: &str) -> rusqlite::Result<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO held_messages (created_at, url, content) VALUES (?1, ?2, ?3)",
+            params![Utc::now().to_rfc3339(), url, content],
+        )?;
+        Ok(())
+    }
+
+    /// The held back Discord messages by id, url and content, the oldest first.
+    pub fn held_messages(&self) -> rusqlite::Result<Vec<(i64, String, String)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT id, url, content FROM held_messages ORDER BY id")?;
+        stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+            .collect()
+    }
+
+    pub fn remove_held_message(&self, id: i64) -> rusqlite::Result<()> {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute("DELETE FROM held_messages WHERE id = ?1", [id])?;
+        Ok(())
+    }
+
+    pub fn get_kv(&self, key: &str) -> rusqlite::Result<Option<String>> {
+        self.conn
+            .lock()
+            .unwrap()
+            .query_row("SELECT value FROM kv WHERE key = ?1", [key], |row| {
+                row.get(0)
+            })
+            .optional()
+    }
+
+    pub fn remove_kv(&self, key: &str) -> rusqlite::Result<()> {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute("DELETE FROM kv WHERE key = ?1", [key])?;
+        Ok(())
+    }
+
+    pub fn set_kv(&self, key: &str, value: &str) -> rusqlite::Result<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO kv (key, value) VALUES (?1, ?2)
+             ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+            [key, value],
+        )?;
+        Ok(())
+    }
+}
+
+fn dead_letter_from_row(row: &rusqlite::Row) -> rusqlite::Result<DeadLetter> {
+    let job: String = row.get(4)?;
+    Ok(DeadLetter {
+        id: row.get(0)?,
+        created_at: row.get(1)?,
+        handler: row.get(2)?,
+        error: row.get(3)?,
+        job: serde_json::from_str(&job).unwrap(),
+    })
+}
+
+fn migrate(conn: &Connection) -> rusqlite::Result<()> {
+    let version: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        conn.execute_batch(migration)?;
+        conn.pragma_update(None, "user_version", i + 1)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(delivery: &str) -> Job {
+        Job {
+            delivery: delivery.to_string(),
+            event: "ping".to_string(),
+            body: "{}".to_string(),
+        }
+    }
+
+    #[test]
+    fn queued_deliveries_are_taken_in_order_once() {
+        let db = Db::open(":memory:").unwrap();
+        assert!(db.queue_push(&job("a")).unwrap());
+        assert!(db.queue_push(&job("b")).unwrap());
+        assert!(!db.queue_push(&job("a")).unwrap());
+        assert_eq!(db.queue_len().unwrap(), 2);
+
+        let (a, raw) = db.queue_take().unwrap().unwrap();
+        assert_eq!(serde_json::from_str::<Job>(&raw).unwrap().delivery, "a");
+        let (_, raw) = db.queue_take().unwrap().unwrap();
+        assert_eq!(serde_json::from_str::<Job>(&raw).unwrap().delivery, "b");
+        assert_eq!(db.queue_take().unwrap(), None);
+        assert_eq!(db.queue_len().unwrap(), 0);
+
+        db.queue_remove(a).unwrap();
+        assert_eq!(db.release_queue().unwrap(), 1);
+        let (_, raw) = db.queue_take().unwrap().unwrap();
+        assert_eq!(serde_json::from_str::<Job>(&raw).unwrap().delivery, "b");
+    }
+
+    #[test]
+    fn migrations_run_once() {
+        let db = Db::open(":memory:").unwrap();
+        let conn = db.conn.lock().unwrap();
+        migrate(&conn).unwrap();
+        let version: usize = conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, MIGRATIONS.len());
+    }
+}
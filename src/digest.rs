@@ -0,0 +1,420 @@
+use std::{collections::HashMap, fmt::Write, time::Duration};
+
+use chrono::{DateTime, Datelike, Utc};
+use octocrab::{
+    Octocrab,
+    models::{self, issues::Issue},
+    params,
+};
+use serde_json::json;
+use tracing::{info, warn};
+
+use crate::{
+    AppState,
+    aging::{UNTRIAGED_7D_LABEL, UNTRIAGED_30D_LABEL},
+    db::CiJobStats,
+    feed::TRIAGE_LABEL,
+    github,
+    handlers::Error,
+    milestones::{self, BLOCKER_LABEL},
+    outbox,
+    queue::Job,
+    quiet,
+    slo::{self, Breach},
+    workflow::WAITING_FOR_REVIEWS_LABEL,
+};
+
+pub const DIGEST_LABEL: &str = "triage-digest";
+
+const STALE_CLAIM_DAYS: i64 = 30;
+const MAX_ITEMS: usize = 15;
+
+/// Keeps a weekly digest issue up to date in the `owner/name` repo.
+///
+/// The issue title contains the ISO week, so refreshes during a week edit the same issue
+/// and the first refresh of a new week opens a new one and closes the previous one.
+pub async fn digest_loop(state: AppState, (owner, name): (String, String), period: Duration) {
+    let mut interval = tokio::time::interval(period);
+
+    loop {
+        interval.tick().await;
+        quiet::wait(state.quiet_hours, "triage digest").await;
+        let res = update_digest(&state, &owner, &name).await;
+        match res {
+            Ok(()) => info!("Updated triage digest"),
+            Err(e) => warn!("Failed to update triage digest: {}", e),
+        }
+    }
+}
+
+/// The open issues and PRs of the installed repos, by digest section.
+#[derive(Debug, Default)]
+struct Buckets {
+    blockers: Vec<Issue>,
+    new_issues: Vec<Issue>,
+    unlabeled: Vec<Issue>,
+    untriaged: Vec<Issue>,
+    stale_claims: Vec<Issue>,
+    waiting_reviews: Vec<Issue>,
+}
+
+impl Buckets {
+    /// Files the issue under the sections it belongs to. Digest issues aren't listed.
+    fn add(&mut self, issue: Issue, now: DateTime<Utc>, blocker_days: u64) {
+        if issue.labels.iter().any(|x| x.name == DIGEST_LABEL) {
+            return;
+        }
+        let is_pr = issue.pull_request.is_some();
+        let idle_days = (now - issue.updated_at).num_days();
+        if issue.labels.iter().any(|x| x.name == BLOCKER_LABEL)
+            && issue
+                .milestone
+                .as_ref()
+                .is_some_and(|x| milestones::due_soon(x, now, blocker_days))
+        {
+            self.blockers.push(issue.clone());
+        }
+
+        if is_pr {
+            if issue
+                .labels
+                .iter()
+                .any(|x| x.name == WAITING_FOR_REVIEWS_LABEL)
+            {
+                self.waiting_reviews.push(issue);
+            }
+            return;
+        }
+
+        if issue.labels.iter().any(|x| x.name == UNTRIAGED_30D_LABEL) {
+            self.untriaged.push(issue);
+        } else if (now - issue.created_at).num_days() < 7 {
+            self.new_issues.push(issue);
+        } else if issue
+            .labels
+            .iter()
+            .all(|x| [TRIAGE_LABEL, UNTRIAGED_7D_LABEL].contains(&x.name.as_str()))
+        {
+            self.unlabeled.push(issue);
+        } else if !issue.assignees.is_empty() && idle_days >= STALE_CLAIM_DAYS {
+            self.stale_claims.push(issue);
+        }
+    }
+}
+
+/// When the open PRs of the repo waiting for reviews were opened, or last marked ready for
+/// review, by URL.
+async fn waiting_since(
+    client: &Octocrab,
+    owner: &str,
+    name: &str,
+) -> Result<HashMap<String, DateTime<Utc>>, Error> {
+    let mut since = HashMap::new();
+    let mut after: Option<String> = None;
+    loop {
+        let res = github::graphql(
+            client,
+            "query($owner: String!, $name: String!, $label: String!, $after: String) { \
+                repository(owner: $owner, name: $name) { \
+                pullRequests(states: OPEN, labels: [$label], first: 100, after: $after) { \
+                pageInfo { hasNextPage endCursor } \
+                nodes { url createdAt \
+                timelineItems(itemTypes: [READY_FOR_REVIEW_EVENT], last: 1) { \
+                nodes { ... on ReadyForReviewEvent { createdAt } } } } \
+                } } }",
+            json!({
+                "owner": owner,
+                "name": name,
+                "label": WAITING_FOR_REVIEWS_LABEL,
+                "after": after,
+            }),
+        )
+        .await?;
+        let prs = &res["data"]["repository"]["pullRequests"];
+        for pr in prs["nodes"].as_array().into_iter().flatten() {
+            let ready = &pr["timelineItems"]["nodes"][0]["createdAt"];
+            let at = ready.as_str().or(pr["createdAt"].as_str());
+            if let (Some(url), Some(at)) = (pr["url"].as_str(), at) {
+                since.insert(url.to_string(), at.parse()?);
+            }
+        }
+        if prs["pageInfo"]["hasNextPage"] != true {
+            return Ok(since);
+        }
+        after = prs["pageInfo"]["endCursor"].as_str().map(String::from);
+    }
+}
+
+async fn update_digest(state: &AppState, owner: &str, name: &str) -> Result<(), Error> {
+    let (db, dry_run, slo) = (&state.db, state.dry_run, state.slo);
+    let rules = state.rules();
+    let now = Utc::now();
+    let week = now.iso_week();
+    let title = format!("Triage digest {}-W{:02}", week.year(), week.week());
+
+    let mut buckets = Buckets::default();
+    let mut since = HashMap::new();
+    let mut digest_client = None;
+
+    for (installation, client, repo) in github::installed_repos(&state.installations).await? {
+        let Some(repo_owner) = repo.owner else {
+            continue;
+        };
+        if repo_owner.login == owner && repo.name == name {
+            digest_client = Some(client.clone());
+        }
+        if !rules.handlers.allows(repo.full_name.as_deref()) {
+            continue;
+        }
+        state
+            .rate_limits
+            .wait_for_budget(installation, "triage digest")
+            .await;
+
+        let page = client
+            .issues(&repo_owner.login, &repo.name)
+            .list()
+            .state(params::State::Open)
+            .per_page(100)
+            .send()
+            .await?;
+
+        let waiting = buckets.waiting_reviews.len();
+        for issue in client.all_pages(page).await? {
+            buckets.add(issue, now, state.blocker_ping_days);
+        }
+        if buckets.waiting_reviews.len() > waiting {
+            since.extend(waiting_since(&client, &repo_owner.login, &repo.name).await?);
+        }
+    }
+
+    let Some(client) = digest_client else {
+        warn!("The app is not installed on {}/{}", owner, name);
+        return Ok(());
+    };
+
+    let Buckets {
+        mut blockers,
+        mut new_issues,
+        mut unlabeled,
+        mut untriaged,
+        mut stale_claims,
+        mut waiting_reviews,
+    } = buckets;
+    blockers.sort_by_key(|x| x.updated_at);
+    new_issues.sort_by_key(|x| x.created_at);
+    unlabeled.sort_by_key(|x| x.created_at);
+    untriaged.sort_by_key(|x| x.created_at);
+    stale_claims.sort_by_key(|x| x.updated_at);
+    waiting_reviews.sort_by_key(|x| {
+        since
+            .get(x.html_url.as_str())
+            .copied()
+            .unwrap_or(x.created_at)
+    });
+
+    let mut body = String::new();
+    if !blockers.is_empty() {
+        section(&mut body, "Blockers of the release due next", &blockers);
+    }
+    section(&mut body, "New issues this week", &new_issues);
+    section(&mut body, "Issues without labels", &unlabeled);
+    section(
+        &mut body,
+        "Issues waiting for triage for 30 days",
+        &untriaged,
+    );
+    section(
+        &mut body,
+        &format!("Claims without activity for {} days", STALE_CLAIM_DAYS),
+        &stale_claims,
+    );
+    section(
+        &mut body,
+        "PRs waiting for review longest",
+        &waiting_reviews,
+    );
+    match db.ci_job_stats(now - chrono::Duration::days(7)) {
+        Ok(jobs) => ci_section(&mut body, &jobs),
+        Err(e) => warn!("Failed to read CI job stats: {}", e),
+    }
+    if slo.first_response_hours.is_some() || slo.triage_hours.is_some() {
+        match db.issue_times(now - chrono::Duration::days(slo::WINDOW_DAYS)) {
+            Ok(times) => slo_section(&mut body, &slo::breaches(&times, slo, now)),
+            Err(e) => warn!("Failed to read issue times: {}", e),
+        }
+    }
+
+    let issues = client.issues(owner, name);
+    let page = issues
+        .list()
+        .labels(&[DIGEST_LABEL.to_string()])
+        .state(params::State::Open)
+        .send()
+        .await?;
+
+    let mut current = None;
+    for issue in page.items {
+        if issue.title == title {
+            current = Some(issue.number);
+        } else {
+            github::mutate(dry_run, format!("close digest #{}", issue.number), || {
+                issues
+                    .update(issue.number)
+                    .state(models::IssueState::Closed)
+                    .send()
+            })
+            .await?;
+        }
+    }
+
+    match current {
+        Some(number) => {
+            github::mutate(dry_run, format!("update digest #{}", number), || {
+                issues.update(number).body(&body).send()
+            })
+            .await?;
+        }
+        None => {
+            // The week's digest is opened once, a run retried after it was opened finds it
+            // again by its marker.
+            let job = Job {
+                delivery: format!("digest:{}/{}:{}", owner, name, title),
+                event: "digest".to_string(),
+                body: String::new(),
+            };
+            let labels = vec![DIGEST_LABEL.to_string()];
+            outbox::run(db, &job, "digest", false, async {
+                github::create_issue(dry_run, &issues, &title, &body, labels, Vec::new()).await
+            })
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The jobs that waited longest for a runner this week.
+fn ci_section(body: &mut String, jobs: &[CiJobStats]) {
+    writeln!(body, "### CI jobs waiting longest for a runner\n").unwrap();
+    if jobs.is_empty() {
+        writeln!(body, "Nothing here.\n").unwrap();
+        return;
+    }
+    writeln!(
+        body,
+        "| Job | Runs | Queued (avg) | Queued (max) | Duration (avg) |\n|---|---|---|---|---|"
+    )
+    .unwrap();
+    let minutes = |secs: f64| format!("{:.1} min", secs / 60.0);
+    for job in jobs.iter().take(MAX_ITEMS) {
+        writeln!(
+            body,
+            "| {} {} / {} | {} | {} | {} | {} |",
+            job.full_name,
+            job.workflow,
+            job.name,
+            job.runs,
+            minutes(job.avg_queued_secs),
+            minutes(job.max_queued_secs),
+            minutes(job.avg_run_secs)
+        )
+        .unwrap();
+    }
+    body.push('\n');
+}
+
+/// The open issues waiting longer for a response or triage than the SLO allows.
+fn slo_section(body: &mut String, breaches: &[Breach]) {
+    writeln!(
+        body,
+        "### Issues over the response time SLO ({})\n",
+        breaches.len()
+    )
+    .unwrap();
+    if breaches.is_empty() {
+        writeln!(body, "Nothing here.\n").unwrap();
+        return;
+    }
+    for x in breaches.iter().take(MAX_ITEMS) {
+        writeln!(
+            body,
+            "- https://github.com/{}/issues/{} waiting {} hours for {}",
+            x.full_name, x.number, x.hours, x.kind
+        )
+        .unwrap();
+    }
+    if breaches.len() > MAX_ITEMS {
+        writeln!(body, "- ...and {} more", breaches.len() - MAX_ITEMS).unwrap();
+    }
+    body.push('\n');
+}
+
+fn section(body: &mut String, title: &str, issues: &[Issue]) {
+    writeln!(body, "### {} ({})\n", title, issues.len()).unwrap();
+    if issues.is_empty() {
+        writeln!(body, "Nothing here.\n").unwrap();
+        return;
+    }
+    for issue in issues.iter().take(MAX_ITEMS) {
+        writeln!(
+            body,
+            "- {} {} (by {}, updated {})",
+            issue.html_url,
+            issue.title,
+            issue.user.login,
+            issue.updated_at.format("%Y-%m-%d")
+        )
+        .unwrap();
+    }
+    if issues.len() > MAX_ITEMS {
+        writeln!(body, "- ...and {} more", issues.len() - MAX_ITEMS).unwrap();
+    }
+    body.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn digests_are_left_out_of_the_buckets() {
+        let fixture: serde_json::Value = serde_json::from_str(include_str!(
+            "../tests/fixtures/issues-7d3e5f20-00b1-11f0-8e8a-3c9e41d2b7a1.json"
+        ))
+        .unwrap();
+        let label = |name: &str| {
+            json!({
+                "id": 1,
+                "node_id": "LA_1",
+                "url": format!("https://api.github.com/repos/ddnet/ddnet/labels/{}", name),
+                "name": name,
+                "color": "d73a4a",
+                "default": false,
+                "description": null,
+            })
+        };
+        let issue = |labels: serde_json::Value| {
+            let mut issue = fixture["body"]["issue"].clone();
+            issue["labels"] = labels;
+            serde_json::from_value::<Issue>(issue).unwrap()
+        };
+        let created_at = issue(json!([])).created_at;
+
+        let mut buckets = Buckets::default();
+        buckets.add(issue(json!([label(DIGEST_LABEL)])), created_at, 7);
+        buckets.add(
+            issue(json!([label(DIGEST_LABEL), label(TRIAGE_LABEL)])),
+            created_at + chrono::Duration::days(10),
+            7,
+        );
+        buckets.add(issue(json!([label(TRIAGE_LABEL)])), created_at, 7);
+        buckets.add(
+            issue(json!([label(TRIAGE_LABEL)])),
+            created_at + chrono::Duration::days(10),
+            7,
+        );
+        assert_eq!(buckets.new_issues.len(), 1);
+        assert_eq!(buckets.unlabeled.len(), 1);
+    }
+}
@@ -0,0 +1,27 @@
+use serde_json::json;
+use tracing::info;
+
+/// Discord rejects messages longer than this.
+pub const MAX_LEN: usize = 2000;
+
+/// Posts a message through a Discord webhook, cut to the length Discord accepts, or only logs
+/// it in dry-run mode.
+pub async fn post(
+    http: &reqwest::Client,
+    dry_run: bool,
+    url: &str,
+    content: &str,
+) -> reqwest::Result<()> {
+    let content: String = content.chars().take(MAX_LEN).collect();
+    if dry_run {
+        info!("Dry run, would post to Discord: {}", content);
+        return Ok(());
+    }
+    http.post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(json!({ "content": content }).to_string())
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
@@ -0,0 +1,171 @@
+use std::time::Duration;
+
+use axum::{
+    extract::State,
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use octocrab::{models, params};
+use tracing::{info, warn};
+
+use crate::{AppState, bulk, github, handlers::Error};
+
+pub const TRIAGE_LABEL: &str = "triage-needed";
+
+/// Periodically rebuilds the triage feed served at `/feeds/triage.xml`.
+pub async fn refresh_loop(state: AppState, period: Duration) {
+    let mut interval = tokio::time::interval(period);
+
+    loop {
+        interval.tick().await;
+        match build_triage_feed(&state).await {
+            Ok(xml) => {
+                *state.triage_feed.write().await = xml;
+                info!("Refreshed triage feed");
+            }
+            Err(e) => warn!("Failed to refresh triage feed: {}", e),
+        }
+    }
+}
+
+/// `GET /feeds/triage.xml`, 503 until the feed was built once, an empty body isn't a feed.
+pub async fn triage_feed(State(state): State<AppState>) -> Response {
+    let xml = state.triage_feed.read().await.clone();
+    if xml.is_empty() {
+        return StatusCode::SERVICE_UNAVAILABLE.into_response();
+    }
+    ([(header::CONTENT_TYPE, "application/atom+xml")], xml).into_response()
+}
+
+async fn build_triage_feed(state: &AppState) -> Result<String, Error> {
+    let labels = vec![TRIAGE_LABEL.to_string()];
+    let rules = state.rules();
+    let mut entries = Vec::new();
+
+    for (installation, client, repo) in github::installed_repos(&state.installations).await? {
+        let Some(owner) = repo.owner else {
+            continue;
+        };
+        if !rules.handlers.allows(repo.full_name.as_deref()) {
+            continue;
+        }
+        state
+            .rate_limits
+            .wait_for_budget(installation, "triage feed refresh")
+            .await;
+        let issues = client.issues(&owner.login, &repo.name);
+        let page = github::retry(|| {
+            issues
+                .list()
+                .labels(&labels)
+                .state(params::State::Open)
+                .per_page(100)
+                .send()
+        })
+        .await?;
+
+        for issue in bulk::pages(&client, page).await? {
+            if issue.pull_request.is_some() {
+                continue;
+            }
+            entries.push((repo.name.clone(), issue));
+        }
+    }
+    Ok(atom(entries))
+}
+
+/// The Atom feed of the issues by repo name, newest first.
+fn atom(mut entries: Vec<(String, models::issues::Issue)>) -> String {
+    entries.sort_by_key(|x| std::cmp::Reverse(x.1.created_at));
+
+    let updated = entries
+        .first()
+        .map(|x| x.1.created_at)
+        .unwrap_or_else(chrono::Utc::now);
+
+    let mut xml = String::new();
+    xml.push_str(r#"<?xml version="1.0" encoding="utf-8"?>"#);
+    xml.push('\n');
+    xml.push_str(r#"<feed xmlns="http://www.w3.org/2005/Atom">"#);
+    xml.push_str("<id>urn:ddbot:triage</id>");
+    xml.push_str("<title>Issues needing triage</title>");
+    xml.push_str(&format!("<updated>{}</updated>", updated.to_rfc3339()));
+
+    for (repo, issue) in entries {
+        xml.push_str("<entry>");
+        xml.push_str(&format!("<id>{}</id>", escape(issue.html_url.as_str())));
+        xml.push_str(&format!(
+            "<title>[{}] #{} {}</title>",
+            escape(&repo),
+            issue.number,
+            escape(&issue.title)
+        ));
+        xml.push_str(&format!(
+            r#"<link href="{}"/>"#,
+            escape(issue.html_url.as_str())
+        ));
+        xml.push_str(&format!(
+            "<author><name>{}</name></author>",
+            escape(&issue.user.login)
+        ));
+        xml.push_str(&format!(
+            "<published>{}</published>",
+            issue.created_at.to_rfc3339()
+        ));
+        xml.push_str(&format!(
+            "<updated>{}</updated>",
+            issue.updated_at.to_rfc3339()
+        ));
+        if let Some(body) = &issue.body {
+            xml.push_str(&format!(
+                r#"<summary type="text">{}</summary>"#,
+                escape(body)
+            ));
+        }
+        xml.push_str("</entry>");
+    }
+
+    xml.push_str("</feed>");
+    xml
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn newest_issues_come_first() {
+        let fixture: serde_json::Value = serde_json::from_str(include_str!(
+            "../tests/fixtures/issues-7d3e5f20-00b1-11f0-8e8a-3c9e41d2b7a1.json"
+        ))
+        .unwrap();
+        let issue = |number: u64, title: &str, created_at: &str| {
+            let mut issue = fixture["body"]["issue"].clone();
+            issue["number"] = number.into();
+            issue["title"] = title.into();
+            issue["created_at"] = created_at.into();
+            serde_json::from_value(issue).unwrap()
+        };
+        let xml = atom(vec![
+            ("ddnet".to_string(), issue(1, "Old", "2025-01-01T00:00:00Z")),
+            (
+                "ddnet".to_string(),
+                issue(2, "Crash on <Ctrl> & F1", "2025-03-01T00:00:00Z"),
+            ),
+        ]);
+        assert!(xml.contains("<updated>2025-03-01T00:00:00+00:00</updated>"));
+        let new = xml
+            .find("<title>[ddnet] #2 Crash on &lt;Ctrl&gt; &amp; F1</title>")
+            .unwrap();
+        let old = xml.find("<title>[ddnet] #1 Old</title>").unwrap();
+        assert!(new < old);
+    }
+}
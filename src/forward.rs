@@ -0,0 +1,231 @@
+use std::{collections::HashSet, sync::Arc, time::Duration};
+
+use axum::{body::Bytes, http::HeaderMap};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use tracing::{Instrument, info, warn};
+
+use crate::config::Config;
+
+const MAX_ATTEMPTS: u32 = 4;
+
+/// A downstream URL that receives a copy of selected webhook deliveries.
+#[derive(Debug, Clone)]
+pub struct ForwardTarget {
+    pub url: String,
+    /// Event names (as in `X-GitHub-Event`) to forward, `None` forwards everything.
+    pub events: Option<HashSet<String>>,
+}
+
+impl ForwardTarget {
+    fn wants(&self, event: &str) -> bool {
+        match &self.events {
+            Some(events) => events.contains(event),
+            None => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Forwarder {
+    client: reqwest::Client,
+    targets: Arc<Vec<ForwardTarget>>,
+    secret: Option<String>,
+}
+
+impl Forwarder {
+    /// Reads the targets from `FORWARD_TARGETS`.
+    ///
+    /// Targets are whitespace separated, each one is `url` or `url|event,event`,
+    /// e.g. `https://staging.example/|issues,issue_comment`.
+    /// Forwarded bodies are re-signed with `FORWARD_SECRET` when set.
+    pub fn from_config(config: &Config) -> Self {
+        let targets = parse_targets(&config.forward_targets);
+        let secret = config.forward_secret.clone();
+
+        for target in &targets {
+            info!("Forwarding {:?} to {}", target.events, target.url);
+        }
+
+        Self {
+            client: reqwest::Client::new(),
+            targets: Arc::new(targets),
+            secret,
+        }
+    }
+
+    /// Sends a copy of the delivery to every target interested in `event`. Only deliveries
+    /// whose signature was checked and whose repo is allowed may be forwarded, as they're signed
+    /// with `FORWARD_SECRET`.
+    ///
+    /// Runs in the background so the webhook response isn't delayed by slow targets.
+    pub fn forward(&self, event: &str, headers: &HeaderMap, body: Bytes) {
+        let delivery = headers
+            .get("X-GitHub-Delivery")
+            .and_then(|x| x.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        let signature = self.secret.as_ref().map(|secret| sign(secret, &body));
+
+        for target in self.targets.iter().filter(|x| x.wants(event)) {
+            let client = self.client.clone();
+            let target = target.clone();
+            let event = event.to_string();
+            let delivery = delivery.clone();
+            let signature = signature.clone();
+            let body = body.clone();
+
+            tokio::spawn(
+                async move {
+                    for attempt in 1..=MAX_ATTEMPTS {
+                        let mut req = client
+                            .post(&target.url)
+                            .header("Content-Type", "application/json")
+                            .header("X-GitHub-Event", &event)
+                            .header("X-GitHub-Delivery", &delivery)
+                            .body(body.clone());
+                        if let Some(signature) = &signature {
+                            req = req.header("X-Hub-Signature-256", signature);
+                        }
+
+                        match req.send().await {
+                            Ok(res) if res.status().is_success() => {
+                                info!(
+                                    "Forwarded delivery {} ({}) to {}: {}",
+                                    delivery,
+                                    event,
+                                    target.url,
+                                    res.status()
+                                );
+                                return;
+                            }
+                            Ok(res) => warn!(
+                                "Forwarding delivery {} to {} failed (attempt {}/{}): {}",
+                                delivery,
+                                target.url,
+                                attempt,
+                                MAX_ATTEMPTS,
+                                res.status()
+                            ),
+                            Err(e) => warn!(
+                                "Forwarding delivery {} to {} failed (attempt {}/{}): {}",
+                                delivery, target.url, attempt, MAX_ATTEMPTS, e
+                            ),
+                        }
+
+                        if attempt < MAX_ATTEMPTS {
+                            tokio::time::sleep(Duration::from_secs(1 << attempt)).await;
+                        }
+                    }
+                    warn!(
+                        "Giving up forwarding delivery {} to {}",
+                        delivery, target.url
+                    );
+                }
+                .in_current_span(),
+            );
+        }
+    }
+}
+
+fn parse_targets(value: &str) -> Vec<ForwardTarget> {
+    value
+        .split_whitespace()
+        .map(|entry| match entry.split_once('|') {
+            Some((url, events)) => ForwardTarget {
+                url: url.to_string(),
+                events: Some(events.split(',').map(|x| x.trim().to_string()).collect()),
+            },
+            None => ForwardTarget {
+                url: entry.to_string(),
+                events: None,
+            },
+        })
+        .collect()
+}
+
+/// Computes the `X-Hub-Signature-256` header value for `body`.
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Whether `signature`, in the format of the `X-Hub-Signature-256` header, is the one of `body`.
+pub fn verify(secret: &str, signature: &str, body: &[u8]) -> bool {
+    let Some(signature) = signature
+        .strip_prefix("sha256=")
+        .and_then(|x| hex::decode(x).ok())
+    else {
+        return false;
+    };
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(body);
+    mac.verify_slice(&signature).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{header, method, path},
+    };
+
+    use super::*;
+
+    #[test]
+    fn signatures_are_verified() {
+        let body = br#"{"action":"opened"}"#;
+        assert!(verify("webhook", &sign("webhook", body), body));
+        assert!(!verify("webhook", &sign("other", body), body));
+        assert!(!verify("webhook", &sign("webhook", body), b"{}"));
+        assert!(!verify("webhook", "sha256=zz", body));
+        assert!(!verify("webhook", "", body));
+    }
+
+    #[tokio::test]
+    async fn deliveries_are_filtered_and_resigned() {
+        let downstream = MockServer::start().await;
+        let body = Bytes::from_static(br#"{"action":"opened"}"#);
+        Mock::given(method("POST"))
+            .and(path("/issues"))
+            .and(header("X-GitHub-Event", "issues"))
+            .and(header("X-GitHub-Delivery", "d1"))
+            .and(header(
+                "X-Hub-Signature-256",
+                sign("staging", &body).as_str(),
+            ))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&downstream)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/pulls"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(0)
+            .mount(&downstream)
+            .await;
+
+        let forwarder = Forwarder {
+            client: reqwest::Client::new(),
+            targets: Arc::new(parse_targets(&format!(
+                "{0}/issues|issues,issue_comment {0}/pulls|pull_request",
+                downstream.uri()
+            ))),
+            secret: Some("staging".to_string()),
+        };
+        let mut headers = HeaderMap::new();
+        headers.insert("X-GitHub-Delivery", HeaderValue::from_static("d1"));
+        forwarder.forward("issues", &headers, body);
+
+        // Forwarding runs in the background.
+        for _ in 0..50 {
+            if !downstream.received_requests().await.unwrap().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        downstream.verify().await;
+    }
+}
@@ -0,0 +1,169 @@
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use octocrab::{
+    Octocrab,
+    params::{
+        self,
+        checks::{CheckRunConclusion, CheckRunOutput, CheckRunStatus},
+    },
+};
+use tracing::{info, warn};
+
+use crate::{AppState, db::Freeze, github, handlers::Error, reminders};
+
+pub const FROZEN_LABEL: &str = "frozen";
+/// The check run frozen PRs fail, branch protection requiring it keeps them from being merged.
+pub const CHECK_NAME: &str = "Freeze";
+
+/// How often it's checked whether a freeze is over.
+const CHECK_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// When a freeze set with `!ddnetbot freeze` ends, a date (`2025-03-01`, the start of the day
+/// in UTC) or a duration like for `!ddnetbot remind` (`14d`).
+pub fn parse_until(text: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        let until = date.and_hms_opt(0, 0, 0)?.and_utc();
+        return (until > now).then_some(until);
+    }
+    reminders::parse_duration(text).map(|x| now + x)
+}
+
+/// Lifts the freezes that are over.
+pub async fn lift_loop(state: AppState) {
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+
+    loop {
+        interval.tick().await;
+        let freezes = match state.db.freezes() {
+            Ok(x) => x,
+            Err(e) => {
+                warn!("Failed to read the branch freezes: {}", e);
+                continue;
+            }
+        };
+        for freeze in freezes.iter().filter(|x| x.until <= Utc::now()) {
+            // Tried again next time if it fails.
+            if let Err(e) = lift_expired(&state, freeze).await {
+                warn!(
+                    "Failed to lift the freeze of {} in {}: {}",
+                    freeze.branch, freeze.full_name, e
+                );
+            }
+        }
+    }
+}
+
+async fn lift_expired(state: &AppState, freeze: &Freeze) -> Result<(), Error> {
+    let Some((_, installation)) = state.db.repo_by_name(&freeze.full_name)? else {
+        return Err(format!("the app isn't installed on {}", freeze.full_name).into());
+    };
+    let client = state.installations.client(installation)?;
+    lift(state, &client, freeze).await
+}
+
+/// Ends the freeze, taking `frozen` off the open PRs into the branch and passing their check.
+pub async fn lift(state: &AppState, client: &Octocrab, freeze: &Freeze) -> Result<(), Error> {
+    let (owner, name) = freeze
+        .full_name
+        .split_once('/')
+        .ok_or("repository without owner")?;
+    let pulls = client.pulls(owner, name);
+    let page = github::retry(|| {
+        pulls
+            .list()
+            .state(params::State::Open)
+            .base(&freeze.branch)
+            .per_page(100)
+            .send()
+    })
+    .await?;
+    let issues = client.issues(owner, name);
+    for pr in client.all_pages(page).await? {
+        if pr.labels.iter().flatten().any(|x| x.name == FROZEN_LABEL) {
+            github::mutate(
+                state.dry_run,
+                format!("remove label {} from #{}", FROZEN_LABEL, pr.number),
+                || issues.remove_label(pr.number, FROZEN_LABEL),
+            )
+            .await?;
+        }
+        report_check(state.dry_run, client, &freeze.full_name, &pr.head.sha, None).await?;
+    }
+    state.db.remove_freeze(&freeze.full_name, &freeze.branch)?;
+    info!(
+        "Lifted the freeze of {} in {}",
+        freeze.branch, freeze.full_name
+    );
+    Ok(())
+}
+
+/// Passes the check of a PR, or fails it if it's frozen.
+pub async fn report_check(
+    dry_run: bool,
+    client: &Octocrab,
+    full_name: &str,
+    sha: &str,
+    frozen: Option<&Freeze>,
+) -> Result<(), Error> {
+    let (owner, name) = full_name
+        .split_once('/')
+        .ok_or("repository without owner")?;
+    let (conclusion, summary) = match frozen {
+        Some(freeze) => (
+            CheckRunConclusion::Failure,
+            format!(
+                "{} is frozen until {}.",
+                freeze.branch,
+                freeze.until.format("%Y-%m-%d %H:%M UTC")
+            ),
+        ),
+        None => (
+            CheckRunConclusion::Success,
+            "The branch isn't frozen.".to_string(),
+        ),
+    };
+    let checks = client.checks(owner, name);
+    github::mutate(
+        dry_run,
+        format!("report {} on {}: {}", CHECK_NAME, sha, summary),
+        || {
+            checks
+                .create_check_run(CHECK_NAME, sha)
+                .status(CheckRunStatus::Completed)
+                .conclusion(conclusion)
+                .output(CheckRunOutput {
+                    title: CHECK_NAME.to_string(),
+                    summary: summary.clone(),
+                    text: None,
+                    annotations: Vec::new(),
+                    images: Vec::new(),
+                })
+                .send()
+        },
+    )
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn freezes_end_at_a_date_or_after_a_while() {
+        let now = Utc.with_ymd_and_hms(2025, 3, 1, 12, 0, 0).unwrap();
+        assert_eq!(
+            parse_until("2025-03-15", now),
+            Some(Utc.with_ymd_and_hms(2025, 3, 15, 0, 0, 0).unwrap())
+        );
+        assert_eq!(
+            parse_until("2d", now),
+            Some(Utc.with_ymd_and_hms(2025, 3, 3, 12, 0, 0).unwrap())
+        );
+        assert_eq!(parse_until("2025-02-01", now), None);
+        assert_eq!(parse_until("soon", now), None);
+    }
+}
@@ -0,0 +1,790 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use axum::http::{HeaderValue, Uri, header::USER_AGENT};
+use chrono::{DateTime, Utc};
+use hyper_util::rt::TokioExecutor;
+use octocrab::{
+    AuthState, Octocrab, OctocrabBuilder,
+    auth::AppAuth,
+    models,
+    service::middleware::{
+        auth_header::AuthHeaderLayer, base_uri::BaseUriLayer, extra_headers::ExtraHeadersLayer,
+    },
+};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use tracing::{info, warn};
+
+use crate::{
+    outbox::{self, Effect},
+    ratelimit::{RateLimits, RecordLayer},
+    report,
+};
+
+const MAX_ATTEMPTS: u32 = 5;
+
+/// Installation tokens are valid for an hour, clients are rebuilt a bit before that.
+const CLIENT_TTL: Duration = Duration::from_secs(50 * 60);
+
+/// The installation id used for everything in personal access token mode.
+pub const PERSONAL_INSTALLATION: models::InstallationId = models::InstallationId(0);
+
+/// Caches one client per installation.
+///
+/// octocrab keeps the installation token inside the client, so reusing clients avoids minting
+/// a new token (and signing a new JWT) for every delivery. Every client records the rate limit
+/// of its installation, see [`RateLimits::layer`].
+#[derive(Debug, Clone)]
+pub struct Installations {
+    app: Arc<Octocrab>,
+    /// Authenticates the clients of installations, `None` when `app` is authenticated with a
+    /// personal access token and used for every installation.
+    app_auth: Option<AppAuth>,
+    base_uri: Uri,
+    rate_limits: RateLimits,
+    clients: Arc<Mutex<HashMap<models::InstallationId, (Octocrab, Instant)>>>,
+}
+
+impl Installations {
+    /// Authenticates as the GitHub App `app_id` against the API at `base_uri`.
+    pub fn new(
+        base_uri: &str,
+        app_id: models::AppId,
+        key: jsonwebtoken::EncodingKey,
+        rate_limits: RateLimits,
+    ) -> Result<Self, crate::handlers::Error> {
+        let base_uri: Uri = base_uri.parse()?;
+        let app_auth = AppAuth { app_id, key };
+        let app = build_client(
+            &base_uri,
+            None,
+            AuthState::App(app_auth.clone()),
+            rate_limits.layer(None),
+        )?;
+        Ok(Self {
+            app: Arc::new(app),
+            app_auth: Some(app_auth),
+            base_uri,
+            rate_limits,
+            clients: Default::default(),
+        })
+    }
+
+    /// Uses a personal access token instead of a GitHub App.
+    pub fn personal(
+        base_uri: &str,
+        token: &str,
+        rate_limits: RateLimits,
+    ) -> Result<Self, crate::handlers::Error> {
+        let base_uri: Uri = base_uri.parse()?;
+        let app = build_client(
+            &base_uri,
+            Some(format!("Bearer {}", token).parse()?),
+            AuthState::None,
+            rate_limits.layer(Some(PERSONAL_INSTALLATION)),
+        )?;
+        Ok(Self {
+            app: Arc::new(app),
+            app_auth: None,
+            base_uri,
+            rate_limits,
+            clients: Default::default(),
+        })
+    }
+
+    pub fn is_personal(&self) -> bool {
+        self.app_auth.is_none()
+    }
+
+    /// Lists the installations of the app, or [`PERSONAL_INSTALLATION`] with a token.
+    pub async fn ids(&self) -> octocrab::Result<Vec<models::InstallationId>> {
+        if self.is_personal() {
+            return Ok(vec![PERSONAL_INSTALLATION]);
+        }
+        let page = self.app.apps().installations().send().await?;
+        let installations = self.app.all_pages(page).await?;
+        Ok(installations.into_iter().map(|x| x.id).collect())
+    }
+
+    /// The client authenticated as the app itself.
+    pub fn app(&self) -> &Octocrab {
+        &self.app
+    }
+
+    /// Returns the client for the installation, creating it if missing or about to expire.
+    #[allow(clippy::result_large_err)]
+    pub fn client(&self, id: models::InstallationId) -> octocrab::Result<Octocrab> {
+        let Some(app_auth) = &self.app_auth else {
+            return Ok(self.app.as_ref().clone());
+        };
+        let mut clients = self.clients.lock().unwrap();
+        if let Some((client, created)) = clients.get(&id)
+            && created.elapsed() < CLIENT_TTL
+        {
+            return Ok(client.clone());
+        }
+
+        // A client of its own, so its responses are known to be the installation's.
+        let client = build_client(
+            &self.base_uri,
+            None,
+            AuthState::App(app_auth.clone()),
+            self.rate_limits.layer(Some(id)),
+        )?
+        .installation(id)?;
+        clients.insert(id, (client.clone(), Instant::now()));
+        Ok(client)
+    }
+
+    /// Drops the cached client of an installation that was removed.
+    pub fn forget(&self, id: models::InstallationId) {
+        self.clients.lock().unwrap().remove(&id);
+    }
+}
+
+/// Builds a client for the API at `base_uri` like octocrab's default one, recording the rate
+/// limit of the responses with `record`. Failed calls aren't retried, see [`retry`].
+#[allow(clippy::result_large_err)]
+fn build_client(
+    base_uri: &Uri,
+    auth_header: Option<HeaderValue>,
+    auth: AuthState,
+    record: RecordLayer,
+) -> octocrab::Result<Octocrab> {
+    let connector = hyper_rustls::HttpsConnectorBuilder::new()
+        .with_native_roots()
+        .map_err(|e| octocrab::Error::Other {
+            source: e.into(),
+            backtrace: std::backtrace::Backtrace::capture(),
+        })?
+        .https_or_http()
+        .enable_http1()
+        .build();
+    let client = hyper_util::client::legacy::Client::builder(TokioExecutor::new()).build(connector);
+    // Uploads go elsewhere, the bot doesn't upload anything.
+    let upload_uri = base_uri.clone();
+    let client = OctocrabBuilder::new_empty()
+        .with_service(client)
+        .with_layer(&record)
+        .with_layer(&ExtraHeadersLayer::new(Arc::new(vec![(
+            USER_AGENT,
+            HeaderValue::from_static("ddbot"),
+        )])))
+        .with_layer(&BaseUriLayer::new(base_uri.clone()))
+        .with_layer(&AuthHeaderLayer::new(
+            auth_header,
+            base_uri.clone(),
+            upload_uri,
+        ))
+        .with_auth(auth)
+        .build();
+    Ok(client.unwrap_or_else(|x| match x {}))
+}
+
+/// Lists every repository the app is installed on, with its installation and a client for it.
+///
+/// With a personal access token these are the repos the token's user has access to.
+pub async fn installed_repos(
+    installations: &Installations,
+) -> octocrab::Result<Vec<(models::InstallationId, Octocrab, models::Repository)>> {
+    let mut repos = Vec::new();
+    for id in installations.ids().await? {
+        let client = installations.client(id)?;
+        for repo in installation_repos(installations, id).await? {
+            repos.push((id, client.clone(), repo));
+        }
+    }
+
+    Ok(repos)
+}
+
+/// Lists the repos of one installation, see [`installed_repos`].
+pub async fn installation_repos(
+    installations: &Installations,
+    id: models::InstallationId,
+) -> octocrab::Result<Vec<models::Repository>> {
+    let client = installations.client(id)?;
+    let route = if installations.is_personal() {
+        "/user/repos"
+    } else {
+        "/installation/repositories"
+    };
+    let page: octocrab::Page<models::Repository> =
+        client.get(route, Some(&[("per_page", 100)])).await?;
+    client.all_pages(page).await
+}
+
+/// A commit of a PR, with only the fields the bot needs.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PrCommit {
+    pub sha: String,
+    pub commit: PrCommitDetails,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PrCommitDetails {
+    pub message: String,
+    pub author: Option<models::repos::CommitAuthor>,
+}
+
+/// Lists the commits of a PR, oldest first (at most 250, GitHub doesn't list more).
+pub async fn pr_commits(
+    client: &Octocrab,
+    repo: &models::Repository,
+    number: u64,
+) -> octocrab::Result<Vec<PrCommit>> {
+    let owner = repo
+        .owner
+        .as_ref()
+        .map(|x| x.login.as_str())
+        .unwrap_or_default();
+    let route = format!("/repos/{}/{}/pulls/{}/commits", owner, repo.name, number);
+    let mut commits: Vec<PrCommit> = Vec::new();
+    for page in 1..=3 {
+        let query = [("per_page", 100), ("page", page)];
+        let res: Vec<PrCommit> = retry(|| client.get(&route, Some(&query))).await?;
+        let done = res.len() < 100;
+        commits.extend(res);
+        if done {
+            break;
+        }
+    }
+    Ok(commits)
+}
+
+/// Runs a GraphQL query through [`retry`], turning the `errors` of the response into an error.
+pub async fn graphql(
+    client: &Octocrab,
+    query: &str,
+    variables: serde_json::Value,
+) -> Result<serde_json::Value, crate::handlers::Error> {
+    let body = serde_json::json!({ "query": query, "variables": variables });
+    let res: serde_json::Value = retry(|| client.graphql(&body)).await?;
+    if let Some(errors) = res.get("errors") {
+        return Err(format!("GraphQL errors: {}", errors).into());
+    }
+    Ok(res)
+}
+
+/// Runs a GraphQL mutation through [`create`], turning the `errors` of the response into an
+/// error. Returns the `data` of the response, `None` in dry-run mode.
+pub async fn graphql_mutation(
+    dry_run: bool,
+    client: &Octocrab,
+    what: String,
+    query: &str,
+    variables: serde_json::Value,
+) -> octocrab::Result<Option<serde_json::Value>> {
+    let body = serde_json::json!({ "query": query, "variables": variables });
+    let effect = Effect::current(&what).filter(|_| !dry_run);
+    // Nothing to look for, a mutation that was started is made again like with `mutate`.
+    let find = |_, _| async { Ok(None) };
+    create(dry_run, what, effect, find, || async {
+        let res: serde_json::Value = client.graphql(&body).await?;
+        match res.get("errors") {
+            Some(errors) => Err(octocrab::Error::Other {
+                source: format!("GraphQL errors: {}", errors).into(),
+                backtrace: std::backtrace::Backtrace::capture(),
+            }),
+            None => Ok(res["data"].clone()),
+        }
+    })
+    .await
+}
+
+/// Hides the comment with the GraphQL id `node_id` as `classifier`.
+pub async fn minimize_comment(
+    dry_run: bool,
+    client: &Octocrab,
+    node_id: &str,
+    classifier: &str,
+) -> octocrab::Result<()> {
+    graphql_mutation(
+        dry_run,
+        client,
+        format!("hide comment {} as {}", node_id, classifier),
+        "mutation($id: ID!, $classifier: ReportedContentClassifiers!) { \
+            minimizeComment(input: { subjectId: $id, classifier: $classifier }) { \
+            clientMutationId } }",
+        serde_json::json!({ "id": node_id, "classifier": classifier }),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Runs a mutating GitHub API call through [`retry`], or only logs `what` in dry-run mode.
+pub async fn mutate<T, F, Fut>(dry_run: bool, what: String, f: F) -> octocrab::Result<()>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = octocrab::Result<T>>,
+{
+    report::record(&what);
+    if dry_run {
+        info!("Dry run, would {}", what);
+        outbox::record_shadow(&what);
+        return Ok(());
+    }
+    // Adding labels and the like can just be done again if it's unknown whether it was.
+    let effect = Effect::current(&what);
+    if let Some(effect) = &effect {
+        if effect.state() == outbox::State::Done {
+            info!("Already done for this delivery, skipping: {}", what);
+            return Ok(());
+        }
+        effect.begin();
+    }
+    retry_mutation(f).await?;
+    if let Some(effect) = &effect {
+        effect.done();
+    }
+    Ok(())
+}
+
+/// Comments on an issue or PR, see [`create_comment`].
+pub async fn comment(
+    dry_run: bool,
+    issues: &octocrab::issues::IssueHandler<'_>,
+    number: u64,
+    body: &str,
+) -> octocrab::Result<()> {
+    create_comment(dry_run, issues, number, body).await?;
+    Ok(())
+}
+
+/// Comments on an issue or PR through [`create`] and returns the comment, `None` in dry-run
+/// mode.
+pub async fn create_comment(
+    dry_run: bool,
+    issues: &octocrab::issues::IssueHandler<'_>,
+    number: u64,
+    body: &str,
+) -> octocrab::Result<Option<models::issues::Comment>> {
+    let what = format!("comment on #{}: {}", number, body);
+    let effect = Effect::current(&what).filter(|_| !dry_run);
+    let body = match &effect {
+        Some(effect) => format!("{}\n\n{}", body, effect.marker()),
+        None => body.to_string(),
+    };
+    // On a busy issue the comment can be past the first page.
+    let find = |started_at, marker: String| async move {
+        for n in 1u32.. {
+            let page = retry(|| {
+                issues
+                    .list_comments(number)
+                    .since(started_at)
+                    .per_page(100)
+                    .page(n)
+                    .send()
+            })
+            .await?;
+            let last = page.next.is_none();
+            let found = page
+                .items
+                .into_iter()
+                .find(|x| x.body.as_deref().is_some_and(|x| x.contains(&marker)));
+            if found.is_some() || last {
+                return Ok(found);
+            }
+        }
+        Ok(None)
+    };
+    create(dry_run, what, effect, find, || {
+        issues.create_comment(number, &body)
+    })
+    .await
+}
+
+/// Opens an issue through [`create`] and returns it, `None` in dry-run mode.
+pub async fn create_issue(
+    dry_run: bool,
+    issues: &octocrab::issues::IssueHandler<'_>,
+    title: &str,
+    body: &str,
+    labels: Vec<String>,
+    assignees: Vec<String>,
+) -> octocrab::Result<Option<models::issues::Issue>> {
+    let what = format!("open issue {:?}", title);
+    let effect = Effect::current(&what).filter(|_| !dry_run);
+    let body = match &effect {
+        Some(effect) => format!("{}\n\n{}", body, effect.marker()),
+        None => body.to_string(),
+    };
+    // Every issue and PR updated since counts, so on a busy repo the issue can be past the
+    // first page.
+    let find = |started_at, marker: String| async move {
+        for n in 1u32.. {
+            let page = retry(|| {
+                issues
+                    .list()
+                    .state(octocrab::params::State::All)
+                    .since(started_at)
+                    .per_page(100)
+                    .page(n)
+                    .send()
+            })
+            .await?;
+            let last = page.next.is_none();
+            let found = page
+                .items
+                .into_iter()
+                .find(|x| x.body.as_deref().is_some_and(|x| x.contains(&marker)));
+            if found.is_some() || last {
+                return Ok(found);
+            }
+        }
+        Ok(None)
+    };
+    create(dry_run, what, effect, find, || {
+        issues
+            .create(title)
+            .body(&body)
+            .labels(labels.clone())
+            .assignees(assignees.clone())
+            .send()
+    })
+    .await
+}
+
+/// Runs an API call creating something on GitHub through [`retry`], or only logs `what` in
+/// dry-run mode.
+///
+/// Unlike [`mutate`] the created object is returned, so it's kept with the `effect` and
+/// returned again when the handler runs again for the delivery. If it's unknown whether it was
+/// created, `find` looks for it by the marker of the effect, which `f` has to hide in it.
+async fn create<T, F, Fut, L, LFut>(
+    dry_run: bool,
+    what: String,
+    effect: Option<Effect>,
+    find: L,
+    f: F,
+) -> octocrab::Result<Option<T>>
+where
+    T: Serialize + DeserializeOwned,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = octocrab::Result<T>>,
+    L: FnOnce(DateTime<Utc>, String) -> LFut,
+    LFut: Future<Output = octocrab::Result<Option<T>>>,
+{
+    report::record(&what);
+    if dry_run {
+        info!("Dry run, would {}", what);
+        outbox::record_shadow(&what);
+        return Ok(None);
+    }
+    let Some(effect) = effect else {
+        return retry_mutation(f).await.map(Some);
+    };
+    match effect.state() {
+        outbox::State::Done => {
+            if let Some(created) = effect.result() {
+                info!("Already done for this delivery, skipping: {}", what);
+                return Ok(Some(created));
+            }
+        }
+        outbox::State::Pending(started_at) => {
+            if let Some(created) = find(started_at, effect.marker()).await? {
+                info!("Already done before the handler stopped: {}", what);
+                effect.done_with(&created);
+                return Ok(Some(created));
+            }
+        }
+        outbox::State::New => {}
+    }
+    effect.begin();
+    let created = retry_mutation(f).await?;
+    effect.done_with(&created);
+    Ok(Some(created))
+}
+
+/// Runs a GitHub API call, retrying transient failures (502/503/504, secondary rate limits
+/// and connection errors) with jittered exponential backoff.
+pub async fn retry<T, F, Fut>(f: F) -> octocrab::Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = octocrab::Result<T>>,
+{
+    retry_if(is_transient, f).await
+}
+
+/// Runs a GitHub API call that changes something like [`retry`], but only retries failures
+/// after which the change certainly wasn't made: GitHub often answers 502 or 504 after a
+/// comment was posted.
+pub async fn retry_mutation<T, F, Fut>(f: F) -> octocrab::Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = octocrab::Result<T>>,
+{
+    retry_if(is_not_made, f).await
+}
+
+async fn retry_if<T, F, Fut>(
+    retryable: fn(&octocrab::Error) -> bool,
+    mut f: F,
+) -> octocrab::Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = octocrab::Result<T>>,
+{
+    let mut attempt = 1;
+    loop {
+        if !crate::budget::record() {
+            // The handler is cancelled, see `crate::budget::run`.
+            std::future::pending::<()>().await;
+        }
+        let res = f().await;
+        match &res {
+            Err(e) if is_secondary_rate_limit(e) => {
+                SECONDARY_LIMITS.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(e) if is_transient(e) => crate::outage::GITHUB.failure(),
+            // Any answer means GitHub is up, even an error.
+            _ => crate::outage::GITHUB.success(),
+        }
+        match res {
+            Err(e) if attempt < MAX_ATTEMPTS && retryable(&e) => {
+                let mut delay = Duration::from_secs(1 << attempt).min(Duration::from_secs(60));
+                if is_secondary_rate_limit(&e) {
+                    // GitHub asks to wait at least a minute before retrying.
+                    delay = Duration::from_secs(60);
+                }
+                let delay = delay.mul_f64(0.5 + rand::random::<f64>() / 2.0);
+                warn!(
+                    "GitHub API call failed (attempt {}/{}), retrying in {:?}: {}",
+                    attempt, MAX_ATTEMPTS, delay, e
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            res => return res,
+        }
+    }
+}
+
+fn is_transient(e: &octocrab::Error) -> bool {
+    match e {
+        octocrab::Error::GitHub { source, .. } => {
+            matches!(source.status_code.as_u16(), 502..=504) || is_secondary_rate_limit(e)
+        }
+        octocrab::Error::Hyper { .. } | octocrab::Error::Service { .. } => true,
+        _ => false,
+    }
+}
+
+/// Whether a failed call certainly didn't change anything: GitHub was unavailable, refused it
+/// with a secondary rate limit or it never connected.
+fn is_not_made(e: &octocrab::Error) -> bool {
+    match e {
+        octocrab::Error::GitHub { source, .. } => {
+            source.status_code.as_u16() == 503 || is_secondary_rate_limit(e)
+        }
+        octocrab::Error::Service { source, .. } => {
+            let mut source: Option<&(dyn std::error::Error + 'static)> = Some(source.as_ref());
+            while let Some(e) = source {
+                if let Some(e) = e.downcast_ref::<hyper_util::client::legacy::Error>() {
+                    return e.is_connect();
+                }
+                source = e.source();
+            }
+            false
+        }
+        _ => false,
+    }
+}
+
+/// How many times GitHub answered with a secondary rate limit since the start.
+static SECONDARY_LIMITS: AtomicU64 = AtomicU64::new(0);
+
+/// How many times GitHub answered with a secondary rate limit since the start, see
+/// [`crate::bulk`].
+pub fn secondary_limits() -> u64 {
+    SECONDARY_LIMITS.load(Ordering::Relaxed)
+}
+
+fn is_secondary_rate_limit(e: &octocrab::Error) -> bool {
+    match e {
+        octocrab::Error::GitHub { source, .. } => {
+            matches!(source.status_code.as_u16(), 403 | 429)
+                && source
+                    .message
+                    .to_lowercase()
+                    .contains("secondary rate limit")
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{method, path, query_param},
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn installed_repos_reads_every_page() {
+        let github = MockServer::start().await;
+        let fixture: serde_json::Value = serde_json::from_str(include_str!(
+            "../tests/fixtures/issues-7d3e5f20-00b1-11f0-8e8a-3c9e41d2b7a1.json"
+        ))
+        .unwrap();
+        let repo = |id: u64| {
+            let mut repo = fixture["body"]["repository"].clone();
+            repo["id"] = json!(id);
+            repo["name"] = json!(format!("repo-{}", id));
+            repo
+        };
+        Mock::given(method("GET"))
+            .and(path("/user/repos"))
+            .and(query_param("page", "2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([repo(101)])))
+            .with_priority(1)
+            .mount(&github)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/user/repos"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!((1..=100).map(repo).collect::<Vec<_>>()))
+                    .insert_header(
+                        "Link",
+                        format!(
+                            r#"<{}/user/repos?per_page=100&page=2>; rel="next""#,
+                            github.uri()
+                        )
+                        .as_str(),
+                    ),
+            )
+            .mount(&github)
+            .await;
+
+        let installations =
+            Installations::personal(&github.uri(), "ghp_test", RateLimits::new(500)).unwrap();
+        let repos = installed_repos(&installations).await.unwrap();
+        assert_eq!(repos.len(), 101);
+        assert_eq!(repos[100].2.name, "repo-101");
+    }
+
+    #[tokio::test]
+    async fn created_issues_are_returned_when_run_again() {
+        let github = MockServer::start().await;
+        let fixture: serde_json::Value = serde_json::from_str(include_str!(
+            "../tests/fixtures/issues-7d3e5f20-00b1-11f0-8e8a-3c9e41d2b7a1.json"
+        ))
+        .unwrap();
+        Mock::given(method("POST"))
+            .and(path("/repos/ddnet/ddnet/issues"))
+            .respond_with(ResponseTemplate::new(201).set_body_json(&fixture["body"]["issue"]))
+            .expect(1)
+            .mount(&github)
+            .await;
+
+        let client = Octocrab::builder()
+            .base_uri(github.uri())
+            .unwrap()
+            .personal_token("ghp_test".to_string())
+            .build()
+            .unwrap();
+        let issues = client.issues("ddnet", "ddnet");
+        let db = crate::db::Db::open(":memory:").unwrap();
+        let job = crate::queue::Job {
+            delivery: "delivery".to_string(),
+            event: "issues".to_string(),
+            body: "{}".to_string(),
+        };
+        for _ in 0..2 {
+            let issue = outbox::run(&db, &job, "todos", false, async {
+                create_issue(false, &issues, "TODO", "body", Vec::new(), Vec::new()).await
+            })
+            .await
+            .unwrap()
+            .unwrap();
+            assert_eq!(issue.number, 9876);
+        }
+        github.verify().await;
+    }
+
+    #[tokio::test]
+    async fn issues_of_failed_runs_are_found_past_the_first_page() {
+        let github = MockServer::start().await;
+        let fixture: serde_json::Value = serde_json::from_str(include_str!(
+            "../tests/fixtures/issues-7d3e5f20-00b1-11f0-8e8a-3c9e41d2b7a1.json"
+        ))
+        .unwrap();
+        // GitHub may have opened the issue even though it answered 502.
+        Mock::given(method("POST"))
+            .and(path("/repos/ddnet/ddnet/issues"))
+            .respond_with(ResponseTemplate::new(502))
+            .expect(1)
+            .mount(&github)
+            .await;
+
+        let client = Octocrab::builder()
+            .base_uri(github.uri())
+            .unwrap()
+            .personal_token("ghp_test".to_string())
+            // Octocrab would post again after the 502 itself.
+            .add_retry_config(octocrab::service::middleware::retry::RetryConfig::None)
+            .build()
+            .unwrap();
+        let issues = client.issues("ddnet", "ddnet");
+        let db = crate::db::Db::open(":memory:").unwrap();
+        let job = crate::queue::Job {
+            delivery: "delivery".to_string(),
+            event: "issues".to_string(),
+            body: "{}".to_string(),
+        };
+        let create = || {
+            outbox::run(&db, &job, "todos", false, async {
+                let marker = outbox::Effect::current("open issue \"TODO\"")
+                    .unwrap()
+                    .marker();
+                let res = create_issue(false, &issues, "TODO", "body", Vec::new(), Vec::new());
+                (marker, res.await)
+            })
+        };
+        let (marker, res) = create().await;
+        assert!(res.is_err());
+
+        let mut other = fixture["body"]["issue"].clone();
+        other["number"] = json!(1);
+        let mut created = fixture["body"]["issue"].clone();
+        created["body"] = json!(format!("body\n\n{}", marker));
+        Mock::given(method("GET"))
+            .and(path("/repos/ddnet/ddnet/issues"))
+            .and(query_param("page", "2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([created])))
+            .with_priority(1)
+            .mount(&github)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/ddnet/ddnet/issues"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!([other]))
+                    .insert_header(
+                        "Link",
+                        format!(
+                            r#"<{}/repos/ddnet/ddnet/issues?per_page=100&page=2>; rel="next""#,
+                            github.uri()
+                        )
+                        .as_str(),
+                    ),
+            )
+            .mount(&github)
+            .await;
+
+        let (_, res) = create().await;
+        assert_eq!(res.unwrap().unwrap().number, 9876);
+        github.verify().await;
+    }
+}
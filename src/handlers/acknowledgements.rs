@@ -0,0 +1,127 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use octocrab::models::webhook_events::{
+    WebhookEvent, WebhookEventPayload, WebhookEventType, payload::IssuesWebhookEventAction,
+};
+use serde_json::json;
+
+use super::{Context, Error, EventHandler, outdated::form_field};
+use crate::{
+    batch,
+    config::{AcknowledgementsConfig, Config},
+    db::ResponseTimer,
+};
+
+/// The issue form fields besides the version a crash or regression report needs.
+const FIELDS: &[&str] = &["OS", "Demo", "Settings"];
+
+/// Acknowledges issues labeled as a crash or regression, asking for what the report lacks of
+/// the version, OS, demo and settings. With someone to ping, a response timer is started too,
+/// see [`crate::reminders::response_loop`].
+#[derive(Debug)]
+pub struct Acknowledgements {
+    config: AcknowledgementsConfig,
+    version_field: String,
+}
+
+impl Acknowledgements {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            config: config.acknowledgements.clone(),
+            version_field: config.version_field.clone(),
+        }
+    }
+
+    /// The fields of the issue form that are missing or left empty in `body`.
+    fn missing<'a>(&'a self, body: &str) -> Vec<&'a str> {
+        std::iter::once(self.version_field.as_str())
+            .chain(FIELDS.iter().copied())
+            .filter(|x| form_field(body, x).is_none())
+            .collect()
+    }
+}
+
+#[async_trait]
+impl EventHandler for Acknowledgements {
+    fn name(&self) -> &'static str {
+        "acknowledgements"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::Issues]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let Some(repo) = &event.repository else {
+            return Ok(());
+        };
+        let WebhookEventPayload::Issues(payload) = &event.specific else {
+            return Ok(());
+        };
+        let issue = &payload.issue;
+        if payload.action != IssuesWebhookEventAction::Labeled || issue.pull_request.is_some() {
+            return Ok(());
+        }
+        let Some(label) = payload
+            .label
+            .as_ref()
+            .filter(|x| self.config.labels.contains(&x.name))
+        else {
+            return Ok(());
+        };
+
+        // Once per issue, a regression that also crashes is acknowledged for the first label.
+        let key = format!("acknowledged:{}:{}", repo.id, issue.number);
+        if ctx.state.db.get_kv(&key)?.is_some() {
+            return Ok(());
+        }
+        let full_name = repo.full_name.as_deref().unwrap_or_default();
+        let missing = self.missing(issue.body.as_deref().unwrap_or_default());
+        let comment = ctx.rules.templates.render(
+            full_name,
+            "report_acknowledgement",
+            json!({
+                "author": issue.user.login,
+                "label": label.name,
+                "missing": missing,
+                "hours": (!self.config.ping.is_empty()).then_some(self.config.hours),
+            }),
+        )?;
+        batch::comment(&ctx.state, &ctx.client, repo.id, issue.number, &comment).await?;
+        ctx.state.db.set_kv(&key, "1")?;
+
+        if !self.config.ping.is_empty() {
+            let now = Utc::now();
+            ctx.state.db.add_response_timer(&ResponseTimer {
+                installation: ctx.installation,
+                repo: repo.id,
+                full_name: full_name.to_string(),
+                number: issue.number,
+                author: issue.user.login.clone(),
+                label: label.name.clone(),
+                started_at: now,
+                due_at: now + chrono::Duration::hours(self.config.hours as i64),
+            })?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_report_fields() {
+        let handler = Acknowledgements {
+            config: AcknowledgementsConfig::default(),
+            version_field: "Version".to_string(),
+        };
+        let body = "### Version\n\n18.9\n\n### OS\n\nWindows 11\n\n### Demo\n\n_No response_\n";
+        assert_eq!(handler.missing(body), ["Demo", "Settings"]);
+        assert_eq!(
+            handler.missing("it crashed"),
+            ["Version", "OS", "Demo", "Settings"]
+        );
+    }
+}
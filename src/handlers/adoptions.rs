@@ -0,0 +1,101 @@
+use async_trait::async_trait;
+use octocrab::models::{
+    self,
+    webhook_events::{
+        WebhookEvent, WebhookEventPayload, WebhookEventType, payload::PullRequestWebhookEventAction,
+    },
+};
+use serde_json::json;
+
+use super::{Context, Error, EventHandler};
+use crate::batch;
+
+pub const ADOPTABLE_LABEL: &str = "adoptable";
+
+/// Links the PR someone opens to take over a PR they adopted with `!ddnetbot adopt` from the
+/// adopted one, once it references it.
+#[derive(Debug)]
+pub struct Adoptions;
+
+impl Adoptions {
+    /// The key remembering who adopted a PR.
+    pub fn adopted_key(repo: models::RepositoryId, number: u64) -> String {
+        format!("adopted:{}:{}", repo, number)
+    }
+}
+
+#[async_trait]
+impl EventHandler for Adoptions {
+    fn name(&self) -> &'static str {
+        "adoptions"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::PullRequest]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let (WebhookEventPayload::PullRequest(payload), Some(repo)) =
+            (&event.specific, &event.repository)
+        else {
+            return Ok(());
+        };
+        if !matches!(
+            payload.action,
+            PullRequestWebhookEventAction::Opened | PullRequestWebhookEventAction::Edited
+        ) {
+            return Ok(());
+        }
+        let Some(author) = &payload.pull_request.user else {
+            return Ok(());
+        };
+        let text = format!(
+            "{}\n{}",
+            payload.pull_request.title.as_deref().unwrap_or_default(),
+            payload.pull_request.body.as_deref().unwrap_or_default()
+        );
+        for number in references(&text) {
+            let adopter = ctx.state.db.get_kv(&Self::adopted_key(repo.id, number))?;
+            if !adopter.is_some_and(|x| x.eq_ignore_ascii_case(&author.login)) {
+                continue;
+            }
+            let key = format!("adoption_superseded:{}:{}", repo.id, number);
+            if ctx.state.db.get_kv(&key)?.is_some() {
+                continue;
+            }
+            let comment = ctx.rules.templates.render(
+                repo.full_name.as_deref().unwrap_or_default(),
+                "adoption_superseded",
+                json!({ "adopter": author.login, "pr": payload.number }),
+            )?;
+            batch::comment(&ctx.state, &ctx.client, repo.id, number, &comment).await?;
+            ctx.state.db.set_kv(&key, &payload.number.to_string())?;
+        }
+        Ok(())
+    }
+}
+
+/// The numbers of the `#123` references in the text, of the same repo.
+fn references(text: &str) -> Vec<u64> {
+    let mut numbers: Vec<u64> = text
+        .split(|x: char| x.is_whitespace() || x == '(' || x == ',')
+        .filter_map(|x| x.strip_prefix('#'))
+        .filter_map(|x| x.trim_end_matches(['.', ')', ':']).parse().ok())
+        .collect();
+    numbers.sort_unstable();
+    numbers.dedup();
+    numbers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn references_are_found() {
+        assert_eq!(
+            references("Supersedes #12.\nTakes over (#7), see #12 and ddnet/ddnet-web#3 or #abc"),
+            [7, 12]
+        );
+    }
+}
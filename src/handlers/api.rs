@@ -0,0 +1,202 @@
+use std::collections::HashSet;
+
+use async_trait::async_trait;
+use octocrab::models::webhook_events::{
+    WebhookEvent, WebhookEventPayload, WebhookEventType, payload::PullRequestWebhookEventAction,
+};
+use serde_json::json;
+
+use super::{Context, Error, EventHandler};
+use crate::{batch, config::Config, github};
+
+pub const BREAKING_CHANGE_LABEL: &str = "breaking-change";
+
+/// A declaration a PR removes from a public header or changes.
+#[derive(Debug, PartialEq)]
+struct Break {
+    path: String,
+    declaration: String,
+    /// Whether something with the same name is still declared.
+    changed: bool,
+}
+
+/// Looks for removed or changed declarations in the public headers a PR touches, and labels
+/// and lists them as suspected API breaks.
+///
+/// It's a heuristic on the diff: moving a declaration or changing only its whitespace is not
+/// a break, anything else a declaration loses is.
+#[derive(Debug)]
+pub struct ApiChanges {
+    paths: Vec<String>,
+}
+
+impl ApiChanges {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            paths: config.api_headers.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for ApiChanges {
+    fn name(&self) -> &'static str {
+        "api-changes"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::PullRequest]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let (WebhookEventPayload::PullRequest(payload), Some(repo)) =
+            (&event.specific, &event.repository)
+        else {
+            return Ok(());
+        };
+        if self.paths.is_empty()
+            || !matches!(
+                payload.action,
+                PullRequestWebhookEventAction::Opened | PullRequestWebhookEventAction::Synchronize
+            )
+        {
+            return Ok(());
+        }
+
+        let owner = repo.owner.as_ref().ok_or("repository without owner")?;
+        let pulls = ctx.client.pulls(&owner.login, &repo.name);
+        let files = github::retry(|| pulls.list_files(payload.number)).await?;
+        let breaks: Vec<Break> = files
+            .items
+            .iter()
+            .filter(|x| {
+                self.paths
+                    .iter()
+                    .any(|p| x.filename.starts_with(p.as_str()))
+            })
+            .filter_map(|x| Some((x.filename.as_str(), x.patch.as_deref()?)))
+            .flat_map(|(path, patch)| breaks(path, patch))
+            .collect();
+        let labeled = payload
+            .pull_request
+            .labels
+            .iter()
+            .flatten()
+            .any(|x| x.name == BREAKING_CHANGE_LABEL);
+        // Updated on every push, but only once something was suspected.
+        if breaks.is_empty() && !labeled {
+            return Ok(());
+        }
+
+        if !labeled {
+            let labels = [BREAKING_CHANGE_LABEL.to_string()];
+            batch::add_labels(&ctx.state, &ctx.client, repo.id, payload.number, &labels).await?;
+        }
+        let text = ctx.rules.templates.render(
+            repo.full_name.as_deref().unwrap_or_default(),
+            "api_breaks",
+            json!({
+                "breaks": breaks
+                    .iter()
+                    .map(|x| json!({ "path": x.path, "declaration": x.declaration, "changed": x.changed }))
+                    .collect::<Vec<_>>(),
+            }),
+        )?;
+        ctx.state
+            .status
+            .set(ctx, repo.id, payload.number, "API", &text)
+            .await?;
+        Ok(())
+    }
+}
+
+/// The declarations the patch removes without adding them back unchanged.
+fn breaks(path: &str, patch: &str) -> Vec<Break> {
+    let normalize = |x: &str| x.split_ascii_whitespace().collect::<Vec<_>>().join(" ");
+    let mut removed = Vec::new();
+    let mut added = HashSet::new();
+    for line in patch.lines() {
+        if line.starts_with("---") || line.starts_with("+++") {
+            continue;
+        }
+        if let Some(line) = line.strip_prefix('-')
+            && declared_name(line).is_some()
+        {
+            removed.push(normalize(line));
+        } else if let Some(line) = line.strip_prefix('+') {
+            added.insert(normalize(line));
+        }
+    }
+    let added_names: HashSet<&str> = added.iter().filter_map(|x| declared_name(x)).collect();
+
+    removed
+        .into_iter()
+        .filter(|x| !added.contains(x))
+        .map(|declaration| Break {
+            path: path.to_string(),
+            changed: declared_name(&declaration).is_some_and(|x| added_names.contains(x)),
+            declaration,
+        })
+        .collect()
+}
+
+/// The name the line declares, if it looks like a declaration of a header: a type, a macro
+/// or a function.
+fn declared_name(line: &str) -> Option<&str> {
+    let line = line.trim();
+    if line.starts_with("//") || line.starts_with("/*") || line.starts_with('*') {
+        return None;
+    }
+    for keyword in ["class ", "struct ", "enum class ", "enum ", "#define "] {
+        if let Some(rest) = line.strip_prefix(keyword) {
+            return ident(rest.trim_start());
+        }
+    }
+    // Functions, but not calls or control flow in inline bodies.
+    let (head, _) = line.split_once('(')?;
+    let name = head.trim_end().rsplit([' ', '*', '&', ':']).next()?;
+    let is_declaration = head.trim_end().contains(' ')
+        && !["return", "if", "while", "for", "switch"]
+            .iter()
+            .any(|x| head.trim_start().starts_with(x))
+        && (line.ends_with(';') || line.ends_with('{') || line.ends_with(')'));
+    is_declaration.then_some(ident(name)?)
+}
+
+/// The identifier `text` starts with.
+fn ident(text: &str) -> Option<&str> {
+    let end = text
+        .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .unwrap_or(text.len());
+    (end > 0).then(|| &text[..end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removed_and_changed_declarations_are_breaks() {
+        let patch = "@@ -1,8 +1,8 @@\n class IGraphics\n {\n-\tvirtual void Clear(float r, float g, float b) = 0;\n+\tvirtual void Clear(float r, float g, float b, bool ForceClearNow) = 0;\n-\tvirtual int  WindowActive() = 0;\n+\tvirtual int WindowActive() = 0;\n-\tvirtual void Swap() = 0;\n-\t\treturn Foo(x);\n-#define MAX_CLIENTS 64\n };\n";
+        assert_eq!(
+            breaks("src/engine/graphics.h", patch),
+            [
+                Break {
+                    path: "src/engine/graphics.h".to_string(),
+                    declaration: "virtual void Clear(float r, float g, float b) = 0;".to_string(),
+                    changed: true,
+                },
+                Break {
+                    path: "src/engine/graphics.h".to_string(),
+                    declaration: "virtual void Swap() = 0;".to_string(),
+                    changed: false,
+                },
+                Break {
+                    path: "src/engine/graphics.h".to_string(),
+                    declaration: "#define MAX_CLIENTS 64".to_string(),
+                    changed: false,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,261 @@
+use async_trait::async_trait;
+use http_body_util::BodyExt;
+use octocrab::{
+    models::{
+        repos::DiffEntryStatus,
+        webhook_events::{
+            WebhookEvent, WebhookEventPayload, WebhookEventType,
+            payload::PullRequestWebhookEventAction,
+        },
+    },
+    params::checks::{
+        CheckRunConclusion, CheckRunOutput, CheckRunOutputAnnotation,
+        CheckRunOutputAnnotationLevel, CheckRunStatus,
+    },
+};
+
+use super::{Context, Error, EventHandler};
+use crate::{
+    config::{AssetRule, Config},
+    github,
+};
+
+const CHECK_NAME: &str = "Assets";
+
+/// Extensions of the files that are checked as images.
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp", "bmp"];
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// The color type of 8-bit RGBA in the PNG header, and the one of palette images.
+const PNG_RGBA: u8 = 6;
+const PNG_PALETTE: u8 = 3;
+
+/// Checks the images PRs add under the asset paths, e.g. skins, against the asset guidelines
+/// and reports the ones that don't follow them as a check run with a message per file.
+#[derive(Debug)]
+pub struct Assets {
+    rules: Vec<AssetRule>,
+}
+
+impl Assets {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            rules: config.assets.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for Assets {
+    fn name(&self) -> &'static str {
+        "assets"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::PullRequest]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let (WebhookEventPayload::PullRequest(payload), Some(repo)) =
+            (&event.specific, &event.repository)
+        else {
+            return Ok(());
+        };
+        if self.rules.is_empty()
+            || !matches!(
+                payload.action,
+                PullRequestWebhookEventAction::Opened
+                    | PullRequestWebhookEventAction::Reopened
+                    | PullRequestWebhookEventAction::Synchronize
+            )
+        {
+            return Ok(());
+        }
+
+        let owner = repo.owner.as_ref().ok_or("repository without owner")?;
+        let pulls = ctx.client.pulls(&owner.login, &repo.name);
+        let files = github::retry(|| pulls.list_files(payload.number)).await?;
+        let assets: Vec<(&str, &AssetRule)> = files
+            .items
+            .iter()
+            .filter(|x| x.status != DiffEntryStatus::Removed)
+            .filter(|x| {
+                x.filename.rsplit_once('.').is_some_and(|(_, extension)| {
+                    IMAGE_EXTENSIONS.contains(&extension.to_lowercase().as_str())
+                })
+            })
+            .filter_map(|x| {
+                let rule = self
+                    .rules
+                    .iter()
+                    .find(|rule| rule.paths.iter().any(|p| x.filename.starts_with(p)))?;
+                Some((x.filename.as_str(), rule))
+            })
+            .collect();
+        if assets.is_empty() {
+            return Ok(());
+        }
+
+        // The head commit is reachable from the base repo, also for PRs from forks.
+        let sha = &payload.pull_request.head.sha;
+        let mut failures = Vec::new();
+        for (filename, rule) in &assets {
+            let response = ctx
+                .client
+                .repos(&owner.login, &repo.name)
+                .raw_file(sha.clone(), *filename)
+                .await?;
+            if !response.status().is_success() {
+                return Err(format!("downloading {}: {}", filename, response.status()).into());
+            }
+            let data = response.into_body().collect().await?.to_bytes();
+            let problems = problems(rule, &data);
+            if !problems.is_empty() {
+                failures.push((*filename, problems));
+            }
+        }
+
+        let (conclusion, summary) = if failures.is_empty() {
+            (
+                CheckRunConclusion::Success,
+                format!("All {} images follow the asset guidelines.", assets.len()),
+            )
+        } else {
+            (
+                CheckRunConclusion::Failure,
+                format!(
+                    "{} of {} images don't follow the asset guidelines.",
+                    failures.len(),
+                    assets.len()
+                ),
+            )
+        };
+        let checks = ctx.client.checks(&owner.login, &repo.name);
+        github::mutate(
+            ctx.state.dry_run,
+            format!("report {} on #{}: {}", CHECK_NAME, payload.number, summary),
+            || {
+                checks
+                    .create_check_run(CHECK_NAME, sha)
+                    .status(CheckRunStatus::Completed)
+                    .conclusion(conclusion)
+                    .output(CheckRunOutput {
+                        title: CHECK_NAME.to_string(),
+                        summary: summary.clone(),
+                        text: None,
+                        annotations: failures
+                            .iter()
+                            .map(|(filename, problems)| CheckRunOutputAnnotation {
+                                path: filename.to_string(),
+                                start_line: 1,
+                                end_line: 1,
+                                start_column: None,
+                                end_column: None,
+                                annotation_level: CheckRunOutputAnnotationLevel::Failure,
+                                message: problems.join("\n"),
+                                title: Some("Invalid asset".to_string()),
+                                raw_details: None,
+                            })
+                            .collect(),
+                        images: Vec::new(),
+                    })
+                    .send()
+            },
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+/// The size, bit depth and color type from the header of a PNG image.
+#[derive(Debug, PartialEq)]
+struct PngHeader {
+    width: u32,
+    height: u32,
+    bit_depth: u8,
+    color_type: u8,
+}
+
+fn png_header(data: &[u8]) -> Option<PngHeader> {
+    if !data.starts_with(PNG_SIGNATURE) || data.get(12..16)? != b"IHDR" {
+        return None;
+    }
+    let u32_at = |i: usize| Some(u32::from_be_bytes(data.get(i..i + 4)?.try_into().ok()?));
+    Some(PngHeader {
+        width: u32_at(16)?,
+        height: u32_at(20)?,
+        bit_depth: *data.get(24)?,
+        color_type: *data.get(25)?,
+    })
+}
+
+/// The ways an image breaks the rule.
+fn problems(rule: &AssetRule, data: &[u8]) -> Vec<String> {
+    let Some(png) = png_header(data) else {
+        return vec!["Not a PNG image, assets must be saved as PNG".to_string()];
+    };
+    let mut problems = Vec::new();
+
+    let [width, height] = rule.size;
+    let sized = if rule.multiples {
+        png.width % width == 0
+            && png.height % height == 0
+            && png.width / width == png.height / height
+            && png.width >= width
+    } else {
+        png.width == width && png.height == height
+    };
+    if !sized {
+        problems.push(format!(
+            "The image is {}x{}, it must be {}x{}{}",
+            png.width,
+            png.height,
+            width,
+            height,
+            if rule.multiples {
+                " or a multiple of it"
+            } else {
+                ""
+            }
+        ));
+    }
+    if rule.rgba && png.color_type == PNG_PALETTE {
+        problems.push("The image uses a palette, save it as 8-bit RGBA".to_string());
+    } else if rule.rgba && (png.color_type != PNG_RGBA || png.bit_depth != 8) {
+        problems.push(format!(
+            "The image has color type {} with {} bits, save it as 8-bit RGBA",
+            png.color_type, png.bit_depth
+        ));
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(width: u32, height: u32, color_type: u8) -> Vec<u8> {
+        let mut data = PNG_SIGNATURE.to_vec();
+        data.extend(13u32.to_be_bytes());
+        data.extend(b"IHDR");
+        data.extend(width.to_be_bytes());
+        data.extend(height.to_be_bytes());
+        data.extend([8, color_type, 0, 0, 0]);
+        data
+    }
+
+    #[test]
+    fn images_are_checked() {
+        let rule = AssetRule {
+            paths: vec!["data/skins/".to_string()],
+            size: [256, 128],
+            multiples: true,
+            rgba: true,
+        };
+        assert!(problems(&rule, &png(256, 128, PNG_RGBA)).is_empty());
+        assert!(problems(&rule, &png(512, 256, PNG_RGBA)).is_empty());
+        assert_eq!(problems(&rule, &png(512, 128, PNG_PALETTE)).len(), 2);
+        assert_eq!(problems(&rule, b"GIF89a").len(), 1);
+    }
+}
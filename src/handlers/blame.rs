@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use octocrab::models::webhook_events::{
+    WebhookEvent, WebhookEventPayload, WebhookEventType, payload::PullRequestWebhookEventAction,
+};
+use serde_json::json;
+
+use super::{Context, Error, EventHandler};
+use crate::{
+    batch,
+    config::{BlameReviewersConfig, Config},
+    github,
+};
+
+/// How many of the changed files are looked up, one API request each.
+const MAX_FILES: usize = 10;
+
+/// How many commits per file are looked at.
+const COMMITS_PER_FILE: u8 = 30;
+
+/// Suggests the people who recently committed the most to the files a PR changes as its
+/// reviewers, or requests their reviews.
+#[derive(Debug)]
+pub struct BlameReviewers {
+    config: Option<BlameReviewersConfig>,
+}
+
+impl BlameReviewers {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            config: config.blame_reviewers.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for BlameReviewers {
+    fn name(&self) -> &'static str {
+        "blame-reviewers"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::PullRequest]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let (WebhookEventPayload::PullRequest(payload), Some(repo), Some(config)) =
+            (&event.specific, &event.repository, &self.config)
+        else {
+            return Ok(());
+        };
+        let ready = match payload.action {
+            PullRequestWebhookEventAction::Opened => payload.pull_request.draft != Some(true),
+            PullRequestWebhookEventAction::ReadyForReview => true,
+            _ => false,
+        };
+        if !ready || config.max == 0 {
+            return Ok(());
+        }
+
+        let owner = repo.owner.as_ref().ok_or("repository without owner")?;
+        let pulls = ctx.client.pulls(&owner.login, &repo.name);
+        let files = github::retry(|| pulls.list_files(payload.number)).await?;
+        let author = payload
+            .pull_request
+            .user
+            .as_ref()
+            .map(|x| x.login.as_str())
+            .unwrap_or_default();
+        let since = Utc::now() - chrono::Duration::days(config.days as i64);
+
+        let mut commits: HashMap<String, usize> = HashMap::new();
+        let repos = ctx.client.repos(&owner.login, &repo.name);
+        for file in files.items.iter().take(MAX_FILES) {
+            let page = github::retry(|| {
+                repos
+                    .list_commits()
+                    .path(file.filename.clone())
+                    .since(since)
+                    .per_page(COMMITS_PER_FILE)
+                    .send()
+            })
+            .await?;
+            for commit in page.items {
+                let Some(login) = commit.author.map(|x| x.login) else {
+                    continue;
+                };
+                if !login.eq_ignore_ascii_case(author) && !login.ends_with("[bot]") {
+                    *commits.entry(login).or_default() += 1;
+                }
+            }
+        }
+        let reviewers = top(commits, config.max);
+        if reviewers.is_empty() {
+            return Ok(());
+        }
+
+        if config.request {
+            github::mutate(
+                ctx.state.dry_run,
+                format!(
+                    "request reviews from {:?} on #{}",
+                    reviewers, payload.number
+                ),
+                || pulls.request_reviews(payload.number, reviewers.clone(), Vec::new()),
+            )
+            .await?;
+            return Ok(());
+        }
+        let comment = ctx.rules.templates.render(
+            repo.full_name.as_deref().unwrap_or_default(),
+            "reviewer_suggestions",
+            json!({ "reviewers": reviewers }),
+        )?;
+        batch::comment(&ctx.state, &ctx.client, repo.id, payload.number, &comment).await?;
+        Ok(())
+    }
+}
+
+/// The `max` logins with the most commits, ties by login.
+fn top(commits: HashMap<String, usize>, max: usize) -> Vec<String> {
+    let mut commits: Vec<(String, usize)> = commits.into_iter().collect();
+    commits.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    commits.into_iter().take(max).map(|(x, _)| x).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn top_committers_are_picked() {
+        let commits = HashMap::from([
+            ("def-".to_string(), 3),
+            ("Robyt3".to_string(), 5),
+            ("heinrich5991".to_string(), 3),
+        ]);
+        assert_eq!(top(commits, 2), ["Robyt3", "def-"]);
+    }
+}
@@ -0,0 +1,76 @@
+use async_trait::async_trait;
+use octocrab::models::webhook_events::{
+    WebhookEvent, WebhookEventPayload, WebhookEventType, payload::PullRequestWebhookEventAction,
+};
+use serde_json::json;
+
+use super::{Context, Error, EventHandler};
+use crate::{
+    batch,
+    config::{ChecklistItem, Config},
+    github,
+};
+
+/// Posts a review checklist on new PRs, with the items for the paths they touch.
+#[derive(Debug)]
+pub struct Checklist {
+    items: Vec<ChecklistItem>,
+}
+
+impl Checklist {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            items: config.checklist.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for Checklist {
+    fn name(&self) -> &'static str {
+        "checklist"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::PullRequest]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let (WebhookEventPayload::PullRequest(payload), Some(repo)) =
+            (&event.specific, &event.repository)
+        else {
+            return Ok(());
+        };
+        if payload.action != PullRequestWebhookEventAction::Opened || self.items.is_empty() {
+            return Ok(());
+        }
+
+        let owner = repo.owner.as_ref().ok_or("repository without owner")?;
+        let pulls = ctx.client.pulls(&owner.login, &repo.name);
+        let files = github::retry(|| pulls.list_files(payload.pull_request.number)).await?;
+
+        let items: Vec<&str> = self
+            .items
+            .iter()
+            .filter(|item| {
+                files.items.iter().any(|file| {
+                    item.paths
+                        .iter()
+                        .any(|path| file.filename.starts_with(path.as_str()))
+                })
+            })
+            .map(|item| item.item.as_str())
+            .collect();
+        if items.is_empty() {
+            return Ok(());
+        }
+
+        let full_name = repo.full_name.as_deref().unwrap_or_default();
+        let comment =
+            ctx.rules
+                .templates
+                .render(full_name, "checklist", json!({ "items": items }))?;
+        batch::comment(&ctx.state, &ctx.client, repo.id, payload.number, &comment).await?;
+        Ok(())
+    }
+}
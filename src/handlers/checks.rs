@@ -0,0 +1,54 @@
+use async_trait::async_trait;
+use octocrab::models::webhook_events::{
+    WebhookEvent, WebhookEventPayload, WebhookEventType, payload::CheckSuiteWebhookEventAction,
+};
+
+use super::{Context, Error, EventHandler};
+
+/// Shows the result of finished check suites in the status comment of their PRs.
+#[derive(Debug)]
+pub struct Checks;
+
+#[async_trait]
+impl EventHandler for Checks {
+    fn name(&self) -> &'static str {
+        "checks"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::CheckSuite]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let (WebhookEventPayload::CheckSuite(payload), Some(repo)) =
+            (&event.specific, &event.repository)
+        else {
+            return Ok(());
+        };
+        if payload.action != CheckSuiteWebhookEventAction::Completed {
+            return Ok(());
+        }
+
+        let suite = &payload.check_suite;
+        let app = suite["app"]["name"].as_str().unwrap_or("CI");
+        let text = match suite["conclusion"].as_str().unwrap_or_default() {
+            "success" => "passed".to_string(),
+            "failure" => "failed".to_string(),
+            x => x.replace('_', " "),
+        };
+        let sha = suite["head_sha"].as_str().unwrap_or_default();
+        let text = format!("{} for {}", text, sha.get(..7).unwrap_or(sha));
+
+        let prs = suite["pull_requests"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+        for number in prs.iter().filter_map(|x| x["number"].as_u64()) {
+            ctx.state
+                .status
+                .set(ctx, repo.id, number, &format!("CI ({})", app), &text)
+                .await?;
+        }
+        Ok(())
+    }
+}
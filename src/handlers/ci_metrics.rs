@@ -0,0 +1,69 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use octocrab::models::webhook_events::{
+    WebhookEvent, WebhookEventPayload, WebhookEventType, payload::WorkflowJobWebhookEventAction,
+};
+
+use super::{Context, Error, EventHandler};
+use crate::db::CiJob;
+
+/// Records how long finished workflow jobs were queued and ran, for `/metrics` and the weekly
+/// digest.
+#[derive(Debug)]
+pub struct CiMetrics;
+
+#[async_trait]
+impl EventHandler for CiMetrics {
+    fn name(&self) -> &'static str {
+        "ci-metrics"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::WorkflowJob]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let (WebhookEventPayload::WorkflowJob(payload), Some(repo)) =
+            (&event.specific, &event.repository)
+        else {
+            return Ok(());
+        };
+        if payload.action != WorkflowJobWebhookEventAction::Completed {
+            return Ok(());
+        }
+        let job = &payload.workflow_job;
+        let time = |key: &str| {
+            job[key]
+                .as_str()
+                .and_then(|x| DateTime::parse_from_rfc3339(x).ok())
+                .map(|x| x.with_timezone(&Utc))
+        };
+        // Skipped jobs never ran.
+        let (Some(id), Some(created), Some(started), Some(completed)) = (
+            job["id"].as_u64(),
+            time("created_at"),
+            time("started_at"),
+            time("completed_at"),
+        ) else {
+            return Ok(());
+        };
+        if job["conclusion"].as_str() == Some("skipped") {
+            return Ok(());
+        }
+
+        let secs = |x: chrono::TimeDelta| x.num_milliseconds().max(0) as f64 / 1000.0;
+        ctx.state.db.add_ci_job(&CiJob {
+            id,
+            full_name: repo.full_name.clone().unwrap_or_default(),
+            workflow: job["workflow_name"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+            name: job["name"].as_str().unwrap_or_default().to_string(),
+            queued_secs: secs(started - created),
+            run_secs: secs(completed - started),
+            completed_at: completed,
+        })?;
+        Ok(())
+    }
+}
@@ -0,0 +1,145 @@
+use async_trait::async_trait;
+use octocrab::{
+    models::{
+        self,
+        webhook_events::{
+            WebhookEvent, WebhookEventPayload, WebhookEventType,
+            payload::PullRequestWebhookEventAction,
+        },
+    },
+    params::checks::{CheckRunConclusion, CheckRunOutput, CheckRunStatus},
+};
+use serde_json::json;
+
+use super::{Context, Error, EventHandler};
+use crate::{batch, github};
+
+const CHECK_NAME: &str = "CLA";
+
+/// Checks that PR authors accepted the CLA, and asks newcomers to accept it with
+/// `!ddnetbot cla accept`.
+#[derive(Debug)]
+pub struct Cla;
+
+impl Cla {
+    /// Reports the CLA check run on the head of the PR, and asks the author to accept it the
+    /// first time it fails.
+    pub async fn check(
+        ctx: &Context,
+        repo: &models::Repository,
+        number: u64,
+        author: &str,
+        sha: &str,
+    ) -> Result<(), Error> {
+        let Some(config) = &ctx.state.cla else {
+            return Ok(());
+        };
+        let accepted = config
+            .signatories
+            .iter()
+            .any(|x| x.eq_ignore_ascii_case(author))
+            || ctx.state.db.has_accepted_cla(author)?;
+        let (conclusion, summary) = if accepted {
+            (
+                CheckRunConclusion::Success,
+                format!("@{} accepted the CLA.", author),
+            )
+        } else {
+            (
+                CheckRunConclusion::Failure,
+                format!(
+                    "@{} hasn't accepted the [CLA]({}) yet, comment `!ddnetbot cla accept` to \
+                     accept it.",
+                    author, config.document
+                ),
+            )
+        };
+
+        let owner = repo.owner.as_ref().ok_or("repository without owner")?;
+        let checks = ctx.client.checks(&owner.login, &repo.name);
+        github::mutate(
+            ctx.state.dry_run,
+            format!("report {} on #{}: {}", CHECK_NAME, number, summary),
+            || {
+                checks
+                    .create_check_run(CHECK_NAME, sha)
+                    .status(CheckRunStatus::Completed)
+                    .conclusion(conclusion)
+                    .output(CheckRunOutput {
+                        title: CHECK_NAME.to_string(),
+                        summary: summary.clone(),
+                        text: None,
+                        annotations: Vec::new(),
+                        images: Vec::new(),
+                    })
+                    .send()
+            },
+        )
+        .await?;
+        if accepted {
+            return Ok(());
+        }
+
+        // Ask once per PR, pushes don't need a new comment.
+        let key = format!("cla_prompted:{}:{}", repo.id, number);
+        if ctx.state.db.get_kv(&key)?.is_some() {
+            return Ok(());
+        }
+        let comment = ctx.rules.templates.render(
+            repo.full_name.as_deref().unwrap_or_default(),
+            "cla",
+            json!({
+                "author": author,
+                "document": config.document,
+            }),
+        )?;
+        batch::comment(&ctx.state, &ctx.client, repo.id, number, &comment).await?;
+        ctx.state.db.set_kv(&key, "1")?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl EventHandler for Cla {
+    fn name(&self) -> &'static str {
+        "cla"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::PullRequest]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let (WebhookEventPayload::PullRequest(payload), Some(repo)) =
+            (&event.specific, &event.repository)
+        else {
+            return Ok(());
+        };
+        if !matches!(
+            payload.action,
+            PullRequestWebhookEventAction::Opened
+                | PullRequestWebhookEventAction::Reopened
+                | PullRequestWebhookEventAction::Synchronize
+        ) {
+            return Ok(());
+        }
+        // Bots like dependabot can't accept anything.
+        let Some(author) = payload
+            .pull_request
+            .user
+            .as_ref()
+            .filter(|x| x.r#type != "Bot")
+        else {
+            return Ok(());
+        };
+
+        Self::check(
+            ctx,
+            repo,
+            payload.number,
+            &author.login,
+            &payload.pull_request.head.sha,
+        )
+        .await
+    }
+}
@@ -0,0 +1,174 @@
+use async_trait::async_trait;
+use octocrab::{
+    models::webhook_events::{
+        WebhookEvent, WebhookEventPayload, WebhookEventType, payload::PullRequestWebhookEventAction,
+    },
+    params::checks::{
+        CheckRunConclusion, CheckRunOutput, CheckRunOutputAnnotation,
+        CheckRunOutputAnnotationLevel, CheckRunStatus,
+    },
+};
+
+use super::{Context, Error, EventHandler};
+use crate::{
+    config::{CommitLintConfig, Config},
+    github::{self, PrCommit},
+};
+
+const CHECK_NAME: &str = "Commit messages";
+
+/// Autosquash prefixes of commits meant to be squashed before merging.
+const SQUASH_PREFIXES: &[&str] = &["fixup!", "squash!", "amend!"];
+
+/// Checks the commit messages of PRs and reports the problems as a check run, with an
+/// annotation per commit.
+#[derive(Debug)]
+pub struct CommitLint {
+    config: Option<CommitLintConfig>,
+}
+
+impl CommitLint {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            config: config.commit_lint.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for CommitLint {
+    fn name(&self) -> &'static str {
+        "commit-lint"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::PullRequest]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let (WebhookEventPayload::PullRequest(payload), Some(repo), Some(config)) =
+            (&event.specific, &event.repository, &self.config)
+        else {
+            return Ok(());
+        };
+        if !matches!(
+            payload.action,
+            PullRequestWebhookEventAction::Opened
+                | PullRequestWebhookEventAction::Reopened
+                | PullRequestWebhookEventAction::Synchronize
+        ) {
+            return Ok(());
+        }
+
+        let commits = github::pr_commits(&ctx.client, repo, payload.number).await?;
+        let problems: Vec<(&PrCommit, Vec<String>)> = commits
+            .iter()
+            .map(|x| (x, lint(config, &x.commit.message)))
+            .filter(|(_, problems)| !problems.is_empty())
+            .collect();
+
+        let (conclusion, summary) = if problems.is_empty() {
+            (
+                CheckRunConclusion::Success,
+                format!("All {} commit messages look good.", commits.len()),
+            )
+        } else {
+            (
+                CheckRunConclusion::Failure,
+                format!(
+                    "{} of {} commit messages need to be fixed, e.g. with `git rebase -i`.",
+                    problems.len(),
+                    commits.len()
+                ),
+            )
+        };
+        let owner = repo.owner.as_ref().ok_or("repository without owner")?;
+        let checks = ctx.client.checks(&owner.login, &repo.name);
+        github::mutate(
+            ctx.state.dry_run,
+            format!("report {} on #{}: {}", CHECK_NAME, payload.number, summary),
+            || {
+                checks
+                    .create_check_run(CHECK_NAME, &payload.pull_request.head.sha)
+                    .status(CheckRunStatus::Completed)
+                    .conclusion(conclusion)
+                    .output(CheckRunOutput {
+                        title: CHECK_NAME.to_string(),
+                        summary: summary.clone(),
+                        text: None,
+                        // The check is about no file in particular.
+                        annotations: problems
+                            .iter()
+                            .map(|(commit, problems)| CheckRunOutputAnnotation {
+                                path: ".".to_string(),
+                                start_line: 1,
+                                end_line: 1,
+                                start_column: None,
+                                end_column: None,
+                                annotation_level: CheckRunOutputAnnotationLevel::Failure,
+                                message: problems.join("\n"),
+                                title: Some(format!(
+                                    "{} {}",
+                                    commit.sha.get(..7).unwrap_or(&commit.sha),
+                                    commit.commit.message.lines().next().unwrap_or_default()
+                                )),
+                                raw_details: None,
+                            })
+                            .collect(),
+                        images: Vec::new(),
+                    })
+                    .send()
+            },
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+/// The problems of a commit message.
+fn lint(config: &CommitLintConfig, message: &str) -> Vec<String> {
+    let subject = message.lines().next().unwrap_or_default();
+    let mut problems = Vec::new();
+
+    if let Some(prefix) = SQUASH_PREFIXES.iter().find(|x| subject.starts_with(*x)) {
+        problems.push(format!(
+            "`{}` commits must be squashed before merging",
+            prefix
+        ));
+    }
+    let len = subject.chars().count();
+    if len > config.max_subject_len {
+        problems.push(format!(
+            "The subject is {} characters long, at most {} are allowed",
+            len, config.max_subject_len
+        ));
+    }
+    if !config.components.is_empty()
+        && !config
+            .components
+            .iter()
+            .any(|x| subject.starts_with(&format!("{}: ", x)))
+    {
+        problems.push(format!(
+            "The subject must start with a component, one of: {}",
+            config.components.join(", ")
+        ));
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_are_linted() {
+        let config = CommitLintConfig {
+            max_subject_len: 20,
+            components: vec!["client".to_string()],
+        };
+        assert!(lint(&config, "client: Fix menus\n\nLonger body").is_empty());
+        assert_eq!(lint(&config, "fixup! client: Fix").len(), 2);
+        assert_eq!(lint(&config, "client: Fix menus and the editor").len(), 1);
+    }
+}
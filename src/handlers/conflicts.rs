@@ -0,0 +1,117 @@
+use std::collections::HashSet;
+
+use async_trait::async_trait;
+use octocrab::{
+    Octocrab,
+    models::{
+        Repository,
+        webhook_events::{
+            WebhookEvent, WebhookEventPayload, WebhookEventType,
+            payload::PullRequestWebhookEventAction,
+        },
+    },
+};
+use serde_json::json;
+
+use super::{Context, Error, EventHandler};
+use crate::{conflicts::reconcile_all, github};
+
+/// Checks the open PRs touching the files of a push to the default branch for conflicts, and
+/// PRs that were pushed to for whether their conflict is resolved.
+#[derive(Debug)]
+pub struct Conflicts;
+
+#[async_trait]
+impl EventHandler for Conflicts {
+    fn name(&self) -> &'static str {
+        "conflicts"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::Push, WebhookEventType::PullRequest]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let Some(repo) = &event.repository else {
+            return Ok(());
+        };
+        let templates = &ctx.rules.templates;
+
+        match &event.specific {
+            WebhookEventPayload::Push(payload) => {
+                let Some(branch) = &repo.default_branch else {
+                    return Ok(());
+                };
+                if payload.r#ref != format!("refs/heads/{}", branch) || payload.deleted {
+                    return Ok(());
+                }
+                let files: HashSet<&str> = payload
+                    .commits
+                    .iter()
+                    .flat_map(|x| x.added.iter().chain(&x.modified).chain(&x.removed))
+                    .map(String::as_str)
+                    .collect();
+                if files.is_empty() {
+                    return Ok(());
+                }
+                let numbers = touching(&ctx.client, repo, branch, &files).await?;
+                reconcile_all(&ctx.state, templates, &ctx.client, repo, numbers).await
+            }
+            WebhookEventPayload::PullRequest(payload)
+                if payload.action == PullRequestWebhookEventAction::Synchronize =>
+            {
+                let numbers = vec![payload.pull_request.number];
+                reconcile_all(&ctx.state, templates, &ctx.client, repo, numbers).await
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The open PRs against `branch` changing any of `files`. The files of 50 PRs are read at once
+/// through GraphQL, PRs with more files than that reads are taken to change them.
+async fn touching(
+    client: &Octocrab,
+    repo: &Repository,
+    branch: &str,
+    files: &HashSet<&str>,
+) -> Result<Vec<u64>, Error> {
+    let owner = repo.owner.as_ref().ok_or("repository without owner")?;
+    let mut numbers = Vec::new();
+    let mut after: Option<String> = None;
+    loop {
+        let res = github::graphql(
+            client,
+            "query($owner: String!, $name: String!, $branch: String!, $after: String) { \
+                repository(owner: $owner, name: $name) { \
+                pullRequests(states: OPEN, baseRefName: $branch, first: 50, after: $after) { \
+                pageInfo { hasNextPage endCursor } \
+                nodes { number files(first: 100) { pageInfo { hasNextPage } nodes { path } } } \
+                } } }",
+            json!({
+                "owner": owner.login,
+                "name": repo.name,
+                "branch": branch,
+                "after": after,
+            }),
+        )
+        .await?;
+        let prs = &res["data"]["repository"]["pullRequests"];
+        for pr in prs["nodes"].as_array().into_iter().flatten() {
+            let pr_files = &pr["files"];
+            let touches = pr_files["pageInfo"]["hasNextPage"] == true
+                || pr_files["nodes"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .any(|x| x["path"].as_str().is_some_and(|x| files.contains(x)));
+            if let (true, Some(number)) = (touches, pr["number"].as_u64()) {
+                numbers.push(number);
+            }
+        }
+        if prs["pageInfo"]["hasNextPage"] != true {
+            return Ok(numbers);
+        }
+        after = prs["pageInfo"]["endCursor"].as_str().map(String::from);
+    }
+}
@@ -0,0 +1,68 @@
+use async_trait::async_trait;
+use octocrab::models::webhook_events::{
+    WebhookEvent, WebhookEventPayload, WebhookEventType,
+    payload::{PullRequestReviewWebhookEventAction, PullRequestWebhookEventAction},
+};
+
+use super::{Context, Error, EventHandler};
+
+/// Records merged PRs and submitted reviews for the contributor stats.
+#[derive(Debug)]
+pub struct Contributions;
+
+#[async_trait]
+impl EventHandler for Contributions {
+    fn name(&self) -> &'static str {
+        "contributions"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[
+            WebhookEventType::PullRequest,
+            WebhookEventType::PullRequestReview,
+        ]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let Some(full_name) = event
+            .repository
+            .as_ref()
+            .and_then(|x| x.full_name.as_deref())
+        else {
+            return Ok(());
+        };
+
+        match &event.specific {
+            WebhookEventPayload::PullRequest(payload)
+                if payload.action == PullRequestWebhookEventAction::Closed =>
+            {
+                if let (Some(merged_at), Some(user)) =
+                    (payload.pull_request.merged_at, &payload.pull_request.user)
+                {
+                    ctx.state.db.add_contribution(
+                        full_name,
+                        payload.number,
+                        &user.login,
+                        "merged_pr",
+                        merged_at,
+                    )?;
+                }
+            }
+            WebhookEventPayload::PullRequestReview(payload)
+                if payload.action == PullRequestReviewWebhookEventAction::Submitted =>
+            {
+                if let Some(user) = &payload.review.user {
+                    ctx.state.db.add_contribution(
+                        full_name,
+                        payload.pull_request.number,
+                        &user.login,
+                        "review",
+                        payload.review.submitted_at.unwrap_or_else(chrono::Utc::now),
+                    )?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
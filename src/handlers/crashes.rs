@@ -0,0 +1,183 @@
+use async_trait::async_trait;
+use octocrab::models::webhook_events::{
+    WebhookEvent, WebhookEventPayload, WebhookEventType, payload::IssuesWebhookEventAction,
+};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use tracing::info;
+
+use super::{Context, Error, EventHandler};
+use crate::batch;
+
+pub const POSSIBLE_DUPLICATE_LABEL: &str = "possible-duplicate";
+
+/// How many of the topmost frames make up a signature.
+const SIGNATURE_FRAMES: usize = 5;
+/// Backtraces with fewer frames are too generic to tell crashes apart.
+const MIN_FRAMES: usize = 2;
+/// Frames of the crash handling itself, the same in every backtrace.
+const NOISE_FRAMES: &[&str] = &[
+    "??",
+    "raise",
+    "abort",
+    "dbg_assert",
+    "dbg_break",
+    "KiUserExceptionDispatcher",
+    "RtlUserThreadStart",
+    "BaseThreadInitThunk",
+    "rust_begin_unwind",
+];
+
+/// Computes a signature from the topmost frames of backtraces pasted in new issues and links
+/// reports of the same crash, labeling the newer ones `possible-duplicate`.
+#[derive(Debug)]
+pub struct CrashSignatures;
+
+#[async_trait]
+impl EventHandler for CrashSignatures {
+    fn name(&self) -> &'static str {
+        "crash-signatures"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::Issues]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let (WebhookEventPayload::Issues(payload), Some(repo)) =
+            (&event.specific, &event.repository)
+        else {
+            return Ok(());
+        };
+        if !matches!(
+            payload.action,
+            IssuesWebhookEventAction::Opened | IssuesWebhookEventAction::Edited
+        ) || payload.issue.pull_request.is_some()
+        {
+            return Ok(());
+        }
+        let issue = &payload.issue;
+        let Some(signature) = signature(issue.body.as_deref().unwrap_or_default()) else {
+            return Ok(());
+        };
+        let full_name = repo.full_name.as_deref().unwrap_or_default();
+        let mut reports = ctx.state.db.crash_reports(full_name, &signature)?;
+        ctx.state
+            .db
+            .add_crash_signature(full_name, &signature, issue.number)?;
+        // Only older reports, the newer ones link this one themselves.
+        reports.retain(|x| *x < issue.number);
+        if reports.is_empty() {
+            return Ok(());
+        }
+
+        let key = format!("crash_linked:{}:{}", repo.id, issue.number);
+        if ctx.state.db.get_kv(&key)?.is_some() {
+            return Ok(());
+        }
+        info!(
+            "#{} has the crash signature {} of {:?}",
+            issue.number, signature, reports
+        );
+        let comment = ctx.rules.templates.render(
+            full_name,
+            "possible_duplicate",
+            json!({
+                "author": issue.user.login,
+                "issues": reports,
+                "signature": signature,
+            }),
+        )?;
+        batch::comment(&ctx.state, &ctx.client, repo.id, issue.number, &comment).await?;
+        let labels = [POSSIBLE_DUPLICATE_LABEL.to_string()];
+        batch::add_labels(&ctx.state, &ctx.client, repo.id, issue.number, &labels).await?;
+        ctx.state.db.set_kv(&key, "1")?;
+        Ok(())
+    }
+}
+
+/// The function of a backtrace line, from gdb and sanitizers (`#3 0x55d in CCollision::GetTile
+/// (int, int) const`), Windows crash logs (`DDNet.exe!CGameClient::OnRender+0x34`) and Rust
+/// panics (`  3: ddnet::map::load`).
+fn frame(line: &str) -> Option<&str> {
+    let line = line.trim();
+    let function = if let Some(rest) = line.strip_prefix('#') {
+        let (index, rest) = rest.split_once(char::is_whitespace)?;
+        index.parse::<u32>().ok()?;
+        let rest = rest.trim_start();
+        match rest.split_once(" in ") {
+            Some((address, function)) if address.starts_with("0x") => function,
+            // Frame 0 has no address in gdb, unlike `#12 is related`.
+            _ if rest.contains(" at ") || rest.contains(" from ") => rest,
+            _ => return None,
+        }
+    } else if let Some((_, rest)) = line.split_once(".exe!") {
+        rest.split_once('+').map(|(x, _)| x).unwrap_or(rest)
+    } else {
+        let (index, rest) = line.split_once(": ")?;
+        index.parse::<u32>().ok()?;
+        // Unlike numbered steps to reproduce, Rust frames are paths.
+        if !rest.contains("::") {
+            return None;
+        }
+        rest
+    };
+    let end = function
+        .find(|x: char| x == '(' || x.is_whitespace())
+        .unwrap_or(function.len());
+    let mut function = &function[..end];
+    // The hash Rust appends to symbols changes with every build.
+    if let Some((path, hash)) = function.rsplit_once("::h")
+        && hash.len() == 16
+        && hash.bytes().all(|x| x.is_ascii_hexdigit())
+    {
+        function = path;
+    }
+    (!function.is_empty()).then_some(function)
+}
+
+/// The signature of the first backtrace in `body`, a hash of its topmost frames outside of the
+/// crash handling. None without a backtrace long enough.
+fn signature(body: &str) -> Option<String> {
+    let frames: Vec<&str> = body
+        .lines()
+        .filter_map(frame)
+        .filter(|x| !x.starts_with("__") && !NOISE_FRAMES.contains(x))
+        .take(SIGNATURE_FRAMES)
+        .collect();
+    if frames.len() < MIN_FRAMES {
+        return None;
+    }
+    let hash = Sha256::digest(frames.join("\n"));
+    Some(hex::encode(&hash[..8]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signatures_ignore_addresses_and_crash_handling() {
+        let gdb = "Crashed when joining, like #12:\n```\n\
+            #0  __pthread_kill_implementation (no_tid=0) at ./nptl/pthread_kill.c:44\n\
+            #1  0x00007ffff7a42428 in raise () from /lib/libc.so.6\n\
+            #2  0x000055555561a2b3 in CCollision::GetTile (this=0x0, x=3) at collision.cpp:80\n\
+            #3  0x000055555561b000 in CGameClient::OnNewSnapshot (this=0x5555) at gameclient.cpp:1\n\
+            #4  0x000055555561c000 in CClient::Update (this=0x5555) at client.cpp:2600\n```";
+        let windows = "\
+            00000000 00000000 DDNet.exe!CCollision::GetTile+0x12 [src/game/collision.cpp @ 80]\n\
+            00000000 00000000 DDNet.exe!CGameClient::OnNewSnapshot+0x3a4\n\
+            00000000 00000000 DDNet.exe!CClient::Update+0x88";
+        assert_eq!(
+            frame("    #2 0x55d in CCollision::GetTile(int, int) const"),
+            Some("CCollision::GetTile")
+        );
+        assert_eq!(
+            frame("  14: ddnet::map::load::h0123456789abcdef"),
+            Some("ddnet::map::load")
+        );
+        assert!(signature(gdb).is_some());
+        assert_eq!(signature(gdb), signature(windows));
+        assert_eq!(signature("Steps:\n1: join a server\n2: it crashes"), None);
+    }
+}
@@ -0,0 +1,170 @@
+use async_trait::async_trait;
+use octocrab::{
+    models::webhook_events::{
+        WebhookEvent, WebhookEventPayload, WebhookEventType, payload::PullRequestWebhookEventAction,
+    },
+    params::checks::{CheckRunConclusion, CheckRunOutput, CheckRunStatus},
+};
+use serde_json::json;
+
+use super::{Context, Error, EventHandler};
+use crate::{
+    batch,
+    github::{self, PrCommit},
+};
+
+const CHECK_NAME: &str = "DCO";
+
+/// Checks that every commit of a PR is signed off by its author, as the Developer Certificate
+/// of Origin asks, and tells the author how to fix it once per failure.
+#[derive(Debug)]
+pub struct Dco;
+
+#[async_trait]
+impl EventHandler for Dco {
+    fn name(&self) -> &'static str {
+        "dco"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::PullRequest]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let (WebhookEventPayload::PullRequest(payload), Some(repo)) =
+            (&event.specific, &event.repository)
+        else {
+            return Ok(());
+        };
+        if !ctx.state.dco
+            || !matches!(
+                payload.action,
+                PullRequestWebhookEventAction::Opened
+                    | PullRequestWebhookEventAction::Reopened
+                    | PullRequestWebhookEventAction::Synchronize
+            )
+        {
+            return Ok(());
+        }
+
+        let commits = github::pr_commits(&ctx.client, repo, payload.number).await?;
+        let unsigned: Vec<&PrCommit> = commits.iter().filter(|x| !signed_off(x)).collect();
+        let summary = if unsigned.is_empty() {
+            format!("All {} commits are signed off.", commits.len())
+        } else {
+            let mut summary = format!(
+                "{} of {} commits aren't signed off by their author:\n",
+                unsigned.len(),
+                commits.len()
+            );
+            for commit in &unsigned {
+                summary.push_str(&format!(
+                    "\n- {} {}",
+                    commit.sha.get(..7).unwrap_or(&commit.sha),
+                    commit.commit.message.lines().next().unwrap_or_default()
+                ));
+            }
+            summary
+        };
+
+        let owner = repo.owner.as_ref().ok_or("repository without owner")?;
+        let checks = ctx.client.checks(&owner.login, &repo.name);
+        let conclusion = if unsigned.is_empty() {
+            CheckRunConclusion::Success
+        } else {
+            CheckRunConclusion::Failure
+        };
+        github::mutate(
+            ctx.state.dry_run,
+            format!("report {} on #{}: {}", CHECK_NAME, payload.number, summary),
+            || {
+                checks
+                    .create_check_run(CHECK_NAME, &payload.pull_request.head.sha)
+                    .status(CheckRunStatus::Completed)
+                    .conclusion(conclusion)
+                    .output(CheckRunOutput {
+                        title: CHECK_NAME.to_string(),
+                        summary: summary.clone(),
+                        text: None,
+                        annotations: Vec::new(),
+                        images: Vec::new(),
+                    })
+                    .send()
+            },
+        )
+        .await?;
+
+        // Only comment the first time it fails, and again after it passed in between.
+        let key = format!("dco_commented:{}:{}", repo.id, payload.number);
+        let db = &ctx.state.db;
+        if unsigned.is_empty() {
+            db.remove_kv(&key)?;
+            return Ok(());
+        }
+        if db.get_kv(&key)?.is_some() {
+            return Ok(());
+        }
+        let comment = ctx.rules.templates.render(
+            repo.full_name.as_deref().unwrap_or_default(),
+            "dco",
+            json!({
+                "author": payload.pull_request.user.as_ref().map(|x| x.login.as_str()).unwrap_or_default(),
+                "count": commits.len(),
+                "base": payload.pull_request.base.ref_field,
+            }),
+        )?;
+        batch::comment(&ctx.state, &ctx.client, repo.id, payload.number, &comment).await?;
+        db.set_kv(&key, "1")?;
+        Ok(())
+    }
+}
+
+/// Whether the message has a `Signed-off-by` trailer with the email of the commit's author.
+fn signed_off(commit: &PrCommit) -> bool {
+    let Some(author) = &commit.commit.author else {
+        return false;
+    };
+    commit.commit.message.lines().any(|line| {
+        line.strip_prefix("Signed-off-by:").is_some_and(|x| {
+            x.trim().rsplit_once('<').is_some_and(|(_, email)| {
+                email
+                    .trim_end_matches('>')
+                    .eq_ignore_ascii_case(&author.email)
+            })
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit(email: &str, message: &str) -> PrCommit {
+        serde_json::from_value(json!({
+            "sha": "0123456789abcdef",
+            "commit": {
+                "message": message,
+                "author": { "name": "Dennis", "email": email, "date": "2025-03-01T12:00:00Z" },
+            },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn sign_offs_have_to_be_by_the_author() {
+        let message = "Fix the menus\n\nSigned-off-by: Dennis <dennis@ddnet.org>";
+        assert!(signed_off(&commit("dennis@ddnet.org", message)));
+        assert!(signed_off(&commit("Dennis@DDNet.org", message)));
+        assert!(!signed_off(&commit("someone@ddnet.org", message)));
+        assert!(!signed_off(&commit("dennis@ddnet.org", "Fix the menus")));
+
+        let message = "Fix the menus\n\nSigned-off-by: Someone <someone@ddnet.org>\n\
+            Signed-off-by: Dennis <dennis@ddnet.org>";
+        assert!(signed_off(&commit("dennis@ddnet.org", message)));
+        assert!(signed_off(&commit("someone@ddnet.org", message)));
+
+        let mut without_author = commit("dennis@ddnet.org", message);
+        without_author.commit.author = None;
+        assert!(!signed_off(&without_author));
+    }
+}
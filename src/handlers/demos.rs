@@ -0,0 +1,125 @@
+use async_trait::async_trait;
+use octocrab::models::webhook_events::{
+    WebhookEvent, WebhookEventPayload, WebhookEventType,
+    payload::{IssueCommentWebhookEventAction, IssuesWebhookEventAction},
+};
+use serde_json::json;
+
+use super::{Context, Error, EventHandler};
+use crate::{
+    batch,
+    config::{Config, DemoRequestConfig},
+};
+
+pub const NEEDS_DEMO_LABEL: &str = "needs-demo";
+
+/// Signs of a demo in a text. GitHub only takes demos zipped, so zips count too.
+const DEMO_MARKERS: &[&str] = &[".demo", ".zip"];
+
+/// Asks the reporters of gameplay and physics bugs for a demo and the map name, until the issue
+/// or a comment has one.
+#[derive(Debug)]
+pub struct Demos {
+    config: Option<DemoRequestConfig>,
+}
+
+impl Demos {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            config: config.demo_request.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for Demos {
+    fn name(&self) -> &'static str {
+        "demos"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::Issues, WebhookEventType::IssueComment]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let (Some(repo), Some(config)) = (&event.repository, &self.config) else {
+            return Ok(());
+        };
+        let (issue, text) = match &event.specific {
+            WebhookEventPayload::Issues(payload)
+                if matches!(
+                    payload.action,
+                    IssuesWebhookEventAction::Opened
+                        | IssuesWebhookEventAction::Edited
+                        | IssuesWebhookEventAction::Labeled
+                ) =>
+            {
+                let text = format!(
+                    "{}\n{}",
+                    payload.issue.title,
+                    payload.issue.body.as_deref().unwrap_or_default()
+                );
+                (&payload.issue, text)
+            }
+            WebhookEventPayload::IssueComment(payload)
+                if payload.action == IssueCommentWebhookEventAction::Created =>
+            {
+                let text = payload.comment.body.clone().unwrap_or_default();
+                (&payload.issue, text)
+            }
+            _ => return Ok(()),
+        };
+        if issue.pull_request.is_some() {
+            return Ok(());
+        }
+        let labeled = issue.labels.iter().any(|x| x.name == NEEDS_DEMO_LABEL);
+        if has_demo(&text) {
+            if labeled {
+                batch::remove_label(
+                    &ctx.state,
+                    &ctx.client,
+                    repo.id,
+                    issue.number,
+                    NEEDS_DEMO_LABEL,
+                )
+                .await?;
+            }
+            return Ok(());
+        }
+        // Comments without a demo don't change anything.
+        if labeled || matches!(event.specific, WebhookEventPayload::IssueComment(_)) {
+            return Ok(());
+        }
+
+        let text = text.to_lowercase();
+        let gameplay = issue.labels.iter().any(|x| config.labels.contains(&x.name))
+            || config
+                .keywords
+                .iter()
+                .any(|x| text.contains(&x.to_lowercase()));
+        if !gameplay {
+            return Ok(());
+        }
+        // Ask once, removing the label means that no demo is needed.
+        let key = format!("demo_requested:{}:{}", repo.id, issue.number);
+        if ctx.state.db.get_kv(&key)?.is_some() {
+            return Ok(());
+        }
+
+        let comment = ctx.rules.templates.render(
+            repo.full_name.as_deref().unwrap_or_default(),
+            "demo_request",
+            json!({ "author": issue.user.login }),
+        )?;
+        batch::comment(&ctx.state, &ctx.client, repo.id, issue.number, &comment).await?;
+        let labels = [NEEDS_DEMO_LABEL.to_string()];
+        batch::add_labels(&ctx.state, &ctx.client, repo.id, issue.number, &labels).await?;
+        ctx.state.db.set_kv(&key, "1")?;
+        Ok(())
+    }
+}
+
+fn has_demo(text: &str) -> bool {
+    let text = text.to_lowercase();
+    DEMO_MARKERS.iter().any(|x| text.contains(x))
+}
@@ -0,0 +1,204 @@
+use async_trait::async_trait;
+use octocrab::models::webhook_events::{
+    WebhookEvent, WebhookEventPayload, WebhookEventType, payload::PullRequestWebhookEventAction,
+};
+use serde_json::json;
+
+use super::{Context, Error, EventHandler};
+use crate::{
+    batch,
+    config::{Config, DependencyUpdatesConfig},
+    github,
+};
+
+/// Labels the PRs of dependency update bots, and approves patch-level updates and lets GitHub
+/// merge them once CI passes when configured to.
+#[derive(Debug)]
+pub struct DependencyUpdates {
+    config: Option<DependencyUpdatesConfig>,
+}
+
+impl DependencyUpdates {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            config: config.dependency_updates.clone(),
+        }
+    }
+}
+
+/// A dependency update, as described by the title of the PR, or its body for renovate.
+#[derive(Debug, PartialEq)]
+struct Update<'a> {
+    dependency: &'a str,
+    from: Option<&'a str>,
+    to: &'a str,
+}
+
+impl Update<'_> {
+    /// Whether only the patch version changes, unknown without the old version.
+    fn is_patch(&self) -> bool {
+        let Some(from) = self.from else {
+            return false;
+        };
+        let minor = |version: &str| {
+            let mut parts = version.split('.');
+            (
+                parts.next().map(str::to_string),
+                parts.next().map(str::to_string),
+            )
+        };
+        let (from_minor, to_minor) = (minor(from), minor(self.to));
+        from != self.to && from_minor.1.is_some() && from_minor == to_minor
+    }
+}
+
+#[async_trait]
+impl EventHandler for DependencyUpdates {
+    fn name(&self) -> &'static str {
+        "dependency-updates"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::PullRequest]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let (WebhookEventPayload::PullRequest(payload), Some(repo), Some(config)) =
+            (&event.specific, &event.repository, &self.config)
+        else {
+            return Ok(());
+        };
+        let Some(author) = &payload.pull_request.user else {
+            return Ok(());
+        };
+        if payload.action != PullRequestWebhookEventAction::Opened
+            || !config.bots.contains(&author.login)
+        {
+            return Ok(());
+        }
+
+        let labels = [config.label.clone()];
+        batch::add_labels(&ctx.state, &ctx.client, repo.id, payload.number, &labels).await?;
+
+        let title = payload.pull_request.title.as_deref().unwrap_or_default();
+        let Some(mut update) = parse(title) else {
+            return Ok(());
+        };
+        if update.from.is_none() {
+            let body = payload.pull_request.body.as_deref().unwrap_or_default();
+            update.from = renovate_from(body, &update);
+        }
+        let allowed = (config.allow.is_empty()
+            || config.allow.iter().any(|x| x == update.dependency))
+            && !config.deny.iter().any(|x| x == update.dependency);
+        if !allowed || !update.is_patch() {
+            return Ok(());
+        }
+
+        let owner = repo.owner.as_ref().ok_or("repository without owner")?;
+        if config.auto_approve {
+            let route = format!(
+                "/repos/{}/{}/pulls/{}/reviews",
+                owner.login, repo.name, payload.number
+            );
+            let review = json!({
+                "event": "APPROVE",
+                "body": format!("Patch-level update of `{}`.", update.dependency),
+            });
+            github::mutate(
+                ctx.state.dry_run,
+                format!("approve #{}", payload.number),
+                || {
+                    ctx.client
+                        .post::<_, serde_json::Value>(&route, Some(&review))
+                },
+            )
+            .await?;
+        }
+        if config.auto_merge {
+            let node_id = payload
+                .pull_request
+                .node_id
+                .as_deref()
+                .ok_or("pull request without node id")?;
+            // GitHub merges it once the required checks pass.
+            github::graphql_mutation(
+                ctx.state.dry_run,
+                &ctx.client,
+                format!("enable auto-merge on #{}", payload.number),
+                "mutation($id: ID!) { \
+                    enablePullRequestAutoMerge(input: { pullRequestId: $id, mergeMethod: SQUASH }) { \
+                    clientMutationId } }",
+                json!({ "id": node_id }),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses titles like dependabot's `Bump serde from 1.0.1 to 1.0.2` and renovate's
+/// `Update dependency serde to v1.0.2`, with an optional conventional commit prefix.
+fn parse(title: &str) -> Option<Update<'_>> {
+    let title = title.split_once(": ").map(|(_, x)| x).unwrap_or(title);
+    let words: Vec<&str> = title.split_whitespace().collect();
+    let first = words.first()?;
+    if !first.eq_ignore_ascii_case("bump") && !first.eq_ignore_ascii_case("update") {
+        return None;
+    }
+    let to = words.iter().position(|x| *x == "to")?;
+    let from = to.checked_sub(2).filter(|i| words[*i] == "from" && *i > 1);
+    let dependency = words[from.unwrap_or(to) - 1];
+    if dependency.eq_ignore_ascii_case(first) {
+        return None;
+    }
+    Some(Update {
+        dependency,
+        from: from.map(|i| words[i + 1].trim_start_matches('v')),
+        to: words.get(to + 1)?.trim_start_matches('v'),
+    })
+}
+
+/// The old version from the table in the body of renovate's PRs, whose titles only have the
+/// new one: ``| [serde](https://...) | `1.0.1` -> `1.0.2` |``.
+fn renovate_from<'a>(body: &'a str, update: &Update) -> Option<&'a str> {
+    body.lines()
+        .filter(|x| x.starts_with('|') && x.contains(update.dependency))
+        .find_map(|line| {
+            let (before, after) = line.split_once("` -> `").or(line.split_once("` → `"))?;
+            let from = before.rsplit('`').next()?;
+            let to = after.split('`').next()?;
+            let version = |x: &'a str| x.trim_start_matches(['^', '~', 'v']);
+            (version(to) == update.to).then(|| version(from))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn titles_are_parsed() {
+        let update = parse("build(deps): Bump serde from 1.0.1 to 1.0.2 in /tools").unwrap();
+        assert_eq!(
+            update,
+            Update {
+                dependency: "serde",
+                from: Some("1.0.1"),
+                to: "1.0.2",
+            }
+        );
+        assert!(update.is_patch());
+        let update = parse("Update dependency serde to v1.1.0").unwrap();
+        assert_eq!(update.from, None);
+        assert!(!update.is_patch());
+        let body = "| Package | Change |\n|---|---|\n\
+            | [serde](https://serde.rs) | `1.0.1` -> `1.0.2` |\n";
+        let mut update = parse("Update dependency serde to v1.0.2").unwrap();
+        update.from = renovate_from(body, &update);
+        assert_eq!(update.from, Some("1.0.1"));
+        assert!(update.is_patch());
+        assert!(!parse("Bump tokio from 1.2.0 to 1.3.0").unwrap().is_patch());
+        assert_eq!(parse("Fix the menus"), None);
+    }
+}
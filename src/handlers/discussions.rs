@@ -0,0 +1,214 @@
+use std::collections::HashSet;
+
+use async_trait::async_trait;
+use octocrab::models::webhook_events::{
+    WebhookEvent, WebhookEventPayload, WebhookEventType,
+    payload::{DiscussionCommentWebhookEventAction, DiscussionWebhookEventAction},
+};
+use serde_json::{Value, json};
+
+use super::{Context, Error, EventHandler};
+use crate::{
+    config::{Config, DiscussionsConfig},
+    github,
+};
+
+/// Triages discussions: labels them by category, suggests turning bug reports into issues,
+/// and runs `!ddnetbot label` from collaborators.
+#[derive(Debug)]
+pub struct Discussions {
+    config: DiscussionsConfig,
+}
+
+impl Discussions {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            config: config.discussions.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for Discussions {
+    fn name(&self) -> &'static str {
+        "discussions"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[
+            WebhookEventType::Discussion,
+            WebhookEventType::DiscussionComment,
+        ]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let Some(repo) = &event.repository else {
+            return Ok(());
+        };
+        match &event.specific {
+            WebhookEventPayload::Discussion(payload)
+                if matches!(
+                    payload.action,
+                    DiscussionWebhookEventAction::Created
+                        | DiscussionWebhookEventAction::CategoryChanged
+                ) =>
+            {
+                let discussion = &payload.discussion;
+                let category = discussion["category"]["name"].as_str().unwrap_or_default();
+                if let Some(labels) = self.config.category_labels.get(category) {
+                    let add: Vec<&str> = labels.iter().map(String::as_str).collect();
+                    set_labels(ctx, repo, discussion, &add, &[]).await?;
+                }
+
+                if payload.action == DiscussionWebhookEventAction::Created
+                    && self.is_bug_report(discussion)
+                {
+                    let comment = ctx.rules.templates.render(
+                        repo.full_name.as_deref().unwrap_or_default(),
+                        "discussion_bug",
+                        json!({
+                            "author": discussion["user"]["login"].as_str().unwrap_or_default(),
+                        }),
+                    )?;
+                    github::graphql_mutation(
+                        ctx.state.dry_run,
+                        &ctx.client,
+                        format!(
+                            "comment on discussion #{}: {}",
+                            discussion["number"], comment
+                        ),
+                        "mutation($id: ID!, $body: String!) { \
+                            addDiscussionComment(input: { discussionId: $id, body: $body }) { \
+                            clientMutationId } }",
+                        json!({ "id": discussion["node_id"], "body": comment }),
+                    )
+                    .await?;
+                }
+            }
+            WebhookEventPayload::DiscussionComment(payload)
+                if payload.action == DiscussionCommentWebhookEventAction::Created =>
+            {
+                // Only the label command, the others are about issues and PRs.
+                let privileged = matches!(
+                    payload.comment["author_association"].as_str(),
+                    Some("OWNER" | "MEMBER" | "COLLABORATOR")
+                );
+                let body = payload.comment["body"].as_str().unwrap_or_default();
+                if !privileged {
+                    return Ok(());
+                }
+                for line in body.lines() {
+                    let Some(labels) = line
+                        .strip_prefix("!ddnetbot")
+                        .and_then(|x| x.trim_start().strip_prefix("label"))
+                    else {
+                        continue;
+                    };
+                    let add: Vec<&str> = labels
+                        .split_ascii_whitespace()
+                        .filter_map(|x| x.strip_prefix('+'))
+                        .collect();
+                    let remove: Vec<&str> = labels
+                        .split_ascii_whitespace()
+                        .filter_map(|x| x.strip_prefix('-'))
+                        .collect();
+                    set_labels(ctx, repo, &payload.discussion, &add, &remove).await?;
+                    ctx.state.db.audit(
+                        repo.full_name.as_deref().unwrap_or_default(),
+                        payload.discussion["number"].as_u64(),
+                        payload.comment["user"]["login"]
+                            .as_str()
+                            .unwrap_or_default(),
+                        "label",
+                        labels.trim(),
+                    )?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl Discussions {
+    /// Whether the discussion reads like a bug report, which is easier to track as an issue.
+    fn is_bug_report(&self, discussion: &Value) -> bool {
+        let category = discussion["category"]["name"].as_str().unwrap_or_default();
+        if self.config.bug_keywords.is_empty()
+            || self
+                .config
+                .ignored_categories
+                .iter()
+                .any(|x| x.eq_ignore_ascii_case(category))
+        {
+            return false;
+        }
+        let text = format!(
+            "{}\n{}",
+            discussion["title"].as_str().unwrap_or_default(),
+            discussion["body"].as_str().unwrap_or_default()
+        )
+        .to_lowercase();
+        self.config
+            .bug_keywords
+            .iter()
+            .any(|x| text.contains(&x.to_lowercase()))
+    }
+}
+
+/// Adds and removes labels of a discussion, ignoring the ones the repo doesn't have.
+async fn set_labels(
+    ctx: &Context,
+    repo: &octocrab::models::Repository,
+    discussion: &Value,
+    add: &[&str],
+    remove: &[&str],
+) -> Result<(), Error> {
+    let route = format!("/repositories/{}/labels", repo.id);
+    let repo_labels =
+        github::retry(|| ctx.state.repo_labels.get_all(&ctx.client, repo.id, &route)).await?;
+    let current: HashSet<&str> = discussion["labels"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|x| x["name"].as_str())
+        .collect();
+    let ids = |names: &[&str], present: bool| -> Vec<String> {
+        repo_labels
+            .iter()
+            .filter(|x| {
+                names.contains(&x.name.as_str()) && current.contains(x.name.as_str()) == present
+            })
+            .map(|x| x.node_id.clone())
+            .collect()
+    };
+    let (add_ids, remove_ids) = (ids(add, false), ids(remove, true));
+    let id = &discussion["node_id"];
+    let number = &discussion["number"];
+
+    if !add_ids.is_empty() {
+        github::graphql_mutation(
+            ctx.state.dry_run,
+            &ctx.client,
+            format!("add labels {:?} to discussion #{}", add, number),
+            "mutation($id: ID!, $labels: [ID!]!) { \
+                addLabelsToLabelable(input: { labelableId: $id, labelIds: $labels }) { \
+                clientMutationId } }",
+            json!({ "id": id, "labels": add_ids }),
+        )
+        .await?;
+    }
+    if !remove_ids.is_empty() {
+        github::graphql_mutation(
+            ctx.state.dry_run,
+            &ctx.client,
+            format!("remove labels {:?} from discussion #{}", remove, number),
+            "mutation($id: ID!, $labels: [ID!]!) { \
+                removeLabelsFromLabelable(input: { labelableId: $id, labelIds: $labels }) { \
+                clientMutationId } }",
+            json!({ "id": id, "labels": remove_ids }),
+        )
+        .await?;
+    }
+    Ok(())
+}
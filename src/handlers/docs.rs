@@ -0,0 +1,76 @@
+use async_trait::async_trait;
+use octocrab::models::webhook_events::{
+    WebhookEvent, WebhookEventPayload, WebhookEventType, payload::PullRequestWebhookEventAction,
+};
+use serde_json::json;
+
+use super::{Context, Error, EventHandler};
+use crate::{
+    batch,
+    config::{Config, DocLink},
+    github,
+};
+
+/// Links the documentation pages new PRs may need to update, by the paths they touch.
+#[derive(Debug)]
+pub struct Docs {
+    links: Vec<DocLink>,
+}
+
+impl Docs {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            links: config.docs.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for Docs {
+    fn name(&self) -> &'static str {
+        "docs"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::PullRequest]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let (WebhookEventPayload::PullRequest(payload), Some(repo)) =
+            (&event.specific, &event.repository)
+        else {
+            return Ok(());
+        };
+        if payload.action != PullRequestWebhookEventAction::Opened || self.links.is_empty() {
+            return Ok(());
+        }
+
+        let owner = repo.owner.as_ref().ok_or("repository without owner")?;
+        let pulls = ctx.client.pulls(&owner.login, &repo.name);
+        let files = github::retry(|| pulls.list_files(payload.number)).await?;
+
+        let links: Vec<_> = self
+            .links
+            .iter()
+            .filter(|link| {
+                files.items.iter().any(|file| {
+                    link.paths
+                        .iter()
+                        .any(|path| file.filename.starts_with(path.as_str()))
+                })
+            })
+            .map(|link| json!({ "title": link.title, "url": link.url }))
+            .collect();
+        if links.is_empty() {
+            return Ok(());
+        }
+
+        let full_name = repo.full_name.as_deref().unwrap_or_default();
+        let comment = ctx
+            .rules
+            .templates
+            .render(full_name, "docs", json!({ "links": links }))?;
+        batch::comment(&ctx.state, &ctx.client, repo.id, payload.number, &comment).await?;
+        Ok(())
+    }
+}
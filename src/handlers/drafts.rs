@@ -0,0 +1,77 @@
+use std::collections::HashSet;
+
+use async_trait::async_trait;
+use octocrab::models::webhook_events::{
+    WebhookEvent, WebhookEventPayload, WebhookEventType, payload::PullRequestWebhookEventAction,
+};
+
+use super::{Context, Error, EventHandler};
+use crate::{
+    batch,
+    workflow::{self, Transition, WAITING_FOR_REVIEWS_LABEL},
+};
+
+pub const WIP_LABEL: &str = "wip";
+
+/// Labels draft PRs as `wip` instead of waiting for reviews, and swaps the labels when they're
+/// ready.
+#[derive(Debug)]
+pub struct Drafts;
+
+#[async_trait]
+impl EventHandler for Drafts {
+    fn name(&self) -> &'static str {
+        "drafts"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::PullRequest]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let (WebhookEventPayload::PullRequest(payload), Some(repo)) =
+            (&event.specific, &event.repository)
+        else {
+            return Ok(());
+        };
+        let number = payload.number;
+        let draft = payload.pull_request.draft == Some(true);
+
+        match payload.action {
+            PullRequestWebhookEventAction::Opened if draft => {
+                let labels = [WIP_LABEL.to_string()];
+                batch::add_labels(&ctx.state, &ctx.client, repo.id, number, &labels).await?;
+            }
+            PullRequestWebhookEventAction::ConvertedToDraft => {
+                let labels = [WIP_LABEL.to_string()];
+                batch::add_labels(&ctx.state, &ctx.client, repo.id, number, &labels).await?;
+                batch::remove_label(
+                    &ctx.state,
+                    &ctx.client,
+                    repo.id,
+                    number,
+                    WAITING_FOR_REVIEWS_LABEL,
+                )
+                .await?;
+            }
+            PullRequestWebhookEventAction::ReadyForReview => {
+                batch::remove_label(&ctx.state, &ctx.client, repo.id, number, WIP_LABEL).await?;
+                let before: HashSet<String> = payload
+                    .pull_request
+                    .labels
+                    .iter()
+                    .flatten()
+                    .map(|x| x.name.clone())
+                    .collect();
+                let mut after = before.clone();
+                // A conflicting PR is reviewed once it's rebased.
+                if workflow::apply(&mut after, Transition::RequestReviews).is_ok() {
+                    workflow::sync(&ctx.state, &ctx.client, repo.id, number, &before, &after)
+                        .await?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,100 @@
+use async_trait::async_trait;
+use octocrab::models::{
+    self,
+    webhook_events::{
+        WebhookEvent, WebhookEventPayload, WebhookEventType,
+        payload::{IssueCommentWebhookEventAction, IssuesWebhookEventAction},
+    },
+};
+use serde_json::json;
+
+use super::{Context, Error, EventHandler};
+use crate::{
+    batch,
+    config::{Config, FaqEntry},
+};
+
+pub const QUESTION_LABEL: &str = "question";
+
+/// Answers frequently asked questions in new issues and their comments, once per issue.
+#[derive(Debug)]
+pub struct Faq {
+    entries: Vec<FaqEntry>,
+}
+
+impl Faq {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            entries: config.faq.clone(),
+        }
+    }
+
+    /// The key remembering that an issue got an answer.
+    pub fn answered_key(repo: models::RepositoryId, number: u64) -> String {
+        format!("faq_answered:{}:{}", repo, number)
+    }
+}
+
+#[async_trait]
+impl EventHandler for Faq {
+    fn name(&self) -> &'static str {
+        "faq"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::Issues, WebhookEventType::IssueComment]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let Some(repo) = &event.repository else {
+            return Ok(());
+        };
+        let (issue, author, text) = match &event.specific {
+            WebhookEventPayload::Issues(payload)
+                if payload.action == IssuesWebhookEventAction::Opened =>
+            {
+                let text = format!(
+                    "{}\n{}",
+                    payload.issue.title,
+                    payload.issue.body.as_deref().unwrap_or_default()
+                );
+                (&payload.issue, &payload.issue.user, text)
+            }
+            WebhookEventPayload::IssueComment(payload)
+                if payload.action == IssueCommentWebhookEventAction::Created
+                    && payload.comment.user.r#type != "Bot" =>
+            {
+                let text = payload.comment.body.clone().unwrap_or_default();
+                (&payload.issue, &payload.comment.user, text)
+            }
+            _ => return Ok(()),
+        };
+        if issue.pull_request.is_some() || self.entries.is_empty() {
+            return Ok(());
+        }
+        let text = text.to_lowercase();
+        let Some(entry) = self.entries.iter().find(|entry| {
+            entry
+                .patterns
+                .iter()
+                .any(|x| text.contains(&x.to_lowercase()))
+        }) else {
+            return Ok(());
+        };
+        let key = Self::answered_key(repo.id, issue.number);
+        if ctx.state.db.get_kv(&key)?.is_some() {
+            return Ok(());
+        }
+
+        let comment = ctx.rules.templates.render(
+            repo.full_name.as_deref().unwrap_or_default(),
+            "faq_answer",
+            json!({ "author": author.login, "answer": entry.answer }),
+        )?;
+        batch::comment(&ctx.state, &ctx.client, repo.id, issue.number, &comment).await?;
+        let labels = [QUESTION_LABEL.to_string()];
+        batch::add_labels(&ctx.state, &ctx.client, repo.id, issue.number, &labels).await?;
+        ctx.state.db.set_kv(&key, "1")?;
+        Ok(())
+    }
+}
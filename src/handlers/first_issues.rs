@@ -0,0 +1,131 @@
+use std::collections::HashSet;
+
+use async_trait::async_trait;
+use octocrab::models::{
+    issues::Issue,
+    webhook_events::{
+        WebhookEvent, WebhookEventPayload, WebhookEventType, payload::IssuesWebhookEventAction,
+    },
+};
+
+use super::{Context, Error, EventHandler};
+use crate::{
+    batch,
+    config::{Config, GoodFirstIssuesConfig},
+    feed::TRIAGE_LABEL,
+};
+
+pub const CANDIDATE_LABEL: &str = "good-first-issue-candidate";
+
+/// Extensions of the files an issue can point at.
+const FILE_EXTENSIONS: &[&str] = &[
+    "c", "cpp", "h", "hpp", "rs", "py", "sh", "cmake", "txt", "json", "toml", "md", "png", "lua",
+];
+
+/// Suggests beginner-friendly issues once they're triaged, by labeling them for maintainers to
+/// confirm.
+#[derive(Debug)]
+pub struct FirstIssues {
+    config: GoodFirstIssuesConfig,
+}
+
+impl FirstIssues {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            config: config.good_first_issues.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for FirstIssues {
+    fn name(&self) -> &'static str {
+        "first-issues"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::Issues]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let (WebhookEventPayload::Issues(payload), Some(repo)) =
+            (&event.specific, &event.repository)
+        else {
+            return Ok(());
+        };
+        // Triage is done when the triage label comes off.
+        if payload.action != IssuesWebhookEventAction::Unlabeled
+            || payload.label.as_ref().map(|x| x.name.as_str()) != Some(TRIAGE_LABEL)
+            || !is_candidate(&self.config, &payload.issue)
+        {
+            return Ok(());
+        }
+
+        let labels = [CANDIDATE_LABEL.to_string()];
+        batch::add_labels(
+            &ctx.state,
+            &ctx.client,
+            repo.id,
+            payload.issue.number,
+            &labels,
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+/// Whether the issue shows at least two signs of being small: a keyword like "typo", only one
+/// file referenced, or one of the configured labels.
+fn is_candidate(config: &GoodFirstIssuesConfig, issue: &Issue) -> bool {
+    if issue.pull_request.is_some()
+        || issue
+            .labels
+            .iter()
+            .any(|x| x.name == CANDIDATE_LABEL || x.name.eq_ignore_ascii_case("good first issue"))
+    {
+        return false;
+    }
+
+    let text = format!(
+        "{}\n{}",
+        issue.title,
+        issue.body.as_deref().unwrap_or_default()
+    );
+    let lowercase = text.to_lowercase();
+    let keyword = config
+        .keywords
+        .iter()
+        .any(|x| lowercase.contains(&x.to_lowercase()));
+    let single_file = referenced_files(&text).len() == 1;
+    let label = issue.labels.iter().any(|x| config.labels.contains(&x.name));
+
+    let signs = [keyword, single_file, label];
+    signs.into_iter().filter(|x| *x).count() >= 2
+}
+
+/// Names of the files mentioned in the text, without their directory so a file mentioned
+/// with and without its path counts once.
+fn referenced_files(text: &str) -> HashSet<&str> {
+    text.split(|c: char| c.is_whitespace() || "`'\"()[]<>,:;".contains(c))
+        .map(|word| word.trim_end_matches(['.', '!', '?']))
+        .map(|word| word.rsplit('/').next().unwrap())
+        .filter(|word| {
+            word.rsplit_once('.').is_some_and(|(name, extension)| {
+                !name.is_empty()
+                    && FILE_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str())
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn files_are_found_in_text() {
+        let text = "The tooltip in `src/game/client/components/menus.cpp` has a typo, see \
+                    menus.cpp. Also e.g. https://ddnet.org/index.html.";
+        assert_eq!(referenced_files(text), HashSet::from(["menus.cpp"]));
+    }
+}
@@ -0,0 +1,117 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use octocrab::models::{
+    self,
+    webhook_events::{
+        WebhookEvent, WebhookEventPayload, WebhookEventType, payload::CheckRunWebhookEventAction,
+    },
+};
+use serde_json::json;
+use tracing::info;
+
+use super::{Context, Error, EventHandler};
+use crate::{db::FlakyOccurrence, github};
+
+pub const FLAKY_LABEL: &str = "flaky-test";
+
+/// Finds checks that fail and then pass when retried on the same commit, and keeps an issue per
+/// flaky check up to date with every time it happened.
+#[derive(Debug)]
+pub struct FlakyTests;
+
+impl FlakyTests {
+    fn failed_key(repo: models::RepositoryId, sha: &str, name: &str) -> String {
+        format!("check_failed:{}:{}:{}", repo, sha, name)
+    }
+}
+
+#[async_trait]
+impl EventHandler for FlakyTests {
+    fn name(&self) -> &'static str {
+        "flaky-tests"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::CheckRun]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let (WebhookEventPayload::CheckRun(payload), Some(repo)) =
+            (&event.specific, &event.repository)
+        else {
+            return Ok(());
+        };
+        if payload.action != CheckRunWebhookEventAction::Completed {
+            return Ok(());
+        }
+        let run = &payload.check_run;
+        let (Some(name), Some(sha)) = (run["name"].as_str(), run["head_sha"].as_str()) else {
+            return Ok(());
+        };
+        let key = Self::failed_key(repo.id, sha, name);
+        match run["conclusion"].as_str() {
+            Some("failure" | "timed_out") => {
+                ctx.state.db.set_kv(&key, "1")?;
+                return Ok(());
+            }
+            Some("success") if ctx.state.db.get_kv(&key)?.is_some() => {
+                ctx.state.db.remove_kv(&key)?;
+            }
+            _ => return Ok(()),
+        }
+
+        // The same commit failed before, so it wasn't the code that got fixed.
+        info!("Check {} is flaky on {}", name, sha);
+        let occurrence = FlakyOccurrence {
+            created_at: Utc::now().to_rfc3339(),
+            sha: sha.to_string(),
+            pr: run["pull_requests"][0]["number"].as_u64(),
+            url: run["html_url"].as_str().unwrap_or_default().to_string(),
+        };
+        ctx.state
+            .db
+            .add_flaky_occurrence(repo.id, name, &occurrence)?;
+        let occurrences = ctx.state.db.flaky_occurrences(repo.id, name)?;
+        let body = ctx.rules.templates.render(
+            repo.full_name.as_deref().unwrap_or_default(),
+            "flaky_test",
+            json!({
+                "name": name,
+                "count": occurrences.len(),
+                "occurrences": occurrences,
+            }),
+        )?;
+
+        let issues = ctx.client.issues_by_id(repo.id);
+        if let Some(number) = ctx.state.db.flaky_issue(repo.id, name)? {
+            let issue = github::retry(|| issues.get(number)).await?;
+            // Closed as fixed, a new occurrence gets a new issue.
+            if issue.state == models::IssueState::Open {
+                github::mutate(
+                    ctx.state.dry_run,
+                    format!("update flaky test issue #{}", number),
+                    || issues.update(number).body(&body).send(),
+                )
+                .await?;
+                return Ok(());
+            }
+        }
+        let title = format!("Flaky test: {}", name);
+        let owner = repo.owner.as_ref().ok_or("repository without owner")?;
+        let repo_issues = ctx.client.issues(&owner.login, &repo.name);
+        let Some(issue) = github::create_issue(
+            ctx.state.dry_run,
+            &repo_issues,
+            &title,
+            &body,
+            vec![FLAKY_LABEL.to_string()],
+            Vec::new(),
+        )
+        .await?
+        else {
+            return Ok(());
+        };
+        ctx.state.db.set_flaky_issue(repo.id, name, issue.number)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,111 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use octocrab::models::webhook_events::{
+    WebhookEvent, WebhookEventPayload, WebhookEventType, payload::PullRequestWebhookEventAction,
+};
+use serde_json::json;
+
+use super::{Context, Error, EventHandler};
+use crate::{
+    batch,
+    freeze::{self, FROZEN_LABEL},
+};
+
+/// Labels PRs into a branch frozen with `!ddnetbot freeze` `frozen` and fails their `Freeze`
+/// check, unless they have one of the exempt labels. Their authors are told once.
+#[derive(Debug)]
+pub struct Freezes;
+
+#[async_trait]
+impl EventHandler for Freezes {
+    fn name(&self) -> &'static str {
+        "freezes"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::PullRequest]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let (WebhookEventPayload::PullRequest(payload), Some(repo)) =
+            (&event.specific, &event.repository)
+        else {
+            return Ok(());
+        };
+        if !matches!(
+            payload.action,
+            PullRequestWebhookEventAction::Opened
+                | PullRequestWebhookEventAction::Reopened
+                | PullRequestWebhookEventAction::Synchronize
+                | PullRequestWebhookEventAction::Edited
+                | PullRequestWebhookEventAction::Labeled
+                | PullRequestWebhookEventAction::Unlabeled
+        ) {
+            return Ok(());
+        }
+        let pr = &payload.pull_request;
+        let full_name = repo.full_name.as_deref().unwrap_or_default();
+        // Freezes that are over are lifted by `freeze::lift_loop`.
+        let Some(freeze) = ctx
+            .state
+            .db
+            .freeze(full_name, &pr.base.ref_field)?
+            .filter(|x| x.until > Utc::now())
+        else {
+            return Ok(());
+        };
+
+        let labels: Vec<&str> = pr
+            .labels
+            .iter()
+            .flatten()
+            .map(|x| x.name.as_str())
+            .collect();
+        let exempt = freeze.exempts(&labels);
+        let frozen = (!exempt).then_some(&freeze);
+        freeze::report_check(
+            ctx.state.dry_run,
+            &ctx.client,
+            full_name,
+            &pr.head.sha,
+            frozen,
+        )
+        .await?;
+
+        let labeled = labels.contains(&FROZEN_LABEL);
+        if exempt && labeled {
+            batch::remove_label(
+                &ctx.state,
+                &ctx.client,
+                repo.id,
+                payload.number,
+                FROZEN_LABEL,
+            )
+            .await?;
+        } else if !exempt && !labeled {
+            let labels = [FROZEN_LABEL.to_string()];
+            batch::add_labels(&ctx.state, &ctx.client, repo.id, payload.number, &labels).await?;
+        }
+
+        let key = format!(
+            "freeze_commented:{}:{}:{}",
+            repo.id, payload.number, freeze.branch
+        );
+        if exempt || ctx.state.db.get_kv(&key)?.is_some() {
+            return Ok(());
+        }
+        let comment = ctx.rules.templates.render(
+            full_name,
+            "pr_frozen",
+            json!({
+                "author": pr.user.as_ref().map(|x| x.login.as_str()).unwrap_or_default(),
+                "branch": freeze.branch,
+                "until": freeze.until.format("%Y-%m-%d %H:%M UTC").to_string(),
+                "exempt": freeze.exempt_labels,
+            }),
+        )?;
+        batch::comment(&ctx.state, &ctx.client, repo.id, payload.number, &comment).await?;
+        ctx.state.db.set_kv(&key, "1")?;
+        Ok(())
+    }
+}
@@ -0,0 +1,53 @@
+use async_trait::async_trait;
+use octocrab::models::{
+    AuthorAssociation,
+    webhook_events::{
+        WebhookEvent, WebhookEventPayload, WebhookEventType, payload::PullRequestWebhookEventAction,
+    },
+};
+use serde_json::json;
+
+use super::{Context, Error, EventHandler};
+use crate::batch;
+
+/// Welcomes first-time contributors on their first PR.
+#[derive(Debug)]
+pub struct Greeter;
+
+#[async_trait]
+impl EventHandler for Greeter {
+    fn name(&self) -> &'static str {
+        "greeter"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::PullRequest]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let (WebhookEventPayload::PullRequest(payload), Some(repo)) =
+            (&event.specific, &event.repository)
+        else {
+            return Ok(());
+        };
+        if payload.action != PullRequestWebhookEventAction::Opened
+            || !matches!(
+                payload.pull_request.author_association,
+                Some(AuthorAssociation::FirstTimer | AuthorAssociation::FirstTimeContributor)
+            )
+        {
+            return Ok(());
+        }
+
+        let comment = ctx.rules.templates.render(
+            repo.full_name.as_deref().unwrap_or_default(),
+            "welcome",
+            json!({
+                "author": payload.pull_request.user.as_ref().map(|x| &x.login),
+                "repo": repo.full_name,
+            }),
+        )?;
+        batch::comment(&ctx.state, &ctx.client, repo.id, payload.number, &comment).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,138 @@
+use async_trait::async_trait;
+use octocrab::models::webhook_events::{
+    WebhookEvent, WebhookEventPayload, WebhookEventType, payload::PullRequestWebhookEventAction,
+};
+use serde_json::json;
+
+use super::{Context, Error, EventHandler};
+use crate::{batch, config::Config};
+
+pub const NEEDS_ISSUE_LINK_LABEL: &str = "needs-issue-link";
+
+/// The keywords GitHub closes the following issue for when the PR is merged.
+const CLOSING_KEYWORDS: &[&str] = &[
+    "close", "closes", "closed", "fix", "fixes", "fixed", "resolve", "resolves", "resolved",
+];
+
+/// Asks bugfix PRs to link the issue they fix, until the description closes one.
+#[derive(Debug)]
+pub struct IssueLinks {
+    labels: Vec<String>,
+}
+
+impl IssueLinks {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            labels: config.bugfix_labels.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for IssueLinks {
+    fn name(&self) -> &'static str {
+        "issue-links"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::PullRequest]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let (WebhookEventPayload::PullRequest(payload), Some(repo)) =
+            (&event.specific, &event.repository)
+        else {
+            return Ok(());
+        };
+        if !matches!(
+            payload.action,
+            PullRequestWebhookEventAction::Opened
+                | PullRequestWebhookEventAction::Edited
+                | PullRequestWebhookEventAction::Labeled
+        ) {
+            return Ok(());
+        }
+        let labels = payload.pull_request.labels.iter().flatten();
+        let labeled = labels.clone().any(|x| x.name == NEEDS_ISSUE_LINK_LABEL);
+
+        if links_issue(payload.pull_request.body.as_deref().unwrap_or_default()) {
+            if labeled {
+                batch::remove_label(
+                    &ctx.state,
+                    &ctx.client,
+                    repo.id,
+                    payload.number,
+                    NEEDS_ISSUE_LINK_LABEL,
+                )
+                .await?;
+            }
+            return Ok(());
+        }
+        let bugfix = labels
+            .clone()
+            .any(|x| self.labels.iter().any(|l| l.eq_ignore_ascii_case(&x.name)))
+            || is_fix_title(payload.pull_request.title.as_deref().unwrap_or_default());
+        if labeled || !bugfix {
+            return Ok(());
+        }
+
+        let labels = [NEEDS_ISSUE_LINK_LABEL.to_string()];
+        batch::add_labels(&ctx.state, &ctx.client, repo.id, payload.number, &labels).await?;
+        // Ask once, the label shows that it's still missing.
+        let key = format!("issue_link_requested:{}:{}", repo.id, payload.number);
+        if ctx.state.db.get_kv(&key)?.is_some() {
+            return Ok(());
+        }
+        let comment = ctx.rules.templates.render(
+            repo.full_name.as_deref().unwrap_or_default(),
+            "issue_link_request",
+            json!({
+                "author": payload.pull_request.user.as_ref().map(|x| x.login.as_str()).unwrap_or_default(),
+            }),
+        )?;
+        batch::comment(&ctx.state, &ctx.client, repo.id, payload.number, &comment).await?;
+        ctx.state.db.set_kv(&key, "1")?;
+        Ok(())
+    }
+}
+
+/// Whether the title starts with "Fix", "Fixes", "Fixed" and the like.
+fn is_fix_title(title: &str) -> bool {
+    title
+        .trim_start()
+        .get(..3)
+        .is_some_and(|x| x.eq_ignore_ascii_case("fix"))
+}
+
+/// Whether the description has a closing keyword followed by `#123`, `owner/repo#123` or an
+/// issue URL.
+fn links_issue(body: &str) -> bool {
+    let words: Vec<&str> = body.split_ascii_whitespace().collect();
+    words.windows(2).any(|pair| {
+        let keyword = pair[0].trim_end_matches(':').to_lowercase();
+        let issue = pair[1].trim_end_matches(['.', ',', ')']);
+        CLOSING_KEYWORDS.contains(&keyword.as_str())
+            && (issue
+                .rsplit_once('#')
+                .is_some_and(|(_, x)| !x.is_empty() && x.bytes().all(|x| x.is_ascii_digit()))
+                || issue.starts_with("https://github.com/") && issue.contains("/issues/"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn closing_keywords_are_found() {
+        assert!(links_issue("Fixes #123"));
+        assert!(links_issue("This PR\n\ncloses: ddnet/ddnet#42."));
+        assert!(links_issue(
+            "Resolves https://github.com/ddnet/ddnet/issues/7"
+        ));
+        assert!(!links_issue("Fixes the crash from #123"));
+        assert!(!links_issue("See #123"));
+        assert!(is_fix_title("fix: crash on join"));
+        assert!(!is_fix_title("Add prefix option"));
+    }
+}
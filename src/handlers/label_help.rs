@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use octocrab::models::{
+    Author,
+    webhook_events::{
+        WebhookEvent, WebhookEventPayload, WebhookEventType,
+        payload::{IssuesWebhookEventAction, PullRequestWebhookEventAction},
+    },
+};
+use serde_json::json;
+
+use super::{Context, Error, EventHandler};
+use crate::{batch, config::Config, conflicts::help_marker};
+
+/// Explains what to do about a label people add, e.g. how to rebase for `needs-rebase`, with
+/// the template the label maps to. Each label is explained once per issue or PR.
+///
+/// Labels added by bots are skipped, the handlers adding labels explain them themselves.
+#[derive(Debug)]
+pub struct LabelHelp {
+    /// Template names by label.
+    templates: HashMap<String, String>,
+}
+
+impl LabelHelp {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            templates: config.label_help.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for LabelHelp {
+    fn name(&self) -> &'static str {
+        "label-help"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::Issues, WebhookEventType::PullRequest]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let Some(repo) = &event.repository else {
+            return Ok(());
+        };
+        let (number, author, label) = match &event.specific {
+            WebhookEventPayload::Issues(payload)
+                if payload.action == IssuesWebhookEventAction::Labeled =>
+            {
+                (
+                    payload.issue.number,
+                    Some(&payload.issue.user),
+                    &payload.label,
+                )
+            }
+            WebhookEventPayload::PullRequest(payload)
+                if payload.action == PullRequestWebhookEventAction::Labeled =>
+            {
+                (
+                    payload.number,
+                    payload.pull_request.user.as_deref(),
+                    &payload.label,
+                )
+            }
+            _ => return Ok(()),
+        };
+        let Some(label) = label else {
+            return Ok(());
+        };
+        let Some(template) = self.templates.get(&label.name) else {
+            return Ok(());
+        };
+        if event.sender.as_ref().is_some_and(|x| x.r#type == "Bot") {
+            return Ok(());
+        }
+
+        let key = format!("label_help:{}:{}:{}", repo.id, number, label.name);
+        if ctx.state.db.get_kv(&key)?.is_some() {
+            return Ok(());
+        }
+        let comment = ctx.rules.templates.render(
+            repo.full_name.as_deref().unwrap_or_default(),
+            template,
+            json!({
+                "author": author.map(|x: &Author| x.login.as_str()).unwrap_or_default(),
+                "label": label.name,
+            }),
+        )?;
+        let comment = format!("{}\n{}", comment, help_marker(&label.name));
+        batch::comment(&ctx.state, &ctx.client, repo.id, number, &comment).await?;
+        ctx.state.db.set_kv(&key, "1")?;
+        Ok(())
+    }
+}
@@ -0,0 +1,87 @@
+use async_trait::async_trait;
+use octocrab::models::webhook_events::{
+    WebhookEvent, WebhookEventPayload, WebhookEventType, payload::PullRequestWebhookEventAction,
+};
+
+use super::{Context, Error, EventHandler};
+use crate::{batch, github};
+
+/// Labels added to a PR when one of its files has the path fragment in its name.
+const PATH_LABELS: &[(&str, &str)] = &[
+    ("client", "client"),
+    ("server", "server"),
+    ("demo", "demo"),
+    ("editor", "editor"),
+    ("engine", "engine"),
+    ("map", "maps"),
+    ("network", "network"),
+];
+
+/// Labels new PRs, and drafts once they're ready, by the areas of the code they touch.
+#[derive(Debug)]
+pub struct Labeler;
+
+#[async_trait]
+impl EventHandler for Labeler {
+    fn name(&self) -> &'static str {
+        "labeler"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::PullRequest]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let (WebhookEventPayload::PullRequest(payload), Some(repo)) =
+            (&event.specific, &event.repository)
+        else {
+            return Ok(());
+        };
+        if !matches!(
+            payload.action,
+            PullRequestWebhookEventAction::Opened
+                | PullRequestWebhookEventAction::Reopened
+                | PullRequestWebhookEventAction::ReadyForReview
+        ) {
+            return Ok(());
+        }
+
+        let owner = repo.owner.as_ref().ok_or("repository without owner")?;
+        let pulls = ctx.client.pulls(&owner.login, &repo.name);
+        let files = github::retry(|| pulls.list_files(payload.pull_request.number)).await?;
+
+        let mut add_labels: Vec<String> = Vec::new();
+        for file in files {
+            for (fragment, label) in PATH_LABELS {
+                if file.filename.contains(fragment) {
+                    add_labels.push(label.to_string());
+                }
+            }
+        }
+        if add_labels.is_empty() {
+            return Ok(());
+        }
+
+        add_labels.sort();
+        add_labels.dedup();
+        batch::add_labels(
+            &ctx.state,
+            &ctx.client,
+            repo.id,
+            payload.number,
+            &add_labels,
+        )
+        .await?;
+        ctx.state
+            .status
+            .set(
+                ctx,
+                repo.id,
+                payload.number,
+                "Labels",
+                &format!("added {}", add_labels.join(", ")),
+            )
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,172 @@
+use async_trait::async_trait;
+use octocrab::{
+    models::{
+        repos::DiffEntryStatus,
+        webhook_events::{
+            WebhookEvent, WebhookEventPayload, WebhookEventType,
+            payload::PullRequestWebhookEventAction,
+        },
+    },
+    params::checks::{
+        CheckRunConclusion, CheckRunOutput, CheckRunOutputAnnotation,
+        CheckRunOutputAnnotationLevel, CheckRunStatus,
+    },
+};
+
+use super::{Context, Error, EventHandler};
+use crate::{
+    config::{Config, LicenseHeaderConfig},
+    github,
+};
+
+const CHECK_NAME: &str = "License headers";
+
+/// Checks that source files added by PRs start with the license header, and reports the ones
+/// that don't as a check run with the header to add.
+#[derive(Debug)]
+pub struct LicenseHeader {
+    config: Option<LicenseHeaderConfig>,
+}
+
+impl LicenseHeader {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            config: config.license_header.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for LicenseHeader {
+    fn name(&self) -> &'static str {
+        "license-header"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::PullRequest]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let (WebhookEventPayload::PullRequest(payload), Some(repo), Some(config)) =
+            (&event.specific, &event.repository, &self.config)
+        else {
+            return Ok(());
+        };
+        if !matches!(
+            payload.action,
+            PullRequestWebhookEventAction::Opened
+                | PullRequestWebhookEventAction::Reopened
+                | PullRequestWebhookEventAction::Synchronize
+        ) {
+            return Ok(());
+        }
+
+        let owner = repo.owner.as_ref().ok_or("repository without owner")?;
+        let pulls = ctx.client.pulls(&owner.login, &repo.name);
+        let files = github::retry(|| pulls.list_files(payload.number)).await?;
+        // GitHub leaves out the patch of big files, those can't be checked.
+        let added: Vec<_> = files
+            .items
+            .iter()
+            .filter(|x| x.status == DiffEntryStatus::Added)
+            .filter(|x| {
+                x.filename
+                    .rsplit_once('.')
+                    .is_some_and(|(_, extension)| config.extensions.iter().any(|e| e == extension))
+            })
+            .filter_map(|x| Some((x.filename.as_str(), x.patch.as_deref()?)))
+            .collect();
+        if added.is_empty() {
+            return Ok(());
+        }
+        let missing: Vec<&str> = added
+            .iter()
+            .filter(|(_, patch)| !has_header(patch, &config.header))
+            .map(|(filename, _)| *filename)
+            .collect();
+
+        let (conclusion, summary) = if missing.is_empty() {
+            (
+                CheckRunConclusion::Success,
+                format!(
+                    "All {} new source files have the license header.",
+                    added.len()
+                ),
+            )
+        } else {
+            (
+                CheckRunConclusion::Failure,
+                format!(
+                    "{} of {} new source files don't start with the license header.",
+                    missing.len(),
+                    added.len()
+                ),
+            )
+        };
+        let checks = ctx.client.checks(&owner.login, &repo.name);
+        github::mutate(
+            ctx.state.dry_run,
+            format!("report {} on #{}: {}", CHECK_NAME, payload.number, summary),
+            || {
+                checks
+                    .create_check_run(CHECK_NAME, &payload.pull_request.head.sha)
+                    .status(CheckRunStatus::Completed)
+                    .conclusion(conclusion)
+                    .output(CheckRunOutput {
+                        title: CHECK_NAME.to_string(),
+                        summary: summary.clone(),
+                        text: None,
+                        annotations: missing
+                            .iter()
+                            .map(|filename| CheckRunOutputAnnotation {
+                                path: filename.to_string(),
+                                start_line: 1,
+                                end_line: 1,
+                                start_column: None,
+                                end_column: None,
+                                annotation_level: CheckRunOutputAnnotationLevel::Failure,
+                                message: "The license header is missing, add it at the top of \
+                                          the file."
+                                    .to_string(),
+                                title: Some("Missing license header".to_string()),
+                                raw_details: Some(config.header.clone()),
+                            })
+                            .collect(),
+                        images: Vec::new(),
+                    })
+                    .send()
+            },
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+/// Whether the file added by the patch starts with the header, ignoring blank lines and
+/// trailing whitespace.
+fn has_header(patch: &str, header: &str) -> bool {
+    let mut content = patch
+        .lines()
+        .filter_map(|x| x.strip_prefix('+'))
+        .map(str::trim_end)
+        .skip_while(|x| x.is_empty());
+    header
+        .lines()
+        .map(str::trim_end)
+        .filter(|x| !x.is_empty())
+        .all(|line| content.next() == Some(line))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headers_are_found_in_patches() {
+        let header = "/* (c) Magnus Auvinen. */\n/* See licence.txt. */\n";
+        let patch =
+            "@@ -0,0 +1,4 @@\n+/* (c) Magnus Auvinen. */\n+/* See licence.txt. */ \n+\n+int x;";
+        assert!(has_header(patch, header));
+        assert!(!has_header("@@ -0,0 +1,1 @@\n+int x;", header));
+    }
+}
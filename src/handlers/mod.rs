@@ -0,0 +1,886 @@
+use std::{collections::HashMap, sync::Arc};
+
+use async_trait::async_trait;
+use axum::{
+    body::{Body, Bytes, to_bytes},
+    extract::{Request, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use http_body_util::LengthLimitError;
+use octocrab::{
+    Octocrab,
+    models::{
+        self,
+        webhook_events::{WebhookEvent, WebhookEventType},
+    },
+};
+use sha2::{Digest, Sha256};
+use tokio::time::Instant;
+use tracing::{Instrument, error, info, info_span, warn};
+
+use crate::{
+    AppState, Config,
+    budget::{self, Budget},
+    commands::Commands,
+    config::{RepoConfig, RolloutConfig},
+    db::DeadLetter,
+    forward, github, outbox,
+    queue::Job,
+    replay,
+    report::{self, Report},
+    state::Rules,
+    unwind,
+};
+
+pub use self::{
+    acknowledgements::Acknowledgements,
+    adoptions::{ADOPTABLE_LABEL, Adoptions},
+    api::ApiChanges,
+    assets::Assets,
+    blame::BlameReviewers,
+    checklist::Checklist,
+    checks::Checks,
+    ci_metrics::CiMetrics,
+    cla::Cla,
+    commit_lint::CommitLint,
+    conflicts::Conflicts,
+    contributions::Contributions,
+    crashes::CrashSignatures,
+    dco::Dco,
+    demos::Demos,
+    dependencies::DependencyUpdates,
+    discussions::Discussions,
+    docs::Docs,
+    drafts::Drafts,
+    faq::Faq,
+    first_issues::FirstIssues,
+    flaky::FlakyTests,
+    freezes::Freezes,
+    greeter::Greeter,
+    issue_links::IssueLinks,
+    label_help::LabelHelp,
+    labeler::Labeler,
+    license_header::LicenseHeader,
+    outdated::OutdatedVersions,
+    plugins::Plugins,
+    pr_dependencies::{BLOCKED_LABEL, PrDependencies},
+    projects::Projects,
+    protected::ProtectedPaths,
+    protocol::Protocol,
+    releases::Releases,
+    response_times::ResponseTimes,
+    reverts::Reverts,
+    reviewers::Reviewers,
+    reviews::Reviews,
+    routing::Routing,
+    screenshots::Screenshots,
+    scripts::Scripts,
+    security::{SecurityAlerts, escalate_report},
+    settings::Settings,
+    setup::{Setup, label_definitions},
+    submodules::Submodules,
+    subscriptions::{Area, Subscriptions},
+    todos::Todos,
+    tracking::Tracking,
+    triager::Triager,
+    workflow_labels::WorkflowLabels,
+};
+
+mod acknowledgements;
+mod adoptions;
+mod api;
+mod assets;
+mod blame;
+mod checklist;
+mod checks;
+mod ci_metrics;
+mod cla;
+mod commit_lint;
+mod conflicts;
+mod contributions;
+mod crashes;
+mod dco;
+mod demos;
+mod dependencies;
+mod discussions;
+mod docs;
+mod drafts;
+mod faq;
+mod first_issues;
+mod flaky;
+mod freezes;
+mod greeter;
+mod issue_links;
+mod label_help;
+mod labeler;
+mod license_header;
+mod outdated;
+mod plugins;
+mod pr_dependencies;
+mod projects;
+mod protected;
+mod protocol;
+mod releases;
+mod response_times;
+mod reverts;
+mod reviewers;
+mod reviews;
+mod routing;
+mod screenshots;
+mod scripts;
+mod security;
+mod settings;
+mod setup;
+mod submodules;
+mod subscriptions;
+mod todos;
+mod tracking;
+mod triager;
+mod workflow_labels;
+
+/// Reads the whole delivery body, rejecting it with 413 if it's larger than `limit`.
+async fn read_body(body: Body, limit: usize) -> Result<Bytes, Response> {
+    match to_bytes(body, limit).await {
+        Ok(bytes) => Ok(bytes),
+        Err(e) => {
+            let e = e.into_inner();
+            if e.is::<LengthLimitError>() {
+                warn!("Rejected delivery larger than {} bytes", limit);
+                Err(StatusCode::PAYLOAD_TOO_LARGE.into_response())
+            } else {
+                warn!("Failed to read delivery body: {}", e);
+                Err(StatusCode::BAD_REQUEST.into_response())
+            }
+        }
+    }
+}
+
+pub async fn webhook_handler(State(state): State<AppState>, req: Request) -> Response {
+    let (parts, body) = req.into_parts();
+    let header = |name: &str| {
+        parts
+            .headers
+            .get(name)
+            .and_then(|x| x.to_str().ok())
+            .unwrap_or_default()
+    };
+    let span = delivery_span(header("X-GitHub-Delivery"), header("X-GitHub-Event"));
+
+    receive(state, parts.headers, body).instrument(span).await
+}
+
+/// Every log line written while handling the delivery carries these fields.
+pub fn delivery_span(delivery: &str, event: &str) -> tracing::Span {
+    info_span!(
+        "delivery",
+        delivery,
+        event,
+        action = tracing::field::Empty,
+        repo = tracing::field::Empty,
+        installation = tracing::field::Empty,
+    )
+}
+
+#[derive(serde::Deserialize)]
+struct DeliveryAction {
+    action: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+struct DeliveryRepo {
+    repository: Option<RepoName>,
+}
+
+#[derive(serde::Deserialize)]
+struct RepoName {
+    full_name: String,
+}
+
+async fn receive(state: AppState, headers: HeaderMap, body: Body) -> Response {
+    let Some(header) = headers.get("X-GitHub-Event").and_then(|x| x.to_str().ok()) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    // Deliveries are deduplicated and their effects keyed by the id, so one without it can't be
+    // told apart from the others.
+    let Some(delivery) = headers
+        .get("X-GitHub-Delivery")
+        .and_then(|x| x.to_str().ok())
+        .filter(|x| !x.is_empty())
+    else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+
+    let bytes = match read_body(body, state.max_body_bytes).await {
+        Ok(bytes) => bytes,
+        Err(res) => return res,
+    };
+
+    if let Some(secret) = &state.webhook_secret {
+        let signature = headers
+            .get("X-Hub-Signature-256")
+            .and_then(|x| x.to_str().ok())
+            .unwrap_or_default();
+        if !forward::verify(secret, signature, &bytes) {
+            warn!("Refused a delivery with an invalid signature");
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+    }
+
+    if let Some(dir) = &state.capture_dir {
+        replay::capture(dir, &headers, &bytes).await;
+    }
+    // Forwarded deliveries are re-signed, so only verified ones from allowed repos are.
+    if state.webhook_secret.is_some() {
+        let repo = serde_json::from_slice::<DeliveryRepo>(&bytes)
+            .ok()
+            .and_then(|x| x.repository)
+            .map(|x| x.full_name);
+        if state.rules().handlers.allows(repo.as_deref()) {
+            state.forwarder.forward(header, &headers, bytes.clone());
+        }
+    }
+
+    let job = Job {
+        delivery: delivery.to_string(),
+        event: header.to_string(),
+        body: String::from_utf8_lossy(&bytes).into_owned(),
+    };
+    if let Some(queue) = &state.queue {
+        match queue.push(&job).await {
+            Ok(true) => return StatusCode::ACCEPTED.into_response(),
+            Ok(false) => {
+                info!("Dropped a delivery that was already queued");
+                return StatusCode::OK.into_response();
+            }
+            // Better to handle it here than to lose it.
+            Err(e) => warn!("Failed to queue delivery, handling it now: {}", e),
+        }
+    }
+
+    dispatch(state, &job).await
+}
+
+/// Parses a delivery and runs the handlers on it.
+pub async fn dispatch(state: AppState, job: &Job) -> Response {
+    let bytes = job.body.as_bytes();
+    let event = match WebhookEvent::try_from_header_and_body(&job.event, bytes) {
+        Ok(x) => x,
+        Err(e) => {
+            warn!("Failed to parse delivery: {}", e);
+            return StatusCode::BAD_REQUEST.into_response();
+        }
+    };
+
+    let span = tracing::Span::current();
+    if let Ok(DeliveryAction {
+        action: Some(action),
+    }) = serde_json::from_slice(bytes)
+    {
+        span.record("action", action);
+    }
+    if let Some(repo) = event
+        .repository
+        .as_ref()
+        .and_then(|x| x.full_name.as_deref())
+    {
+        span.record("repo", repo);
+    }
+
+    handle_event(state, job, event).await
+}
+
+/// An independent piece of automation, run for every delivery of the events it declares.
+#[async_trait]
+pub trait EventHandler: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// The events this handler wants to see.
+    fn events(&self) -> &[WebhookEventType];
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error>;
+}
+
+pub type Error = Box<dyn std::error::Error + Send + Sync>;
+
+/// What handlers get to work with for one delivery.
+#[derive(Debug)]
+pub struct Context {
+    pub state: AppState,
+    /// The rules at the time the delivery arrived, not changed by reloads while it's handled.
+    pub rules: Arc<Rules>,
+    /// The installation the delivery is for.
+    pub installation: models::InstallationId,
+    /// Authenticated as `installation`.
+    pub client: Octocrab,
+}
+
+impl Context {
+    /// The context of a handler in shadow mode, see [`Registry::is_shadow`].
+    fn shadow(&self) -> Self {
+        Self {
+            state: AppState {
+                dry_run: true,
+                ..self.state.clone()
+            },
+            rules: self.rules.clone(),
+            installation: self.installation,
+            client: self.client.clone(),
+        }
+    }
+}
+
+/// The handlers each feature of the per-repo `features` turns on or off. The loops acting on
+/// behalf of a handler check it too, the reminders loop checks `notifications` and the aging and
+/// label hygiene loops `stale-bot`.
+const FEATURES: &[(&str, &[&str])] = &[
+    ("labeler", &["labeler", "label-help"]),
+    ("triage", &["triager"]),
+    ("commands", &["commands"]),
+    ("merge-queue", &["pr-dependencies", "dependency-updates"]),
+    (
+        "notifications",
+        &[
+            "releases",
+            "security-alerts",
+            "protected-paths",
+            "subscriptions",
+        ],
+    ),
+    ("stale-bot", &["outdated-versions"]),
+];
+
+/// The handlers deliveries are dispatched to, in order.
+pub struct Registry {
+    handlers: Vec<Box<dyn EventHandler>>,
+    /// The allowed repos by lowercase `owner/name`, every repo is allowed if empty.
+    repos: HashMap<String, RepoConfig>,
+    /// Names of the handlers in shadow mode, see [`Registry::is_shadow`].
+    shadow: Vec<String>,
+    /// Handlers that only run for some deliveries, by name.
+    rollout: HashMap<String, RolloutConfig>,
+}
+
+impl Registry {
+    pub fn new(handlers: Vec<Box<dyn EventHandler>>) -> Self {
+        Self {
+            handlers,
+            repos: HashMap::new(),
+            shadow: Vec::new(),
+            rollout: HashMap::new(),
+        }
+    }
+
+    /// Every handler that ships with the bot, plus the configured scripts and plugins,
+    /// limited to the configured repos.
+    pub fn builtin(config: &Config) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut registry = Self::new(vec![
+            Box::new(Labeler),
+            Box::new(Triager),
+            Box::new(Greeter),
+            Box::new(Contributions),
+            Box::new(ResponseTimes),
+            Box::new(Commands::from_config(config)),
+            Box::new(Checks),
+            Box::new(FlakyTests),
+            Box::new(CiMetrics),
+            Box::new(Drafts),
+            Box::new(Conflicts),
+            Box::new(ProtectedPaths::from_config(config)),
+            Box::new(CommitLint::from_config(config)),
+            Box::new(Dco),
+            Box::new(Cla),
+            Box::new(LicenseHeader::from_config(config)),
+            Box::new(Assets::from_config(config)),
+            Box::new(Submodules),
+            Box::new(DependencyUpdates::from_config(config)),
+            Box::new(SecurityAlerts::from_config(config)),
+            Box::new(Discussions::from_config(config)),
+            Box::new(Releases::from_config(config)),
+            Box::new(Projects::from_config(config)),
+            Box::new(Tracking),
+            Box::new(Reverts),
+            Box::new(Adoptions),
+            Box::new(Subscriptions),
+            Box::new(Freezes),
+            Box::new(PrDependencies),
+            Box::new(Routing::from_config(config)),
+            Box::new(Faq::from_config(config)),
+            Box::new(CrashSignatures),
+            Box::new(OutdatedVersions::from_config(config)),
+            Box::new(Acknowledgements::from_config(config)),
+            Box::new(Demos::from_config(config)),
+            Box::new(Docs::from_config(config)),
+            Box::new(Screenshots::from_config(config)),
+            Box::new(IssueLinks::from_config(config)),
+            Box::new(LabelHelp::from_config(config)),
+            Box::new(Settings::from_config(config)),
+            Box::new(Protocol::from_config(config)),
+            Box::new(WorkflowLabels),
+            Box::new(ApiChanges::from_config(config)),
+            Box::new(Todos::from_config(config)),
+            Box::new(Checklist::from_config(config)),
+            Box::new(FirstIssues::from_config(config)),
+            Box::new(Reviewers::from_config(config)),
+            Box::new(BlameReviewers::from_config(config)),
+            Box::new(Reviews),
+            Box::new(Setup),
+            Box::new(Scripts::from_config(config)?),
+            Box::new(Plugins::from_config(config)?),
+        ]);
+
+        for (repo, repo_config) in &config.repos {
+            for name in &repo_config.disabled {
+                if !registry.handlers.iter().any(|x| x.name() == name) {
+                    return Err(format!("unknown handler {} disabled for {}", name, repo).into());
+                }
+            }
+            let mut repo_config = repo_config.clone();
+            for (feature, on) in &repo_config.features {
+                let Some((_, handlers)) = FEATURES.iter().find(|(name, _)| name == feature) else {
+                    return Err(format!("unknown feature {} for {}", feature, repo).into());
+                };
+                if !on {
+                    repo_config
+                        .disabled
+                        .extend(handlers.iter().map(|x| x.to_string()));
+                }
+            }
+            registry
+                .repos
+                .insert(repo.to_ascii_lowercase(), repo_config);
+        }
+        for name in &config.shadow_handlers {
+            if !registry.handlers.iter().any(|x| x.name() == name) {
+                return Err(format!("unknown handler {} in shadow mode", name).into());
+            }
+        }
+        registry.shadow = config.shadow_handlers.clone();
+        for (name, rollout) in &config.rollout {
+            if !registry.handlers.iter().any(|x| x.name() == name) {
+                return Err(format!("unknown handler {} rolled out", name).into());
+            }
+            if rollout.percent > 100 {
+                return Err(format!("rollout of {} is over 100 percent", name).into());
+            }
+        }
+        registry.rollout = config.rollout.clone();
+        Ok(registry)
+    }
+
+    /// Whether the handler called `name` runs for `repo`.
+    pub fn enabled(&self, name: &str, repo: Option<&str>) -> bool {
+        let disabled = repo
+            .and_then(|x| self.repos.get(&x.to_ascii_lowercase()))
+            .is_some_and(|x| x.disabled.iter().any(|x| x == name));
+        self.allows(repo) && !disabled && self.rolled_out(name, repo, None)
+    }
+
+    /// Whether the handler called `name` is rolled out to `repo`, and to the delivery if
+    /// given. Which deliveries get a handler is decided by their id, so a retried delivery gets
+    /// the same handlers.
+    fn rolled_out(&self, name: &str, repo: Option<&str>, delivery: Option<&str>) -> bool {
+        let Some(rollout) = self.rollout.get(name) else {
+            return true;
+        };
+        let in_repos = rollout.repos.is_empty()
+            || repo.is_some_and(|repo| rollout.repos.iter().any(|x| x.eq_ignore_ascii_case(repo)));
+        let in_share = delivery.is_none_or(|delivery| {
+            let hash = Sha256::digest(format!("{}\n{}", name, delivery));
+            u16::from_be_bytes([hash[0], hash[1]]) % 100 < u16::from(rollout.percent)
+        });
+        in_repos && in_share
+    }
+
+    /// Whether each feature is on for `repo`.
+    pub fn features(&self, repo: &str) -> Vec<(&'static str, bool)> {
+        let config = self.repos.get(&repo.to_ascii_lowercase());
+        FEATURES
+            .iter()
+            .map(|(name, _)| {
+                let on = config.and_then(|x| x.features.get(*name)).copied();
+                (*name, on.unwrap_or(true))
+            })
+            .collect()
+    }
+
+    /// Whether `feature` is on for `repo`, for what isn't a handler like the reminders loop.
+    pub fn feature_enabled(&self, feature: &str, repo: &str) -> bool {
+        self.allows(Some(repo))
+            && self
+                .repos
+                .get(&repo.to_ascii_lowercase())
+                .and_then(|x| x.features.get(feature))
+                .copied()
+                .unwrap_or(true)
+    }
+
+    /// The handlers turned off for `repo`, by name or by feature.
+    pub fn disabled(&self, repo: &str) -> Vec<String> {
+        let mut disabled = self
+            .repos
+            .get(&repo.to_ascii_lowercase())
+            .map(|x| x.disabled.clone())
+            .unwrap_or_default();
+        disabled.sort();
+        disabled.dedup();
+        disabled
+    }
+
+    /// Whether deliveries from `repo` are handled at all. Deliveries without a repo always are.
+    pub fn allows(&self, repo: Option<&str>) -> bool {
+        match repo {
+            Some(repo) if !self.repos.is_empty() => self
+                .repos
+                .get(&repo.to_ascii_lowercase())
+                .is_some_and(|x| x.enabled),
+            _ => true,
+        }
+    }
+
+    /// Whether the handler called `name` only pretends to change things on GitHub: it runs as
+    /// in dry-run mode, and what it would have done is recorded to check it against real
+    /// deliveries before it's enabled.
+    pub fn is_shadow(&self, name: &str) -> bool {
+        self.shadow.iter().any(|x| x == name)
+    }
+
+    fn get(&self, name: &str) -> Option<&dyn EventHandler> {
+        self.handlers
+            .iter()
+            .map(|x| x.as_ref())
+            .find(|x| x.name() == name)
+    }
+
+    fn wanting<'a>(
+        &'a self,
+        kind: &'a WebhookEventType,
+        repo: Option<&'a str>,
+        delivery: &'a str,
+    ) -> impl Iterator<Item = &'a dyn EventHandler> {
+        let disabled = repo
+            .and_then(|x| self.repos.get(&x.to_ascii_lowercase()))
+            .map(|x| x.disabled.as_slice())
+            .unwrap_or_default();
+        self.handlers
+            .iter()
+            .map(|x| x.as_ref())
+            .filter(move |x| x.events().contains(kind))
+            .filter(move |x| !disabled.iter().any(|name| name == x.name()))
+            .filter(move |x| self.rolled_out(x.name(), repo, Some(delivery)))
+    }
+}
+
+impl std::fmt::Debug for Registry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.handlers.iter().map(|x| x.name()))
+            .finish()
+    }
+}
+
+/// The installation the delivery is for, if the bot can act on it.
+fn installation(state: &AppState, event: &WebhookEvent) -> Option<models::InstallationId> {
+    match &event.installation {
+        Some(models::webhook_events::EventInstallation::Full(installation)) => {
+            Some(installation.id)
+        }
+        Some(models::webhook_events::EventInstallation::Minimal(installation)) => {
+            Some(installation.id)
+        }
+        // Deliveries of repo webhooks, used with a personal access token.
+        None if state.installations.is_personal() => Some(github::PERSONAL_INSTALLATION),
+        None => None,
+    }
+}
+
+/// Runs every handler interested in the event, a failing handler doesn't stop the others.
+///
+/// The API calls of a handler are already retried, so when a handler still fails the delivery
+/// is kept as a dead letter for that handler, to be run again with [`redrive`].
+async fn handle_event(state: AppState, job: &Job, event: WebhookEvent) -> Response {
+    let Some(id) = installation(&state, &event) else {
+        return StatusCode::OK.into_response();
+    };
+    tracing::Span::current().record("installation", id.0);
+    let _permits = state.limiter.acquire(id).await;
+
+    if event.kind == WebhookEventType::Ping {
+        info!("Received a ping");
+        return StatusCode::OK.into_response();
+    }
+
+    let repo = event
+        .repository
+        .as_ref()
+        .and_then(|x| x.full_name.as_deref());
+    let rules = state.rules();
+    if !rules.handlers.allows(repo) {
+        info!("Ignored event from a repo that isn't enabled");
+        return StatusCode::OK.into_response();
+    }
+
+    // E.g. a revoked installation or GitHub failing to hand out a token, the handlers can run
+    // again with `redrive` once it's back.
+    let client = match state.installations.client(id) {
+        Ok(x) => x,
+        Err(e) => {
+            warn!("Failed to authenticate as installation {}: {}", id.0, e);
+            for handler in rules.handlers.wanting(&event.kind, repo, &job.delivery) {
+                if let Err(e) = state.db.add_dead_letter(
+                    job,
+                    handler.name(),
+                    &format!("failed to authenticate: {}", e),
+                ) {
+                    warn!("Failed to keep the delivery as a dead letter: {}", e);
+                }
+            }
+            return StatusCode::BAD_GATEWAY.into_response();
+        }
+    };
+    let ctx = Context {
+        state: state.clone(),
+        rules: rules.clone(),
+        installation: id,
+        client,
+    };
+    let budget = Budget::new(
+        state.max_api_calls_per_event,
+        &job.delivery,
+        &state.api_usage,
+    );
+    let shadow_ctx = ctx.shadow();
+    let deadline = Instant::now() + state.event_timeout;
+    let mut handled = false;
+    let mut panicked = false;
+    let mut report = Report::default();
+    for handler in rules.handlers.wanting(&event.kind, repo, &job.delivery) {
+        handled = true;
+        let span = info_span!("handler", name = handler.name());
+        let shadow = rules.handlers.is_shadow(handler.name());
+        let ctx = if shadow { &shadow_ctx } else { &ctx };
+        let res = outbox::run(
+            &state.db,
+            job,
+            handler.name(),
+            shadow,
+            handler.handle(ctx, &event),
+        );
+        let res = budget::run(&budget, handler.name(), res);
+        let res = with_deadline(deadline, res);
+        let (res, changes) = report::collect(unwind::catch(res.instrument(span))).await;
+        let res = match res {
+            Ok(x) => x,
+            Err(panic) => {
+                error!("Handler {} panicked: {}", handler.name(), panic);
+                panicked = true;
+                Err(format!("panicked: {}", panic).into())
+            }
+        };
+        // What handlers in shadow mode would have done isn't shown to contributors.
+        if !shadow {
+            report.add(handler.name(), changes, &res);
+        }
+        if let Err(e) = res {
+            warn!("Handler {} failed: {}", handler.name(), e);
+            if let Err(e) = state
+                .db
+                .add_dead_letter(job, handler.name(), &e.to_string())
+            {
+                warn!("Failed to keep the delivery as a dead letter: {}", e);
+            }
+        }
+    }
+    if !handled {
+        warn!("Ignored event");
+    } else {
+        info!("Handled with {} GitHub API calls", budget.calls());
+        *state.last_handled.lock().unwrap() = Some(Utc::now());
+    }
+    if state.summary_check
+        && let (Some(repo), Some(sha)) = (&event.repository, report::head_sha(&event))
+        && let Err(e) = report::publish(state.dry_run, &ctx.client, repo, sha, &report).await
+    {
+        warn!("Failed to report what was done on {}: {}", sha, e);
+    }
+
+    if panicked {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    StatusCode::OK.into_response()
+}
+
+/// Gives up on a handler that is still running at the deadline of the delivery, so a stuck API
+/// call doesn't hold up the worker.
+async fn with_deadline(
+    deadline: Instant,
+    fut: impl Future<Output = Result<(), Error>>,
+) -> Result<(), Error> {
+    match tokio::time::timeout_at(deadline, fut).await {
+        Ok(res) => res,
+        Err(_) => Err("timed out, the deadline of the delivery passed".into()),
+    }
+}
+
+/// Runs the handler a dead letter failed in again, and removes the dead letter if it succeeds
+/// this time. The other handlers already handled the delivery and aren't run.
+pub async fn redrive(state: &AppState, letter: &DeadLetter) -> Result<(), Error> {
+    let job = &letter.job;
+    let event = WebhookEvent::try_from_header_and_body(&job.event, job.body.as_bytes())?;
+    let id = installation(state, &event).ok_or("the delivery isn't for an installation")?;
+    let _permits = state.limiter.acquire(id).await;
+    let rules = state.rules();
+    let handler = rules
+        .handlers
+        .get(&letter.handler)
+        .ok_or_else(|| format!("unknown handler {}", letter.handler))?;
+    let ctx = Context {
+        state: state.clone(),
+        rules: rules.clone(),
+        installation: id,
+        client: state.installations.client(id)?,
+    };
+
+    let budget = Budget::new(
+        state.max_api_calls_per_event,
+        &job.delivery,
+        &state.api_usage,
+    );
+    let span = info_span!("handler", name = handler.name());
+    let shadow = rules.handlers.is_shadow(handler.name());
+    let ctx = if shadow { ctx.shadow() } else { ctx };
+    let res = outbox::run(
+        &state.db,
+        job,
+        handler.name(),
+        shadow,
+        handler.handle(&ctx, &event),
+    );
+    let res = budget::run(&budget, handler.name(), res);
+    let res = with_deadline(Instant::now() + state.event_timeout, res);
+    let res = unwind::catch(res.instrument(span))
+        .await
+        .unwrap_or_else(|panic| Err(format!("panicked: {}", panic).into()));
+    match res {
+        Ok(()) => {
+            state.db.remove_dead_letter(letter.id)?;
+            Ok(())
+        }
+        Err(e) => {
+            state.db.set_dead_letter_error(letter.id, &e.to_string())?;
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handlers_are_rolled_out_to_a_share_of_deliveries() {
+        let mut registry = Registry::new(Vec::new());
+        registry.rollout.insert(
+            "labeler".to_string(),
+            RolloutConfig {
+                percent: 30,
+                repos: vec!["ddnet/ddnet".to_string()],
+            },
+        );
+        let repo = Some("DDNet/ddnet");
+        let delivery = |i: u32| i.to_string();
+        let runs = (0..1000)
+            .filter(|&i| registry.rolled_out("labeler", repo, Some(&delivery(i))))
+            .count();
+        assert!((200..400).contains(&runs), "{}", runs);
+        assert!(registry.rolled_out("labeler", repo, None));
+        assert!(!registry.rolled_out("labeler", Some("ddnet/ddnet-web"), None));
+        assert!(registry.rolled_out("triager", Some("ddnet/ddnet-web"), Some("1")));
+    }
+
+    #[test]
+    fn features_turn_their_handlers_off() {
+        for (feature, names) in FEATURES {
+            let figment = figment::Figment::from(figment::providers::Serialized::defaults(
+                serde_json::json!({
+                    "github_token": "ghp_test",
+                    "database_path": ":memory:",
+                    "repos": { "ddnet/ddnet": { "features": { *feature: false } } },
+                }),
+            ));
+            let config = Config::from_figment(&figment).unwrap();
+            let registry = Registry::builtin(&config).unwrap();
+            for name in *names {
+                let handler = registry.get(name).unwrap();
+                for kind in handler.events() {
+                    assert!(
+                        !registry
+                            .wanting(kind, Some("ddnet/ddnet"), "1")
+                            .any(|x| x.name() == *name),
+                        "{} still runs without {}",
+                        name,
+                        feature
+                    );
+                    assert!(
+                        registry
+                            .wanting(kind, Some("ddnet/ddnet-web"), "1")
+                            .any(|x| x.name() == *name)
+                    );
+                }
+            }
+            assert!(!registry.feature_enabled(feature, "ddnet/ddnet"));
+        }
+
+        let figment = figment::Figment::from(figment::providers::Serialized::defaults(
+            serde_json::json!({
+                "github_token": "ghp_test",
+                "database_path": ":memory:",
+                "repos": { "ddnet/ddnet": { "features": { "autopilot": false } } },
+            }),
+        ));
+        let config = Config::from_figment(&figment).unwrap();
+        assert!(Registry::builtin(&config).is_err());
+    }
+
+    #[test]
+    fn stale_bot_and_merge_queue_turn_their_handlers_off() {
+        let figment = figment::Figment::from(figment::providers::Serialized::defaults(
+            serde_json::json!({
+                "github_token": "ghp_test",
+                "database_path": ":memory:",
+                "repos": {
+                    "ddnet/ddnet": { "features": { "stale-bot": false, "merge-queue": false } },
+                    "ddnet/ddnet-web": {},
+                },
+            }),
+        ));
+        let config = Config::from_figment(&figment).unwrap();
+        let registry = Registry::builtin(&config).unwrap();
+        for name in ["outdated-versions", "pr-dependencies", "dependency-updates"] {
+            assert!(!registry.enabled(name, Some("ddnet/ddnet")), "{}", name);
+            assert!(registry.enabled(name, Some("ddnet/ddnet-web")), "{}", name);
+        }
+        assert!(registry.enabled("triager", Some("ddnet/ddnet")));
+        assert!(!registry.feature_enabled("stale-bot", "ddnet/ddnet"));
+        assert!(!registry.feature_enabled("merge-queue", "ddnet/ddnet"));
+        assert!(registry.feature_enabled("stale-bot", "ddnet/ddnet-web"));
+    }
+
+    #[tokio::test]
+    async fn oversized_body_is_rejected() {
+        let limit = 1024 * 50;
+        let payload = format!(r#"{{"action":"opened","body":"{}"}}"#, "a".repeat(limit));
+
+        let res = read_body(Body::from(payload), limit).await.unwrap_err();
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn body_within_limit_is_read() {
+        let payload = r#"{"action":"opened"}"#;
+
+        let bytes = read_body(Body::from(payload), 1024).await.unwrap();
+        assert_eq!(bytes, payload.as_bytes());
+    }
+}
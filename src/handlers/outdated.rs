@@ -0,0 +1,205 @@
+use std::cmp::Ordering;
+
+use async_trait::async_trait;
+use octocrab::models::{
+    self,
+    issues::IssueStateReason,
+    webhook_events::{
+        WebhookEvent, WebhookEventPayload, WebhookEventType,
+        payload::{IssueCommentWebhookEventAction, IssuesWebhookEventAction},
+    },
+};
+use serde_json::json;
+use tracing::info;
+
+use super::{Context, Error, EventHandler};
+use crate::{batch, config::Config, github};
+
+pub const OUTDATED_VERSION_LABEL: &str = "outdated-version";
+
+/// Closes bug reports filed against a release older than the minimum supported one, asking to
+/// upgrade first. The version is read from the field of the issue form. The issue is reopened
+/// once its author comments that it still happens on a supported version.
+#[derive(Debug)]
+pub struct OutdatedVersions {
+    minimum: Option<String>,
+    field: String,
+}
+
+impl OutdatedVersions {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            minimum: config.min_supported_version.clone(),
+            field: config.version_field.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for OutdatedVersions {
+    fn name(&self) -> &'static str {
+        "outdated-versions"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::Issues, WebhookEventType::IssueComment]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let Some(repo) = &event.repository else {
+            return Ok(());
+        };
+        let Some(minimum) = &self.minimum else {
+            return Ok(());
+        };
+        let issues = ctx.client.issues_by_id(repo.id);
+        match &event.specific {
+            WebhookEventPayload::Issues(payload)
+                if payload.action == IssuesWebhookEventAction::Opened =>
+            {
+                let issue = &payload.issue;
+                if issue.pull_request.is_some() {
+                    return Ok(());
+                }
+                let Some(version) = issue
+                    .body
+                    .as_deref()
+                    .and_then(|x| form_field(x, &self.field))
+                else {
+                    return Ok(());
+                };
+                if compare(version, minimum) != Some(Ordering::Less) {
+                    return Ok(());
+                }
+                info!(
+                    "#{} reports {} older than {}, closing it",
+                    issue.number, version, minimum
+                );
+                let comment = ctx.rules.templates.render(
+                    repo.full_name.as_deref().unwrap_or_default(),
+                    "outdated_version",
+                    json!({
+                        "author": issue.user.login,
+                        "version": version,
+                        "minimum": minimum,
+                    }),
+                )?;
+                batch::comment(&ctx.state, &ctx.client, repo.id, issue.number, &comment).await?;
+                let labels = [OUTDATED_VERSION_LABEL.to_string()];
+                batch::add_labels(&ctx.state, &ctx.client, repo.id, issue.number, &labels).await?;
+                github::mutate(
+                    ctx.state.dry_run,
+                    format!("close #{}", issue.number),
+                    || {
+                        issues
+                            .update(issue.number)
+                            .state(models::IssueState::Closed)
+                            .state_reason(IssueStateReason::NotPlanned)
+                            .send()
+                    },
+                )
+                .await?;
+            }
+            WebhookEventPayload::IssueComment(payload)
+                if payload.action == IssueCommentWebhookEventAction::Created =>
+            {
+                let issue = &payload.issue;
+                if issue.state != models::IssueState::Closed
+                    || payload.comment.user.id != issue.user.id
+                    || !issue
+                        .labels
+                        .iter()
+                        .any(|x| x.name == OUTDATED_VERSION_LABEL)
+                {
+                    return Ok(());
+                }
+                let text = payload.comment.body.as_deref().unwrap_or_default();
+                let Some(version) = text
+                    .split_whitespace()
+                    .filter(|x| x.contains('.'))
+                    .find(|x| compare(x, minimum).is_some_and(|x| x != Ordering::Less))
+                else {
+                    return Ok(());
+                };
+                info!(
+                    "#{} still happens on {}, reopening it",
+                    issue.number, version
+                );
+                batch::remove_label(
+                    &ctx.state,
+                    &ctx.client,
+                    repo.id,
+                    issue.number,
+                    OUTDATED_VERSION_LABEL,
+                )
+                .await?;
+                github::mutate(
+                    ctx.state.dry_run,
+                    format!("reopen #{}", issue.number),
+                    || {
+                        issues
+                            .update(issue.number)
+                            .state(models::IssueState::Open)
+                            .send()
+                    },
+                )
+                .await?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// The answer to `### <name>` of an issue form, None if it was left empty.
+pub fn form_field<'a>(body: &'a str, name: &str) -> Option<&'a str> {
+    let mut lines = body.lines();
+    lines.find(|x| {
+        x.strip_prefix("### ")
+            .is_some_and(|x| x.trim().eq_ignore_ascii_case(name))
+    })?;
+    let value = lines
+        .take_while(|x| !x.starts_with("### "))
+        .map(str::trim)
+        .find(|x| !x.is_empty())?;
+    (value != "_No response_").then_some(value)
+}
+
+/// The numbers of the first version in `text`, e.g. `[18, 0, 3]` for `DDNet 18.0.3 (Windows)`.
+/// Trailing zeros are dropped so that 18 and 18.0 are the same release.
+fn parse_version(text: &str) -> Option<Vec<u64>> {
+    let start = text.find(|x: char| x.is_ascii_digit())?;
+    let version = &text[start..];
+    let end = version
+        .find(|x: char| !x.is_ascii_digit() && x != '.')
+        .unwrap_or(version.len());
+    let mut numbers: Vec<u64> = version[..end]
+        .split('.')
+        .map_while(|x| x.parse().ok())
+        .collect();
+    while numbers.last() == Some(&0) {
+        numbers.pop();
+    }
+    Some(numbers)
+}
+
+/// How the version in `text` compares to `minimum`, None if there's no version in it.
+fn compare(text: &str, minimum: &str) -> Option<Ordering> {
+    Some(parse_version(text)?.cmp(&parse_version(minimum)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_are_read_from_the_form() {
+        let body = "### Version\n\nDDNet 18.0.3 (Windows)\n\n### Operating system\n\n_No response_";
+        let version = form_field(body, "version").unwrap();
+        assert_eq!(compare(version, "18"), Some(Ordering::Greater));
+        assert_eq!(compare("v17.4", "18.0"), Some(Ordering::Less));
+        assert_eq!(compare("18.0", "18"), Some(Ordering::Equal));
+        assert_eq!(compare("latest", "18"), None);
+        assert_eq!(form_field(body, "Operating system"), None);
+    }
+}
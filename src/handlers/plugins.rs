@@ -0,0 +1,275 @@
+use std::{error::Error as StdError, sync::Arc};
+
+use async_trait::async_trait;
+use octocrab::models::webhook_events::{WebhookEvent, WebhookEventType};
+use tracing::{info, warn};
+use wasmi::{
+    Caller, Engine, Extern, Linker, Module, Store, StoreLimits, StoreLimitsBuilder, TypedFunc,
+};
+
+use super::{
+    Context, Error, EventHandler,
+    scripts::{self, Action},
+};
+use crate::config::Config;
+
+/// Fuel a plugin gets per delivery, roughly one unit per instruction.
+const FUEL: u64 = 10_000_000;
+/// Largest linear memory a plugin may grow to.
+const MAX_MEMORY: usize = 16 * 1024 * 1024;
+
+#[derive(Debug)]
+struct Plugin {
+    name: String,
+    repos: Vec<String>,
+    events: Vec<WebhookEventType>,
+    module: Module,
+}
+
+/// Runs the WebAssembly plugins from the config on the events they're registered for.
+///
+/// A plugin is a core wasm module exporting `memory`, `alloc(len) -> ptr` and
+/// `handle(ptr, len)`, which is called with the delivery as JSON. It may import
+/// `add_label`, `comment`, `assign` and `log` from the `ddbot` module, each taking a
+/// `(ptr, len)` UTF-8 string. Plugins have no other access to the host and are stopped when
+/// they run out of fuel or memory, or pass a string outside of their memory.
+#[derive(Debug)]
+pub struct Plugins {
+    engine: Engine,
+    plugins: Vec<Arc<Plugin>>,
+    events: Vec<WebhookEventType>,
+}
+
+/// What a plugin instance can reach.
+struct Host {
+    actions: Vec<Action>,
+    limits: StoreLimits,
+}
+
+impl Plugins {
+    /// Loads and validates every plugin in the config.
+    pub fn from_config(config: &Config) -> Result<Self, Box<dyn StdError>> {
+        let engine = engine();
+        let mut plugins = Vec::new();
+        let mut events = Vec::new();
+
+        for plugin in &config.plugins {
+            let wasm = std::fs::read(&plugin.file)
+                .map_err(|e| format!("{}: {}", plugin.file.display(), e))?;
+            let module = Module::new(&engine, &wasm)
+                .map_err(|e| format!("{}: {}", plugin.file.display(), e))?;
+            let plugin_events = scripts::parse_events(&plugin.events)?;
+            for event in &plugin_events {
+                if !events.contains(event) {
+                    events.push(event.clone());
+                }
+            }
+            plugins.push(Arc::new(Plugin {
+                name: format!("Plugin {}", plugin.file.display()),
+                repos: plugin.repos.clone(),
+                events: plugin_events,
+                module,
+            }));
+        }
+
+        Ok(Self {
+            engine,
+            plugins,
+            events,
+        })
+    }
+}
+
+fn engine() -> Engine {
+    let mut config = wasmi::Config::default();
+    config.consume_fuel(true);
+    Engine::new(&config)
+}
+
+/// Runs the plugin on the delivery. It blocks until the plugin returns or is stopped, so it
+/// runs on the blocking threads.
+fn run(engine: &Engine, plugin: &Plugin, payload: &[u8]) -> Result<Vec<Action>, wasmi::Error> {
+    let host = Host {
+        actions: Vec::new(),
+        limits: StoreLimitsBuilder::new()
+            .memory_size(MAX_MEMORY)
+            .instances(1)
+            .build(),
+    };
+    let mut store = Store::new(engine, host);
+    store.limiter(|x| &mut x.limits);
+    store.set_fuel(FUEL)?;
+
+    let mut linker = Linker::<Host>::new(engine);
+    linker.func_wrap(
+        "ddbot",
+        "add_label",
+        |caller: Caller<'_, Host>, ptr: i32, len: i32| push(caller, ptr, len, Action::AddLabel),
+    )?;
+    linker.func_wrap(
+        "ddbot",
+        "comment",
+        |caller: Caller<'_, Host>, ptr: i32, len: i32| push(caller, ptr, len, Action::Comment),
+    )?;
+    linker.func_wrap(
+        "ddbot",
+        "assign",
+        |caller: Caller<'_, Host>, ptr: i32, len: i32| push(caller, ptr, len, Action::Assign),
+    )?;
+    let name = plugin.name.clone();
+    linker.func_wrap(
+        "ddbot",
+        "log",
+        move |caller: Caller<'_, Host>, ptr: i32, len: i32| {
+            info!("{}: {}", name, read_str(&caller, ptr, len)?);
+            Ok(())
+        },
+    )?;
+
+    let instance = linker
+        .instantiate(&mut store, &plugin.module)?
+        .start(&mut store)?;
+    let memory = instance
+        .get_memory(&store, "memory")
+        .ok_or_else(|| wasmi::Error::new("plugin doesn't export memory"))?;
+    let alloc: TypedFunc<i32, i32> = instance.get_typed_func(&store, "alloc")?;
+    let handle: TypedFunc<(i32, i32), ()> = instance.get_typed_func(&store, "handle")?;
+
+    let len = i32::try_from(payload.len()).map_err(|_| wasmi::Error::new("event too big"))?;
+    let ptr = alloc.call(&mut store, len)?;
+    memory
+        .write(&mut store, ptr as u32 as usize, payload)
+        .map_err(|e| wasmi::Error::new(e.to_string()))?;
+    handle.call(&mut store, (ptr, len))?;
+
+    Ok(store.into_data().actions)
+}
+
+#[async_trait]
+impl EventHandler for Plugins {
+    fn name(&self) -> &'static str {
+        "plugins"
+    }
+
+    fn events(&self) -> &[WebhookEventType] {
+        &self.events
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let Some(repo) = &event.repository else {
+            return Ok(());
+        };
+        let full_name = repo.full_name.as_deref().unwrap_or_default();
+        let payload = scripts::event_json(event);
+        let bytes: Arc<[u8]> = serde_json::to_vec(&payload)?.into();
+
+        for plugin in &self.plugins {
+            let wants_repo = plugin.repos.is_empty()
+                || plugin
+                    .repos
+                    .iter()
+                    .any(|x| x.eq_ignore_ascii_case(full_name));
+            if !wants_repo || !plugin.events.contains(&event.kind) {
+                continue;
+            }
+
+            let (engine, plugin, bytes) = (self.engine.clone(), plugin.clone(), bytes.clone());
+            let name = plugin.name.clone();
+            let res = tokio::task::spawn_blocking(move || run(&engine, &plugin, &bytes)).await?;
+            let actions = match res {
+                Ok(x) => x,
+                Err(e) => {
+                    warn!("{} failed: {}", name, e);
+                    continue;
+                }
+            };
+            scripts::apply(ctx, repo, &payload, &name, actions).await?;
+        }
+        Ok(())
+    }
+}
+
+fn read_str(caller: &Caller<'_, Host>, ptr: i32, len: i32) -> Result<String, wasmi::Error> {
+    let memory = caller
+        .get_export("memory")
+        .and_then(Extern::into_memory)
+        .ok_or_else(|| wasmi::Error::new("plugin doesn't export memory"))?;
+    let (ptr, len) = (ptr as u32 as usize, len as u32 as usize);
+    // Checked before allocating, so a plugin can't make the host allocate more than its memory.
+    let bytes = ptr
+        .checked_add(len)
+        .and_then(|end| memory.data(caller).get(ptr..end))
+        .ok_or_else(|| wasmi::Error::new("string outside of the plugin's memory"))?;
+    String::from_utf8(bytes.to_vec()).map_err(|e| wasmi::Error::new(e.to_string()))
+}
+
+fn push(
+    mut caller: Caller<'_, Host>,
+    ptr: i32,
+    len: i32,
+    action: fn(String) -> Action,
+) -> Result<(), wasmi::Error> {
+    let value = read_str(&caller, ptr, len)?;
+    caller.data_mut().actions.push(action(value));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A plugin whose `handle` runs `body`, with "bug" at 0 in its memory of `pages` pages.
+    fn plugin(pages: u32, body: &str) -> Plugin {
+        let wat = format!(
+            r#"(module
+                (import "ddbot" "add_label" (func $add_label (param i32 i32)))
+                (memory (export "memory") {pages})
+                (data (i32.const 0) "bug")
+                (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+                (func (export "handle") (param i32 i32) {body}))"#
+        );
+        Plugin {
+            name: "Plugin test.wasm".to_string(),
+            repos: Vec::new(),
+            events: Vec::new(),
+            module: Module::new(&engine(), &wat::parse_str(wat).unwrap()).unwrap(),
+        }
+    }
+
+    fn run_plugin(pages: u32, body: &str) -> Result<Vec<Action>, wasmi::Error> {
+        let plugin = plugin(pages, body);
+        run(plugin.module.engine(), &plugin, b"{}")
+    }
+
+    #[test]
+    fn plugins_ask_for_actions() {
+        let actions = run_plugin(1, "(call $add_label (i32.const 0) (i32.const 3))").unwrap();
+        assert_eq!(actions, [Action::AddLabel("bug".to_string())]);
+    }
+
+    #[test]
+    fn plugins_are_stopped_without_fuel() {
+        assert!(run_plugin(1, "(loop $l (br $l))").is_err());
+    }
+
+    #[test]
+    fn plugins_are_stopped_above_the_memory_limit() {
+        // 257 pages of 64 KiB are more than 16 MiB.
+        assert!(run_plugin(257, "").is_err());
+        let grow = "(if (i32.ne (memory.grow (i32.const 256)) (i32.const -1)) (then unreachable))";
+        assert!(run_plugin(1, grow).is_ok());
+    }
+
+    #[test]
+    fn strings_outside_of_the_memory_are_rejected() {
+        for (ptr, len) in [(65534, 3), (-1, 3), (0, -1), (0, i32::MAX)] {
+            let body = format!("(call $add_label (i32.const {ptr}) (i32.const {len}))");
+            let e = run_plugin(1, &body).unwrap_err();
+            assert!(
+                e.to_string().contains("outside of the plugin's memory"),
+                "{}",
+                e
+            );
+        }
+    }
+}
@@ -0,0 +1,253 @@
+use std::collections::HashSet;
+
+use async_trait::async_trait;
+use octocrab::{
+    Octocrab,
+    models::{
+        self, Repository,
+        pulls::PullRequest,
+        webhook_events::{
+            WebhookEvent, WebhookEventPayload, WebhookEventType,
+            payload::PullRequestWebhookEventAction,
+        },
+    },
+    params::checks::{CheckRunConclusion, CheckRunOutput, CheckRunStatus},
+};
+use serde_json::json;
+use tracing::info;
+
+use super::{Context, Error, EventHandler};
+use crate::{
+    batch, github,
+    workflow::{self, READY_TO_MERGE_LABEL, Transition},
+};
+
+pub const BLOCKED_LABEL: &str = "blocked";
+/// The check run PRs with unmerged dependencies fail, branch protection requiring it keeps
+/// them and their auto-merge from going through.
+pub const CHECK_NAME: &str = "Dependencies";
+
+/// Labels PRs declaring `depends on #123` in their description `blocked` until the PRs (or
+/// issues) they depend on are merged (or closed). Blocked PRs fail their `Dependencies` check,
+/// can't be moved to `ready-to-merge` and have auto-merge turned off. Their authors are told
+/// once the last dependency is merged.
+#[derive(Debug)]
+pub struct PrDependencies;
+
+#[async_trait]
+impl EventHandler for PrDependencies {
+    fn name(&self) -> &'static str {
+        "pr-dependencies"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::PullRequest]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let (WebhookEventPayload::PullRequest(payload), Some(repo)) =
+            (&event.specific, &event.repository)
+        else {
+            return Ok(());
+        };
+        let full_name = repo.full_name.as_deref().unwrap_or_default();
+        let pr = &payload.pull_request;
+        match payload.action {
+            PullRequestWebhookEventAction::Opened
+            | PullRequestWebhookEventAction::Reopened
+            | PullRequestWebhookEventAction::Edited
+            | PullRequestWebhookEventAction::Synchronize => {
+                let dependencies = dependencies(pr.body.as_deref().unwrap_or_default());
+                ctx.state
+                    .db
+                    .set_pr_dependencies(full_name, payload.number, &dependencies)?;
+                update(ctx, repo, pr, &dependencies).await?;
+            }
+            PullRequestWebhookEventAction::Closed => {
+                ctx.state
+                    .db
+                    .set_pr_dependencies(full_name, payload.number, &[])?;
+                if pr.merged_at.is_none() {
+                    return Ok(());
+                }
+                let owner = repo.owner.as_ref().ok_or("repository without owner")?;
+                let pulls = ctx.client.pulls(&owner.login, &repo.name);
+                for number in ctx.state.db.pr_dependents(full_name, payload.number)? {
+                    let dependent = github::retry(|| pulls.get(number)).await?;
+                    if dependent.state == Some(models::IssueState::Open) {
+                        let dependencies =
+                            dependencies(dependent.body.as_deref().unwrap_or_default());
+                        update(ctx, repo, &dependent, &dependencies).await?;
+                    }
+                }
+            }
+            PullRequestWebhookEventAction::AutoMergeEnabled if is_blocked(pr) => {
+                let node_id = pr
+                    .node_id
+                    .as_deref()
+                    .ok_or("pull request without node id")?;
+                github::graphql_mutation(
+                    ctx.state.dry_run,
+                    &ctx.client,
+                    format!("disable auto-merge on #{}", payload.number),
+                    "mutation($id: ID!) { disablePullRequestAutoMerge(input: { \
+                        pullRequestId: $id }) { clientMutationId } }",
+                    json!({ "id": node_id }),
+                )
+                .await?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+fn is_blocked(pr: &PullRequest) -> bool {
+    pr.labels.iter().flatten().any(|x| x.name == BLOCKED_LABEL)
+}
+
+/// Labels the PR `blocked` and fails its check while some of `dependencies` are open, or takes
+/// the label off and tells the author once they're all merged.
+async fn update(
+    ctx: &Context,
+    repo: &Repository,
+    pr: &PullRequest,
+    dependencies: &[u64],
+) -> Result<(), Error> {
+    let blocked = is_blocked(pr);
+    if dependencies.is_empty() && !blocked {
+        return Ok(());
+    }
+    let mut open = Vec::new();
+    for number in dependencies {
+        if !is_done(&ctx.client, repo, *number).await? {
+            open.push(*number);
+        }
+    }
+    report_check(ctx, repo, &pr.head.sha, &open).await?;
+
+    if !open.is_empty() && !blocked {
+        info!("#{} depends on {:?}, blocking it", pr.number, open);
+        let before: HashSet<String> = pr.labels.iter().flatten().map(|x| x.name.clone()).collect();
+        if before.contains(READY_TO_MERGE_LABEL) {
+            let mut after = before.clone();
+            workflow::apply(&mut after, Transition::RequestReviews)?;
+            workflow::sync(&ctx.state, &ctx.client, repo.id, pr.number, &before, &after).await?;
+        }
+        let labels = [BLOCKED_LABEL.to_string()];
+        batch::add_labels(&ctx.state, &ctx.client, repo.id, pr.number, &labels).await?;
+    } else if open.is_empty() && blocked {
+        info!(
+            "The dependencies of #{} are merged, unblocking it",
+            pr.number
+        );
+        batch::remove_label(&ctx.state, &ctx.client, repo.id, pr.number, BLOCKED_LABEL).await?;
+        let comment = ctx.rules.templates.render(
+            repo.full_name.as_deref().unwrap_or_default(),
+            "dependencies_merged",
+            json!({
+                "author": pr.user.as_ref().map(|x| x.login.as_str()).unwrap_or_default(),
+                "dependencies": dependencies,
+            }),
+        )?;
+        batch::comment(&ctx.state, &ctx.client, repo.id, pr.number, &comment).await?;
+    }
+    Ok(())
+}
+
+/// Whether the dependency is a merged PR or a closed issue. A PR closed without merging still
+/// blocks, the dependent PR likely needs changes.
+async fn is_done(client: &Octocrab, repo: &Repository, number: u64) -> Result<bool, Error> {
+    let issues = client.issues_by_id(repo.id);
+    let issue = github::retry(|| issues.get(number)).await?;
+    if issue.state == models::IssueState::Open {
+        return Ok(false);
+    }
+    if issue.pull_request.is_none() {
+        return Ok(true);
+    }
+    let owner = repo.owner.as_ref().ok_or("repository without owner")?;
+    let pulls = client.pulls(&owner.login, &repo.name);
+    let pr = github::retry(|| pulls.get(number)).await?;
+    Ok(pr.merged_at.is_some())
+}
+
+/// Passes the check of the PR, or fails it listing the open dependencies.
+async fn report_check(
+    ctx: &Context,
+    repo: &Repository,
+    sha: &str,
+    open: &[u64],
+) -> Result<(), Error> {
+    let owner = repo.owner.as_ref().ok_or("repository without owner")?;
+    let (conclusion, summary) = if open.is_empty() {
+        (
+            CheckRunConclusion::Success,
+            "Every dependency is merged.".to_string(),
+        )
+    } else {
+        let numbers: Vec<String> = open.iter().map(|x| format!("#{}", x)).collect();
+        (
+            CheckRunConclusion::Failure,
+            format!("Waiting for {} to be merged.", numbers.join(", ")),
+        )
+    };
+    let checks = ctx.client.checks(&owner.login, &repo.name);
+    github::mutate(
+        ctx.state.dry_run,
+        format!("report {} on {}: {}", CHECK_NAME, sha, summary),
+        || {
+            checks
+                .create_check_run(CHECK_NAME, sha)
+                .status(CheckRunStatus::Completed)
+                .conclusion(conclusion)
+                .output(CheckRunOutput {
+                    title: CHECK_NAME.to_string(),
+                    summary: summary.clone(),
+                    text: None,
+                    annotations: Vec::new(),
+                    images: Vec::new(),
+                })
+                .send()
+        },
+    )
+    .await?;
+    Ok(())
+}
+
+/// The numbers after `depends on` in the description, e.g. `Depends on #12 and #15`. Only the
+/// rest of the line after it is read.
+fn dependencies(body: &str) -> Vec<u64> {
+    let mut numbers = Vec::new();
+    for line in body.lines() {
+        let lower = line.to_lowercase();
+        let Some(start) = lower.find("depends on") else {
+            continue;
+        };
+        for word in lower[start..].split_ascii_whitespace() {
+            let Some(number) = word
+                .strip_prefix('#')
+                .map(|x| x.trim_end_matches(['.', ',', ')', ';']))
+                .and_then(|x| x.parse().ok())
+            else {
+                continue;
+            };
+            if !numbers.contains(&number) {
+                numbers.push(number);
+            }
+        }
+    }
+    numbers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dependencies_are_declared_in_the_description() {
+        let body = "Adds the editor part.\n\nDepends on #12, #15 and #12.\nFixes #3";
+        assert_eq!(dependencies(body), vec![12, 15]);
+        assert_eq!(dependencies("Closes #3"), Vec::<u64>::new());
+    }
+}
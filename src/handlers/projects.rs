@@ -0,0 +1,105 @@
+use async_trait::async_trait;
+use octocrab::models::webhook_events::{
+    WebhookEvent, WebhookEventPayload, WebhookEventType,
+    payload::{IssuesWebhookEventAction, PullRequestWebhookEventAction},
+};
+
+use super::{Context, Error, EventHandler};
+use crate::{
+    config::{Config, ProjectConfig},
+    feed::TRIAGE_LABEL,
+    projects,
+};
+
+/// Adds issues to the project board once they're triaged, and keeps their fields in sync with
+/// their labels. PRs follow their workflow labels, e.g. from `waiting-for-reviews` to
+/// `waiting-on-author`.
+#[derive(Debug)]
+pub struct Projects {
+    config: Option<ProjectConfig>,
+}
+
+impl Projects {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            config: config.project.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for Projects {
+    fn name(&self) -> &'static str {
+        "projects"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::Issues, WebhookEventType::PullRequest]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let Some(config) = &self.config else {
+            return Ok(());
+        };
+        match &event.specific {
+            WebhookEventPayload::Issues(payload)
+                if matches!(
+                    payload.action,
+                    IssuesWebhookEventAction::Labeled | IssuesWebhookEventAction::Unlabeled
+                ) =>
+            {
+                // Untriaged issues stay off the board.
+                let labels: Vec<&str> = payload
+                    .issue
+                    .labels
+                    .iter()
+                    .map(|x| x.name.as_str())
+                    .collect();
+                if labels.contains(&TRIAGE_LABEL) {
+                    return Ok(());
+                }
+                projects::sync(
+                    &ctx.client,
+                    ctx.state.dry_run,
+                    config,
+                    &payload.issue.node_id,
+                    &labels,
+                )
+                .await
+            }
+            WebhookEventPayload::PullRequest(payload)
+                if matches!(
+                    payload.action,
+                    PullRequestWebhookEventAction::Labeled
+                        | PullRequestWebhookEventAction::Unlabeled
+                ) =>
+            {
+                // Only PRs in the label workflow of the board are on it.
+                let Some(label) = &payload.label else {
+                    return Ok(());
+                };
+                if !config
+                    .fields
+                    .iter()
+                    .any(|x| x.labels.contains_key(&label.name))
+                {
+                    return Ok(());
+                }
+                let labels: Vec<&str> = payload
+                    .pull_request
+                    .labels
+                    .iter()
+                    .flatten()
+                    .map(|x| x.name.as_str())
+                    .collect();
+                let node_id = payload
+                    .pull_request
+                    .node_id
+                    .as_deref()
+                    .ok_or("pull request without node id")?;
+                projects::sync(&ctx.client, ctx.state.dry_run, config, node_id, &labels).await
+            }
+            _ => Ok(()),
+        }
+    }
+}
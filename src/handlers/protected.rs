@@ -0,0 +1,111 @@
+use async_trait::async_trait;
+use octocrab::models::webhook_events::{WebhookEvent, WebhookEventPayload, WebhookEventType};
+use serde_json::json;
+use tracing::info;
+
+use super::{Context, Error, EventHandler};
+use crate::{
+    batch,
+    config::{Config, ProtectedPathsConfig},
+    discord, github,
+};
+
+/// Raises an alert when commits touching sensitive paths land on the default branch without
+/// going through a PR.
+#[derive(Debug)]
+pub struct ProtectedPaths {
+    config: ProtectedPathsConfig,
+    http: reqwest::Client,
+}
+
+impl ProtectedPaths {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            config: config.protected_paths.clone(),
+            http: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for ProtectedPaths {
+    fn name(&self) -> &'static str {
+        "protected-paths"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::Push]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let (WebhookEventPayload::Push(payload), Some(repo)) = (&event.specific, &event.repository)
+        else {
+            return Ok(());
+        };
+        let Some(branch) = &repo.default_branch else {
+            return Ok(());
+        };
+        if self.config.paths.is_empty() || payload.r#ref != format!("refs/heads/{}", branch) {
+            return Ok(());
+        }
+        let owner = repo.owner.as_ref().ok_or("repository without owner")?;
+
+        let mut commits = Vec::new();
+        for commit in &payload.commits {
+            let files: Vec<&String> = commit
+                .added
+                .iter()
+                .chain(&commit.modified)
+                .chain(&commit.removed)
+                .filter(|file| {
+                    self.config
+                        .paths
+                        .iter()
+                        .any(|path| file.starts_with(path.as_str()))
+                })
+                .collect();
+            if files.is_empty() {
+                continue;
+            }
+            // Merged PRs were reviewed, only commits without one are direct pushes.
+            let route = format!(
+                "/repos/{}/{}/commits/{}/pulls",
+                owner.login, repo.name, commit.id
+            );
+            let pulls: Vec<serde_json::Value> =
+                github::retry(|| ctx.client.get(&route, None::<&()>)).await?;
+            if pulls.is_empty() {
+                commits.push(json!({
+                    "id": commit.id,
+                    "message": commit.message.lines().next().unwrap_or_default(),
+                    "url": commit.url,
+                    "files": files,
+                }));
+            }
+        }
+        if commits.is_empty() {
+            return Ok(());
+        }
+
+        let full_name = repo.full_name.as_deref().unwrap_or_default();
+        let alert = ctx.rules.templates.render(
+            full_name,
+            "protected_push",
+            json!({
+                "pusher": event.sender.as_ref().map(|x| x.login.as_str()).unwrap_or_default(),
+                "repo": full_name,
+                "branch": branch,
+                "commits": commits,
+            }),
+        )?;
+        info!("Direct push to protected paths: {}", alert);
+
+        if let Some(number) = self.config.issue {
+            batch::comment(&ctx.state, &ctx.client, repo.id, number, &alert).await?;
+        }
+        if let Some(url) = &self.config.discord_webhook {
+            discord::post(&self.http, ctx.state.dry_run, url, &alert).await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,130 @@
+use async_trait::async_trait;
+use octocrab::models::{
+    self,
+    webhook_events::{
+        WebhookEvent, WebhookEventPayload, WebhookEventType, payload::PullRequestWebhookEventAction,
+    },
+};
+use serde_json::json;
+
+use super::{Context, Error, EventHandler};
+use crate::{batch, config::Config, github, workflow::READY_TO_MERGE_LABEL};
+
+pub const BREAKING_PROTOCOL_LABEL: &str = "breaking-protocol";
+
+/// Labels PRs that change the network protocol and keeps them from being marked
+/// `ready-to-merge` until a maintainer acknowledged the change with `!ddnetbot ack-protocol`.
+#[derive(Debug)]
+pub struct Protocol {
+    paths: Vec<String>,
+}
+
+impl Protocol {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            paths: config.protocol_paths.clone(),
+        }
+    }
+
+    /// The key remembering that a maintainer acknowledged the protocol change of a PR.
+    pub fn ack_key(repo: models::RepositoryId, number: u64) -> String {
+        format!("protocol_ack:{}:{}", repo, number)
+    }
+}
+
+#[async_trait]
+impl EventHandler for Protocol {
+    fn name(&self) -> &'static str {
+        "protocol"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::PullRequest]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let (WebhookEventPayload::PullRequest(payload), Some(repo)) =
+            (&event.specific, &event.repository)
+        else {
+            return Ok(());
+        };
+        if self.paths.is_empty() {
+            return Ok(());
+        }
+        let full_name = repo.full_name.as_deref().unwrap_or_default();
+        let labels: Vec<&str> = payload
+            .pull_request
+            .labels
+            .iter()
+            .flatten()
+            .map(|x| x.name.as_str())
+            .collect();
+
+        match payload.action {
+            PullRequestWebhookEventAction::Labeled => {
+                if payload.label.as_ref().map(|x| x.name.as_str()) != Some(READY_TO_MERGE_LABEL)
+                    || !labels.contains(&BREAKING_PROTOCOL_LABEL)
+                    || ctx
+                        .state
+                        .db
+                        .get_kv(&Self::ack_key(repo.id, payload.number))?
+                        .is_some()
+                {
+                    return Ok(());
+                }
+                batch::remove_label(
+                    &ctx.state,
+                    &ctx.client,
+                    repo.id,
+                    payload.number,
+                    READY_TO_MERGE_LABEL,
+                )
+                .await?;
+                let comment = ctx.rules.templates.render(
+                    full_name,
+                    "protocol_unacknowledged",
+                    json!({ "label": READY_TO_MERGE_LABEL }),
+                )?;
+                batch::comment(&ctx.state, &ctx.client, repo.id, payload.number, &comment).await?;
+                Ok(())
+            }
+            PullRequestWebhookEventAction::Opened
+            | PullRequestWebhookEventAction::Reopened
+            | PullRequestWebhookEventAction::Synchronize => {
+                // Flag once, maintainers may remove the label if the change is compatible.
+                let key = format!("protocol_flagged:{}:{}", repo.id, payload.number);
+                if ctx.state.db.get_kv(&key)?.is_some() {
+                    return Ok(());
+                }
+                let owner = repo.owner.as_ref().ok_or("repository without owner")?;
+                let pulls = ctx.client.pulls(&owner.login, &repo.name);
+                let files = github::retry(|| pulls.list_files(payload.number)).await?;
+                let changed: Vec<&str> = files
+                    .items
+                    .iter()
+                    .map(|x| x.filename.as_str())
+                    .filter(|filename| self.paths.iter().any(|x| filename.starts_with(x.as_str())))
+                    .collect();
+                if changed.is_empty() {
+                    return Ok(());
+                }
+
+                let labels = [BREAKING_PROTOCOL_LABEL.to_string()];
+                batch::add_labels(&ctx.state, &ctx.client, repo.id, payload.number, &labels)
+                    .await?;
+                let comment = ctx.rules.templates.render(
+                    full_name,
+                    "protocol_change",
+                    json!({
+                        "author": payload.pull_request.user.as_ref().map(|x| x.login.as_str()).unwrap_or_default(),
+                        "files": changed,
+                    }),
+                )?;
+                batch::comment(&ctx.state, &ctx.client, repo.id, payload.number, &comment).await?;
+                ctx.state.db.set_kv(&key, "1")?;
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use async_trait::async_trait;
+use octocrab::models::webhook_events::{
+    WebhookEvent, WebhookEventPayload, WebhookEventType, payload::ReleaseWebhookEventAction,
+};
+use serde_json::{Value, json};
+use tracing::info;
+
+use super::{Context, Error, EventHandler};
+use crate::{
+    config::{Config, MatrixRoom, ReleasesConfig},
+    github,
+    outbox::{self, Effect},
+    quiet, report,
+};
+
+/// How much of the release notes goes into announcements.
+const CHANGELOG_MAX_LEN: usize = 1000;
+
+/// Announces published releases in the community channels, and opens a pinned issue to collect
+/// known issues of the release.
+#[derive(Debug)]
+pub struct Releases {
+    config: ReleasesConfig,
+    http: reqwest::Client,
+}
+
+impl Releases {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            config: config.releases.clone(),
+            http: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for Releases {
+    fn name(&self) -> &'static str {
+        "releases"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::Release]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let (WebhookEventPayload::Release(payload), Some(repo)) =
+            (&event.specific, &event.repository)
+        else {
+            return Ok(());
+        };
+        let release = &payload.release;
+        if payload.action != ReleaseWebhookEventAction::Published
+            || (release["prerelease"].as_bool() == Some(true) && !self.config.prereleases)
+        {
+            return Ok(());
+        }
+
+        let full_name = repo.full_name.as_deref().unwrap_or_default();
+        let tag = release["tag_name"].as_str().unwrap_or_default();
+        let downloads: Vec<Value> = release["assets"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|x| json!({ "name": x["name"], "url": x["browser_download_url"] }))
+            .collect();
+        let announcement = ctx.rules.templates.render(
+            full_name,
+            "release_announcement",
+            json!({
+                "repo": full_name,
+                "name": release["name"].as_str().filter(|x| !x.is_empty()).unwrap_or(tag),
+                "tag": tag,
+                "url": release["html_url"],
+                "changelog": excerpt(release["body"].as_str().unwrap_or_default()),
+                "downloads": downloads,
+            }),
+        )?;
+        info!("Announcing release {} of {}", tag, full_name);
+
+        for url in &self.config.discord_webhooks {
+            quiet::post_discord(&ctx.state, &self.http, url, &announcement).await?;
+        }
+        for room in &self.config.matrix {
+            let txn = format!("release-{}", release["id"]);
+            self.post_matrix(ctx.state.dry_run, room, &txn, &announcement)
+                .await?;
+        }
+        if self.config.known_issues {
+            self.open_known_issues(ctx, repo, tag, release).await?;
+        }
+        Ok(())
+    }
+}
+
+impl Releases {
+    async fn post_matrix(
+        &self,
+        dry_run: bool,
+        room: &MatrixRoom,
+        txn: &str,
+        body: &str,
+    ) -> Result<(), Error> {
+        let what = format!("post to Matrix room {}: {}", room.room_id, body);
+        report::record(&what);
+        if dry_run {
+            info!("Dry run, would {}", what);
+            outbox::record_shadow(&what);
+            return Ok(());
+        }
+        let effect = Effect::current(&what);
+        if let Some(effect) = &effect {
+            if effect.state() == outbox::State::Done {
+                info!("Already done for this delivery, skipping: {}", what);
+                return Ok(());
+            }
+            effect.begin();
+        }
+        // The transaction ID makes Matrix ignore redelivered announcements.
+        let url = format!(
+            "{}/_matrix/client/v3/rooms/{}/send/m.room.message/{}",
+            room.homeserver.trim_end_matches('/'),
+            room.room_id,
+            txn
+        );
+        self.http
+            .put(url)
+            .bearer_auth(&room.access_token)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(json!({ "msgtype": "m.text", "body": body }).to_string())
+            .send()
+            .await?
+            .error_for_status()?;
+        if let Some(effect) = &effect {
+            effect.done();
+        }
+        Ok(())
+    }
+
+    /// Opens and pins the known issues issue of the release, unpinning the one of the previous
+    /// release.
+    async fn open_known_issues(
+        &self,
+        ctx: &Context,
+        repo: &octocrab::models::Repository,
+        tag: &str,
+        release: &Value,
+    ) -> Result<(), Error> {
+        let full_name = repo.full_name.as_deref().unwrap_or_default();
+        let body = ctx.rules.templates.render(
+            full_name,
+            "known_issues",
+            json!({ "tag": tag, "url": release["html_url"] }),
+        )?;
+        let title = format!("Known issues in {}", tag);
+        let issues = ctx.client.issues_by_id(repo.id);
+        let Some(issue) = github::create_issue(
+            ctx.state.dry_run,
+            &issues,
+            &title,
+            &body,
+            Vec::new(),
+            Vec::new(),
+        )
+        .await?
+        else {
+            return Ok(());
+        };
+        let key = format!("known_issues:{}", repo.id);
+        if let Some(previous) = ctx.state.db.get_kv(&key)? {
+            github::graphql_mutation(
+                ctx.state.dry_run,
+                &ctx.client,
+                format!("unpin issue {}", previous),
+                "mutation($id: ID!) { unpinIssue(input: { issueId: $id }) { clientMutationId } }",
+                json!({ "id": previous }),
+            )
+            .await?;
+        }
+        github::graphql_mutation(
+            ctx.state.dry_run,
+            &ctx.client,
+            format!("pin #{}", issue.number),
+            "mutation($id: ID!) { pinIssue(input: { issueId: $id }) { clientMutationId } }",
+            json!({ "id": issue.node_id }),
+        )
+        .await?;
+        ctx.state.db.set_kv(&key, &issue.node_id)?;
+        Ok(())
+    }
+}
+
+/// The start of the release notes, cut at a line break.
+fn excerpt(notes: &str) -> String {
+    if notes.chars().count() <= CHANGELOG_MAX_LEN {
+        return notes.trim().to_string();
+    }
+    let cut: String = notes.chars().take(CHANGELOG_MAX_LEN).collect();
+    let cut = cut.rsplit_once('\n').map(|(x, _)| x).unwrap_or(&cut);
+    format!("{}\n…", cut.trim_end())
+}
@@ -0,0 +1,76 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use octocrab::models::webhook_events::{
+    WebhookEvent, WebhookEventPayload, WebhookEventType,
+    payload::{IssueCommentWebhookEventAction, IssuesWebhookEventAction},
+};
+
+use super::{Context, Error, EventHandler};
+use crate::feed::TRIAGE_LABEL;
+
+/// Records when issues are opened, first answered by someone other than their author and
+/// triaged, for the response time SLOs, see [`crate::slo`].
+#[derive(Debug)]
+pub struct ResponseTimes;
+
+#[async_trait]
+impl EventHandler for ResponseTimes {
+    fn name(&self) -> &'static str {
+        "response-times"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::Issues, WebhookEventType::IssueComment]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let Some(full_name) = event
+            .repository
+            .as_ref()
+            .and_then(|x| x.full_name.as_deref())
+        else {
+            return Ok(());
+        };
+        let db = &ctx.state.db;
+
+        match &event.specific {
+            WebhookEventPayload::Issues(payload) if payload.issue.pull_request.is_none() => {
+                let number = payload.issue.number;
+                match payload.action {
+                    IssuesWebhookEventAction::Opened => {
+                        db.add_issue_opened(full_name, number, payload.issue.created_at)?;
+                    }
+                    IssuesWebhookEventAction::Unlabeled
+                        if payload.label.as_ref().map(|x| x.name.as_str())
+                            == Some(TRIAGE_LABEL) =>
+                    {
+                        db.set_issue_triaged(full_name, number, Utc::now())?;
+                    }
+                    // Closing counts as triage, e.g. of duplicates.
+                    IssuesWebhookEventAction::Closed => {
+                        db.set_issue_triaged(full_name, number, Utc::now())?;
+                        db.set_issue_closed(full_name, number, true)?;
+                    }
+                    IssuesWebhookEventAction::Reopened => {
+                        db.set_issue_closed(full_name, number, false)?;
+                    }
+                    _ => {}
+                }
+            }
+            WebhookEventPayload::IssueComment(payload)
+                if payload.action == IssueCommentWebhookEventAction::Created
+                    && payload.issue.pull_request.is_none()
+                    && payload.comment.user.r#type != "Bot"
+                    && payload.comment.user.id != payload.issue.user.id =>
+            {
+                db.set_issue_responded(
+                    full_name,
+                    payload.issue.number,
+                    payload.comment.created_at,
+                )?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,159 @@
+use async_trait::async_trait;
+use octocrab::models::{
+    self,
+    webhook_events::{
+        WebhookEvent, WebhookEventPayload, WebhookEventType, payload::PullRequestWebhookEventAction,
+    },
+};
+use serde_json::json;
+use tracing::info;
+
+use super::{Context, Error, EventHandler};
+use crate::{batch, github};
+
+pub const REGRESSION_LABEL: &str = "regression";
+
+/// When a PR reverting another one is merged, reopens the issues the reverted PR closed, labels
+/// them `regression` and links the issues and both PRs to each other.
+#[derive(Debug)]
+pub struct Reverts;
+
+#[async_trait]
+impl EventHandler for Reverts {
+    fn name(&self) -> &'static str {
+        "reverts"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::PullRequest]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let (WebhookEventPayload::PullRequest(payload), Some(repo)) =
+            (&event.specific, &event.repository)
+        else {
+            return Ok(());
+        };
+        if payload.action != PullRequestWebhookEventAction::Closed
+            || payload.pull_request.merged_at.is_none()
+        {
+            return Ok(());
+        }
+        let full_name = repo.full_name.as_deref().unwrap_or_default();
+        let Some(reverted) = reverted_pr(
+            payload.pull_request.title.as_deref().unwrap_or_default(),
+            payload.pull_request.body.as_deref().unwrap_or_default(),
+            full_name,
+        ) else {
+            return Ok(());
+        };
+        let key = format!("reverted:{}:{}", repo.id, reverted);
+        if ctx.state.db.get_kv(&key)?.is_some() {
+            return Ok(());
+        }
+
+        let owner = repo.owner.as_ref().ok_or("repository without owner")?;
+        let res = github::graphql(
+            &ctx.client,
+            "query($owner: String!, $name: String!, $number: Int!) { repository(owner: $owner, \
+                name: $name) { pullRequest(number: $number) { merged closingIssuesReferences(first: \
+                25) { nodes { number state repository { nameWithOwner } } } } } }",
+            json!({ "owner": owner.login, "name": repo.name, "number": reverted }),
+        )
+        .await?;
+        let pr = &res["data"]["repository"]["pullRequest"];
+        if pr["merged"].as_bool() != Some(true) {
+            return Ok(());
+        }
+        let closed: Vec<u64> = pr["closingIssuesReferences"]["nodes"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|x| x["state"] == "CLOSED" && x["repository"]["nameWithOwner"] == full_name)
+            .filter_map(|x| x["number"].as_u64())
+            .collect();
+
+        info!(
+            "#{} reverts #{}, reopening {:?}",
+            payload.number, reverted, closed
+        );
+        let issues = ctx.client.issues_by_id(repo.id);
+        let labels = [REGRESSION_LABEL.to_string()];
+        for &number in &closed {
+            github::mutate(ctx.state.dry_run, format!("reopen #{}", number), || {
+                issues.update(number).state(models::IssueState::Open).send()
+            })
+            .await?;
+            batch::add_labels(&ctx.state, &ctx.client, repo.id, number, &labels).await?;
+            let comment = ctx.rules.templates.render(
+                full_name,
+                "regression_reopened",
+                json!({ "pr": reverted, "revert": payload.number }),
+            )?;
+            batch::comment(&ctx.state, &ctx.client, repo.id, number, &comment).await?;
+        }
+        let comment = ctx.rules.templates.render(
+            full_name,
+            "pr_reverted",
+            json!({ "revert": payload.number, "issues": closed }),
+        )?;
+        batch::comment(&ctx.state, &ctx.client, repo.id, reverted, &comment).await?;
+        ctx.state.db.set_kv(&key, &payload.number.to_string())?;
+        Ok(())
+    }
+}
+
+/// The number of the PR a PR reverts, from GitHub's "Reverts owner/name#123" description or a
+/// `Revert "Title (#123)"` title.
+fn reverted_pr(title: &str, body: &str, full_name: &str) -> Option<u64> {
+    let number = |x: &str| x.trim_end_matches(['.', ',', ')', '"']).parse::<u64>().ok();
+    let words: Vec<&str> = body.split_ascii_whitespace().collect();
+    let from_body = words.windows(2).find_map(|pair| {
+        if !pair[0].eq_ignore_ascii_case("reverts") {
+            return None;
+        }
+        let (repo, pr) = pair[1].rsplit_once('#').or_else(|| {
+            pair[1]
+                .strip_prefix("https://github.com/")?
+                .split_once("/pull/")
+        })?;
+        (repo.is_empty() || repo.eq_ignore_ascii_case(full_name))
+            .then(|| number(pr))
+            .flatten()
+    });
+    from_body.or_else(|| {
+        let title = title.strip_prefix("Revert \"")?;
+        number(title.rsplit_once("(#")?.1)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reverted_prs_are_found() {
+        let full_name = "ddnet/ddnet";
+        assert_eq!(
+            reverted_pr("Revert \"Fix x\"", "Reverts ddnet/ddnet#123", full_name),
+            Some(123)
+        );
+        assert_eq!(
+            reverted_pr(
+                "",
+                "Reverts https://github.com/ddnet/ddnet/pull/7.",
+                full_name
+            ),
+            Some(7)
+        );
+        assert_eq!(
+            reverted_pr("Revert \"Fix x (#45)\"", "", full_name),
+            Some(45)
+        );
+        assert_eq!(
+            reverted_pr("", "Reverts ddnet/ddnet-web#123", full_name),
+            None
+        );
+        assert_eq!(reverted_pr("Fix x (#45)", "", full_name), None);
+    }
+}
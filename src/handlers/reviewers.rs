@@ -0,0 +1,96 @@
+use async_trait::async_trait;
+use octocrab::models::webhook_events::{
+    WebhookEvent, WebhookEventPayload, WebhookEventType, payload::PullRequestWebhookEventAction,
+};
+
+use super::{Context, Error, EventHandler};
+use crate::{
+    config::{Config, ReviewRule},
+    github,
+};
+
+/// Requests reviews from the people and teams responsible for the paths a PR changes, once
+/// it's ready for review.
+#[derive(Debug)]
+pub struct Reviewers {
+    rules: Vec<ReviewRule>,
+}
+
+impl Reviewers {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            rules: config.review_rules.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for Reviewers {
+    fn name(&self) -> &'static str {
+        "reviewers"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::PullRequest]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let (WebhookEventPayload::PullRequest(payload), Some(repo)) =
+            (&event.specific, &event.repository)
+        else {
+            return Ok(());
+        };
+        let ready = match payload.action {
+            PullRequestWebhookEventAction::Opened => payload.pull_request.draft != Some(true),
+            PullRequestWebhookEventAction::ReadyForReview => true,
+            _ => false,
+        };
+        if !ready || self.rules.is_empty() {
+            return Ok(());
+        }
+
+        let owner = repo.owner.as_ref().ok_or("repository without owner")?;
+        let pulls = ctx.client.pulls(&owner.login, &repo.name);
+        let files = github::retry(|| pulls.list_files(payload.number)).await?;
+        let author = payload.pull_request.user.as_ref().map(|x| x.login.as_str());
+
+        let mut reviewers: Vec<String> = Vec::new();
+        let mut teams: Vec<String> = Vec::new();
+        for rule in &self.rules {
+            let matches = files.items.iter().any(|file| {
+                rule.paths
+                    .iter()
+                    .any(|path| file.filename.starts_with(path.as_str()))
+            });
+            if !matches {
+                continue;
+            }
+            // GitHub refuses review requests from the author.
+            reviewers.extend(
+                rule.reviewers
+                    .iter()
+                    .filter(|x| Some(x.as_str()) != author)
+                    .cloned(),
+            );
+            teams.extend(rule.teams.iter().cloned());
+        }
+        reviewers.sort();
+        reviewers.dedup();
+        teams.sort();
+        teams.dedup();
+        if reviewers.is_empty() && teams.is_empty() {
+            return Ok(());
+        }
+
+        github::mutate(
+            ctx.state.dry_run,
+            format!(
+                "request reviews from {:?} and teams {:?} on #{}",
+                reviewers, teams, payload.number
+            ),
+            || pulls.request_reviews(payload.number, reviewers.clone(), teams.clone()),
+        )
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,105 @@
+use async_trait::async_trait;
+use octocrab::models::webhook_events::{
+    WebhookEvent, WebhookEventPayload, WebhookEventType,
+    payload::{PullRequestReviewWebhookEventAction, PullRequestWebhookEventAction},
+};
+
+use super::{Context, Error, EventHandler};
+use crate::{batch, db::ReviewRequest, reviews::OVERDUE_LABEL};
+
+/// Tracks pending review requests, for the reminders sent by [`crate::reviews::reminder_loop`].
+#[derive(Debug)]
+pub struct Reviews;
+
+#[async_trait]
+impl EventHandler for Reviews {
+    fn name(&self) -> &'static str {
+        "reviews"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[
+            WebhookEventType::PullRequest,
+            WebhookEventType::PullRequestReview,
+        ]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let Some(repo) = &event.repository else {
+            return Ok(());
+        };
+        let db = &ctx.state.db;
+
+        match &event.specific {
+            WebhookEventPayload::PullRequest(payload) => {
+                let reviewer = match (&payload.requested_reviewer, &payload.requested_team) {
+                    (Some(user), _) => Some((user.login.clone(), false)),
+                    (_, Some(team)) => Some((team.slug.clone(), true)),
+                    _ => None,
+                };
+                match (&payload.action, reviewer) {
+                    // Nobody is expected to review drafts yet.
+                    (PullRequestWebhookEventAction::ReviewRequested, Some(_))
+                        if payload.pull_request.draft == Some(true) => {}
+                    (PullRequestWebhookEventAction::ReviewRequested, Some((reviewer, team))) => {
+                        db.add_review_request(&ReviewRequest {
+                            installation: ctx.installation,
+                            repo: repo.id,
+                            full_name: repo.full_name.clone().unwrap_or_default(),
+                            number: payload.number,
+                            reviewer,
+                            team,
+                            requested_at: chrono::Utc::now(),
+                            reminded: false,
+                        })?;
+                    }
+                    (
+                        PullRequestWebhookEventAction::ReviewRequestRemoved,
+                        Some((reviewer, team)),
+                    ) => {
+                        db.remove_review_request(repo.id, payload.number, &reviewer, team)?;
+                    }
+                    (
+                        PullRequestWebhookEventAction::Closed
+                        | PullRequestWebhookEventAction::ConvertedToDraft,
+                        _,
+                    ) => {
+                        db.remove_review_requests(repo.id, payload.number, None)?;
+                    }
+                    _ => {}
+                }
+            }
+            WebhookEventPayload::PullRequestReview(payload)
+                if payload.action == PullRequestReviewWebhookEventAction::Submitted =>
+            {
+                let (Some(user), Some(author)) = (&payload.review.user, &payload.pull_request.user)
+                else {
+                    return Ok(());
+                };
+                // Authors answering review comments don't count.
+                if user.id == author.id {
+                    return Ok(());
+                }
+                let number = payload.pull_request.number;
+                db.remove_review_requests(repo.id, number, Some(&user.login))?;
+
+                let overdue = payload
+                    .pull_request
+                    .labels
+                    .iter()
+                    .flatten()
+                    .any(|x| x.name == OVERDUE_LABEL);
+                let still_overdue = db
+                    .review_requests(Some((repo.id, number)))?
+                    .iter()
+                    .any(|x| x.reminded);
+                if overdue && !still_overdue {
+                    batch::remove_label(&ctx.state, &ctx.client, repo.id, number, OVERDUE_LABEL)
+                        .await?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,90 @@
+use async_trait::async_trait;
+use octocrab::models::webhook_events::{
+    WebhookEvent, WebhookEventPayload, WebhookEventType, payload::IssuesWebhookEventAction,
+};
+use serde_json::json;
+
+use super::{Context, Error, EventHandler};
+use crate::{
+    batch,
+    config::{Config, RoutingRule},
+    github,
+};
+
+/// Points out issues that belong in another repo, or transfers them there.
+#[derive(Debug)]
+pub struct Routing {
+    rules: Vec<RoutingRule>,
+}
+
+impl Routing {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            rules: config.routing.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for Routing {
+    fn name(&self) -> &'static str {
+        "routing"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::Issues]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let (WebhookEventPayload::Issues(payload), Some(repo)) =
+            (&event.specific, &event.repository)
+        else {
+            return Ok(());
+        };
+        if payload.action != IssuesWebhookEventAction::Opened || self.rules.is_empty() {
+            return Ok(());
+        }
+        let full_name = repo.full_name.as_deref().unwrap_or_default();
+        let issue = &payload.issue;
+        let text = format!(
+            "{}\n{}",
+            issue.title,
+            issue.body.as_deref().unwrap_or_default()
+        )
+        .to_lowercase();
+        let Some(rule) = self.rules.iter().find(|rule| {
+            !rule.repo.eq_ignore_ascii_case(full_name)
+                && rule
+                    .keywords
+                    .iter()
+                    .any(|x| text.contains(&x.to_lowercase()))
+        }) else {
+            return Ok(());
+        };
+
+        if rule.transfer {
+            let (key, route) = (rule.repo.to_lowercase(), format!("/repos/{}", rule.repo));
+            let target =
+                github::retry(|| ctx.state.repos.get(&ctx.client, key.clone(), &route)).await?;
+            github::graphql_mutation(
+                ctx.state.dry_run,
+                &ctx.client,
+                format!("transfer #{} to {}", issue.number, rule.repo),
+                "mutation($issue: ID!, $repo: ID!) { \
+                    transferIssue(input: { issueId: $issue, repositoryId: $repo }) { \
+                    clientMutationId } }",
+                json!({ "issue": issue.node_id, "repo": target.node_id }),
+            )
+            .await?;
+            return Ok(());
+        }
+
+        let comment = ctx.rules.templates.render(
+            full_name,
+            "misfiled",
+            json!({ "author": issue.user.login, "repo": rule.repo }),
+        )?;
+        batch::comment(&ctx.state, &ctx.client, repo.id, issue.number, &comment).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,128 @@
+use async_trait::async_trait;
+use octocrab::models::webhook_events::{
+    WebhookEvent, WebhookEventPayload, WebhookEventType, payload::PullRequestWebhookEventAction,
+};
+use serde_json::json;
+
+use super::{Context, Error, EventHandler};
+use crate::{batch, config::Config, github};
+
+pub const NEEDS_SCREENSHOT_LABEL: &str = "needs-screenshot";
+
+/// Signs of an image or video in a PR description.
+const MEDIA_MARKERS: &[&str] = &[
+    "![",
+    "<img",
+    "<video",
+    "github.com/user-attachments/",
+    "user-images.githubusercontent.com",
+    ".png",
+    ".jpg",
+    ".jpeg",
+    ".gif",
+    ".webp",
+    ".mp4",
+    ".webm",
+    ".mov",
+];
+
+/// Asks for screenshots on PRs that change the UI or rendering, until the description has some.
+#[derive(Debug)]
+pub struct Screenshots {
+    paths: Vec<String>,
+}
+
+impl Screenshots {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            paths: config.screenshot_paths.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for Screenshots {
+    fn name(&self) -> &'static str {
+        "screenshots"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::PullRequest]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let (WebhookEventPayload::PullRequest(payload), Some(repo)) =
+            (&event.specific, &event.repository)
+        else {
+            return Ok(());
+        };
+        if self.paths.is_empty()
+            || !matches!(
+                payload.action,
+                PullRequestWebhookEventAction::Opened
+                    | PullRequestWebhookEventAction::Edited
+                    | PullRequestWebhookEventAction::Synchronize
+            )
+        {
+            return Ok(());
+        }
+        let labeled = payload
+            .pull_request
+            .labels
+            .iter()
+            .flatten()
+            .any(|x| x.name == NEEDS_SCREENSHOT_LABEL);
+
+        if has_media(payload.pull_request.body.as_deref().unwrap_or_default()) {
+            if labeled {
+                batch::remove_label(
+                    &ctx.state,
+                    &ctx.client,
+                    repo.id,
+                    payload.number,
+                    NEEDS_SCREENSHOT_LABEL,
+                )
+                .await?;
+            }
+            return Ok(());
+        }
+        if labeled {
+            return Ok(());
+        }
+
+        let owner = repo.owner.as_ref().ok_or("repository without owner")?;
+        let pulls = ctx.client.pulls(&owner.login, &repo.name);
+        let files = github::retry(|| pulls.list_files(payload.number)).await?;
+        let visual = files.items.iter().any(|file| {
+            self.paths
+                .iter()
+                .any(|path| file.filename.starts_with(path.as_str()))
+        });
+        if !visual {
+            return Ok(());
+        }
+
+        let labels = [NEEDS_SCREENSHOT_LABEL.to_string()];
+        batch::add_labels(&ctx.state, &ctx.client, repo.id, payload.number, &labels).await?;
+        // Ask once, the label shows that it's still missing.
+        let key = format!("screenshot_requested:{}:{}", repo.id, payload.number);
+        if ctx.state.db.get_kv(&key)?.is_some() {
+            return Ok(());
+        }
+        let comment = ctx.rules.templates.render(
+            repo.full_name.as_deref().unwrap_or_default(),
+            "screenshot_request",
+            json!({
+                "author": payload.pull_request.user.as_ref().map(|x| x.login.as_str()).unwrap_or_default(),
+            }),
+        )?;
+        batch::comment(&ctx.state, &ctx.client, repo.id, payload.number, &comment).await?;
+        ctx.state.db.set_kv(&key, "1")?;
+        Ok(())
+    }
+}
+
+fn has_media(body: &str) -> bool {
+    let body = body.to_lowercase();
+    MEDIA_MARKERS.iter().any(|x| body.contains(x))
+}
@@ -0,0 +1,270 @@
+use std::{
+    error::Error as StdError,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use octocrab::models::{
+    Repository,
+    webhook_events::{WebhookEvent, WebhookEventType},
+};
+use rhai::{AST, Engine, Scope};
+use tracing::{info, warn};
+
+use super::{Context, Error, EventHandler};
+use crate::{batch, config::Config, github};
+
+/// Something a script or plugin asked for, carried out once it finished.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Action {
+    AddLabel(String),
+    Comment(String),
+    Assign(String),
+}
+
+#[derive(Debug)]
+struct Script {
+    name: String,
+    repo: String,
+    events: Vec<WebhookEventType>,
+    ast: AST,
+}
+
+/// Runs the rhai scripts from the config on the events they're registered for.
+///
+/// Scripts get the delivery as the `event` map and can call `add_label(name)`,
+/// `comment(body)` and `assign(login)` on the issue or PR it's about. They can't touch the
+/// filesystem or network, run on the blocking threads, and are stopped after a fixed number
+/// of operations.
+#[derive(Debug)]
+pub struct Scripts {
+    scripts: Vec<Arc<Script>>,
+    events: Vec<WebhookEventType>,
+}
+
+impl Scripts {
+    /// Compiles every script in the config.
+    pub fn from_config(config: &Config) -> Result<Self, Box<dyn StdError>> {
+        let engine = engine();
+        let mut scripts = Vec::new();
+        let mut events = Vec::new();
+
+        for script in &config.scripts {
+            let ast = engine
+                .compile_file(script.file.clone())
+                .map_err(|e| format!("{}: {}", script.file.display(), e))?;
+            let script_events = parse_events(&script.events)?;
+            for event in &script_events {
+                if !events.contains(event) {
+                    events.push(event.clone());
+                }
+            }
+            scripts.push(Arc::new(Script {
+                name: format!("Script {}", script.file.display()),
+                repo: script.repo.clone(),
+                events: script_events,
+                ast,
+            }));
+        }
+
+        Ok(Self { scripts, events })
+    }
+}
+
+#[async_trait]
+impl EventHandler for Scripts {
+    fn name(&self) -> &'static str {
+        "scripts"
+    }
+
+    fn events(&self) -> &[WebhookEventType] {
+        &self.events
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let Some(repo) = &event.repository else {
+            return Ok(());
+        };
+        let full_name = repo.full_name.as_deref().unwrap_or_default();
+        let payload = Arc::new(event_json(event));
+
+        for script in &self.scripts {
+            if !script.repo.eq_ignore_ascii_case(full_name) || !script.events.contains(&event.kind)
+            {
+                continue;
+            }
+
+            let res = {
+                let (script, payload) = (script.clone(), payload.clone());
+                tokio::task::spawn_blocking(move || run(&script.ast, &payload)).await?
+            };
+            let actions = match res {
+                Ok(x) => x,
+                Err(e) => {
+                    warn!("{} failed: {}", script.name, e);
+                    continue;
+                }
+            };
+            apply(ctx, repo, &payload, &script.name, actions).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Carries out the actions `name` asked for on the issue or PR of the delivery.
+pub(super) async fn apply(
+    ctx: &Context,
+    repo: &Repository,
+    payload: &serde_json::Value,
+    name: &str,
+    actions: Vec<Action>,
+) -> Result<(), Error> {
+    if actions.is_empty() {
+        return Ok(());
+    }
+    let number = ["issue", "pull_request"]
+        .iter()
+        .find_map(|x| payload[*x]["number"].as_u64())
+        .or_else(|| payload["number"].as_u64());
+    let Some(number) = number else {
+        warn!("{} acted on an event without an issue or PR", name);
+        return Ok(());
+    };
+
+    info!("{} requested {:?}", name, actions);
+    let issues = ctx.client.issues_by_id(repo.id);
+    for action in actions {
+        match action {
+            Action::AddLabel(label) => {
+                let labels = [label];
+                batch::add_labels(&ctx.state, &ctx.client, repo.id, number, &labels).await?
+            }
+            Action::Comment(body) => {
+                batch::comment(&ctx.state, &ctx.client, repo.id, number, &body).await?
+            }
+            Action::Assign(login) => {
+                let assignees = [login.as_str()];
+                github::mutate(
+                    ctx.state.dry_run,
+                    format!("assign {:?} to #{}", assignees, number),
+                    || issues.add_assignees(number, &assignees),
+                )
+                .await?
+            }
+        }
+    }
+    Ok(())
+}
+
+/// An engine without module imports and with limits, so scripts can't hang the bot.
+fn engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.set_module_resolver(rhai::module_resolvers::DummyModuleResolver::new());
+    engine.set_max_operations(100_000);
+    engine.set_max_call_levels(32);
+    engine.set_max_expr_depths(64, 32);
+    engine.set_max_string_size(64 * 1024);
+    engine.set_max_array_size(10_000);
+    engine.set_max_map_size(10_000);
+    engine.on_print(|x| info!("script: {}", x));
+    engine.on_debug(|x, _, _| info!("script: {}", x));
+    engine
+}
+
+fn run(ast: &AST, payload: &serde_json::Value) -> Result<Vec<Action>, Box<rhai::EvalAltResult>> {
+    let actions = Arc::new(Mutex::new(Vec::new()));
+    let mut engine = engine();
+
+    let x = actions.clone();
+    engine.register_fn("add_label", move |name: &str| {
+        x.lock().unwrap().push(Action::AddLabel(name.to_string()))
+    });
+    let x = actions.clone();
+    engine.register_fn("comment", move |body: &str| {
+        x.lock().unwrap().push(Action::Comment(body.to_string()))
+    });
+    let x = actions.clone();
+    engine.register_fn("assign", move |login: &str| {
+        x.lock().unwrap().push(Action::Assign(login.to_string()))
+    });
+
+    let mut scope = Scope::new();
+    scope.push_constant("event", rhai::serde::to_dynamic(payload)?);
+    engine.run_ast_with_scope(&mut scope, ast)?;
+
+    drop(engine);
+    Ok(Arc::into_inner(actions).unwrap().into_inner().unwrap())
+}
+
+/// Parses event names as in `X-GitHub-Event`.
+pub(super) fn parse_events(names: &[String]) -> serde_json::Result<Vec<WebhookEventType>> {
+    names
+        .iter()
+        .map(|x| serde_json::from_value(serde_json::Value::String(x.clone())))
+        .collect()
+}
+
+/// The delivery as GitHub sent it: the event specific fields plus `sender` and `repository`.
+pub(super) fn event_json(event: &WebhookEvent) -> serde_json::Value {
+    // The payload enum serializes as `{"Variant": {...}}`.
+    let specific = serde_json::to_value(&event.specific).unwrap_or_default();
+    let mut payload = match specific {
+        serde_json::Value::Object(x) => x.into_values().next().unwrap_or_default(),
+        _ => serde_json::Value::Null,
+    };
+    if let serde_json::Value::Object(x) = &mut payload {
+        x.insert(
+            "sender".to_string(),
+            serde_json::to_value(&event.sender).unwrap_or_default(),
+        );
+        x.insert(
+            "repository".to_string(),
+            serde_json::to_value(&event.repository).unwrap_or_default(),
+        );
+    }
+    payload
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn run_script(script: &str) -> Result<Vec<Action>, Box<rhai::EvalAltResult>> {
+        let ast = engine().compile(script).unwrap();
+        run(&ast, &json!({ "sender": { "login": "Robyt3" } }))
+    }
+
+    #[test]
+    fn scripts_ask_for_actions() {
+        let actions = run_script(
+            r#"
+            add_label("bug");
+            comment("Thanks @" + event.sender.login);
+            assign(event.sender.login);
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            actions,
+            [
+                Action::AddLabel("bug".to_string()),
+                Action::Comment("Thanks @Robyt3".to_string()),
+                Action::Assign("Robyt3".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn scripts_are_stopped_at_the_limits() {
+        let e = run_script("loop {}").unwrap_err();
+        assert!(matches!(*e, rhai::EvalAltResult::ErrorTooManyOperations(_)));
+        let e = run_script("fn f() { f() } f()").unwrap_err();
+        assert!(matches!(*e, rhai::EvalAltResult::ErrorStackOverflow(_)));
+        let nested = format!("{}1{}", "(".repeat(100), ")".repeat(100));
+        assert!(engine().compile(nested).is_err());
+        let e = run_script(r#"let x = "a"; loop { x += x; }"#).unwrap_err();
+        assert!(matches!(*e, rhai::EvalAltResult::ErrorDataTooLarge(..)));
+    }
+}
@@ -0,0 +1,228 @@
+use async_trait::async_trait;
+use octocrab::models::{
+    Repository,
+    issues::Issue,
+    webhook_events::{
+        WebhookEvent, WebhookEventPayload, WebhookEventType,
+        payload::{
+            DependabotAlertWebhookEventAction, RepositoryAdvisoryWebhookEventAction,
+            SecretScanningAlertWebhookEventAction,
+        },
+    },
+};
+use serde_json::json;
+use tracing::info;
+
+use super::{Context, Error, EventHandler};
+use crate::{
+    batch, bulk,
+    config::{Config, SecurityAlertsConfig},
+    discord, github,
+};
+
+pub const SECURITY_LABEL: &str = "security";
+
+/// Forwards secret scanning alerts, Dependabot alerts and reported advisories to private
+/// channels, so they don't go unnoticed in the security tab.
+#[derive(Debug)]
+pub struct SecurityAlerts {
+    config: SecurityAlertsConfig,
+    http: reqwest::Client,
+}
+
+impl SecurityAlerts {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            config: config.security_alerts.clone(),
+            http: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for SecurityAlerts {
+    fn name(&self) -> &'static str {
+        "security-alerts"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[
+            WebhookEventType::DependabotAlert,
+            WebhookEventType::SecretScanningAlert,
+            WebhookEventType::RepositoryAdvisory,
+        ]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let Some(repo) = &event.repository else {
+            return Ok(());
+        };
+        if self.config.discord_webhook.is_none() && self.config.repo.is_none() {
+            return Ok(());
+        }
+
+        // The secret itself is never passed on.
+        let (kind, alert, summary) = match &event.specific {
+            WebhookEventPayload::DependabotAlert(payload)
+                if matches!(
+                    payload.action,
+                    DependabotAlertWebhookEventAction::Created
+                        | DependabotAlertWebhookEventAction::Reintroduced
+                        | DependabotAlertWebhookEventAction::Reopened
+                ) =>
+            {
+                let advisory = &payload.alert["security_advisory"];
+                (
+                    "Dependabot alert",
+                    &payload.alert,
+                    format!(
+                        "{} ({})",
+                        advisory["summary"].as_str().unwrap_or_default(),
+                        payload.alert["dependency"]["package"]["name"]
+                            .as_str()
+                            .unwrap_or_default()
+                    ),
+                )
+            }
+            WebhookEventPayload::SecretScanningAlert(payload)
+                if matches!(
+                    payload.action,
+                    SecretScanningAlertWebhookEventAction::Created
+                        | SecretScanningAlertWebhookEventAction::Reopened
+                ) =>
+            {
+                (
+                    "Leaked secret",
+                    &payload.alert,
+                    payload.alert["secret_type_display_name"]
+                        .as_str()
+                        .or(payload.alert["secret_type"].as_str())
+                        .unwrap_or_default()
+                        .to_string(),
+                )
+            }
+            WebhookEventPayload::RepositoryAdvisory(payload)
+                if payload.action == RepositoryAdvisoryWebhookEventAction::Reported =>
+            {
+                (
+                    "Reported vulnerability",
+                    &payload.repository_advisory,
+                    payload.repository_advisory["summary"]
+                        .as_str()
+                        .unwrap_or_default()
+                        .to_string(),
+                )
+            }
+            _ => return Ok(()),
+        };
+        let severity = alert["security_advisory"]["severity"]
+            .as_str()
+            .or(alert["severity"].as_str());
+
+        let full_name = repo.full_name.as_deref().unwrap_or_default();
+        let message = ctx.rules.templates.render(
+            full_name,
+            "security_alert",
+            json!({
+                "kind": kind,
+                "repo": full_name,
+                "summary": summary,
+                "severity": severity,
+                "url": alert["html_url"].as_str().unwrap_or_default(),
+            }),
+        )?;
+        info!("{} in {}: {}", kind, full_name, summary);
+
+        let title = format!("{} in {}: {}", kind, full_name, summary);
+        notify(ctx, &self.config, &self.http, &title, &message).await
+    }
+}
+
+/// Sends the message to the private channel and/or opens a confidential issue with it.
+async fn notify(
+    ctx: &Context,
+    config: &SecurityAlertsConfig,
+    http: &reqwest::Client,
+    title: &str,
+    message: &str,
+) -> Result<(), Error> {
+    if let Some((owner, name)) = config.repo.as_deref().and_then(|x| x.split_once('/')) {
+        let issues = ctx.client.issues(owner, name);
+        github::mutate(
+            ctx.state.dry_run,
+            format!("open issue in {}/{}: {}", owner, name, title),
+            || issues.create(title).body(message).send(),
+        )
+        .await?;
+    }
+    if let Some(url) = &config.discord_webhook {
+        discord::post(http, ctx.state.dry_run, url, message).await?;
+    }
+    Ok(())
+}
+
+/// Moves a vulnerability reported in a public issue to the private channel, for
+/// `!ddnetbot security`: points the author at private vulnerability reporting, hides comments
+/// as they may have details, labels it `security` and tells the maintainers privately. When a
+/// maintainer ran it every other comment is hidden and the issue is locked until it's assessed
+/// if configured, when the reporter did only their own comments are.
+pub async fn escalate_report(
+    ctx: &Context,
+    config: &SecurityAlertsConfig,
+    http: &reqwest::Client,
+    repo: &Repository,
+    issue: &Issue,
+    command_comment: u64,
+    privileged: bool,
+) -> Result<(), Error> {
+    let full_name = repo.full_name.as_deref().unwrap_or_default();
+    let issues = ctx.client.issues_by_id(repo.id);
+    let lock = privileged && config.lock_escalated;
+    let comment = ctx.rules.templates.render(
+        full_name,
+        "security_escalated",
+        json!({
+            "author": issue.user.login,
+            "url": format!("https://github.com/{}/security/advisories/new", full_name),
+            "locked": lock,
+        }),
+    )?;
+    batch::comment(&ctx.state, &ctx.client, repo.id, issue.number, &comment).await?;
+
+    let page = github::retry(|| issues.list_comments(issue.number).per_page(100).send()).await?;
+    for comment in bulk::pages(&ctx.client, page).await? {
+        if *comment.id == command_comment || comment.user.r#type == "Bot" {
+            continue;
+        }
+        if !privileged && comment.user.id != issue.user.id {
+            continue;
+        }
+        github::minimize_comment(ctx.state.dry_run, &ctx.client, &comment.node_id, "OUTDATED")
+            .await?;
+    }
+
+    let labels = [SECURITY_LABEL.to_string()];
+    batch::add_labels(&ctx.state, &ctx.client, repo.id, issue.number, &labels).await?;
+
+    let message = ctx.rules.templates.render(
+        full_name,
+        "security_alert",
+        json!({
+            "kind": "Public security report",
+            "repo": full_name,
+            "summary": issue.title,
+            "severity": None::<&str>,
+            "url": issue.html_url.as_str(),
+        }),
+    )?;
+    let title = format!("Public security report in {}: {}", full_name, issue.title);
+    notify(ctx, config, http, &title, &message).await?;
+
+    if lock {
+        github::mutate(ctx.state.dry_run, format!("lock #{}", issue.number), || {
+            issues.lock(issue.number, None)
+        })
+        .await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,132 @@
+use async_trait::async_trait;
+use octocrab::models::webhook_events::{
+    WebhookEvent, WebhookEventPayload, WebhookEventType, payload::PullRequestWebhookEventAction,
+};
+use serde_json::json;
+
+use super::{Context, Error, EventHandler};
+use crate::{
+    batch,
+    config::{Config, SettingsConfig},
+    github,
+};
+
+pub const NEEDS_DOCS_LABEL: &str = "needs-docs";
+
+/// Labels PRs that add or change console settings and reminds their author once to update the
+/// settings documentation.
+#[derive(Debug)]
+pub struct Settings {
+    config: SettingsConfig,
+}
+
+impl Settings {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            config: config.settings.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for Settings {
+    fn name(&self) -> &'static str {
+        "settings"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::PullRequest]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let (WebhookEventPayload::PullRequest(payload), Some(repo)) =
+            (&event.specific, &event.repository)
+        else {
+            return Ok(());
+        };
+        if self.config.patterns.is_empty()
+            || !matches!(
+                payload.action,
+                PullRequestWebhookEventAction::Opened
+                    | PullRequestWebhookEventAction::Reopened
+                    | PullRequestWebhookEventAction::Synchronize
+            )
+        {
+            return Ok(());
+        }
+
+        let owner = repo.owner.as_ref().ok_or("repository without owner")?;
+        let pulls = ctx.client.pulls(&owner.login, &repo.name);
+        let files = github::retry(|| pulls.list_files(payload.number)).await?;
+        let mut settings: Vec<String> = files
+            .items
+            .iter()
+            .filter_map(|x| x.patch.as_deref())
+            .flat_map(|patch| changed_settings(patch, &self.config.patterns))
+            .collect();
+        settings.sort();
+        settings.dedup();
+        if settings.is_empty() {
+            return Ok(());
+        }
+
+        let labeled = payload
+            .pull_request
+            .labels
+            .iter()
+            .flatten()
+            .any(|x| x.name == NEEDS_DOCS_LABEL);
+        if !labeled {
+            let labels = [NEEDS_DOCS_LABEL.to_string()];
+            batch::add_labels(&ctx.state, &ctx.client, repo.id, payload.number, &labels).await?;
+        }
+        let key = format!("settings_reminded:{}:{}", repo.id, payload.number);
+        if ctx.state.db.get_kv(&key)?.is_some() {
+            return Ok(());
+        }
+        let comment = ctx.rules.templates.render(
+            repo.full_name.as_deref().unwrap_or_default(),
+            "settings_docs",
+            json!({
+                "author": payload.pull_request.user.as_ref().map(|x| x.login.as_str()).unwrap_or_default(),
+                "settings": settings,
+                "docs": self.config.docs,
+            }),
+        )?;
+        batch::comment(&ctx.state, &ctx.client, repo.id, payload.number, &comment).await?;
+        ctx.state.db.set_kv(&key, "1")?;
+        Ok(())
+    }
+}
+
+/// The settings added or changed by the patch, by their console name if the line has one, e.g.
+/// `cl_showhud` from `MACRO_CONFIG_INT(ClShowhud, cl_showhud, 1, ...)`.
+fn changed_settings(patch: &str, patterns: &[String]) -> Vec<String> {
+    patch
+        .lines()
+        .filter_map(|x| x.strip_prefix('+'))
+        .filter(|x| !x.starts_with("++"))
+        .filter_map(|line| {
+            let (_, args) = patterns
+                .iter()
+                .find_map(|pattern| line.split_once(pattern.as_str()))?;
+            let mut args = args.split(',').map(str::trim);
+            let name = args.nth(1).filter(|x| !x.is_empty()).unwrap_or(line.trim());
+            Some(name.trim_matches('"').to_string())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_are_found_in_patches() {
+        let patterns = ["MACRO_CONFIG_INT(".to_string()];
+        let patch = "@@ -1,2 +1,3 @@\n MACRO_CONFIG_INT(ClOld, cl_old, 0, 0, 1, CFGFLAG_CLIENT, \"\")\n\
+            +MACRO_CONFIG_INT(ClNew, cl_new, 0, 0, 1, CFGFLAG_CLIENT, \"New\")\n\
+            -MACRO_CONFIG_INT(ClGone, cl_gone, 0, 0, 1, CFGFLAG_CLIENT, \"\")";
+        assert_eq!(changed_settings(patch, &patterns), ["cl_new"]);
+    }
+}
@@ -0,0 +1,347 @@
+use async_trait::async_trait;
+use octocrab::models::webhook_events::{
+    EventInstallation, InstallationEventRepository, WebhookEvent, WebhookEventPayload,
+    WebhookEventType,
+    payload::{
+        InstallationRepositoriesWebhookEventAction, InstallationWebhookEventAction,
+        RepositoryWebhookEventAction,
+    },
+};
+use serde_json::json;
+use tracing::info;
+
+use super::{
+    Context, Error, EventHandler, FEATURES, adoptions::ADOPTABLE_LABEL, api::BREAKING_CHANGE_LABEL,
+    crashes::POSSIBLE_DUPLICATE_LABEL, demos::NEEDS_DEMO_LABEL, drafts::WIP_LABEL,
+    faq::QUESTION_LABEL, flaky::FLAKY_LABEL, issue_links::NEEDS_ISSUE_LINK_LABEL,
+    outdated::OUTDATED_VERSION_LABEL, pr_dependencies::BLOCKED_LABEL,
+    protocol::BREAKING_PROTOCOL_LABEL, reverts::REGRESSION_LABEL,
+    screenshots::NEEDS_SCREENSHOT_LABEL, security::SECURITY_LABEL, settings::NEEDS_DOCS_LABEL,
+    tracking::TRACKING_LABEL,
+};
+use crate::{
+    aging::{UNTRIAGED_7D_LABEL, UNTRIAGED_30D_LABEL},
+    config::LabelDefinition,
+    conflicts::NEEDS_REBASE_LABEL,
+    feed::TRIAGE_LABEL,
+    freeze::FROZEN_LABEL,
+    github, labels,
+    milestones::BLOCKER_LABEL,
+    workflow::{READY_TO_MERGE_LABEL, WAITING_FOR_REVIEWS_LABEL, WAITING_ON_AUTHOR_LABEL},
+};
+
+/// Labels every repo needs for the commands and triage, with their color and description.
+const DEFAULT_LABELS: &[(&str, &str, &str)] = &[
+    (
+        TRIAGE_LABEL,
+        "d4c5f9",
+        "Needs to be looked at by a maintainer",
+    ),
+    (WAITING_FOR_REVIEWS_LABEL, "0e8a16", "Ready for review"),
+    (
+        WAITING_ON_AUTHOR_LABEL,
+        "fbca04",
+        "Waiting for changes by the author",
+    ),
+    (
+        READY_TO_MERGE_LABEL,
+        "2cbe4e",
+        "Reviewed and ready to merge",
+    ),
+    (
+        WIP_LABEL,
+        "c5def5",
+        "Work in progress, not ready for review",
+    ),
+    (
+        NEEDS_REBASE_LABEL,
+        "e99695",
+        "Conflicts with the base branch",
+    ),
+    (QUESTION_LABEL, "d876e3", "Further information is requested"),
+    (
+        OUTDATED_VERSION_LABEL,
+        "ededed",
+        "Reported on a release that isn't supported anymore",
+    ),
+    (SECURITY_LABEL, "b60205", "Vulnerability, handled privately"),
+    (FROZEN_LABEL, "bfdadc", "Targets a frozen branch"),
+    (BLOCKED_LABEL, "b60205", "Waits for the PRs it depends on"),
+    (
+        BLOCKER_LABEL,
+        "b60205",
+        "Has to be fixed before the release",
+    ),
+    (NEEDS_DEMO_LABEL, "fef2c0", "Needs a demo to reproduce"),
+    (
+        POSSIBLE_DUPLICATE_LABEL,
+        "cfd3d7",
+        "Has the same backtrace as an earlier report",
+    ),
+    (
+        BREAKING_PROTOCOL_LABEL,
+        "b60205",
+        "Changes the network protocol",
+    ),
+    (NEEDS_DOCS_LABEL, "0075ca", "Needs a documentation update"),
+    (
+        BREAKING_CHANGE_LABEL,
+        "b60205",
+        "Changes the public API of the engine",
+    ),
+    (
+        NEEDS_ISSUE_LINK_LABEL,
+        "fef2c0",
+        "Needs a link to the issue it fixes",
+    ),
+    (
+        NEEDS_SCREENSHOT_LABEL,
+        "fef2c0",
+        "Needs before/after screenshots",
+    ),
+    (FLAKY_LABEL, "e4e669", "Fails and passes on the same commit"),
+    (
+        ADOPTABLE_LABEL,
+        "c2e0c6",
+        "Taken over by someone else, the author is inactive",
+    ),
+    (
+        REGRESSION_LABEL,
+        "b60205",
+        "Came back after the fix was reverted",
+    ),
+    (
+        TRACKING_LABEL,
+        "5319e7",
+        "Tracks other issues and PRs in a task list",
+    ),
+    (
+        crate::reviews::OVERDUE_LABEL,
+        "b60205",
+        "A requested review is overdue",
+    ),
+    (
+        UNTRIAGED_7D_LABEL,
+        "f9d0c4",
+        "Waiting for triage for a week",
+    ),
+    (
+        UNTRIAGED_30D_LABEL,
+        "e99695",
+        "Waiting for triage for a month",
+    ),
+];
+
+/// Tracks the repos the app is installed on and sets up new ones: creates their labels and,
+/// the first time, opens an issue explaining the commands and the per-repo config. Repos that
+/// are already installed are tracked by [`crate::repos::sync_loop`].
+#[derive(Debug)]
+pub struct Setup;
+
+#[async_trait]
+impl EventHandler for Setup {
+    fn name(&self) -> &'static str {
+        "setup"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[
+            WebhookEventType::Installation,
+            WebhookEventType::InstallationRepositories,
+            WebhookEventType::Repository,
+        ]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        if let WebhookEventPayload::Repository(payload) = &event.specific {
+            let (
+                RepositoryWebhookEventAction::Renamed | RepositoryWebhookEventAction::Transferred,
+                Some(repo),
+            ) = (&payload.action, &event.repository)
+            else {
+                return Ok(());
+            };
+            let full_name = repo.full_name.as_deref().unwrap_or_default();
+            info!("Repo {} renamed or transferred", full_name);
+            ctx.state
+                .db
+                .add_repo(ctx.installation, repo.id, full_name)?;
+            return Ok(());
+        }
+
+        let Some(EventInstallation::Full(installation)) = &event.installation else {
+            return Ok(());
+        };
+        let id = installation.id;
+        let db = &ctx.state.db;
+
+        let (added, removed) = match &event.specific {
+            WebhookEventPayload::Installation(payload) => match payload.action {
+                InstallationWebhookEventAction::Created => {
+                    (payload.repositories.clone().unwrap_or_default(), Vec::new())
+                }
+                InstallationWebhookEventAction::Deleted => {
+                    info!("Uninstalled from {}", installation.account.login);
+                    db.remove_installation(id)?;
+                    ctx.state.installations.forget(id);
+                    return Ok(());
+                }
+                _ => return Ok(()),
+            },
+            WebhookEventPayload::InstallationRepositories(payload) => match payload.action {
+                InstallationRepositoriesWebhookEventAction::Added => {
+                    (payload.repositories_added.clone(), Vec::new())
+                }
+                InstallationRepositoriesWebhookEventAction::Removed => {
+                    (Vec::new(), payload.repositories_removed.clone())
+                }
+                _ => return Ok(()),
+            },
+            _ => return Ok(()),
+        };
+
+        for repo in removed {
+            info!("Removed from {}", repo.full_name);
+            db.remove_repo(repo.id)?;
+        }
+        for repo in added {
+            info!("Installed on {}", repo.full_name);
+            db.add_repo(id, repo.id, &repo.full_name)?;
+            if ctx.rules.handlers.allows(Some(&repo.full_name)) {
+                bootstrap(ctx, &repo).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The [`DEFAULT_LABELS`] with the labels defined in the config, which win over the defaults.
+pub fn label_definitions(configured: &[LabelDefinition]) -> Vec<LabelDefinition> {
+    let mut definitions: Vec<LabelDefinition> = DEFAULT_LABELS
+        .iter()
+        .filter(|(name, ..)| !configured.iter().any(|x| x.name.eq_ignore_ascii_case(name)))
+        .map(|(name, color, description)| LabelDefinition {
+            name: name.to_string(),
+            color: color.to_string(),
+            description: description.to_string(),
+        })
+        .collect();
+    definitions.extend(configured.iter().cloned());
+    definitions
+}
+
+/// Creates the labels the repo doesn't have yet, see [`labels::sync`], and opens the setup
+/// issue if the bot wasn't set up on the repo before.
+async fn bootstrap(ctx: &Context, repo: &InstallationEventRepository) -> Result<(), Error> {
+    let definitions = label_definitions(&ctx.state.labels);
+    let report = labels::sync(&ctx.state, &ctx.client, repo.id, &definitions).await?;
+    info!(
+        "Labels of {}: created {:?}, updated {:?}",
+        repo.full_name, report.created, report.updated
+    );
+
+    let db = &ctx.state.db;
+    if db.is_onboarded(repo.id)? {
+        return Ok(());
+    }
+    let mut setup_issue = None;
+    if ctx.state.setup_issue {
+        let features: Vec<&str> = FEATURES.iter().map(|(name, _)| *name).collect();
+        let body = ctx.rules.templates.render(
+            &repo.full_name,
+            "setup_issue",
+            json!({
+                "repo": repo.full_name,
+                "features": features,
+                "labels": definitions.len(),
+            }),
+        )?;
+        let issues = ctx.client.issues_by_id(repo.id);
+        let issue = github::create_issue(
+            ctx.state.dry_run,
+            &issues,
+            "ddbot setup",
+            &body,
+            Vec::new(),
+            Vec::new(),
+        )
+        .await?;
+        setup_issue = issue.map(|x| x.number);
+    }
+    if !ctx.state.dry_run {
+        db.add_onboarded_repo(repo.id, &repo.full_name, setup_issue)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use octocrab::models::{InstallationId, RepositoryId};
+
+    use super::*;
+    use crate::db::Db;
+
+    #[test]
+    fn configured_labels_win_over_the_defaults() {
+        let configured = [LabelDefinition {
+            name: "Triage-Needed".to_string(),
+            color: "000000".to_string(),
+            description: String::new(),
+        }];
+        let definitions = label_definitions(&configured);
+        assert_eq!(definitions.len(), DEFAULT_LABELS.len());
+        let triage: Vec<_> = definitions
+            .iter()
+            .filter(|x| x.name.eq_ignore_ascii_case(TRIAGE_LABEL))
+            .collect();
+        assert_eq!(triage.len(), 1);
+        assert_eq!(triage[0].color, "000000");
+    }
+
+    #[test]
+    fn synced_repos_replace_the_tracked_ones() {
+        let db = Db::open(":memory:").unwrap();
+        db.add_repo(InstallationId(1), RepositoryId(10), "ddnet/ddnet")
+            .unwrap();
+        db.set_repos(&[
+            (
+                InstallationId(1),
+                RepositoryId(10),
+                "ddnet/ddnet-renamed".to_string(),
+            ),
+            (
+                InstallationId(2),
+                RepositoryId(20),
+                "other/ddnet".to_string(),
+            ),
+        ])
+        .unwrap();
+        assert_eq!(db.repo_by_name("ddnet/ddnet").unwrap(), None);
+        assert_eq!(
+            db.repo_by_name("ddnet/ddnet-renamed").unwrap(),
+            Some((RepositoryId(10), InstallationId(1)))
+        );
+        assert!(db.repo_by_name("other/ddnet").unwrap().is_some());
+    }
+
+    #[test]
+    fn installed_repos_are_tracked() {
+        let db = Db::open(":memory:").unwrap();
+        db.add_repo(InstallationId(1), RepositoryId(10), "ddnet/ddnet")
+            .unwrap();
+        db.add_repo(InstallationId(1), RepositoryId(11), "ddnet/ddnet-web")
+            .unwrap();
+        db.add_repo(InstallationId(2), RepositoryId(20), "other/ddnet")
+            .unwrap();
+        assert_eq!(
+            db.repo_by_name("DDNet/ddnet").unwrap(),
+            Some((RepositoryId(10), InstallationId(1)))
+        );
+
+        db.remove_repo(RepositoryId(11)).unwrap();
+        assert_eq!(db.repo_by_name("ddnet/ddnet-web").unwrap(), None);
+        db.remove_installation(InstallationId(1)).unwrap();
+        assert_eq!(db.repo_by_name("ddnet/ddnet").unwrap(), None);
+        assert!(db.repo_by_name("other/ddnet").unwrap().is_some());
+    }
+}
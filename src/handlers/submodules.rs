@@ -0,0 +1,86 @@
+use async_trait::async_trait;
+use octocrab::models::webhook_events::{
+    WebhookEvent, WebhookEventPayload, WebhookEventType, payload::PullRequestWebhookEventAction,
+};
+use serde_json::json;
+
+use super::{Context, Error, EventHandler};
+use crate::{batch, github};
+
+/// Asks whether submodule bumps are intended when the PR description doesn't mention them,
+/// since they easily slip into a commit by accident.
+#[derive(Debug)]
+pub struct Submodules;
+
+#[async_trait]
+impl EventHandler for Submodules {
+    fn name(&self) -> &'static str {
+        "submodules"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::PullRequest]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let (WebhookEventPayload::PullRequest(payload), Some(repo)) =
+            (&event.specific, &event.repository)
+        else {
+            return Ok(());
+        };
+        if !matches!(
+            payload.action,
+            PullRequestWebhookEventAction::Opened | PullRequestWebhookEventAction::Synchronize
+        ) {
+            return Ok(());
+        }
+
+        let owner = repo.owner.as_ref().ok_or("repository without owner")?;
+        let pulls = ctx.client.pulls(&owner.login, &repo.name);
+        let files = github::retry(|| pulls.list_files(payload.number)).await?;
+        let body = payload
+            .pull_request
+            .body
+            .as_deref()
+            .unwrap_or_default()
+            .to_lowercase();
+        let bumped: Vec<&str> = files
+            .items
+            .iter()
+            .filter(|x| {
+                x.patch
+                    .as_deref()
+                    .is_some_and(|patch| patch.contains("Subproject commit"))
+            })
+            .map(|x| x.filename.as_str())
+            .filter(|path| !mentioned(&body, path))
+            .collect();
+        if bumped.is_empty() {
+            return Ok(());
+        }
+
+        // Warn once per PR, also when later pushes bump more submodules.
+        let key = format!("submodules_warned:{}:{}", repo.id, payload.number);
+        if ctx.state.db.get_kv(&key)?.is_some() {
+            return Ok(());
+        }
+        let comment = ctx.rules.templates.render(
+            repo.full_name.as_deref().unwrap_or_default(),
+            "submodule_bump",
+            json!({
+                "author": payload.pull_request.user.as_ref().map(|x| x.login.as_str()).unwrap_or_default(),
+                "submodules": bumped,
+            }),
+        )?;
+        batch::comment(&ctx.state, &ctx.client, repo.id, payload.number, &comment).await?;
+        ctx.state.db.set_kv(&key, "1")?;
+        Ok(())
+    }
+}
+
+/// Whether the lowercase description mentions the submodule by path or by name.
+fn mentioned(body: &str, path: &str) -> bool {
+    let path = path.to_lowercase();
+    let name = path.rsplit('/').next().unwrap();
+    body.contains(&path) || body.contains(name)
+}
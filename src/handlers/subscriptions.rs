@@ -0,0 +1,203 @@
+use async_trait::async_trait;
+use octocrab::models::webhook_events::{
+    WebhookEvent, WebhookEventPayload, WebhookEventType,
+    payload::{IssuesWebhookEventAction, PullRequestWebhookEventAction},
+};
+use serde_json::json;
+
+use super::{Context, Error, EventHandler};
+use crate::{batch, db::Subscription, github, subscriptions};
+
+/// What someone subscribes to with `!ddnetbot subscribe`: issues and PRs with a label, or PRs
+/// changing files under a path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Area {
+    Label(String),
+    Path(String),
+}
+
+impl Area {
+    /// `label:network`, `path:src/engine/` or a bare label, or path if it has a `/`.
+    pub fn parse(area: &str) -> Self {
+        match area.split_once(':') {
+            Some(("label", x)) => Area::Label(x.to_string()),
+            Some(("path", x)) => Area::Path(x.to_string()),
+            _ if area.contains('/') => Area::Path(area.to_string()),
+            _ => Area::Label(area.to_string()),
+        }
+    }
+
+    /// The kind and value it's stored as, see [`crate::db::Db::add_subscription`].
+    pub fn parts(&self) -> (&'static str, &str) {
+        match self {
+            Area::Label(x) => ("label", x),
+            Area::Path(x) => ("path", x),
+        }
+    }
+
+    fn from_subscription(subscription: &Subscription) -> Self {
+        match subscription.kind.as_str() {
+            "path" => Area::Path(subscription.value.clone()),
+            _ => Area::Label(subscription.value.clone()),
+        }
+    }
+
+    fn matches(&self, labels: &[&str], paths: &[&str]) -> bool {
+        match self {
+            Area::Label(x) => labels.iter().any(|label| label.eq_ignore_ascii_case(x)),
+            Area::Path(x) => paths.iter().any(|path| path.starts_with(x.as_str())),
+        }
+    }
+}
+
+impl std::fmt::Display for Area {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Area::Label(x) => write!(f, "label:{}", x),
+            Area::Path(x) => write!(f, "path:{}", x),
+        }
+    }
+}
+
+/// Cc's the people subscribed to an area on the issues and PRs in it, a lighter alternative to
+/// CODEOWNERS for volunteers. Labels are matched when the issue or PR is opened and when
+/// they're added, paths when a PR is opened. Everyone is cc'd once per issue or PR, or gets it
+/// in their digest if they asked for one, see [`crate::subscriptions::digest_loop`].
+#[derive(Debug)]
+pub struct Subscriptions;
+
+#[async_trait]
+impl EventHandler for Subscriptions {
+    fn name(&self) -> &'static str {
+        "subscriptions"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::Issues, WebhookEventType::PullRequest]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let Some(repo) = &event.repository else {
+            return Ok(());
+        };
+        let full_name = repo.full_name.as_deref().unwrap_or_default();
+        let (number, title, author, labels, opened_pr) = match &event.specific {
+            WebhookEventPayload::Issues(payload)
+                if matches!(
+                    payload.action,
+                    IssuesWebhookEventAction::Opened | IssuesWebhookEventAction::Labeled
+                ) =>
+            {
+                let labels: Vec<&str> = payload
+                    .issue
+                    .labels
+                    .iter()
+                    .map(|x| x.name.as_str())
+                    .collect();
+                (
+                    payload.issue.number,
+                    payload.issue.title.as_str(),
+                    &payload.issue.user.login,
+                    labels,
+                    false,
+                )
+            }
+            WebhookEventPayload::PullRequest(payload)
+                if matches!(
+                    payload.action,
+                    PullRequestWebhookEventAction::Opened | PullRequestWebhookEventAction::Labeled
+                ) =>
+            {
+                let Some(author) = &payload.pull_request.user else {
+                    return Ok(());
+                };
+                let labels: Vec<&str> = payload
+                    .pull_request
+                    .labels
+                    .iter()
+                    .flatten()
+                    .map(|x| x.name.as_str())
+                    .collect();
+                let opened = payload.action == PullRequestWebhookEventAction::Opened;
+                let title = payload.pull_request.title.as_deref().unwrap_or_default();
+                (payload.number, title, &author.login, labels, opened)
+            }
+            _ => return Ok(()),
+        };
+
+        let subscriptions = ctx.state.db.subscriptions(full_name, None)?;
+        if subscriptions.is_empty() {
+            return Ok(());
+        }
+        let files;
+        let mut paths = Vec::new();
+        if opened_pr && subscriptions.iter().any(|x| x.kind == "path") {
+            let owner = repo.owner.as_ref().ok_or("repository without owner")?;
+            let pulls = ctx.client.pulls(&owner.login, &repo.name);
+            files = github::retry(|| pulls.list_files(number)).await?;
+            paths = files.items.iter().map(|x| x.filename.as_str()).collect();
+        }
+
+        let key = format!("subscriptions_cc:{}:{}", repo.id, number);
+        let cced = ctx.state.db.get_kv(&key)?.unwrap_or_default();
+        let mut users: Vec<&str> = Vec::new();
+        for subscription in &subscriptions {
+            let login = subscription.login.as_str();
+            if login.eq_ignore_ascii_case(author)
+                || users.contains(&login)
+                || cced.lines().any(|x| x == login)
+                || !Area::from_subscription(subscription).matches(&labels, &paths)
+            {
+                continue;
+            }
+            users.push(login);
+        }
+        if users.is_empty() {
+            return Ok(());
+        }
+
+        // Those who asked for a digest get it there instead, if it can reach them.
+        let mut cc = Vec::new();
+        for login in &users {
+            if ctx.state.db.subscription_digest(full_name, login)?
+                && subscriptions::digest_reachable(&ctx.state, login)
+            {
+                ctx.state
+                    .db
+                    .add_digest_item(full_name, login, number, title)?;
+            } else {
+                cc.push(*login);
+            }
+        }
+        if !cc.is_empty() {
+            let comment =
+                ctx.rules
+                    .templates
+                    .render(full_name, "subscribers_cc", json!({ "users": cc }))?;
+            batch::comment(&ctx.state, &ctx.client, repo.id, number, &comment).await?;
+        }
+        let cced: Vec<&str> = cced.lines().chain(users).collect();
+        ctx.state.db.set_kv(&key, &cced.join("\n"))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn areas_match_labels_and_paths() {
+        assert_eq!(Area::parse("network"), Area::Label("network".to_string()));
+        assert_eq!(
+            Area::parse("src/engine/"),
+            Area::Path("src/engine/".to_string())
+        );
+        assert_eq!(Area::parse("label:a/b"), Area::Label("a/b".to_string()));
+        let labels = ["Network", "bug"];
+        let paths = ["src/engine/shared/network.cpp"];
+        assert!(Area::parse("network").matches(&labels, &[]));
+        assert!(Area::parse("path:src/engine").matches(&[], &paths));
+        assert!(!Area::parse("src/game/").matches(&labels, &paths));
+    }
+}
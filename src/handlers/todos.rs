@@ -0,0 +1,220 @@
+use async_trait::async_trait;
+use octocrab::models::{
+    self,
+    webhook_events::{
+        WebhookEvent, WebhookEventPayload, WebhookEventType, payload::PullRequestWebhookEventAction,
+    },
+};
+use serde_json::json;
+
+use super::{Context, Error, EventHandler};
+use crate::{
+    batch,
+    config::{Config, TodosConfig},
+    github,
+};
+
+/// A TODO or FIXME comment a PR adds.
+#[derive(Debug, PartialEq)]
+struct Todo {
+    path: String,
+    line: u64,
+    text: String,
+}
+
+/// Lists the TODO and FIXME comments PRs add and opens issues for them, on
+/// `!ddnetbot todo-issues` or when the PR is merged, so they don't get lost in the code.
+#[derive(Debug)]
+pub struct Todos {
+    config: TodosConfig,
+}
+
+impl Todos {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            config: config.todos.clone(),
+        }
+    }
+
+    /// Opens an issue for each TODO the PR adds, linking the code at `sha`. Issues are only
+    /// opened once per PR, returns how many were.
+    pub async fn open_issues(
+        ctx: &Context,
+        repo: &models::Repository,
+        number: u64,
+        sha: &str,
+    ) -> Result<usize, Error> {
+        let key = format!("todo_issues:{}:{}", repo.id, number);
+        if ctx.state.db.get_kv(&key)?.is_some() {
+            return Ok(0);
+        }
+        let full_name = repo.full_name.as_deref().unwrap_or_default();
+        let markers = &ctx.state.todo_markers;
+        let todos = added_todos(ctx, repo, number, markers).await?;
+
+        let owner = repo.owner.as_ref().ok_or("repository without owner")?;
+        let issues = ctx.client.issues(&owner.login, &repo.name);
+        for todo in &todos {
+            let title = format!("{}: {}", todo.path, todo.text);
+            let body = ctx.rules.templates.render(
+                full_name,
+                "todo_issue",
+                json!({
+                    "text": todo.text,
+                    "path": todo.path,
+                    "line": todo.line,
+                    "url": format!(
+                        "https://github.com/{}/blob/{}/{}#L{}",
+                        full_name, sha, todo.path, todo.line
+                    ),
+                    "number": number,
+                }),
+            )?;
+            github::create_issue(
+                ctx.state.dry_run,
+                &issues,
+                &title,
+                &body,
+                Vec::new(),
+                Vec::new(),
+            )
+            .await?;
+        }
+        ctx.state.db.set_kv(&key, &todos.len().to_string())?;
+        Ok(todos.len())
+    }
+}
+
+#[async_trait]
+impl EventHandler for Todos {
+    fn name(&self) -> &'static str {
+        "todos"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::PullRequest]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let (WebhookEventPayload::PullRequest(payload), Some(repo)) =
+            (&event.specific, &event.repository)
+        else {
+            return Ok(());
+        };
+        if self.config.markers.is_empty() {
+            return Ok(());
+        }
+        match payload.action {
+            PullRequestWebhookEventAction::Closed => {
+                let Some(sha) = &payload.pull_request.merge_commit_sha else {
+                    return Ok(());
+                };
+                if self.config.auto && payload.pull_request.merged_at.is_some() {
+                    Self::open_issues(ctx, repo, payload.number, sha).await?;
+                }
+                Ok(())
+            }
+            PullRequestWebhookEventAction::Opened | PullRequestWebhookEventAction::Synchronize => {
+                // List them once, later pushes don't repeat it.
+                let key = format!("todos_listed:{}:{}", repo.id, payload.number);
+                if ctx.state.db.get_kv(&key)?.is_some() {
+                    return Ok(());
+                }
+                let todos = added_todos(ctx, repo, payload.number, &self.config.markers).await?;
+                if todos.is_empty() {
+                    return Ok(());
+                }
+                let comment = ctx.rules.templates.render(
+                    repo.full_name.as_deref().unwrap_or_default(),
+                    "todos_found",
+                    json!({
+                        "author": payload.pull_request.user.as_ref().map(|x| x.login.as_str()).unwrap_or_default(),
+                        "todos": todos
+                            .iter()
+                            .map(|x| json!({ "path": x.path, "line": x.line, "text": x.text }))
+                            .collect::<Vec<_>>(),
+                        "auto": self.config.auto,
+                    }),
+                )?;
+                batch::comment(&ctx.state, &ctx.client, repo.id, payload.number, &comment).await?;
+                ctx.state.db.set_kv(&key, "1")?;
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+async fn added_todos(
+    ctx: &Context,
+    repo: &models::Repository,
+    number: u64,
+    markers: &[String],
+) -> Result<Vec<Todo>, Error> {
+    let owner = repo.owner.as_ref().ok_or("repository without owner")?;
+    let pulls = ctx.client.pulls(&owner.login, &repo.name);
+    let files = github::retry(|| pulls.list_files(number)).await?;
+    Ok(files
+        .items
+        .iter()
+        .filter_map(|x| Some((x.filename.as_str(), x.patch.as_deref()?)))
+        .flat_map(|(path, patch)| todos_in_patch(path, patch, markers))
+        .collect())
+}
+
+/// The added lines of the patch with one of the markers, with their line in the new file.
+fn todos_in_patch(path: &str, patch: &str, markers: &[String]) -> Vec<Todo> {
+    let mut todos = Vec::new();
+    let mut line = 0;
+    for text in patch.lines() {
+        if let Some(hunk) = text.strip_prefix("@@ ") {
+            // `@@ -old,len +new,len @@`, the next line is `new`.
+            line = hunk
+                .split_ascii_whitespace()
+                .find_map(|x| x.strip_prefix('+'))
+                .and_then(|x| x.split(',').next()?.parse().ok())
+                .unwrap_or(0);
+            continue;
+        }
+        if text.starts_with('-') {
+            continue;
+        }
+        if let Some(added) = text.strip_prefix('+')
+            && let Some(start) = markers.iter().filter_map(|x| added.find(x.as_str())).min()
+        {
+            todos.push(Todo {
+                path: path.to_string(),
+                line,
+                text: added[start..].trim().to_string(),
+            });
+        }
+        line += 1;
+    }
+    todos
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn todos_are_found_with_their_line() {
+        let markers = ["TODO".to_string(), "FIXME".to_string()];
+        let patch = "@@ -10,3 +10,4 @@ void f()\n {\n-\tint x;\n+\t// TODO: use a cache\n+\tint y;\n \treturn;\n+\t// FIXME leaks\n";
+        assert_eq!(
+            todos_in_patch("src/a.cpp", patch, &markers),
+            [
+                Todo {
+                    path: "src/a.cpp".to_string(),
+                    line: 11,
+                    text: "TODO: use a cache".to_string(),
+                },
+                Todo {
+                    path: "src/a.cpp".to_string(),
+                    line: 14,
+                    text: "FIXME leaks".to_string(),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use octocrab::models::{
+    self,
+    webhook_events::{
+        WebhookEvent, WebhookEventPayload, WebhookEventType,
+        payload::{IssuesWebhookEventAction, PullRequestWebhookEventAction},
+    },
+};
+
+use super::{Context, Error, EventHandler};
+use crate::{github, tracking};
+
+pub const TRACKING_LABEL: &str = "tracking";
+
+/// Keeps the task lists of tracking issues in sync, checking the items whose issue was closed or
+/// PR merged, and unchecking them when they're reopened.
+#[derive(Debug)]
+pub struct Tracking;
+
+#[async_trait]
+impl EventHandler for Tracking {
+    fn name(&self) -> &'static str {
+        "tracking"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::Issues, WebhookEventType::PullRequest]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let Some(repo) = &event.repository else {
+            return Ok(());
+        };
+        let full_name = repo.full_name.as_deref().unwrap_or_default();
+
+        let (number, done) = match &event.specific {
+            WebhookEventPayload::Issues(payload) => {
+                let issue = &payload.issue;
+                let tracking = issue.labels.iter().any(|x| x.name == TRACKING_LABEL);
+                match payload.action {
+                    IssuesWebhookEventAction::Unlabeled
+                        if payload.label.as_ref().map(|x| x.name.as_str())
+                            == Some(TRACKING_LABEL) =>
+                    {
+                        ctx.state.db.set_tracked_items(repo.id, issue.number, &[])?;
+                        return Ok(());
+                    }
+                    IssuesWebhookEventAction::Opened
+                    | IssuesWebhookEventAction::Edited
+                    | IssuesWebhookEventAction::Labeled
+                        if tracking =>
+                    {
+                        return sync_tracking_issue(ctx, repo, issue).await;
+                    }
+                    IssuesWebhookEventAction::Closed => (issue.number, true),
+                    IssuesWebhookEventAction::Reopened => (issue.number, false),
+                    _ => return Ok(()),
+                }
+            }
+            WebhookEventPayload::PullRequest(payload) => match payload.action {
+                PullRequestWebhookEventAction::Closed
+                    if payload.pull_request.merged_at.is_some() =>
+                {
+                    (payload.number, true)
+                }
+                PullRequestWebhookEventAction::Reopened => (payload.number, false),
+                _ => return Ok(()),
+            },
+            _ => return Ok(()),
+        };
+
+        let item = format!("{}#{}", full_name, number).to_lowercase();
+        for (tracking_repo, tracking_number) in ctx.state.db.tracking_issues(&item)? {
+            let issues = ctx.client.issues_by_id(tracking_repo);
+            let issue = github::retry(|| issues.get(tracking_number)).await?;
+            let tracking_name = issue_repo(&issue);
+            let body = issue.body.as_deref().unwrap_or_default();
+            let Some(body) = update(body, &tracking_name, |x| (x == item).then_some(done)) else {
+                continue;
+            };
+            github::mutate(
+                ctx.state.dry_run,
+                format!("update the task list of #{}", tracking_number),
+                || issues.update(tracking_number).body(&body).send(),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+}
+
+/// Records the items of a tracking issue, and checks or unchecks them all by the state of
+/// their issue or PR.
+async fn sync_tracking_issue(
+    ctx: &Context,
+    repo: &models::Repository,
+    issue: &models::issues::Issue,
+) -> Result<(), Error> {
+    let full_name = repo.full_name.as_deref().unwrap_or_default();
+    let body = issue.body.as_deref().unwrap_or_default();
+    let items: Vec<String> = body
+        .lines()
+        .filter_map(|x| task(x, full_name))
+        .map(|(_, item)| item)
+        .collect();
+    ctx.state
+        .db
+        .set_tracked_items(repo.id, issue.number, &items)?;
+
+    let mut done = HashMap::new();
+    for item in &items {
+        // Items in repos the bot can't see are left as they are.
+        let Some(state) = tracking::item(&ctx.client, item).await else {
+            continue;
+        };
+        done.insert(item.clone(), state.done());
+    }
+
+    let Some(body) = update(body, full_name, |x| done.get(x).copied()) else {
+        return Ok(());
+    };
+    let issues = ctx.client.issues_by_id(repo.id);
+    github::mutate(
+        ctx.state.dry_run,
+        format!("update the task list of #{}", issue.number),
+        || issues.update(issue.number).body(&body).send(),
+    )
+    .await?;
+    Ok(())
+}
+
+/// `owner/name` of the repo of an issue, from its URL.
+fn issue_repo(issue: &models::issues::Issue) -> String {
+    issue
+        .repository_url
+        .path()
+        .trim_start_matches("/repos/")
+        .to_string()
+}
+
+/// Whether a line is a task list item, and the issue or PR it references as a lowercase
+/// `owner/name#number`. `#123` references are in `repo`.
+fn task(line: &str, repo: &str) -> Option<(bool, String)> {
+    let rest = line.trim_start().strip_prefix(['-', '*'])?.trim_start();
+    let (checked, rest) = if let Some(rest) = rest.strip_prefix("[ ]") {
+        (false, rest)
+    } else {
+        (true, rest.strip_prefix("[x]").or(rest.strip_prefix("[X]"))?)
+    };
+    let item = rest.split_whitespace().find_map(|word| {
+        let word = word.trim_end_matches(['.', ',', ')', ':']);
+        let (name, number) = if let Some(url) = word.strip_prefix("https://github.com/") {
+            let mut parts = url.splitn(4, '/');
+            let (owner, name, kind, number) =
+                (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+            if kind != "issues" && kind != "pull" {
+                return None;
+            }
+            (format!("{}/{}", owner, name), number)
+        } else {
+            let (name, number) = word.split_once('#')?;
+            let name = if name.is_empty() { repo } else { name };
+            (name.to_string(), number)
+        };
+        number.parse::<u64>().ok()?;
+        Some(format!("{}#{}", name, number).to_lowercase())
+    })?;
+    Some((checked, item))
+}
+
+/// The body with the task list items checked where `done` says so, None if nothing changed.
+fn update(body: &str, repo: &str, done: impl Fn(&str) -> Option<bool>) -> Option<String> {
+    let mut changed = false;
+    let body = body
+        .split_inclusive('\n')
+        .map(|line| {
+            let Some((checked, item)) = task(line, repo) else {
+                return line.to_string();
+            };
+            match done(&item) {
+                Some(true) if !checked => {
+                    changed = true;
+                    line.replacen("[ ]", "[x]", 1)
+                }
+                Some(false) if checked => {
+                    changed = true;
+                    line.replacen("[x]", "[ ]", 1).replacen("[X]", "[ ]", 1)
+                }
+                _ => line.to_string(),
+            }
+        })
+        .collect();
+    changed.then_some(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn task_lists_are_updated() {
+        let body = "Parts:\r\n- [ ] #12\r\n- [x] ddnet/ddnet-web#3 (website)\r\n\
+                    * [ ] https://github.com/ddnet/ddnet/pull/40.\r\n- [ ] no issue";
+        let done = |x: &str| match x {
+            "ddnet/ddnet#12" | "ddnet/ddnet#40" => Some(true),
+            "ddnet/ddnet-web#3" => Some(false),
+            _ => None,
+        };
+        assert_eq!(
+            update(body, "ddnet/ddnet", done).unwrap(),
+            "Parts:\r\n- [x] #12\r\n- [ ] ddnet/ddnet-web#3 (website)\r\n\
+             * [x] https://github.com/ddnet/ddnet/pull/40.\r\n- [ ] no issue"
+        );
+        assert_eq!(update(body, "ddnet/ddnet", |_| None), None);
+    }
+}
@@ -0,0 +1,62 @@
+use async_trait::async_trait;
+use octocrab::models::webhook_events::{
+    WebhookEvent, WebhookEventPayload, WebhookEventType, payload::IssuesWebhookEventAction,
+};
+
+use super::{Context, Error, EventHandler};
+use crate::{batch, digest::DIGEST_LABEL, feed::TRIAGE_LABEL};
+
+/// Marks new issues as needing triage and records who labels issues.
+#[derive(Debug)]
+pub struct Triager;
+
+#[async_trait]
+impl EventHandler for Triager {
+    fn name(&self) -> &'static str {
+        "triager"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::Issues]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let (WebhookEventPayload::Issues(payload), Some(repo)) =
+            (&event.specific, &event.repository)
+        else {
+            return Ok(());
+        };
+
+        match payload.action {
+            IssuesWebhookEventAction::Labeled => {
+                if let Some(sender) = &event.sender
+                    && sender.r#type != "Bot"
+                {
+                    ctx.state.db.audit(
+                        repo.full_name.as_deref().unwrap_or_default(),
+                        Some(payload.issue.number),
+                        &sender.login,
+                        "labeled",
+                        "",
+                    )?;
+                }
+            }
+            // The digest is the bot's own, there's nothing to triage.
+            IssuesWebhookEventAction::Opened
+                if !payload.issue.labels.iter().any(|x| x.name == DIGEST_LABEL) =>
+            {
+                let labels = [TRIAGE_LABEL.to_string()];
+                batch::add_labels(
+                    &ctx.state,
+                    &ctx.client,
+                    repo.id,
+                    payload.issue.number,
+                    &labels,
+                )
+                .await?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,92 @@
+use std::collections::HashSet;
+
+use async_trait::async_trait;
+use octocrab::models::webhook_events::{
+    WebhookEvent, WebhookEventPayload, WebhookEventType, payload::PullRequestWebhookEventAction,
+};
+use serde_json::json;
+
+use super::{Context, Error, EventHandler};
+use crate::{batch, workflow};
+
+/// Holds workflow labels people add by hand to the state machine: the label of another state
+/// comes off, and a label the PR can't move to is taken back with a reply why.
+///
+/// Labels added by bots are skipped, the bot moves PRs through [`workflow::apply`] itself.
+#[derive(Debug)]
+pub struct WorkflowLabels;
+
+#[async_trait]
+impl EventHandler for WorkflowLabels {
+    fn name(&self) -> &'static str {
+        "workflow-labels"
+    }
+
+    fn events(&self) -> &'static [WebhookEventType] {
+        &[WebhookEventType::PullRequest]
+    }
+
+    async fn handle(&self, ctx: &Context, event: &WebhookEvent) -> Result<(), Error> {
+        let (WebhookEventPayload::PullRequest(payload), Some(repo)) =
+            (&event.specific, &event.repository)
+        else {
+            return Ok(());
+        };
+        if payload.action != PullRequestWebhookEventAction::Labeled
+            || event.sender.as_ref().is_some_and(|x| x.r#type == "Bot")
+        {
+            return Ok(());
+        }
+        let Some(label) = &payload.label else {
+            return Ok(());
+        };
+        let Some(to) = workflow::State::from_label(&label.name) else {
+            return Ok(());
+        };
+
+        let current: HashSet<String> = payload
+            .pull_request
+            .labels
+            .iter()
+            .flatten()
+            .map(|x| x.name.clone())
+            .collect();
+        let mut before = current.clone();
+        before.remove(&label.name);
+        let mut after = before.clone();
+        match workflow::apply(&mut after, to.requested_by()) {
+            Ok(()) => {
+                workflow::sync(
+                    &ctx.state,
+                    &ctx.client,
+                    repo.id,
+                    payload.number,
+                    &current,
+                    &after,
+                )
+                .await
+            }
+            Err(reason) => {
+                batch::remove_label(
+                    &ctx.state,
+                    &ctx.client,
+                    repo.id,
+                    payload.number,
+                    &label.name,
+                )
+                .await?;
+                let comment = ctx.rules.templates.render(
+                    repo.full_name.as_deref().unwrap_or_default(),
+                    "workflow_rejected",
+                    json!({
+                        "user": event.sender.as_ref().map(|x| x.login.as_str()).unwrap_or_default(),
+                        "label": label.name,
+                        "reason": reason,
+                    }),
+                )?;
+                batch::comment(&ctx.state, &ctx.client, repo.id, payload.number, &comment).await?;
+                Ok(())
+            }
+        }
+    }
+}
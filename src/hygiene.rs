@@ -0,0 +1,293 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    time::Duration,
+};
+
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use octocrab::{Octocrab, models::Repository, params};
+use serde::Deserialize;
+use serde_json::json;
+use tracing::{info, warn};
+
+use crate::{
+    AppState, github,
+    handlers::Error,
+    labels,
+    workflow::{self, State as WorkflowState},
+};
+
+/// Label problems of a repo.
+#[derive(Debug, Default, serde::Serialize)]
+pub struct Hygiene {
+    /// Labels no issue or PR has.
+    pub unused: Vec<String>,
+    /// Pairs of labels that are likely meant to be the same.
+    pub duplicates: Vec<(String, String)>,
+    /// Open issues and PRs with more than one workflow state label, by number.
+    pub contradictions: BTreeMap<u64, Vec<String>>,
+}
+
+impl Hygiene {
+    pub fn is_empty(&self) -> bool {
+        self.unused.is_empty() && self.duplicates.is_empty() && self.contradictions.is_empty()
+    }
+}
+
+/// Periodically looks for label problems in every repo, comments them on `issue` if given and
+/// fixes the contradicting workflow labels with `autofix`, keeping the state holding the PR up
+/// most.
+pub async fn hygiene_loop(
+    state: AppState,
+    period: Duration,
+    issue: Option<(String, u64)>,
+    autofix: bool,
+) {
+    let mut interval = tokio::time::interval(period);
+
+    loop {
+        interval.tick().await;
+        let repos = match github::installed_repos(&state.installations).await {
+            Ok(x) => x,
+            Err(e) => {
+                warn!("Failed to list repos for the label hygiene: {}", e);
+                continue;
+            }
+        };
+        for (installation, client, repo) in repos {
+            let full_name = repo.full_name.clone().unwrap_or_default();
+            if !state
+                .rules()
+                .handlers
+                .feature_enabled("stale-bot", &full_name)
+            {
+                continue;
+            }
+            state
+                .rate_limits
+                .wait_for_budget(installation, "label hygiene")
+                .await;
+            let res = async {
+                let hygiene = check(&client, &repo).await?;
+                if autofix {
+                    fix(&state, &client, &repo, &hygiene).await?;
+                }
+                if let Some(issue) = &issue
+                    && !hygiene.is_empty()
+                {
+                    report(&state, issue, &full_name, &hygiene, autofix).await?;
+                }
+                Ok::<_, Error>(())
+            }
+            .await;
+            if let Err(e) = res {
+                warn!("Failed to check the labels of {}: {}", full_name, e);
+            }
+        }
+    }
+}
+
+/// Finds the label problems of the repo.
+pub async fn check(client: &Octocrab, repo: &Repository) -> Result<Hygiene, Error> {
+    let owner = repo.owner.as_ref().ok_or("repository without owner")?;
+    let mut names = Vec::new();
+    let mut unused = Vec::new();
+    let mut cursor = None;
+    loop {
+        let res = github::graphql(
+            client,
+            "query($owner: String!, $name: String!, $cursor: String) { \
+                repository(owner: $owner, name: $name) { \
+                labels(first: 100, after: $cursor) { \
+                pageInfo { hasNextPage endCursor } \
+                nodes { name issues { totalCount } pullRequests { totalCount } } } } }",
+            json!({ "owner": owner.login, "name": repo.name, "cursor": cursor }),
+        )
+        .await?;
+        let labels = &res["data"]["repository"]["labels"];
+        for label in labels["nodes"].as_array().into_iter().flatten() {
+            let name = label["name"].as_str().unwrap_or_default().to_string();
+            if label["issues"]["totalCount"] == 0 && label["pullRequests"]["totalCount"] == 0 {
+                unused.push(name.clone());
+            }
+            names.push(name);
+        }
+        if labels["pageInfo"]["hasNextPage"] != true {
+            break;
+        }
+        cursor = labels["pageInfo"]["endCursor"].as_str().map(String::from);
+    }
+
+    let issues = client.issues_by_id(repo.id);
+    let mut states: BTreeMap<u64, Vec<String>> = BTreeMap::new();
+    for state in WorkflowState::ALL {
+        let filter = [state.label().to_string()];
+        let page = github::retry(|| {
+            issues
+                .list()
+                .labels(&filter)
+                .state(params::State::Open)
+                .per_page(100)
+                .send()
+        })
+        .await?;
+        for issue in client.all_pages(page).await? {
+            states
+                .entry(issue.number)
+                .or_default()
+                .push(state.label().to_string());
+        }
+    }
+    states.retain(|_, x| x.len() > 1);
+
+    Ok(Hygiene {
+        unused,
+        duplicates: near_duplicates(&names),
+        contradictions: states,
+    })
+}
+
+/// Labels only differing in case, separators or one letter, e.g. `good first issue` and
+/// `good-first-issue`.
+pub fn near_duplicates(names: &[String]) -> Vec<(String, String)> {
+    let normalize = |x: &str| -> String {
+        x.chars()
+            .filter(|x| !matches!(x, ' ' | '-' | '_' | ':' | '/'))
+            .flat_map(char::to_lowercase)
+            .collect()
+    };
+    let mut duplicates = Vec::new();
+    for (i, a) in names.iter().enumerate() {
+        for b in &names[i + 1..] {
+            let (x, y) = (normalize(a), normalize(b));
+            if x == y || (x.len().min(y.len()) >= 5 && labels::edit_distance(&x, &y) == 1) {
+                duplicates.push((a.clone(), b.clone()));
+            }
+        }
+    }
+    duplicates
+}
+
+/// Takes the workflow state labels off the contradicting issues and PRs but the one holding
+/// them up most.
+async fn fix(
+    state: &AppState,
+    client: &Octocrab,
+    repo: &Repository,
+    hygiene: &Hygiene,
+) -> Result<(), Error> {
+    for (number, labels) in &hygiene.contradictions {
+        let before: HashSet<String> = labels.iter().cloned().collect();
+        let after: HashSet<String> = WorkflowState::of(&before)
+            .map(|x| x.label().to_string())
+            .into_iter()
+            .collect();
+        info!(
+            "#{} has the workflow labels {:?}, fixing it",
+            number, labels
+        );
+        workflow::sync(state, client, repo.id, *number, &before, &after).await?;
+    }
+    Ok(())
+}
+
+async fn report(
+    state: &AppState,
+    (report_repo, number): &(String, u64),
+    full_name: &str,
+    hygiene: &Hygiene,
+    fixed: bool,
+) -> Result<(), Error> {
+    let Some((repo, installation)) = state.db.repo_by_name(report_repo)? else {
+        return Err(format!("the app isn't installed on {}", report_repo).into());
+    };
+    let comment = state.rules().templates.render(
+        full_name,
+        "label_hygiene",
+        json!({
+            "repo": full_name,
+            "unused": hygiene.unused,
+            "duplicates": hygiene.duplicates,
+            "contradictions": hygiene
+                .contradictions
+                .iter()
+                .map(|(number, labels)| json!({ "number": number, "labels": labels }))
+                .collect::<Vec<_>>(),
+            "fixed": fixed,
+        }),
+    )?;
+    let client = state.installations.client(installation)?;
+    let issues = client.issues_by_id(repo);
+    github::comment(state.dry_run, &issues, *number, &comment).await?;
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HygieneQuery {
+    /// `owner/name`.
+    repo: String,
+}
+
+/// `GET /admin/label-hygiene?repo=owner/name`: the label problems of the repo right now.
+pub async fn label_hygiene(
+    State(state): State<AppState>,
+    Query(query): Query<HygieneQuery>,
+) -> Response {
+    let res = async {
+        let Some((_, installation)) = state.db.repo_by_name(&query.repo)? else {
+            return Ok(None);
+        };
+        let (owner, name) = query
+            .repo
+            .split_once('/')
+            .ok_or("repository without owner")?;
+        let client = state.installations.client(installation)?;
+        let (key, route) = (
+            query.repo.to_lowercase(),
+            format!("/repos/{}/{}", owner, name),
+        );
+        let repo = github::retry(|| state.repos.get(&client, key.clone(), &route)).await?;
+        check(&client, &repo).await.map(Some)
+    }
+    .await;
+    match res {
+        Ok(Some(hygiene)) => Json(hygiene).into_response(),
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            warn!("Failed to check the labels of {}: {}", query.repo, e);
+            StatusCode::BAD_GATEWAY.into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn near_duplicates_ignore_case_and_separators() {
+        let names = [
+            "good first issue",
+            "good-first-issue",
+            "Graphics",
+            "graphic",
+            "bug",
+            "bugs",
+        ]
+        .map(String::from);
+        assert_eq!(
+            near_duplicates(&names),
+            [
+                (
+                    "good first issue".to_string(),
+                    "good-first-issue".to_string()
+                ),
+                ("Graphics".to_string(), "graphic".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,255 @@
+use octocrab::{
+    Octocrab,
+    models::{Label, RepositoryId},
+    params,
+};
+use serde_json::json;
+
+use crate::{
+    AppState, bulk,
+    config::LabelDefinition,
+    github,
+    handlers::{self, Error},
+};
+
+/// What syncing the labels of a repo changed.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub created: Vec<String>,
+    pub updated: Vec<String>,
+    /// Labels of the repo that aren't defined, left alone.
+    pub unknown: Vec<String>,
+}
+
+/// Creates the defined labels the repo doesn't have, fixes the color and description of the
+/// ones that differ, and reports the labels that aren't defined. The changes are made as a
+/// [`bulk`] operation.
+pub async fn sync(
+    state: &AppState,
+    client: &Octocrab,
+    repo: RepositoryId,
+    definitions: &[LabelDefinition],
+) -> Result<Report, Error> {
+    let issues = client.issues_by_id(repo);
+    let page = github::retry(|| issues.list_labels_for_repo().per_page(100).send()).await?;
+    let existing: Vec<Label> = bulk::pages(client, page).await?;
+
+    let mut report = Report::default();
+    // The definitions to create, or to update with the name of the existing label.
+    let mut changes: Vec<(&LabelDefinition, Option<&str>)> = Vec::new();
+    for definition in definitions {
+        let Some(label) = existing
+            .iter()
+            .find(|x| x.name.eq_ignore_ascii_case(&definition.name))
+        else {
+            changes.push((definition, None));
+            report.created.push(definition.name.clone());
+            continue;
+        };
+        if label.color.eq_ignore_ascii_case(&definition.color)
+            && label.description.as_deref().unwrap_or_default() == definition.description
+        {
+            continue;
+        }
+        changes.push((definition, Some(&label.name)));
+        report.updated.push(label.name.clone());
+    }
+    let id = format!("labels:{}", repo);
+    let key = |(definition, _): &(&LabelDefinition, _)| definition.name.clone();
+    bulk::run(state, &id, &changes, key, |(definition, label)| {
+        let issues = &issues;
+        async move {
+            let Some(label) = label else {
+                github::mutate(
+                    state.dry_run,
+                    format!("create label {}", definition.name),
+                    || {
+                        issues.create_label(
+                            &definition.name,
+                            &definition.color,
+                            &definition.description,
+                        )
+                    },
+                )
+                .await?;
+                return Ok(());
+            };
+            // octocrab can't update labels.
+            let route = format!("/repositories/{}/labels/{}", repo, encode(label));
+            let body = json!({ "color": definition.color, "description": definition.description });
+            github::mutate(state.dry_run, format!("update label {}", label), || {
+                client.patch::<Label, _, _>(&route, Some(&body))
+            })
+            .await?;
+            Ok(())
+        }
+    })
+    .await?;
+    report.unknown = existing
+        .into_iter()
+        .filter(|x| {
+            !definitions
+                .iter()
+                .any(|d| d.name.eq_ignore_ascii_case(&x.name))
+        })
+        .map(|x| x.name)
+        .collect();
+    Ok(report)
+}
+
+/// Moves every issue and PR from label `old` to `new`, creating `new` like `old` if needed,
+/// then deletes `old`. Returns how many were moved. The issues are moved as a [`bulk`]
+/// operation, running it again after an interruption skips the ones already moved.
+pub async fn relabel(
+    state: &AppState,
+    client: &Octocrab,
+    repo: RepositoryId,
+    old: &str,
+    new: &str,
+) -> Result<usize, Error> {
+    let dry_run = state.dry_run;
+    let issues = client.issues_by_id(repo);
+    let page = github::retry(|| issues.list_labels_for_repo().per_page(100).send()).await?;
+    let existing: Vec<Label> = bulk::pages(client, page).await?;
+    let old_label = existing
+        .iter()
+        .find(|x| x.name == old)
+        .ok_or_else(|| format!("no label {:?}", old))?;
+    if !existing.iter().any(|x| x.name == new) {
+        let description = old_label.description.as_deref().unwrap_or_default();
+        github::mutate(dry_run, format!("create label {}", new), || {
+            issues.create_label(new, &old_label.color, description)
+        })
+        .await?;
+    }
+
+    let filter = [old.to_string()];
+    let page = github::retry(|| {
+        issues
+            .list()
+            .labels(&filter)
+            .state(params::State::All)
+            .per_page(100)
+            .send()
+    })
+    .await?;
+    let labeled: Vec<u64> = bulk::pages(client, page)
+        .await?
+        .iter()
+        .map(|x| x.number)
+        .collect();
+    let labels = [new.to_string()];
+    let id = format!("relabel:{}:{}:{}", repo, old, new);
+    bulk::run(state, &id, &labeled, u64::to_string, |number| {
+        let (issues, labels) = (&issues, &labels);
+        async move {
+            github::mutate(
+                dry_run,
+                format!("add labels {:?} to #{}", labels, number),
+                || issues.add_labels(number, labels),
+            )
+            .await?;
+            Ok(())
+        }
+    })
+    .await?;
+    // Deleting it takes it off every issue and PR.
+    github::mutate(dry_run, format!("delete label {}", old), || {
+        issues.delete_label(encode(old))
+    })
+    .await?;
+    Ok(labeled.len())
+}
+
+/// Syncs the labels of every repo the bot acts on, by `owner/name`.
+pub async fn sync_all(state: &AppState) -> Result<Vec<(String, Report)>, Error> {
+    let rules = state.rules();
+    let definitions = handlers::label_definitions(&state.labels);
+    let mut reports = Vec::new();
+    for (_, client, repo) in github::installed_repos(&state.installations).await? {
+        let full_name = repo.full_name.clone().unwrap_or_default();
+        if !rules.handlers.allows(Some(&full_name)) {
+            continue;
+        }
+        let report = sync(state, &client, repo.id, &definitions).await?;
+        reports.push((full_name, report));
+    }
+    Ok(reports)
+}
+
+/// Percent-encodes a label name for a URL path.
+fn encode(name: &str) -> String {
+    name.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// The labels of `existing` most like `name`, a label that doesn't exist, best first: the ones
+/// only differing in case, then the ones starting with it, then the ones at most two edits away.
+pub fn similar<'a>(name: &str, existing: impl IntoIterator<Item = &'a String>) -> Vec<&'a str> {
+    let name = name.to_lowercase();
+    let mut matches: Vec<(usize, &str)> = existing
+        .into_iter()
+        .filter_map(|label| {
+            let lower = label.to_lowercase();
+            let score = if lower == name {
+                0
+            } else if name.len() >= 3 && lower.starts_with(&name) {
+                1
+            } else {
+                match edit_distance(&lower, &name) {
+                    x @ 1..=2 => x + 1,
+                    _ => return None,
+                }
+            };
+            Some((score, label.as_str()))
+        })
+        .collect();
+    matches.sort_unstable();
+    matches.into_iter().take(3).map(|(_, x)| x).collect()
+}
+
+/// The Levenshtein distance of two strings, by chars.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, x) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, y) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = (diagonal + usize::from(x != *y))
+                .min(row[j] + 1)
+                .min(above + 1);
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn similar_labels_are_suggested() {
+        let existing = [
+            "bug",
+            "Crash",
+            "good first issue",
+            "needs-info",
+            "needs-rebase",
+        ]
+        .map(String::from);
+        assert_eq!(similar("crash", &existing), ["Crash"]);
+        assert_eq!(similar("bgu", &existing), ["bug"]);
+        assert_eq!(similar("good", &existing), ["good first issue"]);
+        assert_eq!(similar("needs", &existing), ["needs-info", "needs-rebase"]);
+        assert!(similar("feature", &existing).is_empty());
+    }
+}
@@ -0,0 +1,346 @@
+use std::{error::Error, path::Path, time::Duration};
+
+use axum::{
+    Router, middleware,
+    routing::{get, post},
+};
+use tracing::{info, warn};
+
+pub use crate::{
+    config::{Config, LogFormat},
+    state::AppState,
+};
+
+pub mod admin;
+pub mod aging;
+pub mod batch;
+pub mod benchmarks;
+pub mod bisect;
+pub mod budget;
+pub mod bulk;
+pub mod cache;
+pub mod cli;
+pub mod commands;
+pub mod config;
+pub mod conflicts;
+pub mod db;
+pub mod digest;
+pub mod discord;
+pub mod feed;
+pub mod forward;
+pub mod freeze;
+pub mod github;
+pub mod handlers;
+pub mod hygiene;
+pub mod labels;
+pub mod limiter;
+pub mod login;
+pub mod metrics;
+pub mod milestones;
+pub mod outage;
+pub mod outbox;
+pub mod projects;
+pub mod queue;
+pub mod quiet;
+pub mod ratelimit;
+pub mod reload;
+pub mod reminders;
+pub mod replay;
+pub mod report;
+pub mod repos;
+pub mod reviews;
+pub mod roles;
+pub mod slo;
+pub mod state;
+pub mod stats;
+pub mod status;
+pub mod subscriptions;
+pub mod summary;
+pub mod systemd;
+pub mod templates;
+pub mod tls;
+pub mod tracking;
+pub mod triage;
+pub mod triggers;
+pub mod unwind;
+pub mod usage;
+pub mod workflow;
+
+pub fn router(state: AppState, webhook_path: &str) -> Router {
+    let dashboard = Router::new()
+        .route("/stats", get(stats::all_stats))
+        .route("/stats/response-times", get(slo::response_times))
+        .route("/stats/{user}", get(stats::user_stats))
+        .route("/reviewers", get(reviews::reviewers))
+        .route("/triage/next", get(triage::next))
+        .route("/milestones/burndown", get(milestones::burndown))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            login::require_session,
+        ));
+    Router::new()
+        .route(webhook_path, post(handlers::webhook_handler))
+        .route("/feeds/triage.xml", get(feed::triage_feed))
+        .merge(dashboard)
+        .route("/metrics", get(metrics::metrics))
+        .route("/benchmarks", post(benchmarks::post_results))
+        .route("/triggers/{caller}", post(triggers::trigger))
+        .nest("/admin", admin::router(state.clone()))
+        .nest("/login", login::router())
+        .with_state(state)
+}
+
+pub async fn run(config: Config) -> Result<(), Box<dyn Error>> {
+    init_logging(config.log_format);
+
+    let mut state = AppState::from_config(&config)?;
+    let installations = state.installations.clone();
+
+    if let Some(url) = &config.redis_url {
+        let queue = queue::RedisQueue::connect(url).await?;
+        tokio::spawn(queue::requeue_loop(queue.clone()));
+        state.queue = Some(queue::Queue::Redis(queue));
+    } else if config.disk_queue {
+        let queue = queue::DiskQueue::new(state.db.clone())?;
+        state.queue = Some(queue::Queue::Disk(queue));
+    }
+    if let Some(queue) = &state.queue {
+        for _ in 0..config.max_workers {
+            tokio::spawn(queue::worker_loop(state.clone(), queue.clone()));
+        }
+    }
+
+    tokio::spawn(repos::sync_loop(
+        state.clone(),
+        Duration::from_secs(config.repo_sync_secs),
+    ));
+    tokio::spawn(state.roles.clone().refresh_loop(installations.clone()));
+    tokio::spawn(feed::refresh_loop(
+        state.clone(),
+        Duration::from_secs(config.feed_refresh_secs),
+    ));
+    if let Some(digest_repo) = config.digest_repo.clone() {
+        tokio::spawn(digest::digest_loop(
+            state.clone(),
+            digest_repo,
+            Duration::from_secs(config.digest_refresh_secs),
+        ));
+    }
+    if config.quiet_hours.is_some() {
+        tokio::spawn(quiet::flush_loop(state.clone()));
+    }
+    tokio::spawn(reload::reload_loop(state.clone()));
+    tokio::spawn(conflicts::poll_loop(
+        state.clone(),
+        Duration::from_secs(config.conflict_check_secs),
+    ));
+    tokio::spawn(tracking::progress_loop(
+        state.clone(),
+        Duration::from_secs(config.tracking_progress_secs),
+    ));
+    tokio::spawn(aging::aging_loop(
+        state.clone(),
+        Duration::from_secs(config.triage_aging_secs),
+    ));
+    tokio::spawn(milestones::snapshot_loop(
+        state.clone(),
+        Duration::from_secs(config.milestone_snapshot_secs),
+    ));
+    tokio::spawn(milestones::blocker_loop(state.clone()));
+    if config.label_hygiene_issue.is_some() || config.label_hygiene_autofix {
+        tokio::spawn(hygiene::hygiene_loop(
+            state.clone(),
+            Duration::from_secs(config.label_hygiene_secs),
+            config.label_hygiene_issue.clone(),
+            config.label_hygiene_autofix,
+        ));
+    }
+    tokio::spawn(reminders::remind_loop(state.clone()));
+    if !config.acknowledgements.ping.is_empty() {
+        tokio::spawn(reminders::response_loop(
+            state.clone(),
+            config.acknowledgements.clone(),
+        ));
+    }
+    tokio::spawn(freeze::lift_loop(state.clone()));
+    tokio::spawn(subscriptions::digest_loop(
+        state.clone(),
+        Duration::from_secs(config.subscription_digest_secs),
+    ));
+    if let Some(issue) = config.command_report_issue.clone() {
+        tokio::spawn(usage::report_loop(state.clone(), issue));
+    }
+    tokio::spawn(reviews::reminder_loop(
+        state.clone(),
+        config.review_sla.clone(),
+    ));
+    tokio::spawn(stats::sync_loop(
+        state.clone(),
+        Duration::from_secs(config.stats_sync_secs),
+    ));
+
+    let webhook_path = &config.webhook_path;
+    let app = router(state, webhook_path);
+
+    let Some(app) = systemd::serve_inherited(app).await? else {
+        return Ok(());
+    };
+
+    if let Some(path) = &config.unix_socket_path {
+        info!(
+            "Listening on {}, webhooks at {}",
+            path.display(),
+            webhook_path
+        );
+        serve_unix(path, config.unix_socket_mode, app).await?;
+        return Ok(());
+    }
+
+    // run our app with hyper, listening globally on port 3000 by default
+    let addr = listen_address(&config.bind_address, config.port).await?;
+    info!("Listening on {}, webhooks at {}", addr, webhook_path);
+
+    match (&config.tls_cert_path, &config.tls_key_path) {
+        (Some(cert), Some(key)) => {
+            tls::serve(addr, app, cert.clone(), key.clone()).await?;
+        }
+        _ => {
+            let listener = tokio::net::TcpListener::bind(addr)
+                .await
+                .map_err(|e| format!("failed to listen on {}: {}", addr, e))?;
+            systemd::notify_ready();
+            axum::serve(listener, app).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Logs in the human readable format, or as one JSON object per line with `LOG_FORMAT=json`.
+///
+/// JSON lines carry the fields of the delivery being handled (delivery ID, event type, repo and
+/// installation), for ingestion into Loki or ELK.
+///
+/// When `OTEL_EXPORTER_OTLP_ENDPOINT` is set, spans for deliveries, commands and every GitHub API
+/// call are also exported there over OTLP/HTTP. If the exporter can't be set up, the bot runs
+/// without it.
+pub fn init_logging(format: LogFormat) {
+    use tracing_subscriber::{Layer, layer::SubscriberExt, util::SubscriberInitExt};
+
+    let fmt = tracing_subscriber::fmt::layer();
+    let fmt = match format {
+        LogFormat::Json => fmt.json().with_current_span(true).boxed(),
+        LogFormat::Text => fmt.boxed(),
+    };
+
+    let exporter = std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT")
+        .is_ok()
+        .then(|| {
+            opentelemetry_otlp::SpanExporter::builder()
+                .with_http()
+                .build()
+        })
+        .transpose();
+    // Logged once the subscriber is set up.
+    let (exporter, otel_error) = match exporter {
+        Ok(x) => (x, None),
+        Err(e) => (None, Some(e)),
+    };
+    let otel = exporter.map(|exporter| {
+        use opentelemetry::trace::TracerProvider;
+
+        let provider = opentelemetry_sdk::trace::SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(
+                opentelemetry_sdk::Resource::builder()
+                    .with_service_name("ddbot")
+                    .build(),
+            )
+            .build();
+        let tracer = provider.tracer("ddbot");
+        opentelemetry::global::set_tracer_provider(provider);
+
+        // octocrab records a debug span per request.
+        let targets = tracing_subscriber::filter::Targets::new()
+            .with_target("ddbot", tracing::Level::INFO)
+            .with_target("octocrab", tracing::Level::DEBUG);
+        tracing_opentelemetry::layer()
+            .with_tracer(tracer)
+            .with_filter(targets)
+    });
+
+    tracing_subscriber::registry()
+        .with(fmt.with_filter(tracing_subscriber::EnvFilter::from_default_env()))
+        .with(otel)
+        .init();
+    if let Some(e) = otel_error {
+        warn!(
+            "Not exporting spans, setting up the OTLP exporter failed: {}",
+            e
+        );
+    }
+}
+
+/// The socket address of `BIND_ADDRESS` and `PORT`. IPv6 addresses are given without brackets,
+/// host names are resolved.
+async fn listen_address(address: &str, port: u16) -> Result<std::net::SocketAddr, String> {
+    let listen = if address.contains(':') {
+        format!("[{}]:{}", address, port)
+    } else {
+        format!("{}:{}", address, port)
+    };
+    if let Ok(addr) = listen.parse() {
+        return Ok(addr);
+    }
+    tokio::net::lookup_host(&listen)
+        .await
+        .ok()
+        .and_then(|mut x| x.next())
+        .ok_or_else(|| format!("invalid listen address {}", listen))
+}
+
+/// Serves `app` on a Unix socket, for reverse proxies running on the same host.
+///
+/// The socket's permissions are set from the octal `UNIX_SOCKET_MODE` (default 660).
+async fn serve_unix(path: &Path, mode: u32, app: Router) -> std::io::Result<()> {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    // A socket left behind by a previous run would make bind fail, anything else there is
+    // left alone.
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path)?,
+        Ok(_) => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("{} exists and isn't a socket", path.display()),
+            ));
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+
+    let listener = tokio::net::UnixListener::bind(path)?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+    systemd::notify_ready();
+
+    axum::serve(listener, app).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn listen_addresses() {
+        assert_eq!(
+            listen_address("0.0.0.0", 3000).await.unwrap().to_string(),
+            "0.0.0.0:3000"
+        );
+        assert_eq!(
+            listen_address("::1", 3000).await.unwrap().to_string(),
+            "[::1]:3000"
+        );
+        assert!(listen_address("localhost", 3000).await.is_ok());
+        assert!(listen_address("::1::2::", 3000).await.is_err());
+    }
+}
@@ -0,0 +1,136 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use octocrab::models::{InstallationId, UserId};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::config::Config;
+
+/// Bounds how many deliveries are processed at once, globally and per installation,
+/// so a burst of events from one repo can't starve the others or trip abuse limits.
+#[derive(Debug, Clone)]
+pub struct Limiter {
+    global: Arc<Semaphore>,
+    installations: Arc<Mutex<HashMap<InstallationId, Arc<Semaphore>>>>,
+    per_installation: usize,
+}
+
+/// Held while a delivery is processed.
+#[derive(Debug)]
+pub struct Permits {
+    _installation: OwnedSemaphorePermit,
+    _global: OwnedSemaphorePermit,
+}
+
+impl Limiter {
+    /// Reads the limits from `MAX_WORKERS` (default 16) and
+    /// `MAX_WORKERS_PER_INSTALLATION` (default 4).
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            global: Arc::new(Semaphore::new(config.max_workers)),
+            installations: Default::default(),
+            per_installation: config.max_workers_per_installation,
+        }
+    }
+
+    pub async fn acquire(&self, id: InstallationId) -> Permits {
+        let installation = self
+            .installations
+            .lock()
+            .unwrap()
+            .entry(id)
+            .or_insert_with(|| Arc::new(Semaphore::new(self.per_installation)))
+            .clone();
+
+        // Wait for the installation slot first so queued events of a busy installation
+        // don't hold global slots.
+        let installation = installation.acquire_owned().await.unwrap();
+        let global = self.global.clone().acquire_owned().await.unwrap();
+
+        Permits {
+            _installation: installation,
+            _global: global,
+        }
+    }
+}
+
+/// Sliding window limit of how many commands a user may run.
+#[derive(Debug, Clone)]
+pub struct CommandLimiter {
+    history: Arc<Mutex<HashMap<UserId, VecDeque<Instant>>>>,
+    max_commands: usize,
+    window: Duration,
+}
+
+impl CommandLimiter {
+    /// Reads the limit from `COMMAND_RATE_LIMIT` (default 5 commands) per
+    /// `COMMAND_RATE_WINDOW_SECS` (default 600).
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            history: Default::default(),
+            max_commands: config.command_rate_limit,
+            window: Duration::from_secs(config.command_rate_window_secs),
+        }
+    }
+
+    /// Records a command by `user`, or returns how long they have to wait if over the limit.
+    pub fn check(&self, user: UserId) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut history = self.history.lock().unwrap();
+        history.retain(|_, x| x.back().is_some_and(|t| now - *t < self.window));
+
+        let commands = history.entry(user).or_default();
+        while commands.front().is_some_and(|t| now - *t >= self.window) {
+            commands.pop_front();
+        }
+
+        if commands.len() >= self.max_commands {
+            return Err(self.window - (now - commands[0]));
+        }
+        commands.push_back(now);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn installations_wait_only_for_themselves() {
+        let limiter = Limiter {
+            global: Arc::new(Semaphore::new(2)),
+            installations: Default::default(),
+            per_installation: 1,
+        };
+        let wait = Duration::from_millis(50);
+        let _busy = limiter.acquire(InstallationId(1)).await;
+        assert!(
+            tokio::time::timeout(wait, limiter.acquire(InstallationId(1)))
+                .await
+                .is_err()
+        );
+        assert!(
+            tokio::time::timeout(wait, limiter.acquire(InstallationId(2)))
+                .await
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn commands_are_limited_per_user() {
+        let limiter = CommandLimiter {
+            history: Default::default(),
+            max_commands: 2,
+            window: Duration::from_secs(600),
+        };
+        assert!(limiter.check(UserId(1)).is_ok());
+        assert!(limiter.check(UserId(1)).is_ok());
+        let wait = limiter.check(UserId(1)).unwrap_err();
+        assert!(wait > Duration::from_secs(590) && wait <= Duration::from_secs(600));
+        assert!(limiter.check(UserId(2)).is_ok());
+    }
+}
@@ -0,0 +1,339 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    Json, Router,
+    extract::{Query, Request, State},
+    http::{HeaderMap, Method, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Redirect, Response},
+    routing::{get, post},
+};
+use octocrab::{Octocrab, Page};
+use serde::Deserialize;
+use serde_json::json;
+use subtle::ConstantTimeEq;
+use tracing::{info, warn};
+
+use crate::{
+    AppState,
+    config::{Config, LoginConfig},
+    handlers::Error,
+};
+
+const SESSION_COOKIE: &str = "ddbot_session";
+
+/// Holds the state of a login until GitHub redirects back, against forged callbacks.
+const STATE_COOKIE: &str = "ddbot_login";
+
+/// Requests changing something with a session have to send its CSRF token in this header.
+const CSRF_HEADER: &str = "x-csrf-token";
+
+/// How long a session lasts, the team membership is checked again at the next login.
+const SESSION_TTL: Duration = Duration::from_secs(8 * 60 * 60);
+
+/// Where people end up after logging in without a `next` page.
+const DEFAULT_PAGE: &str = "/reviewers";
+
+/// A logged in team member.
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub login: String,
+    pub csrf_token: String,
+    expires: Instant,
+}
+
+/// Logging in with GitHub, for `/admin` and the dashboard.
+///
+/// Sessions are kept in memory, so people log in again after a restart and per replica.
+#[derive(Debug, Clone)]
+pub struct Login {
+    config: LoginConfig,
+    api_url: String,
+    http: reqwest::Client,
+    sessions: Arc<Mutex<HashMap<String, Session>>>,
+}
+
+impl Login {
+    pub fn from_config(config: &Config) -> Option<Self> {
+        Some(Self {
+            config: config.login.clone()?,
+            api_url: config.github_api_url.clone(),
+            http: reqwest::Client::new(),
+            sessions: Default::default(),
+        })
+    }
+
+    /// The session of the request, if it has one that didn't expire.
+    pub fn session(&self, headers: &HeaderMap) -> Option<Session> {
+        let id = cookie(headers, SESSION_COOKIE)?;
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.retain(|_, x| x.expires > Instant::now());
+        sessions.get(id).cloned()
+    }
+
+    /// The login of the user of the token if they're in one of the teams.
+    async fn is_allowed(&self, token: &str) -> Result<Option<String>, Error> {
+        let client = Octocrab::builder()
+            .base_uri(&self.api_url)?
+            .personal_token(token.to_string())
+            .build()?;
+        let user: serde_json::Value = client.get("/user", None::<&()>).await?;
+        let page: Page<serde_json::Value> = client
+            .get("/user/teams", Some(&[("per_page", 100)]))
+            .await?;
+        let teams = client.all_pages(page).await?;
+        let allowed = teams.iter().any(|team| {
+            let name = format!(
+                "{}/{}",
+                team["organization"]["login"].as_str().unwrap_or_default(),
+                team["slug"].as_str().unwrap_or_default()
+            );
+            self.config
+                .teams
+                .iter()
+                .any(|x| x.eq_ignore_ascii_case(&name))
+        });
+        let login = user["login"].as_str().unwrap_or_default().to_string();
+        Ok(allowed.then_some(login))
+    }
+}
+
+/// The login pages, mounted under `/login`. They don't exist without a `[login]` config.
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(start))
+        .route("/callback", get(callback))
+        .route("/session", get(session))
+        .route("/logout", post(logout))
+}
+
+/// Lets requests through that send `Authorization: Bearer <ADMIN_TOKEN>` or have a session.
+/// Requests with a session that change something also need its CSRF token. Without an admin
+/// token and a `[login]` config the pages behind it don't exist.
+pub async fn require_session(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    if state.admin_token.is_none() && state.login.is_none() {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let sent = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|x| x.to_str().ok())
+        .and_then(|x| x.strip_prefix("Bearer "));
+    if let (Some(sent), Some(token)) = (sent, &state.admin_token)
+        && bool::from(sent.as_bytes().ct_eq(token.as_bytes()))
+    {
+        return next.run(req).await;
+    }
+    match state.login.as_ref().and_then(|x| x.session(req.headers())) {
+        Some(session) if csrf_ok(&session, req.method(), req.headers()) => next.run(req).await,
+        Some(_) => StatusCode::FORBIDDEN.into_response(),
+        None => StatusCode::UNAUTHORIZED.into_response(),
+    }
+}
+
+/// Whether a request with a session may do what it does, reads need no token.
+pub fn csrf_ok(session: &Session, method: &Method, headers: &HeaderMap) -> bool {
+    if matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS) {
+        return true;
+    }
+    headers
+        .get(CSRF_HEADER)
+        .and_then(|x| x.to_str().ok())
+        .is_some_and(|x| bool::from(x.as_bytes().ct_eq(session.csrf_token.as_bytes())))
+}
+
+#[derive(Debug, Deserialize)]
+struct StartQuery {
+    next: Option<String>,
+}
+
+/// `GET /login?next=/page`: sends people to GitHub to log in.
+async fn start(State(state): State<AppState>, Query(query): Query<StartQuery>) -> Response {
+    let Some(login) = &state.login else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let next = query
+        .next
+        .filter(|x| is_local(x))
+        .unwrap_or(DEFAULT_PAGE.to_string());
+    let nonce = token();
+    let url = format!(
+        "{}/login/oauth/authorize?client_id={}&scope=read:org&state={}",
+        login.config.github_url.trim_end_matches('/'),
+        login.config.client_id,
+        nonce
+    );
+    let cookie = format!(
+        "{}={}|{}; Path=/login; Max-Age=600; HttpOnly; Secure; SameSite=Lax",
+        STATE_COOKIE, nonce, next
+    );
+    ([(header::SET_COOKIE, cookie)], Redirect::to(&url)).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+struct CallbackQuery {
+    code: String,
+    state: String,
+}
+
+/// `GET /login/callback`: where GitHub sends people back to, with a code for their token.
+async fn callback(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<CallbackQuery>,
+) -> Response {
+    let Some(login) = &state.login else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let Some((nonce, next)) = cookie(&headers, STATE_COOKIE).and_then(|x| x.split_once('|')) else {
+        return StatusCode::FORBIDDEN.into_response();
+    };
+    if nonce != query.state || !is_local(next) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    let user = match exchange(login, &query.code).await {
+        Ok(token) => login.is_allowed(&token).await,
+        Err(e) => Err(e),
+    };
+    let user = match user {
+        Ok(Some(user)) => user,
+        Ok(None) => return StatusCode::FORBIDDEN.into_response(),
+        Err(e) => {
+            warn!("Failed to log in: {}", e);
+            return StatusCode::BAD_GATEWAY.into_response();
+        }
+    };
+
+    info!("{} logged in", user);
+    let id = token();
+    login.sessions.lock().unwrap().insert(
+        id.clone(),
+        Session {
+            login: user,
+            csrf_token: token(),
+            expires: Instant::now() + SESSION_TTL,
+        },
+    );
+    let session = format!(
+        "{}={}; Path=/; Max-Age={}; HttpOnly; Secure; SameSite=Lax",
+        SESSION_COOKIE,
+        id,
+        SESSION_TTL.as_secs()
+    );
+    let state = format!("{}=; Path=/login; Max-Age=0", STATE_COOKIE);
+    (
+        [(header::SET_COOKIE, session), (header::SET_COOKIE, state)],
+        Redirect::to(next),
+    )
+        .into_response()
+}
+
+/// Trades the code GitHub sent back for a token of the user.
+async fn exchange(login: &Login, code: &str) -> Result<String, Error> {
+    let res = login
+        .http
+        .post(format!(
+            "{}/login/oauth/access_token",
+            login.config.github_url.trim_end_matches('/')
+        ))
+        .header(header::ACCEPT, "application/json")
+        .form(&[
+            ("client_id", login.config.client_id.as_str()),
+            ("client_secret", login.config.client_secret.as_str()),
+            ("code", code),
+        ])
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    let res: serde_json::Value = serde_json::from_str(&res)?;
+    match res["access_token"].as_str() {
+        Some(token) => Ok(token.to_string()),
+        None => Err(format!("no token: {}", res["error_description"]).into()),
+    }
+}
+
+/// `GET /login/session`: who is logged in, and the CSRF token to send along.
+async fn session(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    let Some(login) = &state.login else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    match login.session(&headers) {
+        Some(session) => Json(json!({
+            "login": session.login,
+            "csrf_token": session.csrf_token,
+        }))
+        .into_response(),
+        None => StatusCode::UNAUTHORIZED.into_response(),
+    }
+}
+
+/// `POST /login/logout`, with the CSRF token.
+async fn logout(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    let Some(login) = &state.login else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let Some(session) = login.session(&headers) else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    if !csrf_ok(&session, &Method::POST, &headers) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    if let Some(id) = cookie(&headers, SESSION_COOKIE) {
+        login.sessions.lock().unwrap().remove(id);
+    }
+    let cookie = format!("{}=; Path=/; Max-Age=0", SESSION_COOKIE);
+    ([(header::SET_COOKIE, cookie)], StatusCode::NO_CONTENT).into_response()
+}
+
+fn token() -> String {
+    hex::encode(rand::random::<[u8; 32]>())
+}
+
+fn cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|x| x.to_str().ok())
+        .flat_map(|x| x.split(';'))
+        .filter_map(|x| x.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+/// Whether `next` is a page of the bot, not somewhere else to be redirected to.
+fn is_local(next: &str) -> bool {
+    next.starts_with('/') && !next.starts_with("//") && !next.contains(['\\', '|', ';'])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cookies_and_redirects_are_checked() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::COOKIE, "a=1; ddbot_session=abc".parse().unwrap());
+        assert_eq!(cookie(&headers, SESSION_COOKIE), Some("abc"));
+        assert_eq!(cookie(&headers, STATE_COOKIE), None);
+        assert!(is_local("/admin/dead-letters"));
+        assert!(!is_local("//evil.example"));
+        assert!(!is_local("https://evil.example"));
+
+        let session = Session {
+            login: "someone".to_string(),
+            csrf_token: "token".to_string(),
+            expires: Instant::now(),
+        };
+        assert!(csrf_ok(&session, &Method::GET, &headers));
+        assert!(!csrf_ok(&session, &Method::DELETE, &headers));
+        headers.insert(CSRF_HEADER, "token".parse().unwrap());
+        assert!(csrf_ok(&session, &Method::DELETE, &headers));
+    }
+}
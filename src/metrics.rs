@@ -0,0 +1,198 @@
+use std::fmt::{Display, Write};
+
+use axum::{
+    extract::State,
+    http::header,
+    response::{IntoResponse, Response},
+};
+
+use chrono::{DateTime, Utc};
+use tracing::warn;
+
+use crate::{AppState, commands, slo};
+
+/// The window of the CI job metrics.
+const CI_WINDOW_HOURS: i64 = 24;
+
+/// Prometheus text exposition of the bot's metrics.
+pub async fn metrics(State(state): State<AppState>) -> Response {
+    let mut out = String::new();
+
+    let budgets = state.rate_limits.budgets();
+    write_metric(
+        &mut out,
+        "ddbot_github_rate_limit_remaining",
+        "gauge",
+        "Remaining core API requests per installation.",
+        budgets
+            .iter()
+            .map(|(id, rate)| (format!("installation=\"{}\"", id), rate.remaining)),
+    );
+    write_metric(
+        &mut out,
+        "ddbot_github_rate_limit_limit",
+        "gauge",
+        "Core API request limit per installation.",
+        budgets
+            .iter()
+            .map(|(id, rate)| (format!("installation=\"{}\"", id), rate.limit)),
+    );
+
+    write_metric(
+        &mut out,
+        "ddbot_github_outage",
+        "gauge",
+        "1 while the workers pause because GitHub API calls keep failing.",
+        [(String::new(), u8::from(crate::outage::GITHUB.is_open()))],
+    );
+    write_metric(
+        &mut out,
+        "ddbot_github_api_calls_total",
+        "counter",
+        "GitHub API calls made by each handler.",
+        state
+            .api_usage
+            .calls()
+            .into_iter()
+            .map(|(handler, calls)| (format!("handler=\"{}\"", handler), calls)),
+    );
+
+    let usage = state
+        .db
+        .command_usage(DateTime::UNIX_EPOCH, Utc::now())
+        .unwrap_or_else(|e| {
+            warn!("Failed to read command usage: {}", e);
+            Vec::new()
+        });
+    write_metric(
+        &mut out,
+        "ddbot_commands_total",
+        "counter",
+        "Commands run, by whether they succeeded.",
+        // Only known commands get a series, whatever older versions recorded.
+        usage
+            .iter()
+            .filter(|x| commands::NAMES.contains(&x.command.as_str()))
+            .flat_map(|x| {
+                let command = escape(&x.command);
+                [
+                    (
+                        format!("command=\"{}\",outcome=\"ok\"", command),
+                        x.uses - x.failures,
+                    ),
+                    (
+                        format!("command=\"{}\",outcome=\"failed\"", command),
+                        x.failures,
+                    ),
+                ]
+            }),
+    );
+
+    let since = Utc::now() - chrono::Duration::hours(CI_WINDOW_HOURS);
+    let jobs = state.db.ci_job_stats(since).unwrap_or_else(|e| {
+        warn!("Failed to read CI job stats: {}", e);
+        Vec::new()
+    });
+    let job_labels = |x: &crate::db::CiJobStats| {
+        format!(
+            "repo=\"{}\",workflow=\"{}\",job=\"{}\"",
+            escape(&x.full_name),
+            escape(&x.workflow),
+            escape(&x.name)
+        )
+    };
+    write_metric(
+        &mut out,
+        "ddbot_ci_jobs",
+        "gauge",
+        "Workflow jobs completed in the last 24 hours.",
+        jobs.iter().map(|x| (job_labels(x), x.runs)),
+    );
+    write_metric(
+        &mut out,
+        "ddbot_ci_job_queue_seconds",
+        "gauge",
+        "Average time workflow jobs of the last 24 hours waited for a runner.",
+        jobs.iter().map(|x| (job_labels(x), x.avg_queued_secs)),
+    );
+    write_metric(
+        &mut out,
+        "ddbot_ci_job_run_seconds",
+        "gauge",
+        "Average time workflow jobs of the last 24 hours ran.",
+        jobs.iter().map(|x| (job_labels(x), x.avg_run_secs)),
+    );
+
+    let now = Utc::now();
+    let since = now - chrono::Duration::days(slo::WINDOW_DAYS);
+    let issues = state.db.issue_times(since).unwrap_or_else(|e| {
+        warn!("Failed to read issue times: {}", e);
+        Vec::new()
+    });
+    let repos = slo::by_repo(&issues, since);
+    let quantiles = |repo: &str, x: &Option<slo::Distribution>| {
+        let repo = escape(repo);
+        x.iter()
+            .flat_map(|x| [("0.5", x.p50), ("0.9", x.p90), ("1", x.max)])
+            .map(|(q, hours)| (format!("repo=\"{}\",quantile=\"{}\"", repo, q), hours))
+            .collect::<Vec<_>>()
+    };
+    write_metric(
+        &mut out,
+        "ddbot_issue_first_response_hours",
+        "gauge",
+        "Hours issues of the last 30 days waited for a first response, by quantile.",
+        repos
+            .iter()
+            .flat_map(|(repo, x)| quantiles(repo, &x.first_response)),
+    );
+    write_metric(
+        &mut out,
+        "ddbot_issue_triage_hours",
+        "gauge",
+        "Hours issues of the last 30 days waited for triage, by quantile.",
+        repos
+            .iter()
+            .flat_map(|(repo, x)| quantiles(repo, &x.triage)),
+    );
+    let breaches = slo::breaches(&issues, state.slo, now);
+    write_metric(
+        &mut out,
+        "ddbot_issue_slo_breaches",
+        "gauge",
+        "Open issues waiting longer than the SLO allows.",
+        ["first response", "triage"].map(|kind| {
+            let count = breaches.iter().filter(|x| x.kind == kind).count();
+            (format!("slo=\"{}\"", kind), count)
+        }),
+    );
+
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], out).into_response()
+}
+
+/// Escapes a label value.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Writes one metric family, `samples` being `(labels, value)` pairs.
+fn write_metric<V: Display>(
+    out: &mut String,
+    name: &str,
+    kind: &str,
+    help: &str,
+    samples: impl IntoIterator<Item = (String, V)>,
+) {
+    writeln!(out, "# HELP {} {}", name, help).unwrap();
+    writeln!(out, "# TYPE {} {}", name, kind).unwrap();
+    for (labels, value) in samples {
+        if labels.is_empty() {
+            writeln!(out, "{} {}", name, value).unwrap();
+        } else {
+            writeln!(out, "{}{{{}}} {}", name, labels, value).unwrap();
+        }
+    }
+}
@@ -0,0 +1,264 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use octocrab::{
+    Octocrab,
+    models::{Milestone, Repository, issues::Issue},
+};
+use serde::Deserialize;
+use serde_json::json;
+use tracing::{info, warn};
+
+use crate::{AppState, github, handlers::Error};
+
+pub const BLOCKER_LABEL: &str = "blocker";
+
+/// How many of the open issues and PRs of a milestone `!ddnetbot milestone-status` lists.
+pub const MILESTONE_BLOCKERS: u8 = 10;
+
+/// How often blockers of milestones due soon are looked for.
+const BLOCKER_CHECK_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+/// How long a blocker may go without activity before its assignees are pinged, again after
+/// as long since the ping.
+const BLOCKER_IDLE_DAYS: i64 = 3;
+
+/// Periodically records how many issues and PRs of every open milestone are open and closed,
+/// one count per day for the burndown.
+pub async fn snapshot_loop(state: AppState, period: Duration) {
+    let mut interval = tokio::time::interval(period);
+
+    loop {
+        interval.tick().await;
+        let repos = match github::installed_repos(&state.installations).await {
+            Ok(x) => x,
+            Err(e) => {
+                warn!("Failed to list repos for the milestone burndown: {}", e);
+                continue;
+            }
+        };
+        let rules = state.rules();
+        for (installation, client, repo) in repos {
+            if !rules.handlers.allows(repo.full_name.as_deref()) {
+                continue;
+            }
+            state
+                .rate_limits
+                .wait_for_budget(installation, "milestone burndown")
+                .await;
+            if let Err(e) = snapshot(&state, &client, &repo).await {
+                warn!(
+                    "Failed to count the milestones of {}: {}",
+                    repo.full_name.as_deref().unwrap_or_default(),
+                    e
+                );
+            }
+        }
+    }
+}
+
+async fn snapshot(state: &AppState, client: &Octocrab, repo: &Repository) -> Result<(), Error> {
+    let full_name = repo.full_name.as_deref().unwrap_or_default();
+    for milestone in open_milestones(client, full_name).await? {
+        state.db.set_milestone_count(
+            full_name,
+            milestone.number,
+            milestone.open_issues.unwrap_or_default().max(0) as u64,
+            milestone.closed_issues.unwrap_or_default().max(0) as u64,
+        )?;
+    }
+    Ok(())
+}
+
+/// Pings the assignees of the open `blocker` issues of milestones due within
+/// `blocker_ping_days` that haven't moved for a while.
+pub async fn blocker_loop(state: AppState) {
+    let mut interval = tokio::time::interval(BLOCKER_CHECK_INTERVAL);
+
+    loop {
+        interval.tick().await;
+        let repos = match github::installed_repos(&state.installations).await {
+            Ok(x) => x,
+            Err(e) => {
+                warn!("Failed to list repos for the release blockers: {}", e);
+                continue;
+            }
+        };
+        let rules = state.rules();
+        for (installation, client, repo) in repos {
+            if !rules.handlers.allows(repo.full_name.as_deref()) {
+                continue;
+            }
+            state
+                .rate_limits
+                .wait_for_budget(installation, "release blockers")
+                .await;
+            if let Err(e) = ping_blockers(&state, &client, &repo).await {
+                warn!(
+                    "Failed to ping the release blockers of {}: {}",
+                    repo.full_name.as_deref().unwrap_or_default(),
+                    e
+                );
+            }
+        }
+    }
+}
+
+/// Whether the milestone is due within `days`.
+pub fn due_soon(milestone: &Milestone, now: DateTime<Utc>, days: u64) -> bool {
+    milestone.due_on.is_some_and(|due| {
+        due >= now && due - now <= chrono::Duration::days(days.try_into().unwrap_or(i64::MAX))
+    })
+}
+
+async fn ping_blockers(
+    state: &AppState,
+    client: &Octocrab,
+    repo: &Repository,
+) -> Result<(), Error> {
+    let full_name = repo.full_name.as_deref().unwrap_or_default();
+    let now = Utc::now();
+    let idle_since = (now - chrono::Duration::days(BLOCKER_IDLE_DAYS)).format("%Y-%m-%d");
+    let issues = client.issues_by_id(repo.id);
+    for milestone in open_milestones(client, full_name).await? {
+        if !due_soon(&milestone, now, state.blocker_ping_days) {
+            continue;
+        }
+        // The ping updates the issue, so it's pinged again only once it's idle again.
+        let query = format!(
+            "repo:{} is:open milestone:\"{}\" label:{} updated:<{}",
+            full_name, milestone.title, BLOCKER_LABEL, idle_since
+        );
+        let page = github::retry(|| {
+            client
+                .search()
+                .issues_and_pull_requests(&query)
+                .per_page(100)
+                .send()
+        })
+        .await?;
+        for issue in page.items {
+            if issue.assignees.is_empty() {
+                continue;
+            }
+            let comment = state.rules().templates.render(
+                full_name,
+                "blocker_ping",
+                json!({
+                    "assignees": issue.assignees.iter().map(|x| &x.login).collect::<Vec<_>>(),
+                    "milestone": milestone.title,
+                    "due": milestone.due_on.map(|x| x.format("%Y-%m-%d").to_string()),
+                }),
+            )?;
+            info!("#{} blocks {} and is idle", issue.number, milestone.title);
+            github::comment(state.dry_run, &issues, issue.number, &comment).await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn open_milestones(client: &Octocrab, full_name: &str) -> Result<Vec<Milestone>, Error> {
+    let route = format!("/repos/{}/milestones?state=open&per_page=100", full_name);
+    let milestones: Vec<Milestone> = github::retry(|| client.get(&route, None::<&()>)).await?;
+    Ok(milestones)
+}
+
+/// The milestone due next, milestones without a due date after the others by number.
+pub fn upcoming(milestones: &[Milestone]) -> Option<&Milestone> {
+    milestones
+        .iter()
+        .min_by_key(|x| (x.due_on.is_none(), x.due_on, x.number))
+}
+
+/// The oldest open issues and PRs of the milestone, what's still in the way of the release.
+pub async fn blockers(
+    client: &Octocrab,
+    full_name: &str,
+    milestone: &Milestone,
+) -> Result<Vec<Issue>, Error> {
+    let query = format!(
+        "repo:{} is:open milestone:\"{}\" sort:created-asc",
+        full_name, milestone.title
+    );
+    let page = github::retry(|| {
+        client
+            .search()
+            .issues_and_pull_requests(&query)
+            .per_page(MILESTONE_BLOCKERS)
+            .send()
+    })
+    .await?;
+    Ok(page.items)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BurndownQuery {
+    /// `owner/name`.
+    repo: String,
+    /// The milestone number.
+    milestone: i64,
+}
+
+/// `GET /milestones/burndown?repo=owner/name&milestone=12`: the recorded daily counts.
+pub async fn burndown(
+    State(state): State<AppState>,
+    Query(query): Query<BurndownQuery>,
+) -> Response {
+    match state.db.milestone_counts(&query.repo, query.milestone) {
+        Ok(counts) if counts.is_empty() => StatusCode::NOT_FOUND.into_response(),
+        Ok(counts) => Json(counts).into_response(),
+        Err(e) => {
+            warn!("Failed to read the burndown of {}: {}", query.repo, e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use serde_json::json;
+
+    use super::*;
+
+    fn milestone(number: i64, due_on: Option<&str>) -> Milestone {
+        serde_json::from_value(json!({
+            "url": "https://api.github.com/repos/ddnet/ddnet/milestones/1",
+            "html_url": "https://github.com/ddnet/ddnet/milestone/1",
+            "id": number,
+            "node_id": "",
+            "number": number,
+            "title": format!("19.{}", number),
+            "created_at": "2025-01-01T00:00:00Z",
+            "due_on": due_on,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn milestones_due_first_come_first() {
+        let milestones = [
+            milestone(1, None),
+            milestone(2, Some("2025-04-01T00:00:00Z")),
+            milestone(3, Some("2025-03-01T00:00:00Z")),
+        ];
+        let next = upcoming(&milestones).unwrap();
+        assert_eq!(next.number, 3);
+        assert_eq!(
+            next.due_on,
+            Some(Utc.with_ymd_and_hms(2025, 3, 1, 0, 0, 0).unwrap())
+        );
+        assert_eq!(upcoming(&milestones[..1]).unwrap().number, 1);
+
+        let now = Utc.with_ymd_and_hms(2025, 2, 25, 0, 0, 0).unwrap();
+        assert!(due_soon(&milestones[2], now, 7));
+        assert!(!due_soon(&milestones[1], now, 7));
+        assert!(!due_soon(&milestones[0], now, 7));
+    }
+}
@@ -0,0 +1,107 @@
+use std::{
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use tracing::{info, warn};
+
+/// Consecutive failed GitHub API calls after which GitHub is considered down.
+const FAILURE_THRESHOLD: usize = 20;
+
+/// How long the workers pause before trying GitHub again.
+const PAUSE: Duration = Duration::from_secs(60);
+
+/// Notices when GitHub is down, shared by every API call of the process.
+pub static GITHUB: Breaker = Breaker::new(FAILURE_THRESHOLD, PAUSE);
+
+/// A circuit breaker: after `threshold` consecutive failures it opens for `pause`, and the
+/// queue workers stop taking deliveries until it closes again. Deliveries keep being queued
+/// meanwhile and are caught up on once GitHub is back.
+#[derive(Debug)]
+pub struct Breaker {
+    threshold: usize,
+    pause: Duration,
+    failures: AtomicUsize,
+    open_until: Mutex<Option<Instant>>,
+}
+
+impl Breaker {
+    pub const fn new(threshold: usize, pause: Duration) -> Self {
+        Self {
+            threshold,
+            pause,
+            failures: AtomicUsize::new(0),
+            open_until: Mutex::new(None),
+        }
+    }
+
+    pub fn success(&self) {
+        if self.failures.swap(0, Ordering::Relaxed) >= self.threshold {
+            info!("GitHub is back, resuming");
+        }
+        *self.open_until.lock().unwrap() = None;
+    }
+
+    /// Counts a failure that wasn't the fault of the request, like a 502 or a timeout.
+    pub fn failure(&self) {
+        let failures = self.failures.fetch_add(1, Ordering::Relaxed) + 1;
+        // After a pause, a single failure opens it again.
+        if failures >= self.threshold {
+            let mut open_until = self.open_until.lock().unwrap();
+            if open_until.is_none_or(|x| x <= Instant::now()) {
+                warn!(
+                    "{} GitHub API calls failed in a row, pausing for {:?}",
+                    failures, self.pause
+                );
+                *open_until = Some(Instant::now() + self.pause);
+            }
+        }
+    }
+
+    /// How long it stays open, zero if it's closed.
+    pub fn remaining(&self) -> Duration {
+        self.open_until
+            .lock()
+            .unwrap()
+            .map(|x| x.saturating_duration_since(Instant::now()))
+            .unwrap_or_default()
+    }
+
+    pub fn is_open(&self) -> bool {
+        !self.remaining().is_zero()
+    }
+
+    /// Waits until it's closed.
+    pub async fn wait(&self) {
+        loop {
+            let remaining = self.remaining();
+            if remaining.is_zero() {
+                return;
+            }
+            tokio::time::sleep(remaining).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn breaker_opens_after_consecutive_failures() {
+        let breaker = Breaker::new(3, Duration::from_secs(60));
+        breaker.failure();
+        breaker.failure();
+        breaker.success();
+        breaker.failure();
+        breaker.failure();
+        assert!(!breaker.is_open());
+        breaker.failure();
+        assert!(breaker.is_open());
+        breaker.success();
+        assert!(!breaker.is_open());
+    }
+}
@@ -0,0 +1,207 @@
+use std::future::Future;
+
+use chrono::{DateTime, Utc};
+use serde::{Serialize, de::DeserializeOwned};
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::{db::Db, queue::Job};
+
+tokio::task_local! {
+    /// The delivery and handler the changes are made for, and whether the handler is in
+    /// shadow mode.
+    static CURRENT: (Db, Job, &'static str, bool);
+}
+
+/// Runs a handler with its changes on GitHub recorded, see [`Effect`].
+///
+/// A handler in `shadow` mode runs as in dry-run mode, and the changes it would have made are
+/// recorded instead, see [`record_shadow`].
+pub async fn run<F: Future>(
+    db: &Db,
+    job: &Job,
+    handler: &'static str,
+    shadow: bool,
+    fut: F,
+) -> F::Output {
+    CURRENT
+        .scope((db.clone(), job.clone(), handler, shadow), fut)
+        .await
+}
+
+/// The name of the running handler, `None` outside of handlers.
+pub fn handler() -> Option<&'static str> {
+    CURRENT.try_with(|(_, _, handler, _)| *handler).ok()
+}
+
+/// The delivery the running handler handles, with the handler's name.
+pub fn job() -> Option<(Job, &'static str)> {
+    CURRENT
+        .try_with(|(_, job, handler, _)| (job.clone(), *handler))
+        .ok()
+}
+
+/// Records a change that wasn't made, if the running handler is in shadow mode.
+pub fn record_shadow(what: &str) {
+    let _ = CURRENT.try_with(|(db, job, handler, shadow)| {
+        if *shadow && let Err(e) = db.add_shadow_action(&job.delivery, handler, what) {
+            warn!("Failed to record the shadow action of {}: {}", handler, e);
+        }
+    });
+}
+
+/// Where a change stands.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum State {
+    New,
+    /// Started at the time but not known to be done, the handler failed or the bot stopped in
+    /// between.
+    Pending(DateTime<Utc>),
+    Done,
+}
+
+/// A change on GitHub a handler makes, recorded before it's made and marked done after.
+///
+/// When the handler runs again for the same delivery, after a crash or as a dead letter, the
+/// changes it already made are skipped, so it doesn't comment twice. Changes are told apart by
+/// their description, a handler making the same change twice for a delivery makes it once.
+#[derive(Debug)]
+pub struct Effect {
+    db: Db,
+    key: String,
+}
+
+impl Effect {
+    /// The change `what` of the running handler, `None` outside of handlers.
+    pub fn current(what: &str) -> Option<Self> {
+        CURRENT
+            .try_with(|(db, job, handler, _)| {
+                let hash = Sha256::digest(format!("{}\n{}\n{}", job.delivery, handler, what));
+                Self {
+                    db: db.clone(),
+                    key: hex::encode(&hash[..16]),
+                }
+            })
+            .ok()
+    }
+
+    /// Hidden in comments, to find them again if it's unknown whether they were posted.
+    pub fn marker(&self) -> String {
+        format!("<!-- ddbot:effect:{} -->", self.key)
+    }
+
+    /// Errors of the database are logged, the change is made as if it was new.
+    pub fn state(&self) -> State {
+        match self.db.effect(&self.key) {
+            Ok(Some((_, true))) => State::Done,
+            Ok(Some((started_at, false))) => State::Pending(started_at),
+            Ok(None) => State::New,
+            Err(e) => {
+                warn!("Failed to read effect {}: {}", self.key, e);
+                State::New
+            }
+        }
+    }
+
+    pub fn begin(&self) {
+        if let Err(e) = self.db.begin_effect(&self.key) {
+            warn!("Failed to record effect {}: {}", self.key, e);
+        }
+    }
+
+    pub fn done(&self) {
+        if let Err(e) = self.db.finish_effect(&self.key) {
+            warn!("Failed to mark effect {} as done: {}", self.key, e);
+        }
+    }
+
+    /// Marks the change as done, keeping what it created to be returned by [`Effect::result`].
+    pub fn done_with<T: Serialize>(&self, result: &T) {
+        let result = serde_json::to_string(result).unwrap();
+        if let Err(e) = self.db.finish_effect_with(&self.key, &result) {
+            warn!("Failed to mark effect {} as done: {}", self.key, e);
+        }
+    }
+
+    /// What the change created, if it's done and kept it.
+    pub fn result<T: DeserializeOwned>(&self) -> Option<T> {
+        match self.db.effect_result(&self.key) {
+            Ok(result) => serde_json::from_str(&result?).ok(),
+            Err(e) => {
+                warn!("Failed to read effect {}: {}", self.key, e);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(delivery: &str) -> Job {
+        Job {
+            delivery: delivery.to_string(),
+            event: "issues".to_string(),
+            body: "{}".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn effects_are_recorded_per_delivery() {
+        let db = Db::open(":memory:").unwrap();
+        assert!(Effect::current("comment").is_none());
+        run(&db, &job("delivery"), "greeter", false, async {
+            let effect = Effect::current("comment").unwrap();
+            assert_eq!(effect.state(), State::New);
+            effect.begin();
+            assert!(matches!(effect.state(), State::Pending(_)));
+            effect.done();
+        })
+        .await;
+        run(&db, &job("delivery"), "greeter", false, async {
+            assert_eq!(Effect::current("comment").unwrap().state(), State::Done);
+        })
+        .await;
+        run(&db, &job("other"), "greeter", false, async {
+            assert_eq!(Effect::current("comment").unwrap().state(), State::New);
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn created_objects_are_kept() {
+        let db = Db::open(":memory:").unwrap();
+        run(&db, &job("delivery"), "todos", false, async {
+            let effect = Effect::current("open issue").unwrap();
+            effect.begin();
+            assert_eq!(effect.result::<u64>(), None);
+            effect.done_with(&9876u64);
+        })
+        .await;
+        run(&db, &job("delivery"), "todos", false, async {
+            let effect = Effect::current("open issue").unwrap();
+            assert_eq!(effect.state(), State::Done);
+            assert_eq!(effect.result::<u64>(), Some(9876));
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn shadow_actions_are_recorded() {
+        let db = Db::open(":memory:").unwrap();
+        record_shadow("comment");
+        run(&db, &job("delivery"), "greeter", false, async {
+            record_shadow("comment")
+        })
+        .await;
+        run(&db, &job("delivery"), "labeler", true, async {
+            record_shadow("add labels")
+        })
+        .await;
+        let actions = db.shadow_actions(None, 10).unwrap();
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].handler, "labeler");
+        assert_eq!(actions[0].what, "add labels");
+    }
+}
@@ -0,0 +1,120 @@
+//! Keeps a Projects (v2) board in sync with labels, through the GraphQL API.
+
+use crate::{config::ProjectConfig, github, handlers::Error};
+use serde_json::json;
+
+/// A single select field of the board, with its options by name.
+#[derive(Debug)]
+struct Field {
+    id: String,
+    name: String,
+    options: Vec<(String, String)>,
+}
+
+async fn fields(client: &octocrab::Octocrab, project: &str) -> Result<Vec<Field>, Error> {
+    let res = github::graphql(
+        client,
+        "query($id: ID!) { node(id: $id) { ... on ProjectV2 { \
+            fields(first: 50) { nodes { ... on ProjectV2SingleSelectField { \
+            id name options { id name } } } } } } }",
+        json!({ "id": project }),
+    )
+    .await?;
+    let nodes = res["data"]["node"]["fields"]["nodes"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+    Ok(nodes
+        .iter()
+        .filter_map(|x| {
+            Some(Field {
+                id: x["id"].as_str()?.to_string(),
+                name: x["name"].as_str()?.to_string(),
+                options: x["options"]
+                    .as_array()?
+                    .iter()
+                    .filter_map(|o| Some((o["id"].as_str()?.into(), o["name"].as_str()?.into())))
+                    .collect(),
+            })
+        })
+        .collect())
+}
+
+/// Adds an issue or PR to the board, returns its item, `None` in dry-run mode. Adding it again
+/// returns the same item.
+async fn add_item(
+    client: &octocrab::Octocrab,
+    dry_run: bool,
+    project: &str,
+    content: &str,
+) -> Result<Option<String>, Error> {
+    let Some(data) = github::graphql_mutation(
+        dry_run,
+        client,
+        format!("add {} to the project", content),
+        "mutation($project: ID!, $content: ID!) { \
+            addProjectV2ItemById(input: { projectId: $project, contentId: $content }) { \
+            item { id } } }",
+        json!({ "project": project, "content": content }),
+    )
+    .await?
+    else {
+        return Ok(None);
+    };
+    let item = data["addProjectV2ItemById"]["item"]["id"]
+        .as_str()
+        .ok_or("project item without id")?;
+    Ok(Some(item.to_string()))
+}
+
+/// Adds the issue or PR with node id `content` to the board, and sets the fields of the board
+/// from its labels. Fields none of the labels map to are left alone.
+pub async fn sync(
+    client: &octocrab::Octocrab,
+    dry_run: bool,
+    config: &ProjectConfig,
+    content: &str,
+    labels: &[&str],
+) -> Result<(), Error> {
+    let values: Vec<(&str, &str)> = config
+        .fields
+        .iter()
+        .filter_map(|field| {
+            let value = labels.iter().find_map(|x| field.labels.get(*x))?;
+            Some((field.name.as_str(), value.as_str()))
+        })
+        .collect();
+    // Without an item in dry-run mode, the changes of the fields are only logged.
+    let item = add_item(client, dry_run, &config.id, content)
+        .await?
+        .unwrap_or_default();
+    if values.is_empty() {
+        return Ok(());
+    }
+    let fields = fields(client, &config.id).await?;
+    for (name, value) in values {
+        let Some(field) = fields.iter().find(|x| x.name == name) else {
+            return Err(format!("project has no single select field {:?}", name).into());
+        };
+        let Some((option, _)) = field.options.iter().find(|(_, x)| x == value) else {
+            return Err(format!("project field {:?} has no option {:?}", name, value).into());
+        };
+        github::graphql_mutation(
+            dry_run,
+            client,
+            format!("set {} of {} to {} on the project", name, content, value),
+            "mutation($project: ID!, $item: ID!, $field: ID!, $option: String!) { \
+                updateProjectV2ItemFieldValue(input: { projectId: $project, itemId: $item, \
+                fieldId: $field, value: { singleSelectOptionId: $option } }) { \
+                projectV2Item { id } } }",
+            json!({
+                "project": config.id,
+                "item": item,
+                "field": field.id,
+                "option": option,
+            }),
+        )
+        .await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,401 @@
+use std::{sync::Arc, time::Duration};
+
+use redis::{AsyncCommands, Script, aio::ConnectionManager};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+use tracing::{Instrument, info, warn};
+
+use crate::{AppState, db::Db, handlers::Error, outage};
+
+/// Prefix of every key of the queue.
+const KEY_PREFIX: &str = "ddbot:";
+const QUEUE_KEY: &str = "queue";
+const PROCESSING_KEY: &str = "processing";
+const LEASE_PREFIX: &str = "lease:";
+const SEEN_PREFIX: &str = "seen:";
+
+/// How long a worker holds a delivery without renewing before another replica takes it over.
+const LEASE_SECS: u64 = 300;
+/// How long delivery ids are remembered, so a delivery sent twice is only handled once.
+const DEDUP_SECS: u64 = 24 * 60 * 60;
+
+/// Moves the next job to the processing list and leases it, in one step so the job is never
+/// in the processing list without a lease.
+const POP_SCRIPT: &str = r"
+local job = redis.call('LMOVE', KEYS[1], KEYS[2], 'RIGHT', 'LEFT')
+if job then
+    redis.call('SET', ARGV[2] .. cjson.decode(job).delivery, '1', 'EX', ARGV[1])
+end
+return job
+";
+
+/// Puts jobs whose lease expired, e.g. because their replica died, back at the front of the
+/// queue.
+const REQUEUE_SCRIPT: &str = r"
+local requeued = 0
+for _, job in ipairs(redis.call('LRANGE', KEYS[2], 0, -1)) do
+    if redis.call('EXISTS', ARGV[1] .. cjson.decode(job).delivery) == 0 then
+        redis.call('LREM', KEYS[2], 1, job)
+        redis.call('RPUSH', KEYS[1], job)
+        requeued = requeued + 1
+    end
+end
+return requeued
+";
+
+/// A delivery as it was received.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub delivery: String,
+    pub event: String,
+    pub body: String,
+}
+
+/// Deliveries shared by every replica through Redis, so several of them can run behind a
+/// load balancer and each delivery is handled by exactly one.
+#[derive(Clone)]
+pub struct RedisQueue {
+    conn: ConnectionManager,
+    prefix: String,
+}
+
+impl std::fmt::Debug for RedisQueue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RedisQueue").finish_non_exhaustive()
+    }
+}
+
+impl RedisQueue {
+    pub async fn connect(url: &str) -> redis::RedisResult<Self> {
+        Self::connect_with_prefix(url, KEY_PREFIX).await
+    }
+
+    async fn connect_with_prefix(url: &str, prefix: &str) -> redis::RedisResult<Self> {
+        let conn = redis::Client::open(url)?.get_connection_manager().await?;
+        Ok(Self {
+            conn,
+            prefix: prefix.to_string(),
+        })
+    }
+
+    fn key(&self, name: &str) -> String {
+        format!("{}{}", self.prefix, name)
+    }
+
+    /// Queues a job, returns false if the delivery was queued before.
+    pub async fn push(&self, job: &Job) -> redis::RedisResult<bool> {
+        let mut conn = self.conn.clone();
+        let new: Option<String> = redis::cmd("SET")
+            .arg(format!("{}{}", self.key(SEEN_PREFIX), job.delivery))
+            .arg(1)
+            .arg("NX")
+            .arg("EX")
+            .arg(DEDUP_SECS)
+            .query_async(&mut conn)
+            .await?;
+        if new.is_none() {
+            return Ok(false);
+        }
+        let _: () = conn
+            .lpush(self.key(QUEUE_KEY), serde_json::to_string(job).unwrap())
+            .await?;
+        Ok(true)
+    }
+
+    async fn len(&self) -> redis::RedisResult<u64> {
+        self.conn.clone().llen(self.key(QUEUE_KEY)).await
+    }
+
+    async fn pop(&self) -> redis::RedisResult<Option<String>> {
+        Script::new(POP_SCRIPT)
+            .key(self.key(QUEUE_KEY))
+            .key(self.key(PROCESSING_KEY))
+            .arg(LEASE_SECS)
+            .arg(self.key(LEASE_PREFIX))
+            .invoke_async(&mut self.conn.clone())
+            .await
+    }
+
+    async fn renew(&self, delivery: &str) -> redis::RedisResult<()> {
+        self.conn
+            .clone()
+            .expire(
+                format!("{}{}", self.key(LEASE_PREFIX), delivery),
+                LEASE_SECS as i64,
+            )
+            .await
+    }
+
+    async fn ack(&self, raw: &str, delivery: &str) -> redis::RedisResult<()> {
+        let mut conn = self.conn.clone();
+        let _: () = conn.lrem(self.key(PROCESSING_KEY), 1, raw).await?;
+        conn.del(format!("{}{}", self.key(LEASE_PREFIX), delivery))
+            .await
+    }
+
+    async fn requeue_expired(&self) -> redis::RedisResult<u64> {
+        Script::new(REQUEUE_SCRIPT)
+            .key(self.key(QUEUE_KEY))
+            .key(self.key(PROCESSING_KEY))
+            .arg(self.key(LEASE_PREFIX))
+            .invoke_async(&mut self.conn.clone())
+            .await
+    }
+}
+
+/// Deliveries kept in the SQLite database until they're handled, so a crash or restart after
+/// answering GitHub doesn't lose them. Deliveries that were being handled when the bot stopped
+/// are handled again on the next start.
+#[derive(Debug, Clone)]
+pub struct DiskQueue {
+    db: Db,
+    pushed: Arc<Notify>,
+}
+
+impl DiskQueue {
+    pub fn new(db: Db) -> rusqlite::Result<Self> {
+        let released = db.release_queue()?;
+        if released > 0 {
+            info!(
+                "Requeued {} deliveries that were being handled at shutdown",
+                released
+            );
+        }
+        Ok(Self {
+            db,
+            pushed: Arc::new(Notify::new()),
+        })
+    }
+}
+
+/// Where accepted deliveries wait for a worker.
+#[derive(Debug, Clone)]
+pub enum Queue {
+    Redis(RedisQueue),
+    Disk(DiskQueue),
+}
+
+/// A job a worker took off the queue, and what's needed to remove it once handled.
+enum Lease {
+    Redis(String),
+    Disk(i64),
+}
+
+impl Queue {
+    /// Queues a job, returns false if the delivery is already queued.
+    pub async fn push(&self, job: &Job) -> Result<bool, Error> {
+        match self {
+            Self::Redis(queue) => Ok(queue.push(job).await?),
+            Self::Disk(queue) => {
+                let new = queue.db.queue_push(job)?;
+                queue.pushed.notify_one();
+                Ok(new)
+            }
+        }
+    }
+
+    /// How many jobs wait for a worker.
+    pub async fn waiting(&self) -> Result<u64, Error> {
+        match self {
+            Self::Redis(queue) => Ok(queue.len().await?),
+            Self::Disk(queue) => Ok(queue.db.queue_len()?),
+        }
+    }
+
+    /// Takes the next job, waiting a bit for one if the queue is empty.
+    async fn pop(&self) -> Result<Option<(Lease, String)>, Error> {
+        match self {
+            Self::Redis(queue) => match queue.pop().await? {
+                Some(raw) => Ok(Some((Lease::Redis(raw.clone()), raw))),
+                None => {
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    Ok(None)
+                }
+            },
+            Self::Disk(queue) => match queue.db.queue_take()? {
+                Some((id, raw)) => Ok(Some((Lease::Disk(id), raw))),
+                None => {
+                    tokio::time::timeout(Duration::from_secs(1), queue.pushed.notified())
+                        .await
+                        .ok();
+                    Ok(None)
+                }
+            },
+        }
+    }
+
+    async fn renew(&self, job: &Job) -> Result<(), Error> {
+        match self {
+            Self::Redis(queue) => Ok(queue.renew(&job.delivery).await?),
+            // Only this process uses the database.
+            Self::Disk(_) => Ok(()),
+        }
+    }
+
+    async fn ack(&self, lease: &Lease, delivery: &str) -> Result<(), Error> {
+        match (self, lease) {
+            (Self::Redis(queue), Lease::Redis(raw)) => Ok(queue.ack(raw, delivery).await?),
+            (Self::Disk(queue), Lease::Disk(id)) => Ok(queue.db.queue_remove(*id)?),
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// Takes jobs off the queue and handles them, renewing the lease while a job is handled.
+pub async fn worker_loop(state: AppState, queue: Queue) {
+    loop {
+        if outage::GITHUB.is_open() {
+            info!("GitHub seems to be down, waiting before taking deliveries");
+            outage::GITHUB.wait().await;
+        }
+        let (lease, raw) = match queue.pop().await {
+            Ok(Some(x)) => x,
+            Ok(None) => continue,
+            Err(e) => {
+                warn!("Failed to take a delivery off the queue: {}", e);
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                continue;
+            }
+        };
+        let job: Job = match serde_json::from_str(&raw) {
+            Ok(x) => x,
+            Err(e) => {
+                warn!("Dropped a malformed job: {}", e);
+                queue.ack(&lease, "").await.ok();
+                continue;
+            }
+        };
+
+        let span = crate::handlers::delivery_span(&job.delivery, &job.event);
+        let handle = crate::handlers::dispatch(state.clone(), &job).instrument(span);
+        tokio::pin!(handle);
+        let mut renew = tokio::time::interval(Duration::from_secs(LEASE_SECS / 3));
+        renew.tick().await;
+        loop {
+            tokio::select! {
+                _ = &mut handle => break,
+                _ = renew.tick() => {
+                    if let Err(e) = queue.renew(&job).await {
+                        warn!("Failed to renew the lease of {}: {}", job.delivery, e);
+                    }
+                }
+            }
+        }
+
+        if let Err(e) = queue.ack(&lease, &job.delivery).await {
+            warn!("Failed to remove {} from the queue: {}", job.delivery, e);
+        }
+    }
+}
+
+/// Periodically puts deliveries whose worker went away back on the queue.
+pub async fn requeue_loop(queue: RedisQueue) {
+    let mut interval = tokio::time::interval(Duration::from_secs(LEASE_SECS / 5));
+
+    loop {
+        interval.tick().await;
+        match queue.requeue_expired().await {
+            Ok(0) => {}
+            Ok(n) => info!("Requeued {} deliveries with expired leases", n),
+            Err(e) => warn!("Failed to requeue expired deliveries: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(delivery: &str) -> Job {
+        Job {
+            delivery: delivery.to_string(),
+            event: "ping".to_string(),
+            body: "{}".to_string(),
+        }
+    }
+
+    /// A queue with keys of its own on the Redis server at `TEST_REDIS_URL`.
+    async fn redis_queue(name: &str) -> RedisQueue {
+        let url = std::env::var("TEST_REDIS_URL").expect("TEST_REDIS_URL is not set");
+        let prefix = format!("ddbot-test:{}:{}:", name, rand::random::<u64>());
+        RedisQueue::connect_with_prefix(&url, &prefix)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn taken_deliveries_are_requeued_at_the_start() {
+        let db = Db::open(":memory:").unwrap();
+        let queue = Queue::Disk(DiskQueue::new(db.clone()).unwrap());
+        assert!(queue.push(&job("a")).await.unwrap());
+        assert!(queue.push(&job("b")).await.unwrap());
+        let (lease, raw) = queue.pop().await.unwrap().unwrap();
+        assert_eq!(serde_json::from_str::<Job>(&raw).unwrap().delivery, "a");
+        queue.ack(&lease, "a").await.unwrap();
+        queue.pop().await.unwrap().unwrap();
+        assert_eq!(queue.waiting().await.unwrap(), 0);
+
+        // The bot stopped while handling `b`.
+        let queue = Queue::Disk(DiskQueue::new(db).unwrap());
+        assert_eq!(queue.waiting().await.unwrap(), 1);
+        let (_, raw) = queue.pop().await.unwrap().unwrap();
+        assert_eq!(serde_json::from_str::<Job>(&raw).unwrap().delivery, "b");
+    }
+
+    async fn ttl(queue: &RedisQueue, delivery: &str) -> i64 {
+        queue
+            .conn
+            .clone()
+            .ttl(format!("{}{}", queue.key(LEASE_PREFIX), delivery))
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Redis server at TEST_REDIS_URL"]
+    async fn deliveries_are_queued_once_and_leased() {
+        let queue = redis_queue("lease").await;
+        assert!(queue.push(&job("a")).await.unwrap());
+        assert!(!queue.push(&job("a")).await.unwrap());
+        assert_eq!(queue.len().await.unwrap(), 1);
+
+        let raw = queue.pop().await.unwrap().unwrap();
+        assert_eq!(serde_json::from_str::<Job>(&raw).unwrap().delivery, "a");
+        assert_eq!(queue.len().await.unwrap(), 0);
+        assert!(ttl(&queue, "a").await > 0);
+        assert_eq!(queue.pop().await.unwrap(), None);
+
+        queue.ack(&raw, "a").await.unwrap();
+        assert_eq!(ttl(&queue, "a").await, -2);
+        assert_eq!(queue.requeue_expired().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Redis server at TEST_REDIS_URL"]
+    async fn expired_leases_are_requeued() {
+        let queue = redis_queue("requeue").await;
+        queue.push(&job("a")).await.unwrap();
+        queue.push(&job("b")).await.unwrap();
+        queue.pop().await.unwrap().unwrap();
+        queue.pop().await.unwrap().unwrap();
+
+        // The worker of `a` went away, `b` is still handled.
+        let mut conn = queue.conn.clone();
+        let _: () = conn
+            .expire(format!("{}a", queue.key(LEASE_PREFIX)), 1)
+            .await
+            .unwrap();
+        let _: () = conn
+            .expire(format!("{}b", queue.key(LEASE_PREFIX)), 1)
+            .await
+            .unwrap();
+        queue.renew("b").await.unwrap();
+        assert!(ttl(&queue, "b").await > 1);
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+
+        assert_eq!(queue.requeue_expired().await.unwrap(), 1);
+        let raw = queue.pop().await.unwrap().unwrap();
+        assert_eq!(serde_json::from_str::<Job>(&raw).unwrap().delivery, "a");
+        assert_eq!(queue.requeue_expired().await.unwrap(), 0);
+    }
+}
@@ -0,0 +1,87 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use tracing::{info, warn};
+
+use crate::{AppState, config::QuietHours, discord, handlers::Error};
+
+/// How often held back messages are looked at.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Waits until the quiet hours are over, if there are any.
+pub async fn wait(quiet_hours: Option<QuietHours>, what: &str) {
+    let Some(quiet_hours) = quiet_hours else {
+        return;
+    };
+    let remaining = quiet_hours.remaining(Utc::now());
+    if !remaining.is_zero() {
+        info!("Quiet hours, {} waits {:?}", what, remaining);
+        tokio::time::sleep(remaining).await;
+    }
+}
+
+/// Posts a non-urgent message through a Discord webhook, or holds it back until the quiet
+/// hours are over.
+pub async fn post_discord(
+    state: &AppState,
+    http: &reqwest::Client,
+    url: &str,
+    content: &str,
+) -> Result<(), Error> {
+    if state.quiet_hours.is_some_and(|x| x.contains(Utc::now())) {
+        info!("Quiet hours, holding back Discord message: {}", content);
+        state.db.hold_message(url, content)?;
+        return Ok(());
+    }
+    discord::post(http, state.dry_run, url, content).await?;
+    Ok(())
+}
+
+/// Posts the messages that were held back once the quiet hours are over.
+pub async fn flush_loop(state: AppState) {
+    let http = reqwest::Client::new();
+    let mut interval = tokio::time::interval(FLUSH_INTERVAL);
+
+    loop {
+        interval.tick().await;
+        wait(state.quiet_hours, "posting held back messages").await;
+        let messages = match state.db.held_messages() {
+            Ok(x) => x,
+            Err(e) => {
+                warn!("Failed to read held back messages: {}", e);
+                continue;
+            }
+        };
+        for (id, url, content) in messages {
+            // Kept to be tried again if Discord is down.
+            if let Err(e) = discord::post(&http, state.dry_run, &url, &content).await {
+                warn!("Failed to post held back message: {}", e);
+                continue;
+            }
+            if let Err(e) = state.db.remove_held_message(id) {
+                warn!("Failed to remove held back message: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn quiet_hours_wrap_around_midnight() {
+        let quiet_hours = QuietHours { start: 22, end: 7 };
+        let at = |hour, minute| Utc.with_ymd_and_hms(2025, 3, 1, hour, minute, 0).unwrap();
+        assert!(quiet_hours.contains(at(23, 0)));
+        assert!(quiet_hours.contains(at(3, 0)));
+        assert!(!quiet_hours.contains(at(7, 0)));
+        assert_eq!(
+            quiet_hours.remaining(at(22, 30)),
+            Duration::from_secs(8 * 60 * 60 + 30 * 60)
+        );
+        assert_eq!(quiet_hours.remaining(at(12, 0)), Duration::ZERO);
+    }
+}
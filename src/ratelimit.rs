@@ -0,0 +1,207 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use axum::http::{HeaderMap, Request, Response};
+use octocrab::models::{InstallationId, Rate};
+use tower::{Layer, Service};
+use tracing::{info, warn};
+
+use crate::config::Config;
+
+/// How long a waiting job sleeps at least before looking at the budget again.
+const MIN_WAIT_SECS: u64 = 60;
+
+/// The latest known core API budget of every installation.
+///
+/// Budgets are read from the `x-ratelimit-*` headers of the responses to each installation's
+/// API calls, see [`RateLimits::layer`].
+#[derive(Debug, Clone)]
+pub struct RateLimits {
+    budgets: Arc<Mutex<HashMap<u64, Rate>>>,
+    threshold: usize,
+}
+
+impl RateLimits {
+    /// Background jobs back off when an installation has less than `RATE_LIMIT_THRESHOLD`
+    /// (default 500) requests left.
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(config.rate_limit_threshold)
+    }
+
+    pub fn new(threshold: usize) -> Self {
+        Self {
+            budgets: Default::default(),
+            threshold,
+        }
+    }
+
+    pub fn budgets(&self) -> HashMap<u64, Rate> {
+        self.budgets.lock().unwrap().clone()
+    }
+
+    /// Records the budget of `id` from the headers of a response. Responses of other APIs than
+    /// the core one, like search, have their own budget and are ignored.
+    pub fn update(&self, id: InstallationId, headers: &HeaderMap) {
+        let header = |name: &str| -> Option<u64> { headers.get(name)?.to_str().ok()?.parse().ok() };
+        let resource = headers
+            .get("x-ratelimit-resource")
+            .and_then(|x| x.to_str().ok());
+        if resource.is_some_and(|x| x != "core") {
+            return;
+        }
+        let (Some(limit), Some(remaining), Some(reset)) = (
+            header("x-ratelimit-limit"),
+            header("x-ratelimit-remaining"),
+            header("x-ratelimit-reset"),
+        ) else {
+            return;
+        };
+        let rate = Rate {
+            limit: limit as usize,
+            used: header("x-ratelimit-used").unwrap_or(limit - remaining.min(limit)) as usize,
+            remaining: remaining as usize,
+            reset,
+        };
+        let previous = self.budgets.lock().unwrap().insert(id.0, rate.clone());
+        let was_low = previous.is_some_and(|x| x.remaining < self.threshold);
+        if rate.remaining < self.threshold && !was_low {
+            warn!(
+                "Installation {} has {}/{} requests left",
+                id, rate.remaining, rate.limit
+            );
+        }
+    }
+
+    /// Waits until the installation has more than the threshold of requests left, or its budget
+    /// was reset.
+    ///
+    /// Low priority background jobs call this before working on a repo, so webhook handling
+    /// keeps the remaining budget. Other installations aren't held up.
+    pub async fn wait_for_budget(&self, id: InstallationId, job: &str) {
+        loop {
+            let now = chrono::Utc::now().timestamp() as u64;
+            let reset = match self.budgets.lock().unwrap().get(&id.0) {
+                Some(x) if x.remaining < self.threshold && x.reset > now => x.reset,
+                _ => return,
+            };
+
+            let wait = reset.saturating_sub(now).max(MIN_WAIT_SECS);
+            info!(
+                "Delaying {} for {}s, rate limit budget of installation {} is below {}",
+                job, wait, id, self.threshold
+            );
+            tokio::time::sleep(Duration::from_secs(wait)).await;
+        }
+    }
+
+    /// A layer for the client of an installation that records its budget from every response.
+    /// `None` for the app itself, whose calls have a budget of their own.
+    pub fn layer(&self, id: Option<InstallationId>) -> RecordLayer {
+        RecordLayer {
+            limits: self.clone(),
+            id,
+        }
+    }
+}
+
+/// See [`RateLimits::layer`].
+#[derive(Debug, Clone)]
+pub struct RecordLayer {
+    limits: RateLimits,
+    id: Option<InstallationId>,
+}
+
+impl<S> Layer<S> for RecordLayer {
+    type Service = Record<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Record {
+            inner,
+            limits: self.limits.clone(),
+            id: self.id,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Record<S> {
+    inner: S,
+    limits: RateLimits,
+    id: Option<InstallationId>,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for Record<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
+        let (limits, id) = (self.limits.clone(), self.id);
+        let fut = self.inner.call(req);
+        Box::pin(async move {
+            let res = fut.await?;
+            if let Some(id) = id {
+                limits.update(id, res.headers());
+            }
+            Ok(res)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(resource: &str, remaining: u64, reset: u64) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ratelimit-resource", resource.parse().unwrap());
+        headers.insert("x-ratelimit-limit", "5000".parse().unwrap());
+        headers.insert("x-ratelimit-remaining", remaining.into());
+        headers.insert("x-ratelimit-reset", reset.into());
+        headers
+    }
+
+    #[tokio::test]
+    async fn only_the_low_installation_waits() {
+        let limits = RateLimits::new(500);
+        let reset = chrono::Utc::now().timestamp() as u64 + 3600;
+        limits.update(InstallationId(1), &headers("core", 10, reset));
+        limits.update(InstallationId(2), &headers("core", 4000, reset));
+        // Search has its own budget.
+        limits.update(InstallationId(2), &headers("search", 0, reset));
+        assert_eq!(limits.budgets()[&1].remaining, 10);
+        assert_eq!(limits.budgets()[&2].remaining, 4000);
+
+        let other = limits.wait_for_budget(InstallationId(2), "test");
+        tokio::time::timeout(Duration::from_secs(1), other)
+            .await
+            .unwrap();
+        let low = limits.wait_for_budget(InstallationId(1), "test");
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), low)
+                .await
+                .is_err()
+        );
+
+        // Once the budget was reset it's no longer waited for.
+        limits.update(InstallationId(1), &headers("core", 10, reset - 7200));
+        let reset = limits.wait_for_budget(InstallationId(1), "test");
+        tokio::time::timeout(Duration::from_secs(1), reset)
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,43 @@
+use std::{
+    path::Path,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use tracing::{info, warn};
+
+use crate::{AppState, config::Config, state::Rules};
+
+/// Swaps in new [`Rules`] whenever the config file changes, so rules can be changed without
+/// a restart that would drop deliveries. Other settings still need a restart.
+pub async fn reload_loop(state: AppState) {
+    let path = Config::path();
+    let mut interval = tokio::time::interval(Duration::from_secs(10));
+    let mut last = modified(&path);
+
+    loop {
+        interval.tick().await;
+        let current = modified(&path);
+        if current == last {
+            continue;
+        }
+        last = current;
+
+        // A broken config keeps the current rules until it's fixed.
+        let rules = match Config::load() {
+            Ok(config) => Rules::from_config(&config),
+            Err(e) => Err(e.into()),
+        };
+        match rules {
+            Ok(rules) => {
+                *state.rules.write().unwrap() = Arc::new(rules);
+                info!("Reloaded {}", path.display());
+            }
+            Err(e) => warn!("Failed to reload {}: {}", path.display(), e),
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|x| x.modified()).ok()
+}
@@ -0,0 +1,187 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use octocrab::models;
+use serde_json::json;
+use tracing::{info, warn};
+
+use crate::{
+    AppState,
+    config::AcknowledgementsConfig,
+    db::{Reminder, ResponseTimer},
+    github,
+    handlers::Error,
+};
+
+/// The longest duration `!ddnetbot remind` and `!ddnetbot snooze` accept.
+const MAX_DAYS: i64 = 365;
+
+/// Parses durations like `30m`, `12h`, `3d` or `2w`.
+pub fn parse_duration(text: &str) -> Option<chrono::Duration> {
+    let unit = text.chars().last()?;
+    let count: i64 = text[..text.len() - unit.len_utf8()].parse().ok()?;
+    let duration = match unit {
+        'm' => chrono::Duration::try_minutes(count)?,
+        'h' => chrono::Duration::try_hours(count)?,
+        'd' => chrono::Duration::try_days(count)?,
+        'w' => chrono::Duration::try_weeks(count)?,
+        _ => return None,
+    };
+    (duration > chrono::Duration::zero() && duration <= chrono::Duration::days(MAX_DAYS))
+        .then_some(duration)
+}
+
+/// Posts the reminders set with `!ddnetbot remind` once they're due, and puts snoozed issues
+/// back into triage.
+pub async fn remind_loop(state: AppState) {
+    let mut interval = tokio::time::interval(Duration::from_secs(60));
+
+    loop {
+        interval.tick().await;
+        let reminders = match state.db.due_reminders(Utc::now()) {
+            Ok(x) => x,
+            Err(e) => {
+                warn!("Failed to read reminders: {}", e);
+                continue;
+            }
+        };
+
+        for reminder in reminders {
+            match remind(&state, &reminder).await {
+                Ok(()) => info!(
+                    "Reminded {} on {}#{}",
+                    reminder.login, reminder.full_name, reminder.number
+                ),
+                Err(e) => warn!(
+                    "Failed to remind {} on {}#{}: {}",
+                    reminder.login, reminder.full_name, reminder.number, e
+                ),
+            }
+        }
+    }
+}
+
+async fn remind(state: &AppState, reminder: &Reminder) -> Result<(), Error> {
+    let client = state.installations.client(reminder.installation)?;
+    let issues = client.issues_by_id(reminder.repo);
+    let comment = match &reminder.label {
+        Some(label) => {
+            // Issues closed while snoozed stay out of triage.
+            let issue = github::retry(|| issues.get(reminder.number)).await?;
+            if issue.state == models::IssueState::Closed {
+                state.db.remove_reminder(reminder.id)?;
+                return Ok(());
+            }
+            let labels = [label.clone()];
+            github::mutate(
+                state.dry_run,
+                format!("add labels {:?} to #{}", labels, reminder.number),
+                || issues.add_labels(reminder.number, &labels),
+            )
+            .await?;
+            state.rules().templates.render(
+                &reminder.full_name,
+                "snooze_over",
+                json!({ "user": reminder.login, "label": label }),
+            )?
+        }
+        // Turning notifications off drops the reminders of the repo.
+        None if !state
+            .rules()
+            .handlers
+            .feature_enabled("notifications", &reminder.full_name) =>
+        {
+            info!(
+                "Notifications are off for {}, dropping the reminder",
+                reminder.full_name
+            );
+            state.db.remove_reminder(reminder.id)?;
+            return Ok(());
+        }
+        None => state.rules().templates.render(
+            &reminder.full_name,
+            "reminder",
+            json!({ "user": reminder.login, "message": reminder.message }),
+        )?,
+    };
+    github::comment(state.dry_run, &issues, reminder.number, &comment).await?;
+    state.db.remove_reminder(reminder.id)?;
+    Ok(())
+}
+
+/// Pings `config.ping` on the crash and regression reports acknowledged by
+/// [`crate::handlers::Acknowledgements`] that nobody but their author answered in time.
+pub async fn response_loop(state: AppState, config: AcknowledgementsConfig) {
+    let mut interval = tokio::time::interval(Duration::from_secs(60));
+
+    loop {
+        interval.tick().await;
+        let timers = match state.db.due_response_timers(Utc::now()) {
+            Ok(x) => x,
+            Err(e) => {
+                warn!("Failed to read response timers: {}", e);
+                continue;
+            }
+        };
+
+        for timer in timers {
+            if let Err(e) = check_response(&state, &config, &timer).await {
+                warn!(
+                    "Failed to check for a response on {}#{}: {}",
+                    timer.full_name, timer.number, e
+                );
+            }
+        }
+    }
+}
+
+async fn check_response(
+    state: &AppState,
+    config: &AcknowledgementsConfig,
+    timer: &ResponseTimer,
+) -> Result<(), Error> {
+    let client = state.installations.client(timer.installation)?;
+    let issues = client.issues_by_id(timer.repo);
+    let issue = github::retry(|| issues.get(timer.number)).await?;
+    let page = github::retry(|| {
+        issues
+            .list_comments(timer.number)
+            .since(timer.started_at)
+            .per_page(100)
+            .send()
+    })
+    .await?;
+    let answered = page
+        .items
+        .iter()
+        .any(|x| x.user.r#type != "Bot" && x.user.login != timer.author);
+    if issue.state != models::IssueState::Closed && !answered {
+        let comment = state.rules().templates.render(
+            &timer.full_name,
+            "response_overdue",
+            json!({ "ping": config.ping, "label": timer.label, "hours": config.hours }),
+        )?;
+        github::comment(state.dry_run, &issues, timer.number, &comment).await?;
+        info!(
+            "Nobody answered {}#{}, pinged {:?}",
+            timer.full_name, timer.number, config.ping
+        );
+    }
+    state.db.remove_response_timer(timer.repo, timer.number)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_are_parsed() {
+        assert_eq!(parse_duration("2w"), Some(chrono::Duration::days(14)));
+        assert_eq!(parse_duration("90m"), Some(chrono::Duration::minutes(90)));
+        assert_eq!(parse_duration("0d"), None);
+        assert_eq!(parse_duration("2y"), None);
+        assert_eq!(parse_duration("9999w"), None);
+        assert_eq!(parse_duration("w"), None);
+    }
+}
@@ -0,0 +1,200 @@
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+use axum::http::HeaderMap;
+
+use serde::{Deserialize, Serialize};
+use tracing::{Instrument, info, info_span, warn};
+
+use crate::{AppState, queue::Job};
+
+/// Headers that are derived from or contain secrets, and aren't written to recordings.
+const REDACTED_HEADERS: &[&str] = &[
+    "authorization",
+    "cookie",
+    "proxy-authorization",
+    "x-hub-signature",
+    "x-hub-signature-256",
+];
+
+/// A delivery saved to disk together with its headers (lowercase names).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Recording {
+    pub headers: BTreeMap<String, String>,
+    pub body: serde_json::Value,
+}
+
+impl Recording {
+    /// Reads a recording, or a bare delivery body if `event` is given.
+    pub fn load(path: &Path, event: Option<&str>) -> Result<Self, Box<dyn Error>> {
+        let data = std::fs::read(path)?;
+        match event {
+            Some(event) => Ok(Self {
+                headers: BTreeMap::from([("x-github-event".to_string(), event.to_string())]),
+                body: serde_json::from_slice(&data)?,
+            }),
+            None => Ok(serde_json::from_slice(&data)?),
+        }
+    }
+
+    /// Records a received delivery, redacting [`REDACTED_HEADERS`].
+    pub fn from_delivery(headers: &HeaderMap, body: &[u8]) -> serde_json::Result<Self> {
+        let headers = headers
+            .iter()
+            .map(|(name, value)| {
+                let value = if REDACTED_HEADERS.contains(&name.as_str()) {
+                    "<redacted>"
+                } else {
+                    value.to_str().unwrap_or_default()
+                };
+                (name.to_string(), value.to_string())
+            })
+            .collect();
+        Ok(Self {
+            headers,
+            body: serde_json::from_slice(body)?,
+        })
+    }
+
+    pub fn header(&self, name: &str) -> &str {
+        self.headers
+            .get(name)
+            .map(|x| x.as_str())
+            .unwrap_or_default()
+    }
+}
+
+/// Writes a received delivery to `dir` as `<event>-<delivery id>.json`, to be used as a test
+/// fixture or replayed later.
+pub async fn capture(dir: &Path, headers: &HeaderMap, body: &[u8]) {
+    let recording = match Recording::from_delivery(headers, body) {
+        Ok(x) => x,
+        Err(e) => {
+            warn!("Failed to capture delivery: {}", e);
+            return;
+        }
+    };
+    let Some(path) = capture_path(dir, &recording) else {
+        warn!("Not capturing a delivery with an invalid event or delivery id");
+        return;
+    };
+    let json = serde_json::to_string_pretty(&recording).unwrap();
+    let res = tokio::task::spawn_blocking(move || {
+        std::fs::write(&path, json).map_err(|e| format!("{}: {}", path.display(), e))
+    })
+    .await;
+    match res {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => warn!("Failed to capture delivery to {}", e),
+        Err(e) => warn!("Failed to capture delivery: {}", e),
+    }
+}
+
+/// The file a recording is captured to. Both headers come from the unauthenticated request, so
+/// anything that could leave `dir` is refused.
+fn capture_path(dir: &Path, recording: &Recording) -> Option<PathBuf> {
+    let event = recording.header("x-github-event");
+    let delivery = recording.header("x-github-delivery");
+    let valid = |x: &str| {
+        !x.is_empty()
+            && x.bytes()
+                .all(|c| c.is_ascii_alphanumeric() || c == b'_' || c == b'-')
+    };
+    if !valid(event) || !valid(delivery) {
+        return None;
+    }
+    let path = dir.join(format!("{}-{}.json", event, delivery));
+    (path.parent() == Some(dir)).then_some(path)
+}
+
+/// Re-processes saved deliveries against the handlers, in order.
+///
+/// In dry-run mode the handlers run as usual but API calls that would change something on
+/// GitHub are only logged.
+pub async fn replay(state: AppState, files: &[impl AsRef<Path>], event: Option<&str>) {
+    for path in files {
+        let path = path.as_ref();
+        let recording = match Recording::load(path, event) {
+            Ok(x) => x,
+            Err(e) => {
+                warn!("Failed to read {}: {}", path.display(), e);
+                continue;
+            }
+        };
+
+        let job = Job {
+            delivery: recording.header("x-github-delivery").to_string(),
+            event: recording.header("x-github-event").to_string(),
+            body: serde_json::to_string(&recording.body).unwrap(),
+        };
+
+        let span = info_span!(
+            "replay",
+            file = %path.display(),
+            delivery = job.delivery,
+            event = job.event,
+            action = tracing::field::Empty,
+            repo = tracing::field::Empty,
+            installation = tracing::field::Empty,
+        );
+        let res = crate::handlers::dispatch(state.clone(), &job)
+            .instrument(span)
+            .await;
+        info!("Replayed {}: {}", path.display(), res.status());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use octocrab::models::webhook_events::WebhookEvent;
+
+    /// Every captured fixture still parses, so payload changes in octocrab are caught.
+    #[test]
+    fn fixtures_parse() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            let recording = Recording::load(&path, None).unwrap();
+            let body = serde_json::to_vec(&recording.body).unwrap();
+            WebhookEvent::try_from_header_and_body(recording.header("x-github-event"), &body)
+                .unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        }
+    }
+
+    #[test]
+    fn signatures_are_redacted() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-github-event", "ping".parse().unwrap());
+        headers.insert("x-hub-signature-256", "sha256=abcd".parse().unwrap());
+
+        let recording =
+            Recording::from_delivery(&headers, br#"{"zen":"Keep it simple."}"#).unwrap();
+        assert_eq!(recording.header("x-github-event"), "ping");
+        assert_eq!(recording.header("x-hub-signature-256"), "<redacted>");
+    }
+
+    #[test]
+    fn captures_stay_in_their_directory() {
+        let dir = Path::new("/var/lib/ddbot/captures");
+        let recording = |event: &str, delivery: &str| Recording {
+            headers: BTreeMap::from([
+                ("x-github-event".to_string(), event.to_string()),
+                ("x-github-delivery".to_string(), delivery.to_string()),
+            ]),
+            body: serde_json::Value::Null,
+        };
+
+        assert_eq!(
+            capture_path(dir, &recording("issues", "72d3162e-cc78-11e3")),
+            Some(dir.join("issues-72d3162e-cc78-11e3.json"))
+        );
+        assert_eq!(capture_path(dir, &recording("../../../tmp/x", "1")), None);
+        assert_eq!(capture_path(dir, &recording("/etc/cron.d/x", "1")), None);
+        assert_eq!(capture_path(dir, &recording("push", "../1")), None);
+        assert_eq!(capture_path(dir, &recording("push", "")), None);
+    }
+}
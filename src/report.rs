@@ -0,0 +1,178 @@
+use std::{
+    fmt::Write,
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+use octocrab::{
+    Octocrab,
+    models::{
+        Repository,
+        webhook_events::{
+            WebhookEvent, WebhookEventPayload, payload::PullRequestWebhookEventAction,
+        },
+    },
+    params::checks::{CheckRunConclusion, CheckRunOutput, CheckRunStatus},
+};
+
+use crate::{github, handlers::Error};
+
+/// The check run summing up what the bot did for a PR.
+pub const CHECK_NAME: &str = "ddbot";
+
+tokio::task_local! {
+    /// The changes the running handler made.
+    static CHANGES: Arc<Mutex<Vec<String>>>;
+}
+
+/// Runs a handler and returns the changes it made on GitHub along with its result, see
+/// [`record`].
+pub async fn collect<F: Future>(fut: F) -> (F::Output, Vec<String>) {
+    let changes = Arc::new(Mutex::new(Vec::new()));
+    let output = CHANGES.scope(changes.clone(), fut).await;
+    let changes = std::mem::take(&mut *changes.lock().unwrap());
+    (output, changes)
+}
+
+/// Records a change of the running handler, by the first line of its description.
+pub fn record(what: &str) {
+    let _ = CHANGES.try_with(|x| {
+        let what = what.lines().next().unwrap_or_default();
+        x.lock().unwrap().push(what.to_string());
+    });
+}
+
+/// What the handlers did for a delivery.
+#[derive(Debug, Default)]
+pub struct Report {
+    /// The changes by handler, without the handlers that didn't change anything.
+    pub changes: Vec<(&'static str, Vec<String>)>,
+    /// The handlers that failed, with why.
+    pub warnings: Vec<(&'static str, String)>,
+}
+
+impl Report {
+    pub fn add(&mut self, handler: &'static str, changes: Vec<String>, res: &Result<(), Error>) {
+        if !changes.is_empty() {
+            self.changes.push((handler, changes));
+        }
+        if let Err(e) = res {
+            self.warnings.push((handler, e.to_string()));
+        }
+    }
+
+    /// The text of the check run, a list of changes per handler and then the warnings.
+    fn text(&self) -> String {
+        let mut text = String::new();
+        for (handler, changes) in &self.changes {
+            writeln!(text, "**{}**", handler).unwrap();
+            for change in changes {
+                writeln!(text, "- {}", change).unwrap();
+            }
+            writeln!(text).unwrap();
+        }
+        if !self.warnings.is_empty() {
+            writeln!(text, "**Warnings**").unwrap();
+            for (handler, warning) in &self.warnings {
+                writeln!(text, "- {} failed: {}", handler, warning).unwrap();
+            }
+        }
+        text
+    }
+
+    fn summary(&self) -> String {
+        let changes: usize = self.changes.iter().map(|(_, x)| x.len()).sum();
+        let mut summary = match changes {
+            0 => "Nothing to do.".to_string(),
+            1 => "Made 1 change.".to_string(),
+            n => format!("Made {} changes.", n),
+        };
+        if !self.warnings.is_empty() {
+            write!(summary, " {} handler(s) failed.", self.warnings.len()).unwrap();
+        }
+        summary
+    }
+}
+
+/// The head commit of a PR that was opened or pushed to, the deliveries the check run is for.
+pub fn head_sha(event: &WebhookEvent) -> Option<&str> {
+    match &event.specific {
+        WebhookEventPayload::PullRequest(payload)
+            if matches!(
+                payload.action,
+                PullRequestWebhookEventAction::Opened
+                    | PullRequestWebhookEventAction::Reopened
+                    | PullRequestWebhookEventAction::Synchronize
+            ) =>
+        {
+            Some(&payload.pull_request.head.sha)
+        }
+        _ => None,
+    }
+}
+
+/// Creates the `ddbot` check run on the commit, neutral if a handler failed.
+pub async fn publish(
+    dry_run: bool,
+    client: &Octocrab,
+    repo: &Repository,
+    sha: &str,
+    report: &Report,
+) -> Result<(), Error> {
+    let owner = repo.owner.as_ref().ok_or("repository without owner")?;
+    let conclusion = if report.warnings.is_empty() {
+        CheckRunConclusion::Success
+    } else {
+        CheckRunConclusion::Neutral
+    };
+    let summary = report.summary();
+    let text = report.text();
+    let checks = client.checks(&owner.login, &repo.name);
+    github::mutate(
+        dry_run,
+        format!("report {} on {}: {}", CHECK_NAME, sha, summary),
+        || {
+            checks
+                .create_check_run(CHECK_NAME, sha)
+                .status(CheckRunStatus::Completed)
+                .conclusion(conclusion)
+                .output(CheckRunOutput {
+                    title: summary.clone(),
+                    summary: summary.clone(),
+                    text: Some(text.clone()),
+                    annotations: Vec::new(),
+                    images: Vec::new(),
+                })
+                .send()
+        },
+    )
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn changes_are_collected_per_handler() {
+        record("outside of a handler");
+        let (_, changes) = collect(async {
+            record("add labels [\"bug\"] to #1");
+            record("comment on #1: Welcome!\n\nThanks for the PR.");
+        })
+        .await;
+        assert_eq!(
+            changes,
+            ["add labels [\"bug\"] to #1", "comment on #1: Welcome!"]
+        );
+
+        let mut report = Report::default();
+        report.add("labeler", changes, &Ok(()));
+        report.add("greeter", Vec::new(), &Ok(()));
+        report.add("dco", Vec::new(), &Err("timed out".into()));
+        assert_eq!(report.summary(), "Made 2 changes. 1 handler(s) failed.");
+        assert!(report.text().starts_with("**labeler**\n- add labels"));
+        assert!(report.text().ends_with("- dco failed: timed out\n"));
+    }
+}
@@ -0,0 +1,35 @@
+use std::time::Duration;
+
+use tracing::{info, warn};
+
+use crate::{AppState, github, handlers::Error};
+
+/// Periodically replaces the tracked repos with the ones the app is installed on, starting right
+/// away. Repos the app was installed on before they were tracked, and every repo with a personal
+/// access token, never show up in `installation` deliveries.
+pub async fn sync_loop(state: AppState, period: Duration) {
+    let mut interval = tokio::time::interval(period);
+
+    loop {
+        interval.tick().await;
+        if let Err(e) = sync(&state).await {
+            warn!("Failed to sync the installed repos: {}", e);
+        }
+    }
+}
+
+/// Replaces the tracked repos with the ones the app is installed on, see
+/// [`crate::db::Db::repo_by_name`].
+pub async fn sync(state: &AppState) -> Result<(), Error> {
+    let mut repos = Vec::new();
+    for id in state.installations.ids().await? {
+        for repo in github::installation_repos(&state.installations, id).await? {
+            if let Some(full_name) = repo.full_name {
+                repos.push((id, repo.id, full_name));
+            }
+        }
+    }
+    state.db.set_repos(&repos)?;
+    info!("Tracking {} installed repos", repos.len());
+    Ok(())
+}
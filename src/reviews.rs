@@ -0,0 +1,136 @@
+use std::{collections::HashMap, time::Duration};
+
+use axum::{
+    Json,
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use serde::Serialize;
+use serde_json::json;
+use tracing::{info, warn};
+
+use crate::{
+    AppState,
+    config::ReviewSlaConfig,
+    db::{Db, ReviewRequest},
+    github,
+    handlers::Error,
+    quiet,
+};
+
+pub const OVERDUE_LABEL: &str = "review-overdue";
+
+/// The open review requests of one reviewer, across every repo.
+#[derive(Debug, Clone, Serialize)]
+pub struct Workload {
+    pub login: String,
+    pub pending: u64,
+    /// Pending requests the reviewer was reminded of.
+    pub overdue: u64,
+    pub overloaded: bool,
+}
+
+/// The workload of every reviewer with pending review requests, the busiest first. Requests
+/// of teams aren't counted, they're not on anyone in particular.
+pub fn workload(db: &Db, max_load: u64) -> rusqlite::Result<Vec<Workload>> {
+    let mut reviewers: HashMap<String, Workload> = HashMap::new();
+    for request in db.review_requests(None)?.into_iter().filter(|x| !x.team) {
+        let workload = reviewers
+            .entry(request.reviewer.to_lowercase())
+            .or_insert_with(|| Workload {
+                login: request.reviewer.clone(),
+                pending: 0,
+                overdue: 0,
+                overloaded: false,
+            });
+        workload.pending += 1;
+        workload.overdue += request.reminded as u64;
+        workload.overloaded = workload.pending > max_load;
+    }
+
+    let mut reviewers: Vec<Workload> = reviewers.into_values().collect();
+    reviewers.sort_by(|a, b| b.pending.cmp(&a.pending).then(a.login.cmp(&b.login)));
+    Ok(reviewers)
+}
+
+pub async fn reviewers(State(state): State<AppState>) -> Response {
+    match workload(&state.db, state.max_review_load) {
+        Ok(reviewers) => Json(reviewers).into_response(),
+        Err(e) => {
+            warn!("Failed to read review requests: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Reminds reviewers of reviews that were requested longer ago than their SLA, once per
+/// request, and labels the PR as having an overdue review.
+pub async fn reminder_loop(state: AppState, sla: ReviewSlaConfig) {
+    let mut interval = tokio::time::interval(Duration::from_secs(60 * 60));
+
+    loop {
+        interval.tick().await;
+        quiet::wait(state.quiet_hours, "review reminders").await;
+        let requests = match state.db.review_requests(None) {
+            Ok(x) => x,
+            Err(e) => {
+                warn!("Failed to read review requests: {}", e);
+                continue;
+            }
+        };
+
+        for request in requests.iter().filter(|x| !x.reminded) {
+            let days = sla.days(request);
+            if Utc::now() - request.requested_at < chrono::Duration::days(days as i64) {
+                continue;
+            }
+            state
+                .rate_limits
+                .wait_for_budget(request.installation, "review reminders")
+                .await;
+            match remind(&state, request, days).await {
+                Ok(()) => info!(
+                    "Reminded {} of the review of {}#{}",
+                    request.reviewer, request.full_name, request.number
+                ),
+                Err(e) => warn!(
+                    "Failed to remind {} of the review of {}#{}: {}",
+                    request.reviewer, request.full_name, request.number, e
+                ),
+            }
+        }
+    }
+}
+
+async fn remind(state: &AppState, request: &ReviewRequest, days: u64) -> Result<(), Error> {
+    let client = state.installations.client(request.installation)?;
+    let reviewer = if request.team {
+        let owner = request.full_name.split('/').next().unwrap_or_default();
+        format!("@{}/{}", owner, request.reviewer)
+    } else {
+        format!("@{}", request.reviewer)
+    };
+    let comment = state.rules().templates.render(
+        &request.full_name,
+        "review_overdue",
+        json!({ "reviewer": reviewer, "days": days }),
+    )?;
+
+    let issues = client.issues_by_id(request.repo);
+    github::comment(state.dry_run, &issues, request.number, &comment).await?;
+    let labels = [OVERDUE_LABEL.to_string()];
+    github::mutate(
+        state.dry_run,
+        format!("add labels {:?} to #{}", labels, request.number),
+        || issues.add_labels(request.number, &labels),
+    )
+    .await?;
+
+    state.db.add_review_request(&ReviewRequest {
+        reminded: true,
+        ..request.clone()
+    })?;
+    Ok(())
+}
@@ -0,0 +1,235 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::http::StatusCode;
+use octocrab::{Octocrab, models};
+use tracing::warn;
+
+use crate::github::{self, Installations};
+
+/// How long a resolved role is used before it's resolved again.
+const TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Roles of people who didn't comment for this long are dropped instead of refreshed.
+const IDLE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// What someone may do in a repo, ordered by the privilege levels of the commands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    None,
+    Collaborator,
+    Member,
+}
+
+impl Role {
+    pub fn from_association(association: &models::AuthorAssociation) -> Self {
+        match association {
+            models::AuthorAssociation::Collaborator => Self::Collaborator,
+            models::AuthorAssociation::Member | models::AuthorAssociation::Owner => Self::Member,
+            _ => Self::None,
+        }
+    }
+
+    /// 0 for anyone, 1 for collaborators and 2 for members of the org.
+    pub fn privilege_level(self) -> u8 {
+        self as u8
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Subject {
+    /// Lowercase `owner/name` and login.
+    Repo(String, String),
+    /// Lowercase org, team slug and login, members of the team have [`Role::Member`].
+    Team(String, String, String),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    role: Role,
+    installation: models::InstallationId,
+    fetched: Instant,
+    used: Instant,
+}
+
+/// Resolves roles from the org, team and repo memberships.
+///
+/// The `author_association` of comments says `CONTRIBUTOR` or `NONE` for org members with a
+/// private membership or commenting on a repo for the first time. Resolved roles are cached,
+/// and refreshed in the background once they're older than [`TTL`] so commands don't wait for
+/// GitHub.
+#[derive(Debug, Clone, Default)]
+pub struct Roles {
+    entries: Arc<Mutex<HashMap<Subject, Entry>>>,
+}
+
+impl Roles {
+    /// The role of `login` in the repo, at least the one `association` gives.
+    pub async fn role(
+        &self,
+        client: &Octocrab,
+        installation: models::InstallationId,
+        repo: &models::Repository,
+        login: &str,
+        association: &models::AuthorAssociation,
+    ) -> octocrab::Result<Role> {
+        let role = Role::from_association(association);
+        if role == Role::Member {
+            return Ok(role);
+        }
+        let full_name = repo.full_name.as_deref().unwrap_or_default();
+        let subject = Subject::Repo(full_name.to_lowercase(), login.to_lowercase());
+        Ok(role.max(self.get(client, installation, subject).await?))
+    }
+
+    /// Whether `login` is an active member of the team of `org`.
+    pub async fn in_team(
+        &self,
+        client: &Octocrab,
+        installation: models::InstallationId,
+        org: &str,
+        team: &str,
+        login: &str,
+    ) -> octocrab::Result<bool> {
+        let subject = Subject::Team(
+            org.to_lowercase(),
+            team.to_lowercase(),
+            login.to_lowercase(),
+        );
+        Ok(self.get(client, installation, subject).await? == Role::Member)
+    }
+
+    async fn get(
+        &self,
+        client: &Octocrab,
+        installation: models::InstallationId,
+        subject: Subject,
+    ) -> octocrab::Result<Role> {
+        if let Some(entry) = self.entries.lock().unwrap().get_mut(&subject) {
+            // Stale ones are used until the refresh gets to them.
+            entry.used = Instant::now();
+            return Ok(entry.role);
+        }
+        let role = resolve(client, &subject).await?;
+        self.entries.lock().unwrap().insert(
+            subject,
+            Entry {
+                role,
+                installation,
+                fetched: Instant::now(),
+                used: Instant::now(),
+            },
+        );
+        Ok(role)
+    }
+
+    pub async fn refresh_loop(self, installations: Installations) {
+        let mut interval = tokio::time::interval(TTL / 10);
+
+        loop {
+            interval.tick().await;
+            self.refresh(&installations).await;
+        }
+    }
+
+    async fn refresh(&self, installations: &Installations) {
+        let stale: Vec<(Subject, models::InstallationId)> = {
+            let mut entries = self.entries.lock().unwrap();
+            entries.retain(|_, x| x.used.elapsed() < IDLE);
+            entries
+                .iter()
+                .filter(|(_, x)| x.fetched.elapsed() >= TTL)
+                .map(|(subject, x)| (subject.clone(), x.installation))
+                .collect()
+        };
+
+        for (subject, installation) in stale {
+            let res = match installations.client(installation) {
+                Ok(client) => resolve(&client, &subject).await,
+                Err(e) => Err(e),
+            };
+            let mut entries = self.entries.lock().unwrap();
+            match res {
+                Ok(role) => {
+                    if let Some(entry) = entries.get_mut(&subject) {
+                        entry.role = role;
+                        entry.fetched = Instant::now();
+                    }
+                }
+                Err(e) => {
+                    // Resolved again when it's needed instead of keeping an outdated role.
+                    warn!("Failed to refresh the role of {:?}: {}", subject, e);
+                    entries.remove(&subject);
+                }
+            }
+        }
+    }
+}
+
+async fn resolve(client: &Octocrab, subject: &Subject) -> octocrab::Result<Role> {
+    match subject {
+        Subject::Repo(repo, login) => {
+            let owner = repo.split('/').next().unwrap_or_default();
+            if has_member(client, &format!("/orgs/{}/members/{}", owner, login)).await? {
+                return Ok(Role::Member);
+            }
+            let route = format!("/repos/{}/collaborators/{}", repo, login);
+            if has_member(client, &route).await? {
+                return Ok(Role::Collaborator);
+            }
+            Ok(Role::None)
+        }
+        Subject::Team(org, team, login) => {
+            let route = format!("/orgs/{}/teams/{}/memberships/{}", org, team, login);
+            let res: octocrab::Result<serde_json::Value> =
+                github::retry(|| client.get(&route, None::<&()>)).await;
+            match res {
+                Ok(membership) if membership["state"] == "active" => Ok(Role::Member),
+                Ok(_) => Ok(Role::None),
+                Err(octocrab::Error::GitHub { source, .. }) if source.status_code == 404 => {
+                    Ok(Role::None)
+                }
+                Err(e) => Err(e),
+            }
+        }
+    }
+}
+
+/// Whether a membership check answers `204 No Content`, it answers 404 (or 302 for org
+/// members the app can't see) otherwise.
+async fn has_member(client: &Octocrab, route: &str) -> octocrab::Result<bool> {
+    let res = github::retry(|| client._get(route)).await?;
+    Ok(res.status() == StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn cached_roles_are_used() {
+        let roles = Roles::default();
+        let subject = Subject::Repo("ddnet/ddnet".to_string(), "someone".to_string());
+        roles.entries.lock().unwrap().insert(
+            subject.clone(),
+            Entry {
+                role: Role::Collaborator,
+                installation: models::InstallationId(1),
+                fetched: Instant::now(),
+                used: Instant::now(),
+            },
+        );
+        let client = Octocrab::default();
+        let role = roles
+            .get(&client, models::InstallationId(1), subject)
+            .await
+            .unwrap();
+        assert_eq!(role, Role::Collaborator);
+        assert_eq!(role.privilege_level(), 1);
+        let owner = Role::from_association(&models::AuthorAssociation::Owner);
+        assert_eq!(role.max(owner), Role::Member);
+    }
+}
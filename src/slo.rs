@@ -0,0 +1,206 @@
+use std::collections::BTreeMap;
+
+use axum::{
+    Json,
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tracing::warn;
+
+use crate::{AppState, db::IssueTimes};
+
+/// The issues the distributions are made of were opened in this many last days.
+pub const WINDOW_DAYS: i64 = 30;
+
+/// How long issues may wait for a first response and for triage, in hours. Issues waiting
+/// longer are listed in the triage digest.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Slo {
+    pub first_response_hours: Option<u64>,
+    pub triage_hours: Option<u64>,
+}
+
+/// Hours from opening to something happening on the issues it happened on.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Distribution {
+    pub count: usize,
+    pub p50: f64,
+    pub p90: f64,
+    pub max: f64,
+}
+
+impl Distribution {
+    /// Quantiles by nearest rank, `None` without any value.
+    pub fn new(mut hours: Vec<f64>) -> Option<Self> {
+        if hours.is_empty() {
+            return None;
+        }
+        hours.sort_by(f64::total_cmp);
+        let quantile = |q: f64| hours[((q * hours.len() as f64).ceil() as usize).max(1) - 1];
+        Some(Self {
+            count: hours.len(),
+            p50: quantile(0.5),
+            p90: quantile(0.9),
+            max: hours[hours.len() - 1],
+        })
+    }
+}
+
+/// The response times of a repo.
+#[derive(Debug, Clone, Serialize)]
+pub struct RepoTimes {
+    pub first_response: Option<Distribution>,
+    pub triage: Option<Distribution>,
+    /// Open issues nobody but their author answered yet.
+    pub waiting_for_response: usize,
+    pub waiting_for_triage: usize,
+}
+
+/// An open issue waiting longer than the SLO allows.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Breach {
+    pub full_name: String,
+    pub number: u64,
+    /// `first response` or `triage`.
+    pub kind: &'static str,
+    pub hours: i64,
+}
+
+fn hours(from: DateTime<Utc>, to: DateTime<Utc>) -> f64 {
+    (to - from).num_seconds() as f64 / 3600.0
+}
+
+/// The response times by repo, of the issues opened since `since`.
+pub fn by_repo(times: &[IssueTimes], since: DateTime<Utc>) -> BTreeMap<String, RepoTimes> {
+    let mut repos: BTreeMap<&str, Vec<&IssueTimes>> = BTreeMap::new();
+    for x in times.iter().filter(|x| x.opened_at >= since) {
+        repos.entry(&x.full_name).or_default().push(x);
+    }
+    repos
+        .into_iter()
+        .map(|(repo, issues)| {
+            let after = |at: fn(&IssueTimes) -> Option<DateTime<Utc>>| {
+                Distribution::new(
+                    issues
+                        .iter()
+                        .filter_map(|x| at(x).map(|at| hours(x.opened_at, at)))
+                        .collect(),
+                )
+            };
+            let waiting = |at: fn(&IssueTimes) -> Option<DateTime<Utc>>| {
+                issues
+                    .iter()
+                    .filter(|x| !x.closed && at(x).is_none())
+                    .count()
+            };
+            let times = RepoTimes {
+                first_response: after(|x| x.first_response_at),
+                triage: after(|x| x.triaged_at),
+                waiting_for_response: waiting(|x| x.first_response_at),
+                waiting_for_triage: waiting(|x| x.triaged_at),
+            };
+            (repo.to_string(), times)
+        })
+        .collect()
+}
+
+/// The open issues waiting longer than the SLO allows, the longest waiting first.
+pub fn breaches(times: &[IssueTimes], slo: Slo, now: DateTime<Utc>) -> Vec<Breach> {
+    let mut breaches = Vec::new();
+    for x in times.iter().filter(|x| !x.closed) {
+        let waited = (now - x.opened_at).num_hours();
+        let checks = [
+            (
+                "first response",
+                slo.first_response_hours,
+                x.first_response_at,
+            ),
+            ("triage", slo.triage_hours, x.triaged_at),
+        ];
+        for (kind, limit, done) in checks {
+            if let Some(limit) = limit
+                && done.is_none()
+                && waited > limit as i64
+            {
+                breaches.push(Breach {
+                    full_name: x.full_name.clone(),
+                    number: x.number,
+                    kind,
+                    hours: waited,
+                });
+            }
+        }
+    }
+    breaches.sort_by_key(|x| -x.hours);
+    breaches
+}
+
+/// `GET /stats/response-times`: the response times by repo of the last [`WINDOW_DAYS`] days, and
+/// the issues breaching the SLO.
+pub async fn response_times(State(state): State<AppState>) -> Response {
+    let now = Utc::now();
+    let since = now - chrono::Duration::days(WINDOW_DAYS);
+    match state.db.issue_times(since) {
+        Ok(times) => Json(serde_json::json!({
+            "window_days": WINDOW_DAYS,
+            "repos": by_repo(&times, since),
+            "breaches": breaches(&times, state.slo, now),
+        }))
+        .into_response(),
+        Err(e) => {
+            warn!("Failed to read issue times: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn response_times_and_breaches() {
+        let now = Utc::now();
+        let issue = |number, age: i64, response: Option<i64>, closed| IssueTimes {
+            full_name: "ddnet/ddnet".to_string(),
+            number,
+            opened_at: now - chrono::Duration::hours(age),
+            first_response_at: response.map(|x| now - chrono::Duration::hours(age - x)),
+            triaged_at: None,
+            closed,
+        };
+        let times = [
+            issue(1, 100, Some(2), true),
+            issue(2, 90, Some(10), false),
+            issue(3, 80, None, false),
+            issue(4, 10, None, false),
+        ];
+        let repos = by_repo(&times, now - chrono::Duration::days(30));
+        let repo = &repos["ddnet/ddnet"];
+        assert_eq!(
+            repo.first_response,
+            Some(Distribution {
+                count: 2,
+                p50: 2.0,
+                p90: 10.0,
+                max: 10.0
+            })
+        );
+        assert_eq!(repo.waiting_for_response, 2);
+        assert_eq!(repo.triage, None);
+
+        let slo = Slo {
+            first_response_hours: Some(48),
+            triage_hours: None,
+        };
+        let breaches = breaches(&times, slo, now);
+        assert_eq!(breaches.len(), 1);
+        assert_eq!(
+            (breaches[0].number, breaches[0].kind),
+            (3, "first response")
+        );
+    }
+}
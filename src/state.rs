@@ -0,0 +1,224 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+
+use octocrab::models;
+use tokio::sync::RwLock;
+
+use crate::{
+    batch::Batches,
+    budget::ApiUsage,
+    bulk,
+    cache::EtagCache,
+    config::{BenchmarksConfig, ClaConfig, Config, LabelDefinition, QuietHours, TriggerCaller},
+    db::Db,
+    forward::Forwarder,
+    github::Installations,
+    handlers::Registry,
+    limiter::{CommandLimiter, Limiter},
+    login::Login,
+    queue::Queue,
+    ratelimit::RateLimits,
+    roles::Roles,
+    slo::Slo,
+    status::StatusComments,
+    templates::Templates,
+};
+
+/// Everything the handlers and background jobs share.
+#[derive(Debug, Clone)]
+pub struct AppState {
+    pub installations: Installations,
+    /// Deliveries must be signed with it when set.
+    pub webhook_secret: Option<String>,
+    pub forwarder: Forwarder,
+    pub triage_feed: Arc<RwLock<String>>,
+    pub db: Db,
+    pub rate_limits: RateLimits,
+    pub repo_labels: EtagCache<models::RepositoryId, Vec<models::Label>>,
+    /// Members of teams by lowercase org and team slug.
+    pub team_members: EtagCache<(String, String), Vec<models::Author>>,
+    /// Repos by lowercase full name.
+    pub repos: EtagCache<String, models::Repository>,
+    /// What commenters may do, see [`Roles`].
+    pub roles: Roles,
+    pub limiter: Limiter,
+    pub command_limiter: CommandLimiter,
+    pub max_body_bytes: usize,
+    /// GitHub API calls the handlers of a delivery may make together.
+    pub max_api_calls_per_event: usize,
+    /// How long the handlers of a delivery may take together.
+    pub event_timeout: Duration,
+    pub api_usage: ApiUsage,
+    pub bulk: bulk::Operations,
+    /// Where received deliveries are recorded, if anywhere.
+    pub capture_dir: Option<PathBuf>,
+    /// Only log API calls that would change something on GitHub.
+    pub dry_run: bool,
+    /// Replaced when the config file changes, see [`AppState::rules`].
+    pub rules: Arc<std::sync::RwLock<Arc<Rules>>>,
+    /// Where deliveries wait to be handled, they're handled as they arrive without one.
+    pub queue: Option<Queue>,
+    pub started_at: DateTime<Utc>,
+    /// When the last delivery was handled, see `!ddnetbot status`.
+    pub last_handled: Arc<Mutex<Option<DateTime<Utc>>>>,
+    pub status: StatusComments,
+    /// Changes waiting to be made together, see [`crate::batch`].
+    pub batches: Batches,
+    pub admin_token: Option<String>,
+    pub login: Option<Login>,
+    pub benchmarks: Option<BenchmarksConfig>,
+    /// Callers of `/triggers/<name>` by name, see [`crate::triggers`].
+    pub triggers: HashMap<String, TriggerCaller>,
+    pub maintainer_only_labels: Vec<String>,
+    /// The labels of the areas people triage by login, see [`crate::triage::next_issues`].
+    pub triage_areas: HashMap<String, Vec<String>>,
+    /// Where subscription digests go, see [`crate::subscriptions::digest_loop`].
+    pub subscription_webhooks: HashMap<String, String>,
+    pub subscription_digest_issue: Option<(String, u64)>,
+    pub mentionable_teams: Vec<String>,
+    /// Taken off issues while they're snoozed with `!ddnetbot snooze`.
+    pub snooze_label: String,
+    pub max_review_load: u64,
+    pub blocker_ping_days: u64,
+    pub slo: Slo,
+    pub quiet_hours: Option<QuietHours>,
+    /// Who `!ddnetbot roulette` picks reviewers from.
+    pub review_pool: Vec<String>,
+    /// What `!ddnetbot todo-issues` opens issues for.
+    pub todo_markers: Vec<String>,
+    pub dco: bool,
+    pub summary_check: bool,
+    pub setup_issue: bool,
+    pub cla: Option<ClaConfig>,
+    pub labels: Vec<LabelDefinition>,
+}
+
+impl AppState {
+    /// Fails when the private key can't be read, the database can't be opened or the rules
+    /// are invalid.
+    pub fn from_config(config: &Config) -> Result<Self, Box<dyn Error>> {
+        let rate_limits = RateLimits::from_config(config);
+        let installations = match &config.github_token {
+            Some(token) => {
+                Installations::personal(&config.github_api_url, token, rate_limits.clone())
+                    .map_err(|e| e as Box<dyn Error>)?
+            }
+            None => {
+                let key = app_key(config)?;
+                let app_id = config.github_app_id.ok_or("GITHUB_APP_ID is not set")?;
+                Installations::new(
+                    &config.github_api_url,
+                    app_id.into(),
+                    key,
+                    rate_limits.clone(),
+                )
+                .map_err(|e| e as Box<dyn Error>)?
+            }
+        };
+
+        let db = Db::open(&config.database_path)?;
+        Ok(Self {
+            installations,
+            webhook_secret: config.webhook_secret.clone(),
+            forwarder: Forwarder::from_config(config),
+            triage_feed: Arc::new(RwLock::new(String::new())),
+            batches: Batches::new(&config.batch_windows, db.clone())?,
+            db,
+            rate_limits,
+            repo_labels: EtagCache::new(Duration::from_secs(60)),
+            team_members: EtagCache::new(Duration::from_secs(60)),
+            repos: EtagCache::new(Duration::from_secs(60)),
+            roles: Roles::default(),
+            limiter: Limiter::from_config(config),
+            command_limiter: CommandLimiter::from_config(config),
+            max_body_bytes: config.max_body_bytes,
+            max_api_calls_per_event: config.max_api_calls_per_event,
+            event_timeout: Duration::from_secs(config.event_timeout_secs),
+            api_usage: ApiUsage::default(),
+            bulk: bulk::Operations::default(),
+            capture_dir: config.capture_dir.clone(),
+            dry_run: config.dry_run,
+            rules: Arc::new(std::sync::RwLock::new(Arc::new(Rules::from_config(
+                config,
+            )?))),
+            queue: None,
+            started_at: Utc::now(),
+            last_handled: Arc::default(),
+            status: StatusComments::default(),
+            admin_token: config.admin_token.clone(),
+            login: Login::from_config(config),
+            benchmarks: config.benchmarks.clone(),
+            triggers: config.triggers.clone(),
+            maintainer_only_labels: config.maintainer_only_labels.clone(),
+            triage_areas: config.triage_areas.clone(),
+            subscription_webhooks: config.subscription_webhooks.clone(),
+            subscription_digest_issue: config.subscription_digest_issue.clone(),
+            mentionable_teams: config.mentionable_teams.clone(),
+            snooze_label: config.snooze_label.clone(),
+            max_review_load: config.max_review_load,
+            blocker_ping_days: config.blocker_ping_days,
+            slo: Slo {
+                first_response_hours: config.slo_first_response_hours,
+                triage_hours: config.slo_triage_hours,
+            },
+            quiet_hours: config.quiet_hours,
+            review_pool: config.review_pool.clone(),
+            todo_markers: config.todos.markers.clone(),
+            dco: config.dco,
+            summary_check: config.summary_check,
+            setup_issue: config.setup_issue,
+            cla: config.cla.clone(),
+            labels: config.labels.clone(),
+        })
+    }
+}
+
+impl AppState {
+    /// The current rules, deliveries keep using the ones they started with.
+    pub fn rules(&self) -> Arc<Rules> {
+        self.rules.read().unwrap().clone()
+    }
+}
+
+/// The settings that are swapped in when the config file changes: the handlers with their
+/// scripts, plugins, checklist and repos, and the comment templates.
+#[derive(Debug)]
+pub struct Rules {
+    pub handlers: Registry,
+    pub templates: Templates,
+}
+
+impl Rules {
+    pub fn from_config(config: &Config) -> Result<Self, Box<dyn Error>> {
+        let templates = Templates::from_config(config)?;
+        for (label, template) in &config.label_help {
+            if !templates.has(template) {
+                return Err(format!("unknown template {} for {}", template, label).into());
+            }
+        }
+        Ok(Self {
+            handlers: Registry::builtin(config)?,
+            templates,
+        })
+    }
+}
+
+/// Reads the GitHub App private key.
+pub fn app_key(config: &Config) -> Result<jsonwebtoken::EncodingKey, Box<dyn Error>> {
+    let path = config
+        .app_private_key_path
+        .as_ref()
+        .ok_or("APP_PRIVATE_KEY_PATH is not set")?;
+    let private_key = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    Ok(jsonwebtoken::EncodingKey::from_rsa_pem(
+        private_key.as_bytes(),
+    )?)
+}
@@ -0,0 +1,177 @@
+use std::time::Duration;
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use octocrab::params;
+use tracing::{info, warn};
+
+use crate::{AppState, github, handlers::Error};
+
+const LAST_SYNC_KEY: &str = "stats_last_sync";
+
+pub async fn all_stats(State(state): State<AppState>) -> Response {
+    match state.db.all_stats() {
+        Ok(stats) => Json(stats).into_response(),
+        Err(e) => {
+            warn!("Failed to read stats: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+pub async fn user_stats(State(state): State<AppState>, Path(user): Path<String>) -> Response {
+    match state.db.user_stats(&user) {
+        Ok(stats) => Json(stats).into_response(),
+        Err(e) => {
+            warn!("Failed to read stats: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Imports merged PRs and their reviews from the API every `interval`, so stats also cover
+/// activity from before the bot was installed or deliveries that were missed.
+pub async fn sync_loop(state: AppState, interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+
+    loop {
+        interval.tick().await;
+        let since = state
+            .db
+            .get_kv(LAST_SYNC_KEY)
+            .ok()
+            .flatten()
+            .and_then(|x| DateTime::parse_from_rfc3339(&x).ok())
+            .map(|x| x.with_timezone(&Utc));
+        let started = Utc::now();
+
+        match sync(&state, since).await {
+            Ok(()) => {
+                state.db.set_kv(LAST_SYNC_KEY, &started.to_rfc3339()).ok();
+                info!("Synced contributor stats");
+            }
+            Err(e) => warn!("Failed to sync contributor stats: {}", e),
+        }
+    }
+}
+
+/// Imports the PRs closed since `since`, newest first, or every closed PR without it.
+async fn sync(state: &AppState, since: Option<DateTime<Utc>>) -> Result<(), Error> {
+    let db = &state.db;
+    let rules = state.rules();
+    for (installation, client, repo) in github::installed_repos(&state.installations).await? {
+        let (Some(owner), Some(full_name)) = (repo.owner, repo.full_name) else {
+            continue;
+        };
+        if !rules.handlers.allows(Some(&full_name)) {
+            continue;
+        }
+        state
+            .rate_limits
+            .wait_for_budget(installation, "stats sync")
+            .await;
+        let pulls = client.pulls(&owner.login, &repo.name);
+        let mut page = github::retry(|| {
+            pulls
+                .list()
+                .state(params::State::Closed)
+                .sort(params::pulls::Sort::Updated)
+                .direction(params::Direction::Descending)
+                .per_page(100)
+                .send()
+        })
+        .await?;
+
+        'pages: loop {
+            for pr in page.take_items() {
+                if let (Some(since), Some(updated_at)) = (since, pr.updated_at)
+                    && updated_at < since
+                {
+                    break 'pages;
+                }
+                let (Some(merged_at), Some(user)) = (pr.merged_at, &pr.user) else {
+                    continue;
+                };
+                db.add_contribution(&full_name, pr.number, &user.login, "merged_pr", merged_at)
+                    .ok();
+
+                let mut reviews =
+                    github::retry(|| pulls.list_reviews(pr.number).per_page(100).send()).await?;
+                let mut items = reviews.take_items();
+                while let Some(mut next) = github::retry(|| client.get_page(&reviews.next)).await? {
+                    items.append(&mut next.take_items());
+                    reviews = next;
+                }
+                for review in items {
+                    if let (Some(user), Some(submitted_at)) = (review.user, review.submitted_at) {
+                        db.add_contribution(
+                            &full_name,
+                            pr.number,
+                            &user.login,
+                            "review",
+                            submitted_at,
+                        )
+                        .ok();
+                    }
+                }
+            }
+            match github::retry(|| client.get_page(&page.next)).await? {
+                Some(next) => page = next,
+                None => break,
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use figment::{Figment, providers::Serialized};
+    use serde_json::json;
+
+    use super::*;
+    use crate::Config;
+
+    #[tokio::test]
+    async fn stats_count_contributions_and_triage() {
+        let figment = Figment::from(Serialized::defaults(json!({
+            "github_token": "ghp_test",
+            "database_path": ":memory:",
+        })));
+        let config = Config::from_figment(&figment).unwrap();
+        let state = AppState::from_config(&config).unwrap();
+        let db = &state.db;
+        let at = Utc::now();
+        db.add_contribution("ddnet/ddnet", 1, "Robyt3", "merged_pr", at)
+            .unwrap();
+        // The sync imports the same PR again.
+        db.add_contribution("ddnet/ddnet", 1, "Robyt3", "merged_pr", at)
+            .unwrap();
+        db.add_contribution("ddnet/ddnet", 2, "Robyt3", "review", at)
+            .unwrap();
+        db.audit("ddnet/ddnet", Some(3), "Robyt3", "label", "+bug")
+            .unwrap();
+        db.audit("ddnet/ddnet", Some(3), "Robyt3", "claim", "")
+            .unwrap();
+
+        let res = user_stats(State(state.clone()), Path("robyt3".to_string())).await;
+        let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let stats: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(stats, json!({ "merged_prs": 1, "reviews": 1, "triage": 1 }));
+
+        let res = all_stats(State(state)).await;
+        let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let stats: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(stats.as_object().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,82 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use octocrab::models::{CommentId, RepositoryId};
+use tokio::sync::Mutex;
+
+use crate::{
+    github,
+    handlers::{Context, Error},
+};
+
+/// Marks the comment as the bot's status comment.
+const MARKER: &str = "<!-- ddbot:status -->";
+
+/// The one comment per PR the bot keeps its status in, edited instead of posting new
+/// comments.
+///
+/// The comment is made of named sections, e.g. the labels that were applied or the CI
+/// result, each set on its own by the handler that knows about it.
+#[derive(Debug, Clone, Default)]
+pub struct StatusComments {
+    /// Held while a comment is updated, so concurrent deliveries don't create two.
+    lock: Arc<Mutex<()>>,
+}
+
+impl StatusComments {
+    /// Sets `section` of the status comment on PR `number`, creating the comment if needed.
+    pub async fn set(
+        &self,
+        ctx: &Context,
+        repo: RepositoryId,
+        number: u64,
+        section: &str,
+        text: &str,
+    ) -> Result<(), Error> {
+        let _guard = self.lock.lock().await;
+        let db = &ctx.state.db;
+        let mut status = db.status_comment(repo, number)?.unwrap_or_default();
+        if status.sections.get(section).map(|x| x.as_str()) == Some(text) {
+            return Ok(());
+        }
+        status
+            .sections
+            .insert(section.to_string(), text.to_string());
+        let body = render(&status.sections);
+
+        let issues = ctx.client.issues_by_id(repo);
+        if let Some(id) = status.comment_id {
+            let res = github::mutate(
+                ctx.state.dry_run,
+                format!("update status comment on #{}: {}", number, body),
+                || issues.update_comment(CommentId(id), &body),
+            )
+            .await;
+            match res {
+                Ok(()) => {
+                    db.set_status_comment(repo, number, &status)?;
+                    return Ok(());
+                }
+                // Someone deleted it, post a new one.
+                Err(octocrab::Error::GitHub { source, .. }) if source.status_code == 404 => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        let Some(comment) =
+            github::create_comment(ctx.state.dry_run, &issues, number, &body).await?
+        else {
+            return Ok(());
+        };
+        status.comment_id = Some(comment.id.0);
+        db.set_status_comment(repo, number, &status)?;
+        Ok(())
+    }
+}
+
+fn render(sections: &BTreeMap<String, String>) -> String {
+    let mut body = format!("{}\n### Status\n\n", MARKER);
+    for (section, text) in sections {
+        body.push_str(&format!("- **{}**: {}\n", section, text));
+    }
+    body
+}
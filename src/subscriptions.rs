@@ -0,0 +1,82 @@
+use std::time::Duration;
+
+use serde_json::json;
+use tracing::{info, warn};
+
+use crate::{AppState, db::DigestItem, discord, github, handlers::Error, quiet};
+
+/// Whether a digest can reach the person, through their Discord webhook or the digest issue.
+/// People it can't reach are cc'd right away even if they asked for a digest.
+pub fn digest_reachable(state: &AppState, login: &str) -> bool {
+    state.subscription_webhooks.contains_key(login) || state.subscription_digest_issue.is_some()
+}
+
+/// Periodically sends the people who asked for a digest what opened in their areas since the
+/// last one, through their Discord webhook or as a comment on the digest issue.
+pub async fn digest_loop(state: AppState, period: Duration) {
+    let http = reqwest::Client::new();
+    let mut interval = tokio::time::interval(period);
+
+    loop {
+        interval.tick().await;
+        quiet::wait(state.quiet_hours, "subscription digests").await;
+        let items = match state.db.digest_items() {
+            Ok(x) => x,
+            Err(e) => {
+                warn!("Failed to read the subscription digests: {}", e);
+                continue;
+            }
+        };
+        for digest in items.chunk_by(|a, b| a.full_name == b.full_name && a.login == b.login) {
+            let (full_name, login) = (&digest[0].full_name, &digest[0].login);
+            // Dropped when notifications were turned off for the repo since.
+            let enabled = state
+                .rules()
+                .handlers
+                .enabled("subscriptions", Some(full_name));
+            // Kept for the next digest if it can't be sent.
+            if enabled && let Err(e) = send(&state, &http, digest).await {
+                warn!(
+                    "Failed to send {} their digest of {}: {}",
+                    login, full_name, e
+                );
+                continue;
+            }
+            if let Err(e) = state.db.remove_digest_items(full_name, login) {
+                warn!("Failed to remove the digest of {}: {}", login, e);
+            }
+        }
+    }
+}
+
+async fn send(
+    state: &AppState,
+    http: &reqwest::Client,
+    digest: &[DigestItem],
+) -> Result<(), Error> {
+    let (full_name, login) = (&digest[0].full_name, &digest[0].login);
+    let items: Vec<_> = digest
+        .iter()
+        .map(|x| json!({ "number": x.number, "title": x.title }))
+        .collect();
+    let message = state.rules().templates.render(
+        full_name,
+        "subscription_digest",
+        json!({ "user": login, "repo": full_name, "items": items }),
+    )?;
+
+    if let Some(url) = state.subscription_webhooks.get(login) {
+        discord::post(http, state.dry_run, url, &message).await?;
+    } else if let Some((digest_repo, number)) = &state.subscription_digest_issue {
+        let Some((repo, installation)) = state.db.repo_by_name(digest_repo)? else {
+            return Err(format!("the app isn't installed on {}", digest_repo).into());
+        };
+        let client = state.installations.client(installation)?;
+        let issues = client.issues_by_id(repo);
+        github::comment(state.dry_run, &issues, *number, &message).await?;
+    } else {
+        return Err("there's neither a webhook nor a digest issue".into());
+    }
+    info!("Sent {} their digest of {}", login, full_name);
+    Ok(())
+}
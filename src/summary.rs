@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+
+use serde_json::json;
+
+#[cfg(feature = "llm-summary")]
+use crate::config::SummaryLlmConfig;
+use crate::handlers::Error;
+
+/// How many decisions a summary lists, the latest ones.
+const MAX_DECISIONS: usize = 10;
+/// How long a quoted decision may get.
+const EXCERPT_LEN: usize = 160;
+
+/// Phrases a comment settling something usually has.
+const DECISION_PHRASES: &[&str] = &[
+    "we decided",
+    "decided to",
+    "agreed",
+    "let's go with",
+    "we'll go with",
+    "the plan is",
+    "in conclusion",
+    "won't fix",
+    "closing in favor",
+    "superseded by",
+];
+
+/// A comment of the thread, or its description.
+#[derive(Debug, Clone)]
+pub struct Post<'a> {
+    pub login: &'a str,
+    pub body: &'a str,
+    pub url: &'a str,
+}
+
+/// What `!ddnetbot summarize` tells someone catching up on a long thread.
+#[derive(Debug, Default, serde::Serialize)]
+pub struct Summary {
+    pub comments: usize,
+    /// The most active first.
+    pub participants: Vec<Participant>,
+    /// Lines that look like decisions, with who wrote them and a link.
+    pub decisions: Vec<serde_json::Value>,
+    /// Issues and PRs referenced as `#123`, in order of their first mention.
+    pub references: Vec<u64>,
+}
+
+#[derive(Debug, PartialEq, Eq, serde::Serialize)]
+pub struct Participant {
+    pub login: String,
+    /// The description counts as one.
+    pub comments: usize,
+}
+
+/// Summarizes the thread with simple heuristics, the description first.
+pub fn summarize(posts: &[Post]) -> Summary {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    let mut summary = Summary {
+        comments: posts.len().saturating_sub(1),
+        ..Summary::default()
+    };
+    for post in posts {
+        *counts.entry(post.login).or_default() += 1;
+        for line in post.body.lines() {
+            let lower = line.to_lowercase();
+            if !line.starts_with('>') && DECISION_PHRASES.iter().any(|x| lower.contains(x)) {
+                let excerpt: String = line.trim().chars().take(EXCERPT_LEN).collect();
+                summary.decisions.push(json!({
+                    "login": post.login,
+                    "excerpt": excerpt,
+                    "url": post.url,
+                }));
+            }
+        }
+        for number in references(post.body) {
+            if !summary.references.contains(&number) {
+                summary.references.push(number);
+            }
+        }
+    }
+    let skip = summary.decisions.len().saturating_sub(MAX_DECISIONS);
+    summary.decisions.drain(..skip);
+    summary.participants = counts
+        .into_iter()
+        .map(|(login, comments)| Participant {
+            login: login.to_string(),
+            comments,
+        })
+        .collect();
+    summary
+        .participants
+        .sort_by(|a, b| (b.comments, &a.login).cmp(&(a.comments, &b.login)));
+    summary
+}
+
+/// The `#123` references in the text.
+fn references(text: &str) -> impl Iterator<Item = u64> + '_ {
+    text.split('#').skip(1).filter_map(|x| {
+        let digits: String = x.chars().take_while(char::is_ascii_digit).collect();
+        digits.parse().ok()
+    })
+}
+
+/// A summary written by a language model behind an OpenAI compatible chat completions API.
+#[cfg(feature = "llm-summary")]
+pub async fn llm_summary(
+    http: &reqwest::Client,
+    config: &SummaryLlmConfig,
+    title: &str,
+    posts: &[Post<'_>],
+) -> Result<String, Error> {
+    let mut thread = format!("# {}\n", title);
+    for post in posts {
+        thread.push_str(&format!("\n## {}\n{}\n", post.login, post.body));
+    }
+    let thread: String = thread.chars().take(config.max_chars).collect();
+    let body = json!({
+            "model": config.model,
+            "messages": [
+                {
+                    "role": "system",
+                    "content": "Summarize this GitHub issue thread for a newcomer in a few \
+                        sentences: the problem, what was decided and what's left to do.",
+                },
+            { "role": "user", "content": thread },
+        ],
+    });
+    let res = http
+        .post(&config.url)
+        .bearer_auth(&config.api_key)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(body.to_string())
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    let res: serde_json::Value = serde_json::from_str(&res)?;
+    res["choices"][0]["message"]["content"]
+        .as_str()
+        .map(|x| x.trim().to_string())
+        .ok_or_else(|| "the model didn't answer".into())
+}
+
+/// Without the `llm-summary` feature there's only the heuristic summary.
+#[cfg(not(feature = "llm-summary"))]
+pub async fn llm_summary(
+    _http: &reqwest::Client,
+    _config: &crate::config::SummaryLlmConfig,
+    _title: &str,
+    _posts: &[Post<'_>],
+) -> Result<String, Error> {
+    Err("built without the llm-summary feature".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decisions_and_references_are_picked_up() {
+        let post = |login, body| Post {
+            login,
+            body,
+            url: "https://github.com/ddnet/ddnet/issues/1",
+        };
+        let posts = [
+            post("a", "The tiles flicker, see #12."),
+            post("b", "Same here, also #12 and #34"),
+            post("a", "> we decided nothing\nBump"),
+            post("c", "We decided to revert #34 for now."),
+        ];
+        let summary = summarize(&posts);
+        assert_eq!(summary.comments, 3);
+        let participants: Vec<_> = summary
+            .participants
+            .iter()
+            .map(|x| (x.login.as_str(), x.comments))
+            .collect();
+        assert_eq!(participants, [("a", 2), ("b", 1), ("c", 1)]);
+        assert_eq!(summary.references, [12, 34]);
+        assert_eq!(summary.decisions.len(), 1);
+        assert_eq!(summary.decisions[0]["login"], "c");
+    }
+}
@@ -0,0 +1,55 @@
+use std::time::Duration;
+
+use axum::Router;
+use listenfd::ListenFd;
+use sd_notify::NotifyState;
+use tracing::{info, warn};
+
+/// Serves `app` on a socket passed by systemd socket activation, if there is one.
+///
+/// Returns `app` back when the process wasn't socket activated.
+pub async fn serve_inherited(app: Router) -> std::io::Result<Option<Router>> {
+    let mut fds = ListenFd::from_env();
+
+    if let Ok(Some(listener)) = fds.take_tcp_listener(0) {
+        info!("Listening on inherited socket {:?}", listener.local_addr());
+        listener.set_nonblocking(true)?;
+        let listener = tokio::net::TcpListener::from_std(listener)?;
+        notify_ready();
+        axum::serve(listener, app).await?;
+        return Ok(None);
+    }
+
+    if let Ok(Some(listener)) = fds.take_unix_listener(0) {
+        info!("Listening on inherited unix socket");
+        listener.set_nonblocking(true)?;
+        let listener = tokio::net::UnixListener::from_std(listener)?;
+        notify_ready();
+        axum::serve(listener, app).await?;
+        return Ok(None);
+    }
+
+    Ok(Some(app))
+}
+
+/// Tells systemd the service is ready and starts pinging the watchdog if it's enabled.
+///
+/// Does nothing when not running under systemd.
+pub fn notify_ready() {
+    if let Err(e) = sd_notify::notify(false, &[NotifyState::Ready]) {
+        warn!("Failed to notify systemd: {}", e);
+    }
+
+    let mut usec = 0;
+    if sd_notify::watchdog_enabled(false, &mut usec) {
+        let interval = Duration::from_micros(usec) / 2;
+        info!("Pinging the systemd watchdog every {:?}", interval);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            loop {
+                interval.tick().await;
+                sd_notify::notify(false, &[NotifyState::Watchdog]).ok();
+            }
+        });
+    }
+}
@@ -0,0 +1,700 @@
+use std::{collections::HashMap, error::Error, sync::Arc};
+
+use figment::{
+    Figment,
+    providers::{Format, Toml},
+};
+use minijinja::{Environment, UndefinedBehavior};
+use serde::Serialize;
+
+use crate::config::Config;
+
+/// English text of every comment the bot posts, overridable in the `[templates]` table of the
+/// config. The variables each template gets are listed next to it.
+const DEFAULTS: &[(&str, &str)] = &[
+    // author, repo
+    (
+        "welcome",
+        "Thanks for your first pull request to DDNet! \
+A maintainer will review it soon, in the meantime make sure the CI checks pass.",
+    ),
+    // user, minutes
+    (
+        "rate_limited",
+        "@{{ user }} you are running commands too quickly, please try again in {{ minutes }} minutes.",
+    ),
+    // user, message
+    (
+        "reminder",
+        "⏰ @{{ user }} you asked to be reminded{% if message %}: {{ message }}{% else %} of this.{% endif %}",
+    ),
+    // user, label
+    (
+        "snooze_over",
+        "@{{ user }} the snooze is over, this issue is back in `{{ label }}`.",
+    ),
+    // user, errors
+    (
+        "commands_rejected",
+        "@{{ user }} none of the commands were applied:\n{% for x in errors %}\n- {{ x }}{% endfor %}",
+    ),
+    // user
+    (
+        "draft_not_ready",
+        "@{{ user }} this PR is still a draft, please mark it as ready for review instead.",
+    ),
+    // author, base
+    (
+        "needs_rebase",
+        "@{{ author }} this PR conflicts with `{{ base }}` now, please rebase it.",
+    ),
+    // pusher, repo, branch, commits (each with id, message, url and files)
+    (
+        "protected_push",
+        "@{{ pusher }} pushed directly to `{{ branch }}` of {{ repo }}, changing protected paths:\n\n\
+{% for commit in commits %}- [{{ commit.id[:8] }}]({{ commit.url }}) {{ commit.message }}: \
+{{ commit.files | join(\", \") }}\n{% endfor %}",
+    ),
+    // author
+    (
+        "discussion_bug",
+        "@{{ author }} this sounds like a bug. Bugs are easier to track as issues, a maintainer \
+can convert this discussion into one, or you can open an issue with the steps to reproduce it.",
+    ),
+    // repo, name, tag, url, changelog, downloads (each with `name`, `url`)
+    (
+        "release_announcement",
+        "**{{ repo }} {{ name }}** is out! {{ url }}\n\n{{ changelog }}\
+{% if downloads %}\n\nDownloads:{% for x in downloads %}\n- [{{ x.name }}]({{ x.url }}){% endfor %}{% endif %}",
+    ),
+    // tag, url
+    (
+        "known_issues",
+        "Known issues in [{{ tag }}]({{ url }}). Please comment here if you run into a problem \
+with this release that isn't listed yet, or open an issue and link it.",
+    ),
+    // done, total, percent, bar, items (each with `item`, `title`, `url`, `state`, `done`)
+    (
+        "tracking_progress",
+        "### Progress: {{ done }}/{{ total }} ({{ percent }}%)\n\n`{{ bar }}`\n\
+{% for x in items %}\n- {% if x.done %}✅{% else %}⬜{% endif %} [{{ x.title }}]({{ x.url }}) \
+({{ x.state }}){% endfor %}",
+    ),
+    // pr, revert
+    (
+        "regression_reopened",
+        "Reopened, #{{ pr }} fixed this but was reverted in #{{ revert }}.",
+    ),
+    // revert, issues
+    (
+        "pr_reverted",
+        "This was reverted in #{{ revert }}.{% if issues %} Reopened the issues it fixed: \
+{% for x in issues %}#{{ x }}{% if not loop.last %}, {% endif %}{% endfor %}.{% endif %}",
+    ),
+    // name, count, occurrences (each with `created_at`, `sha`, `pr`, `url`)
+    (
+        "flaky_test",
+        "The `{{ name }}` check failed and then passed on the same commit {{ count }} \
+time{% if count > 1 %}s{% endif %}:\n{% for x in occurrences %}\n- {{ x.created_at }}: \
+[{{ x.sha[:7] }}]({{ x.url }}){% if x.pr %} in #{{ x.pr }}{% endif %}{% endfor %}",
+    ),
+    // author
+    (
+        "demo_request",
+        "@{{ author }} thanks for the report! Gameplay bugs are much easier to fix with a demo: \
+please attach one (zipped, GitHub doesn't take `.demo` files) and name the map it happened on.",
+    ),
+    // user, team, members
+    (
+        "team_ping",
+        "@{{ user }} is asking the {{ team }} team: \
+{% for x in members %}@{{ x }}{% if not loop.last %} {% endif %}{% endfor %}",
+    ),
+    // reviewers
+    (
+        "reviewer_suggestions",
+        "Suggested reviewers, by their recent commits to the changed files: \
+{% for x in reviewers %}@{{ x }}{% if not loop.last %}, {% endif %}{% endfor %}",
+    ),
+    // reviewer
+    (
+        "roulette",
+        "🎲 @{{ reviewer }} was picked to review this PR.",
+    ),
+    // user
+    (
+        "roulette_empty",
+        "@{{ user }} there is no reviewer to pick right now, everyone in the pool is busy.",
+    ),
+    // author, files
+    (
+        "protocol_change",
+        "@{{ author }} this PR changes the network protocol ({% for x in files %}`{{ x }}`\
+{% if not loop.last %}, {% endif %}{% endfor %}). Does it need a version bump? A maintainer has \
+to acknowledge the change with `!ddnetbot ack-protocol` before it can be merged.",
+    ),
+    // user, label, reason
+    (
+        "workflow_rejected",
+        "@{{ user }} `{{ label }}` was taken off again, {{ reason }}.",
+    ),
+    // label
+    (
+        "protocol_unacknowledged",
+        "This PR changes the network protocol, `{{ label }}` needs `!ddnetbot ack-protocol` by a \
+maintainer first.",
+    ),
+    // breaks (each with `path`, `declaration`, `changed`)
+    (
+        "api_breaks",
+        "{% if breaks %}Suspected breaks of the public API:\n{% for x in breaks %}\n- \
+{% if x.changed %}changed{% else %}removed{% endif %} in `{{ x.path }}`: `{{ x.declaration }}`\
+{% endfor %}{% else %}No suspected breaks of the public API anymore.{% endif %}",
+    ),
+    // author, settings, docs
+    (
+        "settings_docs",
+        "@{{ author }} this PR adds or changes settings ({% for x in settings %}`{{ x }}`\
+{% if not loop.last %}, {% endif %}{% endfor %}), please update the settings documentation\
+{% if docs %} at {{ docs }}{% endif %} too.",
+    ),
+    // author
+    (
+        "issue_link_request",
+        "@{{ author }} this PR looks like a bugfix, please link the issue it fixes in the \
+description, e.g. `Fixes #123`. If there is none, please open one first.",
+    ),
+    // author
+    (
+        "screenshot_request",
+        "@{{ author }} this PR changes how the game looks, please add before/after screenshots \
+or a short video to the description.",
+    ),
+    // terms, results (each with `number`, `title`, `url`, `state`)
+    (
+        "search_results",
+        "{% if results %}Issues matching `{{ terms }}`:\n{% for x in results %}\n- #{{ x.number }} \
+{{ x.title }} ({{ x.state }}){% endfor %}{% else %}No issues match `{{ terms }}`.{% endif %}",
+    ),
+    // users
+    (
+        "subscribers_cc",
+        "cc {% for x in users %}@{{ x }}{% if not loop.last %} {% endif %}{% endfor %} \
+(subscribed with `!ddnetbot subscribe`)",
+    ),
+    // user, areas, digest
+    (
+        "subscribed",
+        "@{{ user }} {% if areas %}you're subscribed to \
+{% for x in areas %}`{{ x }}`{% if not loop.last %}, {% endif %}{% endfor %} and \
+{% if digest %}get a digest of{% else %}get cc'd on{% endif %} new issues and PRs in them. \
+`!ddnetbot unsubscribe` stops it.{% else %}you aren't subscribed to anything, e.g. \
+`!ddnetbot subscribe network path:src/engine/`.{% endif %}",
+    ),
+    // user, repo, items (each with `number`, `title`)
+    (
+        "subscription_digest",
+        "New in {{ repo }} for {{ user }}:\n\
+{% for x in items %}- https://github.com/{{ repo }}/issues/{{ x.number }} {{ x.title }}\n{% endfor %}",
+    ),
+    // user, areas, issues (each with `number`, `title`)
+    (
+        "next_issues",
+        "{% if issues %}@{{ user }} the oldest issues waiting for triage or someone to work on them\
+{% if areas %} in {% for x in areas %}`{{ x }}`{% if not loop.last %}, {% endif %}{% endfor %}\
+{% endif %}:\n{% for x in issues %}\n- #{{ x.number }} {{ x.title }}{% endfor %}\
+{% else %}@{{ user }} nothing is waiting for triage{% if areas %} in your areas{% endif %}.{% endif %}",
+    ),
+    // links (each with `title`, `url`)
+    (
+        "docs",
+        "This PR touches code that is documented, please check whether these pages need an \
+update:\n{% for x in links %}\n- [{{ x.title }}]({{ x.url }}){% endfor %}",
+    ),
+    // author, answer
+    (
+        "faq_answer",
+        "@{{ author }} this is a common question:\n\n{{ answer }}\n\nIf that solves it, comment \
+`!ddnetbot solved` to close this issue.",
+    ),
+    // author, dependencies (numbers)
+    (
+        "dependencies_merged",
+        "@{{ author }} everything this PR depends on is merged \
+({% for x in dependencies %}{% if not loop.first %}, {% endif %}#{{ x }}{% endfor %}), it's \
+not blocked anymore. Please rebase it if needed.",
+    ),
+    // author, issues (numbers), signature
+    (
+        "possible_duplicate",
+        "@{{ author }} thanks for the report! The backtrace looks like the crash of \
+{% for x in issues %}{% if not loop.first %}, {% endif %}#{{ x }}{% endfor %}, please check \
+whether it's the same and add anything new there.",
+    ),
+    // commit, title, url, good, bad, remaining, steps
+    (
+        "bisect_step",
+        "Please test [{{ commit[:10] }}]({{ url }}) ({{ title }}), about {{ steps }} more \
+step{% if steps != 1 %}s{% endif %} to go ({{ remaining }} commits left). Build it with `git checkout \
+{{ commit }}` and `cmake` as usual, or download the build of its CI run from the checks of the \
+commit, then reply `!ddnetbot bisect good` or `!ddnetbot bisect bad`.",
+    ),
+    // commit, title, url, good
+    (
+        "bisect_done",
+        "The first broken commit is [{{ commit[:10] }}]({{ url }}) ({{ title }}), the one \
+before it ({{ good[:10] }}) works.",
+    ),
+    // author, version, minimum
+    (
+        "outdated_version",
+        "@{{ author }} {{ version }} isn't supported anymore, please update to {{ minimum }} or \
+newer. If the bug still happens there, comment with the version you reproduced it on and this \
+issue is reopened.",
+    ),
+    // version, items, caller
+    (
+        "release_checklist",
+        "Checklist for releasing {{ version }}, opened by {{ caller }}.\n\n\
+{% for x in items %}- [ ] {{ x }}\n{% endfor %}",
+    ),
+    // repo, features, labels
+    (
+        "setup_issue",
+        "ddbot is now set up on {{ repo }}, with {{ labels }} labels it needs for triage and its \
+commands.\n\n\
+### Commands\n\n\
+Comment `!ddnetbot <command>` on an issue or PR:\n\
+- `label +x -y`: add and remove labels\n\
+- `claim`: assign yourself\n\
+- `ready`, `author`: mark a PR as waiting for reviews or for its author\n\
+- `remind 2w <message>`: get reminded later\n\
+- `search <terms>`: find similar issues\n\
+- `subscribe <labels>`: get cc'ed on new issues and PRs\n\
+- `summarize`: sum up a long thread\n\
+- `bisect start <good> <bad>`: find the commit that broke something\n\n\
+### Configuration\n\n\
+Handlers and features ({% for x in features %}{% if not loop.first %}, {% endif %}`{{ x }}`\
+{% endfor %}) can be turned off for this repo in the bot's `ddbot.toml`:\n\n\
+```toml\n\
+[repos.\"{{ repo }}\"]\n\
+disabled = [\"labeler\"]\n\n\
+[repos.\"{{ repo }}\".features]\n\
+triage = false\n\
+```\n\n\
+Close this issue once you've read it.",
+    ),
+    // version, uptime, queue (may be none), last_handled (may be none), repo, features (each
+    // with name, enabled), disabled
+    (
+        "bot_status",
+        "ddbot {{ version }}, up for {{ uptime }}.\n\n\
+- Deliveries waiting: {% if queue is none %}none, they're handled as they arrive\
+{% else %}{{ queue }}{% endif %}\n\
+- Last delivery handled: {% if last_handled %}{{ last_handled }}{% else %}none since the start\
+{% endif %}\n\
+- Features on {{ repo }}: {% for x in features %}{% if not loop.first %}, {% endif %}`{{ x.name }}`\
+{% if not x.enabled %} (off){% endif %}{% endfor %}\
+{% if disabled %}\n- Handlers turned off: {% for x in disabled %}{% if not loop.first %}, {% endif %}\
+`{{ x }}`{% endfor %}{% endif %}",
+    ),
+    // author, label, missing (form fields), hours (none without anyone to ping)
+    (
+        "report_acknowledgement",
+        "Thanks for the report @{{ author }}, it's marked as a {{ label }} and the team will look \
+into it.{% if missing %} To track it down faster, please add: {% for x in missing %}\
+{% if not loop.first %}, {% endif %}{{ x }}{% endfor %}. A demo or the settings file \
+(`settings_ddnet.cfg`) can be dragged into a comment.{% endif %}\
+{% if hours %} Someone should answer within {{ hours }} hours.{% endif %}",
+    ),
+    // ping, label, hours
+    (
+        "response_overdue",
+        "{% for x in ping %}{% if not loop.first %} {% endif %}{{ x }}{% endfor %} nobody answered \
+this {{ label }} report in {{ hours }} hours.",
+    ),
+    // author, repo
+    (
+        "misfiled",
+        "@{{ author }} it looks like this belongs in https://github.com/{{ repo }}, please open \
+the issue there if so.",
+    ),
+    // author, url, body
+    (
+        "ported_issue",
+        "_Moved from {{ url }}, originally reported by @{{ author }}._\n\n{{ body }}",
+    ),
+    // url
+    ("ported", "This issue was moved to {{ url }}."),
+    // author, todos (each with `path`, `line`, `text`), auto
+    (
+        "todos_found",
+        "@{{ author }} this PR adds these TODOs:\n{% for x in todos %}\n- `{{ x.path }}:{{ x.line }}` \
+{{ x.text }}{% endfor %}\n\n{% if auto %}Issues are opened for them when it's merged.{% else %}\
+Comment `!ddnetbot todo-issues` to open an issue for each of them.{% endif %}",
+    ),
+    // text, path, line, url, number
+    (
+        "todo_issue",
+        "{{ text }}\n\nAdded in `{{ path }}` line {{ line }} by #{{ number }}: {{ url }}",
+    ),
+    // count
+    (
+        "todo_issues",
+        "{% if count %}Opened {{ count }} issue{% if count > 1 %}s{% endif %} for the TODOs of \
+this PR.{% else %}There are no TODOs left to open issues for.{% endif %}",
+    ),
+    // user, pr, comment
+    (
+        "follow_up_issue",
+        "Follow-up of {{ pr }}, noted by @{{ user }} in {{ comment }}.",
+    ),
+    // adopter, author, pr, branch
+    (
+        "adoption",
+        "@{{ adopter }} adopted this PR since @{{ author }} hasn't been active on it for a while. \
+To take it over, check it out with `gh pr checkout {{ pr }}` (or \
+`git fetch origin pull/{{ pr }}/head:{{ branch }}`), push it to a branch of yours and open a new \
+PR mentioning #{{ pr }}.\n\nPlease keep @{{ author }}'s commits, or credit them with a \
+`Co-authored-by:` trailer if you squash them.",
+    ),
+    // adopter, pr
+    (
+        "adoption_superseded",
+        "@{{ adopter }} continues this in #{{ pr }}.",
+    ),
+    // user, added, removed, skipped (each with `label`, `reason`)
+    (
+        "labels_changed",
+        "@{{ user }} {% if added or removed %}\
+{% if added %}added {% for x in added %}`{{ x }}`{% if not loop.last %}, {% endif %}{% endfor %}\
+{% if removed %} and {% endif %}{% endif %}\
+{% if removed %}removed {% for x in removed %}`{{ x }}`{% if not loop.last %}, {% endif %}{% endfor %}\
+{% endif %}.{% else %}no labels changed.{% endif %}\
+{% for x in skipped %}\n- skipped `{{ x.label }}`, it {{ x.reason }}{% endfor %}",
+    ),
+    // url
+    ("follow_up", "Opened {{ url }} to follow up on this."),
+    // old, new
+    (
+        "relabel_started",
+        "Moving the issues and PRs from `{{ old }}` to `{{ new }}`, I'll comment here once it's done.",
+    ),
+    // old, new, count
+    (
+        "relabeled",
+        "Moved {{ count }} issues and PRs from `{{ old }}` to `{{ new }}` and deleted `{{ old }}`.",
+    ),
+    // kind, repo, summary, severity (may be none), url
+    (
+        "security_alert",
+        "**{{ kind }}** in {{ repo }}{% if severity %} ({{ severity }} severity){% endif %}: \
+{{ summary }}\n{{ url }}",
+    ),
+    // milestone, url, due (may be none), open, closed, blockers (each with `number`, `title`)
+    (
+        "milestone_status",
+        "**[{{ milestone }}]({{ url }})**{% if due %}, due {{ due }}{% endif %}: {{ closed }} \
+closed, {{ open }} open{% if blockers %}. Oldest open:\n\
+{% for x in blockers %}- #{{ x.number }} {{ x.title }}\n{% endfor %}{% else %}, ready to \
+release.{% endif %}",
+    ),
+    // comments, participants (each with `login`, `comments`), decisions (each with `login`,
+    // `excerpt`, `url`), references, llm (may be none)
+    (
+        "thread_summary",
+        "**Summary of {{ comments }} comments**\n\n\
+{% if llm %}{{ llm }}\n\n{% endif %}\
+Participants: {% for x in participants %}{{ x.login }} ({{ x.comments }})\
+{% if not loop.last %}, {% endif %}{% endfor %}\n\
+{% if decisions %}\nDecisions:\n{% for x in decisions %}- {{ x.login }}: \
+[{{ x.excerpt }}]({{ x.url }})\n{% endfor %}{% endif %}\
+{% if references %}\nReferenced: {% for x in references %}#{{ x }}\
+{% if not loop.last %}, {% endif %}{% endfor %}\n{% endif %}",
+    ),
+    // repo, unused, duplicates (pairs), contradictions (each with `number`, `labels`), fixed
+    (
+        "label_hygiene",
+        "**Label problems in {{ repo }}**\n\
+{% if unused %}\nUnused: {% for x in unused %}`{{ x }}`{% if not loop.last %}, {% endif %}\
+{% endfor %}\n{% endif %}\
+{% if duplicates %}\nLikely duplicates:\n{% for x in duplicates %}- `{{ x[0] }}` and \
+`{{ x[1] }}`\n{% endfor %}{% endif %}\
+{% if contradictions %}\nContradicting workflow labels{% if fixed %} (fixed){% endif %}:\n\
+{% for x in contradictions %}- {{ repo }}#{{ x.number }}: {% for l in x.labels %}`{{ l }}`\
+{% if not loop.last %}, {% endif %}{% endfor %}\n{% endfor %}{% endif %}",
+    ),
+    // assignees, milestone, due (may be none)
+    (
+        "blocker_ping",
+        "{% for x in assignees %}@{{ x }} {% endfor %}this blocks {{ milestone }}\
+{% if due %}, due {{ due }},{% endif %} and hasn't moved in a while. Is it still on track?",
+    ),
+    // author, branch, until, exempt
+    (
+        "pr_frozen",
+        "@{{ author }} `{{ branch }}` is frozen for the release until {{ until }}, this PR can't be \
+merged before{% if exempt %} unless a maintainer labels it \
+{% for x in exempt %}`{{ x }}`{% if not loop.last %} or {% endif %}{% endfor %}{% endif %}.",
+    ),
+    // branch, until, exempt (none when lifted)
+    (
+        "branch_frozen",
+        "{% if until %}`{{ branch }}` is frozen until {{ until }}{% if exempt %}, only PRs labeled \
+{% for x in exempt %}`{{ x }}`{% if not loop.last %} or {% endif %}{% endfor %} can be \
+merged{% endif %}.{% else %}`{{ branch }}` isn't frozen anymore.{% endif %}",
+    ),
+    // author, url, locked
+    (
+        "security_escalated",
+        "@{{ author }} thanks for the report! Vulnerabilities are handled privately, please report \
+it at {{ url }} and edit the details out of this issue. Comments that may have details were \
+hidden and the maintainers were told.{% if locked %} The issue is locked until it's \
+assessed.{% endif %}",
+    ),
+    // author, submodules
+    (
+        "submodule_bump",
+        "@{{ author }} this PR changes the {% for x in submodules %}`{{ x }}`{% if not loop.last %}, {% endif %}{% endfor %} \
+submodule{% if submodules | length > 1 %}s{% endif %} without mentioning it. Is that intended? \
+If not, run `git submodule update` and commit the reset pointer.",
+    ),
+    // author, document
+    (
+        "cla",
+        "Thanks for the PR @{{ author }}! Before it can be merged, please read the \
+[Contributor License Agreement]({{ document }}) and comment `!ddnetbot cla accept` to accept it.",
+    ),
+    // author, count, base
+    (
+        "dco",
+        "@{{ author }} every commit needs a `Signed-off-by` line with your name and email \
+(see https://developercertificate.org). To add it to all commits of this PR, run:\n\n\
+```\ngit rebase --signoff HEAD~{{ count }}\ngit push --force-with-lease\n```",
+    ),
+    // user, label
+    (
+        "claim_refused",
+        "@{{ user }} this issue is labeled `{{ label }}` and can't be claimed, please ask a maintainer about it first.",
+    ),
+    // reviewer, days
+    (
+        "review_overdue",
+        "{{ reviewer }} your review was requested more than {{ days }} days ago, could you take a look?",
+    ),
+    // reviewers (each with login, pending, overdue and overloaded)
+    (
+        "reviewers",
+        "| Reviewer | Pending reviews | Overdue |\n|---|---|---|\n\
+{% for x in reviewers %}| {{ x.login }}{% if x.overloaded %} (overloaded){% endif %} \
+| {{ x.pending }} | {{ x.overdue }} |\n{% endfor %}",
+    ),
+    // items
+    (
+        "checklist",
+        "Before this is merged, please check:\n\n\
+{% for item in items %}- [ ] {{ item }}\n{% endfor %}",
+    ),
+    // user, merged_prs, reviews, triage
+    (
+        "stats",
+        "Stats for `{{ user }}`:\n\n| Merged PRs | Reviews | Triage actions |\n|---|---|---|\n\
+| {{ merged_prs }} | {{ reviews }} | {{ triage }} |",
+    ),
+    // month, commands
+    (
+        "command_usage",
+        "{% if commands %}Commands used in {{ month }}:\n\n| Command | Uses | Failed | Users |\n\
+|---|---|---|---|{% for x in commands %}\n| `{{ x.command }}` | {{ x.uses }} | {{ x.failures }} | \
+{{ x.users }} |{% endfor %}{% else %}No commands were used in {{ month }}.{% endif %}",
+    ),
+    // author, label
+    (
+        "help_needs_rebase",
+        "@{{ author }} this PR conflicts with its base branch. To rebase it, run \
+`git fetch upstream`, `git rebase upstream/master`, fix the conflicts and `git push --force-with-lease`.",
+    ),
+    // author, label
+    (
+        "help_needs_changelog",
+        "@{{ author }} please add a changelog entry to the PR description, a short line for \
+players about what changed.",
+    ),
+    // author, label
+    (
+        "help_needs_screenshot",
+        "@{{ author }} please add screenshots or a video of the change to the PR description, \
+showing it before and after.",
+    ),
+];
+
+/// The templates of the comments the bot posts, in English and the translated languages.
+///
+/// Templates are [minijinja](https://docs.rs/minijinja) without autoescaping, and using a
+/// variable a template doesn't get is an error instead of rendering as empty. Translations are
+/// named `<language>/<template>`, and anything not translated falls back to English.
+#[derive(Debug, Clone)]
+pub struct Templates {
+    env: Arc<Environment<'static>>,
+    /// Language of each repo, by lowercase `owner/name`.
+    languages: Arc<HashMap<String, String>>,
+}
+
+impl Templates {
+    /// The default templates with the overrides and translations from the config.
+    ///
+    /// Translations are read from `<language>.toml` files in `translations_dir`, each a table
+    /// of template names to templates.
+    pub fn from_config(config: &Config) -> Result<Self, Box<dyn Error>> {
+        let mut translations = HashMap::new();
+        if let Some(dir) = &config.translations_dir {
+            for entry in std::fs::read_dir(dir)? {
+                let path = entry?.path();
+                if path.extension().is_none_or(|x| x != "toml") {
+                    continue;
+                }
+                let language = path.file_stem().unwrap().to_string_lossy().to_string();
+                let templates: HashMap<String, String> = Figment::from(Toml::file(&path))
+                    .extract()
+                    .map_err(|e| format!("{}: {}", path.display(), e))?;
+                translations.insert(language, templates);
+            }
+        }
+
+        for (repo, language) in &config.languages {
+            if language != "en" && !translations.contains_key(language) {
+                return Err(format!("no translations for {} of {}", language, repo).into());
+            }
+        }
+        let languages = config
+            .languages
+            .iter()
+            .map(|(repo, language)| (repo.to_ascii_lowercase(), language.clone()))
+            .collect();
+
+        Ok(Self::new(&config.templates, &translations, languages)?)
+    }
+
+    pub fn new(
+        overrides: &HashMap<String, String>,
+        translations: &HashMap<String, HashMap<String, String>>,
+        languages: HashMap<String, String>,
+    ) -> Result<Self, minijinja::Error> {
+        let mut env = Environment::new();
+        env.set_undefined_behavior(UndefinedBehavior::Strict);
+        env.set_keep_trailing_newline(true);
+
+        for (name, source) in DEFAULTS {
+            env.add_template(name, source)?;
+        }
+        add_templates(&mut env, "", overrides)?;
+        for (language, templates) in translations {
+            add_templates(&mut env, &format!("{}/", language), templates)?;
+        }
+
+        Ok(Self {
+            env: Arc::new(env),
+            languages: Arc::new(languages),
+        })
+    }
+
+    pub fn has(&self, name: &str) -> bool {
+        self.env.get_template(name).is_ok()
+    }
+
+    /// Renders `name` in the language of `repo` (`owner/name`).
+    pub fn render(
+        &self,
+        repo: &str,
+        name: &str,
+        context: impl Serialize,
+    ) -> Result<String, minijinja::Error> {
+        let translated = self
+            .languages
+            .get(&repo.to_ascii_lowercase())
+            .and_then(|x| self.env.get_template(&format!("{}/{}", x, name)).ok());
+        match translated {
+            Some(template) => template.render(context),
+            None => self.env.get_template(name)?.render(context),
+        }
+    }
+}
+
+fn add_templates(
+    env: &mut Environment<'static>,
+    prefix: &str,
+    templates: &HashMap<String, String>,
+) -> Result<(), minijinja::Error> {
+    for (name, source) in templates {
+        // More help for labels can be added, see `crate::handlers::LabelHelp`.
+        if !DEFAULTS.iter().any(|(x, _)| x == name) && !name.starts_with("help_") {
+            return Err(minijinja::Error::new(
+                minijinja::ErrorKind::TemplateNotFound,
+                format!("unknown template {}{}", prefix, name),
+            ));
+        }
+        env.add_template_owned(format!("{}{}", prefix, name), source.clone())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn overrides_replace_defaults() {
+        let overrides = HashMap::from([(
+            "rate_limited".to_string(),
+            "Slow down @{{ user }}".to_string(),
+        )]);
+        let templates = Templates::new(&overrides, &HashMap::new(), HashMap::new()).unwrap();
+        let context = json!({ "user": "heinrich5991", "minutes": 3 });
+        assert_eq!(
+            templates
+                .render("ddnet/ddnet", "rate_limited", &context)
+                .unwrap(),
+            "Slow down @heinrich5991"
+        );
+        assert!(templates.render("ddnet/ddnet", "stats", &context).is_err());
+    }
+
+    #[test]
+    fn unknown_templates_are_rejected() {
+        let overrides = HashMap::from([("welcom".to_string(), "Hi".to_string())]);
+        assert!(Templates::new(&overrides, &HashMap::new(), HashMap::new()).is_err());
+    }
+
+    #[test]
+    fn translations_fall_back_to_english() {
+        let translations = HashMap::from([(
+            "de".to_string(),
+            HashMap::from([("welcome".to_string(), "Danke, @{{ author }}!".to_string())]),
+        )]);
+        let languages = HashMap::from([("ddnet/ddnet-de".to_string(), "de".to_string())]);
+        let templates = Templates::new(&HashMap::new(), &translations, languages).unwrap();
+
+        let context = json!({ "author": "Jupeyy", "repo": "ddnet/ddnet-de" });
+        assert_eq!(
+            templates
+                .render("DDNet/ddnet-de", "welcome", &context)
+                .unwrap(),
+            "Danke, @Jupeyy!"
+        );
+        assert!(
+            templates
+                .render("ddnet/ddnet", "welcome", &context)
+                .unwrap()
+                .starts_with("Thanks")
+        );
+        let context = json!({ "user": "Jupeyy", "minutes": 3 });
+        assert!(
+            templates
+                .render("ddnet/ddnet-de", "rate_limited", &context)
+                .unwrap()
+                .starts_with("@Jupeyy")
+        );
+    }
+}
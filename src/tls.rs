@@ -0,0 +1,55 @@
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use axum::Router;
+use axum_server::tls_rustls::RustlsConfig;
+use tracing::{info, warn};
+
+/// Serves `app` over HTTPS, reloading the certificate whenever the files change on disk.
+pub async fn serve(
+    addr: SocketAddr,
+    app: Router,
+    cert: PathBuf,
+    key: PathBuf,
+) -> std::io::Result<()> {
+    rustls::crypto::ring::default_provider()
+        .install_default()
+        .ok();
+
+    let config = RustlsConfig::from_pem_file(&cert, &key).await?;
+    tokio::spawn(reload_loop(config.clone(), cert, key));
+    crate::systemd::notify_ready();
+
+    axum_server::bind_rustls(addr, config)
+        .serve(app.into_make_service())
+        .await
+}
+
+async fn reload_loop(config: RustlsConfig, cert: PathBuf, key: PathBuf) {
+    let mut interval = tokio::time::interval(Duration::from_secs(60));
+    let mut last = (modified(&cert), modified(&key));
+
+    loop {
+        interval.tick().await;
+        let current = (modified(&cert), modified(&key));
+        if current == last {
+            continue;
+        }
+
+        match config.reload_from_pem_file(&cert, &key).await {
+            Ok(()) => {
+                info!("Reloaded TLS certificate");
+                last = current;
+            }
+            // The files may be in the middle of being replaced, retry on the next tick.
+            Err(e) => warn!("Failed to reload TLS certificate: {}", e),
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|x| x.modified()).ok()
+}
@@ -0,0 +1,184 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use octocrab::{
+    Octocrab,
+    models::{CommentId, Repository},
+};
+use serde_json::{Value, json};
+use tracing::warn;
+
+use crate::{AppState, github, handlers::Error, templates::Templates};
+
+/// Marks the bot's progress comment on a tracking issue.
+const MARKER: &str = "<!-- ddbot:progress -->";
+
+/// Length of the progress bar, in characters.
+const BAR_LEN: usize = 20;
+
+/// An issue or PR in the task list of a tracking issue, as `owner/name#number`.
+#[derive(Debug)]
+pub struct Item {
+    pub title: String,
+    pub url: String,
+    /// `open`, `closed` or `merged`.
+    pub state: &'static str,
+}
+
+impl Item {
+    /// Closed issues and merged PRs are done, closed PRs aren't.
+    pub fn done(&self) -> bool {
+        match self.state {
+            "merged" => true,
+            "closed" => !self.url.contains("/pull/"),
+            _ => false,
+        }
+    }
+}
+
+/// Looks up an item, None if the bot can't see it.
+pub async fn item(client: &Octocrab, item: &str) -> Option<Item> {
+    let (name, number) = item.rsplit_once('#')?;
+    let route = format!("/repos/{}/issues/{}", name, number);
+    let res: Value = github::retry(|| client.get(&route, None::<&()>))
+        .await
+        .ok()?;
+    let merged = res["pull_request"]["merged_at"].is_string();
+    Some(Item {
+        title: res["title"].as_str().unwrap_or_default().to_string(),
+        url: res["html_url"].as_str().unwrap_or_default().to_string(),
+        state: match res["state"].as_str() {
+            Some("closed") if merged => "merged",
+            Some("closed") => "closed",
+            _ => "open",
+        },
+    })
+}
+
+/// Periodically updates a comment on every tracking issue with the progress of its items.
+pub async fn progress_loop(state: AppState, period: Duration) {
+    let mut interval = tokio::time::interval(period);
+
+    loop {
+        interval.tick().await;
+        let repos = match github::installed_repos(&state.installations).await {
+            Ok(x) => x,
+            Err(e) => {
+                warn!("Failed to list repos for the tracking progress: {}", e);
+                continue;
+            }
+        };
+        for (installation, client, repo) in repos {
+            let rules = state.rules();
+            if !rules
+                .handlers
+                .enabled("tracking", repo.full_name.as_deref())
+            {
+                continue;
+            }
+            state
+                .rate_limits
+                .wait_for_budget(installation, "tracking progress")
+                .await;
+            if let Err(e) = update_repo(&state, &rules.templates, &client, &repo).await {
+                warn!(
+                    "Failed to update the tracking progress of {}: {}",
+                    repo.full_name.as_deref().unwrap_or_default(),
+                    e
+                );
+            }
+        }
+    }
+}
+
+async fn update_repo(
+    state: &AppState,
+    templates: &Templates,
+    client: &Octocrab,
+    repo: &Repository,
+) -> Result<(), Error> {
+    let full_name = repo.full_name.as_deref().unwrap_or_default();
+    let mut issues: BTreeMap<u64, Vec<String>> = BTreeMap::new();
+    for (number, item) in state.db.tracked_items(repo.id)? {
+        issues.entry(number).or_default().push(item);
+    }
+    for (number, items) in issues {
+        let mut rendered = Vec::new();
+        for name in &items {
+            let Some(item) = item(client, name).await else {
+                continue;
+            };
+            rendered.push(json!({
+                "item": name,
+                "title": item.title,
+                "url": item.url,
+                "state": item.state,
+                "done": item.done(),
+            }));
+        }
+        if rendered.is_empty() {
+            continue;
+        }
+
+        let done = rendered.iter().filter(|x| x["done"] == true).count();
+        let total = rendered.len();
+        let filled = done * BAR_LEN / total;
+        let body = templates.render(
+            full_name,
+            "tracking_progress",
+            json!({
+                "done": done,
+                "total": total,
+                "percent": done * 100 / total,
+                "bar": format!("{}{}", "█".repeat(filled), "░".repeat(BAR_LEN - filled)),
+                "items": rendered,
+            }),
+        )?;
+        let body = format!("{}\n{}", MARKER, body);
+        upsert(state, client, repo, number, &body).await?;
+    }
+    Ok(())
+}
+
+/// Edits the progress comment of the issue if it changed, or posts it.
+async fn upsert(
+    state: &AppState,
+    client: &Octocrab,
+    repo: &Repository,
+    number: u64,
+    body: &str,
+) -> Result<(), Error> {
+    let key = format!("progress_comment:{}:{}", repo.id, number);
+    let previous: Option<(u64, String)> = state
+        .db
+        .get_kv(&key)?
+        .and_then(|x| serde_json::from_str(&x).ok());
+    let issues = client.issues_by_id(repo.id);
+    if let Some((id, previous)) = &previous {
+        if previous == body {
+            return Ok(());
+        }
+        let res = github::mutate(
+            state.dry_run,
+            format!("update progress comment on #{}", number),
+            || issues.update_comment(CommentId(*id), body),
+        )
+        .await;
+        match res {
+            Ok(()) => {
+                state.db.set_kv(&key, &json!([id, body]).to_string())?;
+                return Ok(());
+            }
+            // Someone deleted it, post a new one.
+            Err(octocrab::Error::GitHub { source, .. }) if source.status_code == 404 => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    let Some(comment) = github::create_comment(state.dry_run, &issues, number, body).await? else {
+        return Ok(());
+    };
+    state
+        .db
+        .set_kv(&key, &json!([comment.id.0, body]).to_string())?;
+    Ok(())
+}
@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use octocrab::{Octocrab, models::issues::Issue};
+use serde::Deserialize;
+use serde_json::json;
+use tracing::warn;
+
+use crate::{AppState, feed::TRIAGE_LABEL, github, handlers::Error};
+
+/// How many issues `!ddnetbot next` and `/triage/next` list.
+pub const NEXT_ISSUES: usize = 5;
+
+/// The oldest open issues that still need triage or someone to work on them, with one of the
+/// labels of the triager's areas if they have any.
+pub async fn next_issues(
+    client: &Octocrab,
+    full_name: &str,
+    areas: &[String],
+) -> Result<Vec<Issue>, Error> {
+    let areas = if areas.is_empty() {
+        String::new()
+    } else {
+        let labels: Vec<String> = areas.iter().map(|x| format!("\"{}\"", x)).collect();
+        format!(" label:{}", labels.join(","))
+    };
+    let mut issues = Vec::new();
+    // Search can't combine qualifiers with OR.
+    for filter in [format!("label:{}", TRIAGE_LABEL), "no:assignee".to_string()] {
+        let query = format!(
+            "repo:{} is:issue is:open {}{} sort:created-asc",
+            full_name, filter, areas
+        );
+        let page = github::retry(|| {
+            client
+                .search()
+                .issues_and_pull_requests(&query)
+                .per_page(NEXT_ISSUES as u8)
+                .send()
+        })
+        .await?;
+        issues.extend(page.items);
+    }
+    issues.sort_by_key(|x| (x.created_at, x.number));
+    issues.dedup_by_key(|x| x.number);
+    issues.truncate(NEXT_ISSUES);
+    Ok(issues)
+}
+
+/// The labels of the areas `login` triages, none if they didn't configure any.
+pub fn areas<'a>(areas: &'a HashMap<String, Vec<String>>, login: &str) -> &'a [String] {
+    areas
+        .iter()
+        .find(|(x, _)| x.eq_ignore_ascii_case(login))
+        .map(|(_, x)| x.as_slice())
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NextQuery {
+    /// `owner/name`.
+    repo: String,
+    /// Whose areas to match, the logged in user by default.
+    user: Option<String>,
+}
+
+/// `GET /triage/next?repo=owner/name&user=login`: what to triage next.
+pub async fn next(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<NextQuery>,
+) -> Response {
+    let user = query.user.or_else(|| {
+        let login = state.login.as_ref()?;
+        Some(login.session(&headers)?.login)
+    });
+    let areas = user
+        .as_deref()
+        .map(|x| areas(&state.triage_areas, x))
+        .unwrap_or_default();
+    let issues = match state.db.repo_by_name(&query.repo) {
+        Ok(Some((_, installation))) => match state.installations.client(installation) {
+            Ok(client) => next_issues(&client, &query.repo, areas).await,
+            Err(e) => Err(e.into()),
+        },
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(e) => Err(e.into()),
+    };
+    match issues {
+        Ok(issues) => Json(
+            issues
+                .iter()
+                .map(|x| {
+                    json!({
+                        "number": x.number,
+                        "title": x.title,
+                        "url": x.html_url,
+                        "created_at": x.created_at,
+                        "labels": x.labels.iter().map(|x| &x.name).collect::<Vec<_>>(),
+                    })
+                })
+                .collect::<Vec<_>>(),
+        )
+        .into_response(),
+        Err(e) => {
+            warn!("Failed to find the next issues to triage: {}", e);
+            StatusCode::BAD_GATEWAY.into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn areas_are_found_by_login_in_any_case() {
+        let areas_by_login = HashMap::from([(
+            "Robyt3".to_string(),
+            vec!["client".to_string(), "editor".to_string()],
+        )]);
+        assert_eq!(areas(&areas_by_login, "robyt3"), ["client", "editor"]);
+        assert!(areas(&areas_by_login, "heinrich5991").is_empty());
+    }
+}
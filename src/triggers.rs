@@ -0,0 +1,249 @@
+use axum::{
+    Json,
+    body::Bytes,
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::json;
+use tracing::{info, warn};
+
+use crate::{
+    AppState, forward, github,
+    handlers::{Context, Error},
+};
+
+/// How far the timestamp of a trigger may be from now, in seconds. Signed requests older than
+/// this are rejected, so a captured one can't be replayed later.
+const MAX_SKEW_SECS: i64 = 5 * 60;
+
+/// Something another service asks the bot to do, see [`trigger`].
+#[derive(Debug, Deserialize)]
+#[serde(tag = "action", rename_all = "kebab-case")]
+pub enum Action {
+    /// Opens an issue listing what's left to do for a release.
+    ReleaseChecklist {
+        repo: String,
+        version: String,
+        items: Vec<String>,
+    },
+    /// Sets a section of the status comment on an issue or PR, see
+    /// [`crate::status::StatusComments`].
+    Status {
+        repo: String,
+        number: u64,
+        section: String,
+        text: String,
+    },
+}
+
+impl Action {
+    /// The scope a caller needs to trigger the action.
+    fn scope(&self) -> &'static str {
+        match self {
+            Action::ReleaseChecklist { .. } => "release-checklist",
+            Action::Status { .. } => "status",
+        }
+    }
+
+    fn repo(&self) -> &str {
+        match self {
+            Action::ReleaseChecklist { repo, .. } | Action::Status { repo, .. } => repo,
+        }
+    }
+}
+
+/// `POST /triggers/<caller>`: runs an action for a service that isn't GitHub, like the release
+/// pipeline. The timestamp and body must be signed with the caller's secret, see [`verify`], and
+/// the action must be in the caller's scopes.
+pub async fn trigger(
+    State(state): State<AppState>,
+    Path(caller): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let Some(config) = state.triggers.get(&caller) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|x| x.to_str().ok())
+            .unwrap_or_default()
+    };
+    if !verify(
+        &config.secret,
+        header("X-Signature-Timestamp"),
+        header("X-Signature-256"),
+        &body,
+        Utc::now().timestamp(),
+    ) {
+        warn!("Trigger from {} with a bad or stale signature", caller);
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    let action: Action = match serde_json::from_slice(&body) {
+        Ok(x) => x,
+        Err(e) => return (StatusCode::UNPROCESSABLE_ENTITY, e.to_string()).into_response(),
+    };
+    if !config.allows(action.scope(), action.repo()) {
+        warn!(
+            "{} may not trigger {} on {}",
+            caller,
+            action.scope(),
+            action.repo()
+        );
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    info!(
+        "{} triggered {} on {}",
+        caller,
+        action.scope(),
+        action.repo()
+    );
+    match run(&state, &caller, &action).await {
+        Ok(Some(answer)) => Json(answer).into_response(),
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            warn!("Failed to run {} for {}: {}", action.scope(), caller, e);
+            (StatusCode::BAD_GATEWAY, e.to_string()).into_response()
+        }
+    }
+}
+
+/// Whether `signature` is the one of `timestamp.body`, and `timestamp`, in seconds since the
+/// epoch, is within [`MAX_SKEW_SECS`] of `now`.
+fn verify(secret: &str, timestamp: &str, signature: &str, body: &[u8], now: i64) -> bool {
+    let Ok(secs) = timestamp.parse::<i64>() else {
+        return false;
+    };
+    if (now - secs).abs() > MAX_SKEW_SECS {
+        return false;
+    }
+    forward::verify(secret, signature, &signed_payload(timestamp, body))
+}
+
+/// What a trigger's signature covers: the timestamp, a dot and the body.
+fn signed_payload(timestamp: &str, body: &[u8]) -> Vec<u8> {
+    [timestamp.as_bytes(), b".", body].concat()
+}
+
+/// Runs the action, `None` if the app isn't installed on the repo or the repo isn't handled.
+async fn run(
+    state: &AppState,
+    caller: &str,
+    action: &Action,
+) -> Result<Option<serde_json::Value>, Error> {
+    if !state.rules().handlers.allows(Some(action.repo())) {
+        return Ok(None);
+    }
+    let Some((repo, installation)) = state.db.repo_by_name(action.repo())? else {
+        return Ok(None);
+    };
+    let ctx = Context {
+        state: state.clone(),
+        rules: state.rules(),
+        installation,
+        client: state.installations.client(installation)?,
+    };
+    match action {
+        Action::ReleaseChecklist {
+            repo: full_name,
+            version,
+            items,
+        } => {
+            let body = ctx.rules.templates.render(
+                full_name,
+                "release_checklist",
+                json!({ "version": version, "items": items, "caller": caller }),
+            )?;
+            let title = format!("Release {} checklist", version);
+            let issues = ctx.client.issues_by_id(repo);
+            let issue = github::create_issue(
+                state.dry_run,
+                &issues,
+                &title,
+                &body,
+                Vec::new(),
+                Vec::new(),
+            )
+            .await?;
+            Ok(Some(json!({ "number": issue.map(|x| x.number) })))
+        }
+        Action::Status {
+            number,
+            section,
+            text,
+            ..
+        } => {
+            state.status.set(&ctx, repo, *number, section, text).await?;
+            Ok(Some(json!({})))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TriggerCaller;
+
+    #[test]
+    fn triggers_are_signed_and_scoped() {
+        let body = br#"{"action": "status", "repo": "ddnet/ddnet", "number": 1, "section": "nightly", "text": "ok"}"#;
+        let now = 1_800_000_000;
+        let signature = forward::sign("secret", &signed_payload("1800000000", body));
+        assert!(verify("secret", "1800000000", &signature, body, now));
+        assert!(!verify("other", "1800000000", &signature, body, now));
+        assert!(!verify("secret", "1800000000", &signature, b"{}", now));
+        // The body alone isn't enough, the timestamp is signed too.
+        assert!(!verify(
+            "secret",
+            "1800000000",
+            &forward::sign("secret", body),
+            body,
+            now
+        ));
+
+        let action: Action = serde_json::from_slice(body).unwrap();
+        let caller = TriggerCaller {
+            secret: "secret".to_string(),
+            scopes: vec!["status".to_string()],
+            repos: vec!["ddnet/ddnet".to_string()],
+        };
+        assert!(caller.allows(action.scope(), action.repo()));
+        assert!(!caller.allows("release-checklist", "ddnet/ddnet"));
+        assert!(!caller.allows("status", "ddnet/ddnet-web"));
+    }
+
+    #[test]
+    fn stale_and_replayed_triggers_are_rejected() {
+        let body = br#"{"action": "release-checklist", "repo": "ddnet/ddnet", "version": "19.1", "items": []}"#;
+        let signature = forward::sign("secret", &signed_payload("1800000000", body));
+        assert!(verify(
+            "secret",
+            "1800000000",
+            &signature,
+            body,
+            1_800_000_000 + MAX_SKEW_SECS
+        ));
+        // The captured request replayed later.
+        assert!(!verify(
+            "secret",
+            "1800000000",
+            &signature,
+            body,
+            1_800_000_000 + MAX_SKEW_SECS + 1
+        ));
+        // The same signature with a fresh timestamp.
+        assert!(!verify(
+            "secret",
+            "1800000600",
+            &signature,
+            body,
+            1_800_000_600
+        ));
+        assert!(!verify("secret", "soon", &signature, body, 1_800_000_000));
+    }
+}
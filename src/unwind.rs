@@ -0,0 +1,53 @@
+use std::{
+    any::Any,
+    future::Future,
+    panic::{AssertUnwindSafe, catch_unwind},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// Runs a future, turning a panic while polling it into an error with the panic message, so
+/// a bug in one handler doesn't take down the worker or the other handlers of the delivery.
+pub async fn catch<'a, T>(fut: impl Future<Output = T> + Send + 'a) -> Result<T, String> {
+    CatchUnwind(Box::pin(fut)).await
+}
+
+struct CatchUnwind<'a, T>(Pin<Box<dyn Future<Output = T> + Send + 'a>>);
+
+impl<T> Future for CatchUnwind<'_, T> {
+    type Output = Result<T, String>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Whatever the future left half done is dropped with it.
+        match catch_unwind(AssertUnwindSafe(|| self.0.as_mut().poll(cx))) {
+            Ok(x) => x.map(Ok),
+            Err(panic) => Poll::Ready(Err(message(panic))),
+        }
+    }
+}
+
+fn message(panic: Box<dyn Any + Send>) -> String {
+    match panic.downcast::<String>() {
+        Ok(x) => *x,
+        Err(panic) => panic
+            .downcast_ref::<&str>()
+            .map(|x| x.to_string())
+            .unwrap_or("unknown panic".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn panics_become_errors() {
+        assert_eq!(catch(async { 1 }).await, Ok(1));
+        let res = catch(async {
+            tokio::task::yield_now().await;
+            panic!("handler panicked")
+        })
+        .await;
+        assert_eq!(res, Err::<(), _>("handler panicked".to_string()));
+    }
+}
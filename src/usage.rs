@@ -0,0 +1,95 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Datelike, TimeZone, Utc};
+use serde_json::json;
+use tracing::{info, warn};
+
+use crate::{AppState, commands, db::Db, github, handlers::Error};
+
+/// How often it's checked whether the summary of last month is due.
+const CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Records a command that ran, by its name without the arguments. Only the commands of
+/// [`commands::NAMES`] are recorded, not e.g. a sentence after `!ddnetbot`.
+pub fn record(db: &Db, full_name: &str, line: &str, login: &str, ok: bool) {
+    let command = line.split_ascii_whitespace().next().unwrap_or_default();
+    if !commands::NAMES.contains(&command) {
+        return;
+    }
+    if let Err(e) = db.add_command_use(full_name, command, login, ok) {
+        warn!("Failed to record the use of {}: {}", command, e);
+    }
+}
+
+/// Comments the command usage of the last month on the issue at the start of every month.
+pub async fn report_loop(state: AppState, (full_name, number): (String, u64)) {
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+
+    loop {
+        interval.tick().await;
+        if let Err(e) = report(&state, &full_name, number).await {
+            warn!("Failed to post the command usage: {}", e);
+        }
+    }
+}
+
+async fn report(state: &AppState, full_name: &str, number: u64) -> Result<(), Error> {
+    let now = Utc::now();
+    let (since, until) = last_month(now);
+    let month = since.format("%Y-%m").to_string();
+    let key = format!("command_report:{}", month);
+    if state.db.get_kv(&key)?.is_some() {
+        return Ok(());
+    }
+    let Some((repo, installation)) = state.db.repo_by_name(full_name)? else {
+        return Err(format!("the app isn't installed on {}", full_name).into());
+    };
+
+    let commands = state.db.command_usage(since, until)?;
+    let comment = state.rules().templates.render(
+        full_name,
+        "command_usage",
+        json!({ "month": month, "commands": commands }),
+    )?;
+    let client = state.installations.client(installation)?;
+    let issues = client.issues_by_id(repo);
+    github::comment(state.dry_run, &issues, number, &comment).await?;
+    state.db.set_kv(&key, &now.to_rfc3339())?;
+    info!("Posted the command usage of {}", month);
+    Ok(())
+}
+
+/// The start of the last month and of this one.
+fn last_month(now: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+    let start = |year, month| Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0).unwrap();
+    let since = match now.month() {
+        1 => start(now.year() - 1, 12),
+        month => start(now.year(), month - 1),
+    };
+    (since, start(now.year(), now.month()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn last_month_wraps_around_the_year() {
+        let now = Utc.with_ymd_and_hms(2025, 1, 14, 12, 0, 0).unwrap();
+        let (since, until) = last_month(now);
+        assert_eq!(since, Utc.with_ymd_and_hms(2024, 12, 1, 0, 0, 0).unwrap());
+        assert_eq!(until, Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn only_known_commands_are_recorded() {
+        let db = Db::open(":memory:").unwrap();
+        record(&db, "ddnet/ddnet", "claim", "alice", true);
+        record(&db, "ddnet/ddnet", "anything-here", "alice", true);
+        record(&db, "ddnet/ddnet", "stats @bob", "alice", false);
+        let usage = db.command_usage(DateTime::UNIX_EPOCH, Utc::now()).unwrap();
+        let mut names: Vec<_> = usage.iter().map(|x| x.command.as_str()).collect();
+        names.sort();
+        assert_eq!(names, ["claim", "stats"]);
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::HashSet;
+
+use octocrab::{Octocrab, models::RepositoryId};
+
+use crate::{
+    AppState, batch,
+    conflicts::NEEDS_REBASE_LABEL,
+    handlers::{BLOCKED_LABEL, Error},
+};
+
+pub const WAITING_FOR_REVIEWS_LABEL: &str = "waiting-for-reviews";
+pub const WAITING_ON_AUTHOR_LABEL: &str = "waiting-on-author";
+pub const READY_TO_MERGE_LABEL: &str = "ready-to-merge";
+
+/// Where a PR is in its workflow, each state has a label and a PR has at most one of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    WaitingForReviews,
+    WaitingOnAuthor,
+    ReadyToMerge,
+    NeedsRebase,
+}
+
+/// What asks a PR to move to another state, from commands, labels people add and events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transition {
+    /// `!ddnetbot ready`, or the PR was marked as ready for review.
+    RequestReviews,
+    /// `!ddnetbot author`, the author has to change something.
+    RequestChanges,
+    /// A maintainer considers it done.
+    Approve,
+    /// It conflicts with its base branch.
+    Conflict,
+    /// The conflict is gone, it's reviewed again.
+    Rebase,
+}
+
+impl State {
+    pub const ALL: [State; 4] = [
+        State::NeedsRebase,
+        State::WaitingOnAuthor,
+        State::WaitingForReviews,
+        State::ReadyToMerge,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            State::WaitingForReviews => WAITING_FOR_REVIEWS_LABEL,
+            State::WaitingOnAuthor => WAITING_ON_AUTHOR_LABEL,
+            State::ReadyToMerge => READY_TO_MERGE_LABEL,
+            State::NeedsRebase => NEEDS_REBASE_LABEL,
+        }
+    }
+
+    pub fn from_label(label: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|x| x.label() == label)
+    }
+
+    /// The state of a PR with these labels. Should it have several, e.g. labeled by hand before
+    /// the state machine, the one holding it up most wins.
+    pub fn of(labels: &HashSet<String>) -> Option<Self> {
+        Self::ALL.into_iter().find(|x| labels.contains(x.label()))
+    }
+
+    /// What adding the label of this state asks for.
+    pub fn requested_by(self) -> Transition {
+        match self {
+            State::WaitingForReviews => Transition::RequestReviews,
+            State::WaitingOnAuthor => Transition::RequestChanges,
+            State::ReadyToMerge => Transition::Approve,
+            State::NeedsRebase => Transition::Conflict,
+        }
+    }
+}
+
+/// The state after `transition`, or why it can't happen.
+pub fn next(from: Option<State>, transition: Transition) -> Result<Option<State>, &'static str> {
+    use State::*;
+    use Transition::*;
+
+    match (from, transition) {
+        (_, Conflict) => Ok(Some(NeedsRebase)),
+        (Some(NeedsRebase), Rebase) => Ok(Some(WaitingForReviews)),
+        (from, Rebase) => Ok(from),
+        (Some(NeedsRebase), _) => {
+            Err("the conflicts with the base branch have to be resolved first")
+        }
+        (_, RequestReviews) => Ok(Some(WaitingForReviews)),
+        (_, RequestChanges) => Ok(Some(WaitingOnAuthor)),
+        (Some(WaitingForReviews | ReadyToMerge), Approve) => Ok(Some(ReadyToMerge)),
+        (None | Some(WaitingOnAuthor), Approve) => {
+            Err("it has to be waiting for reviews before it's ready to merge")
+        }
+    }
+}
+
+/// Moves the labels to the state after `transition`, taking off the labels of other states.
+/// PRs labeled `blocked` can't be approved until what they depend on is merged.
+pub fn apply(labels: &mut HashSet<String>, transition: Transition) -> Result<(), &'static str> {
+    if transition == Transition::Approve && labels.contains(BLOCKED_LABEL) {
+        return Err("it depends on PRs that aren't merged yet");
+    }
+    let to = next(State::of(labels), transition)?;
+    for state in State::ALL {
+        if Some(state) != to {
+            labels.remove(state.label());
+        }
+    }
+    if let Some(to) = to {
+        labels.insert(to.label().to_string());
+    }
+    Ok(())
+}
+
+/// Changes the state labels of the issue or PR from `before` to `after` through [`batch`],
+/// leaving the others alone.
+pub async fn sync(
+    app: &AppState,
+    client: &Octocrab,
+    repo: RepositoryId,
+    number: u64,
+    before: &HashSet<String>,
+    after: &HashSet<String>,
+) -> Result<(), Error> {
+    for state in State::ALL {
+        let label = state.label();
+        match (before.contains(label), after.contains(label)) {
+            (false, true) => {
+                batch::add_labels(app, client, repo, number, &[label.to_string()]).await?;
+            }
+            (true, false) => batch::remove_label(app, client, repo, number, label).await?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn states_never_coexist() {
+        let mut labels: HashSet<String> =
+            ["bug", WAITING_FOR_REVIEWS_LABEL, WAITING_ON_AUTHOR_LABEL]
+                .map(String::from)
+                .into();
+        assert_eq!(State::of(&labels), Some(State::WaitingOnAuthor));
+        assert!(apply(&mut labels, Transition::Approve).is_err());
+
+        apply(&mut labels, Transition::RequestReviews).unwrap();
+        apply(&mut labels, Transition::Approve).unwrap();
+        assert_eq!(
+            labels,
+            ["bug", READY_TO_MERGE_LABEL].map(String::from).into()
+        );
+
+        apply(&mut labels, Transition::Conflict).unwrap();
+        assert!(apply(&mut labels, Transition::RequestReviews).is_err());
+        apply(&mut labels, Transition::Rebase).unwrap();
+        assert_eq!(
+            labels,
+            ["bug", WAITING_FOR_REVIEWS_LABEL].map(String::from).into()
+        );
+
+        labels.insert(BLOCKED_LABEL.to_string());
+        assert!(apply(&mut labels, Transition::Approve).is_err());
+    }
+}
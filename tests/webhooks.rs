@@ -0,0 +1,812 @@
+//! Posts recorded deliveries to the app and checks the calls it makes to a mock GitHub API.
+
+use std::path::Path;
+
+use axum::{Router, body::Body, extract::Request, http::StatusCode};
+use ddbot::{AppState, Config, replay::Recording};
+use serde_json::json;
+use tower::ServiceExt;
+use wiremock::{
+    Mock, MockServer, ResponseTemplate,
+    matchers::{body_json, body_partial_json, body_string_contains, method, path, path_regex},
+};
+
+const INSTALLATION_ID: u64 = 61234567;
+const REPO_ID: u64 = 3286547;
+/// The head of the PR of [`pull_request`].
+const HEAD_SHA: &str = "4f1c2e9d7a3b5c6e8f0a1b2c3d4e5f6a7b8c9d0e";
+
+/// The app with GitHub replaced by `github`, which hands out installation tokens.
+async fn mock_app(github: &MockServer) -> Router {
+    mock_app_with(github, json!({})).await
+}
+
+/// [`mock_app`] with more settings.
+async fn mock_app_with(github: &MockServer, settings: serde_json::Value) -> Router {
+    let mut defaults = json!({
+        "github_app_id": 1,
+        "github_api_url": github.uri(),
+        "app_private_key_path": concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test-app-key.pem"),
+        "database_path": ":memory:",
+        "admin_token": "admin",
+    });
+    defaults
+        .as_object_mut()
+        .unwrap()
+        .extend(settings.as_object().unwrap().clone());
+    let figment = figment::Figment::from(figment::providers::Serialized::defaults(defaults));
+    let config = Config::from_figment(&figment).unwrap();
+
+    Mock::given(method("POST"))
+        .and(path(format!(
+            "/app/installations/{}/access_tokens",
+            INSTALLATION_ID
+        )))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!({
+            "token": "ghs_test",
+            "expires_at": "2099-01-01T00:00:00Z",
+            "permissions": {},
+        })))
+        .mount(github)
+        .await;
+
+    ddbot::router(
+        AppState::from_config(&config).unwrap(),
+        &config.webhook_path,
+    )
+}
+
+fn fixture(name: &str) -> Recording {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name);
+    Recording::load(&path, None).unwrap()
+}
+
+/// The recorded delivery `name`, delivered again as delivery `delivery`.
+fn redelivered(name: &str, delivery: &str) -> Recording {
+    let mut recording = fixture(name);
+    recording
+        .headers
+        .insert("x-github-delivery".to_string(), delivery.to_string());
+    recording
+}
+
+/// A PR of kog-player, as delivered with `action` as delivery `delivery`.
+fn pull_request(action: &str, delivery: &str) -> Recording {
+    let mut recording = redelivered(
+        "pull_request-c2b9e4a0-00f4-11f0-8f3c-5a7d2e1b9c44.json",
+        delivery,
+    );
+    recording.body["action"] = json!(action);
+    recording
+}
+
+/// A comment of the maintainer heinrich5991 on the PR of [`pull_request`], delivered as
+/// delivery `delivery`.
+fn pr_comment(body: &str, delivery: &str) -> Recording {
+    let mut recording = redelivered(
+        "issue_comment-a41c9e70-00b6-11f0-9d2f-1e5b7c0a4f88.json",
+        delivery,
+    );
+    recording.body["comment"]["body"] = json!(body);
+    recording.body["issue"]["number"] = json!(9880);
+    recording.body["issue"]["pull_request"] = json!({
+        "url": "https://api.github.com/repos/ddnet/ddnet/pulls/9880",
+        "html_url": "https://github.com/ddnet/ddnet/pull/9880",
+        "diff_url": "https://github.com/ddnet/ddnet/pull/9880.diff",
+        "patch_url": "https://github.com/ddnet/ddnet/pull/9880.patch",
+    });
+    recording
+}
+
+/// What GitHub answers when the bot comments.
+fn comment() -> serde_json::Value {
+    fixture("issue_comment-a41c9e70-00b6-11f0-9d2f-1e5b7c0a4f88.json").body["comment"].clone()
+}
+
+fn label(id: u64, name: &str) -> serde_json::Value {
+    json!({
+        "id": id,
+        "node_id": format!("LA_{}", id),
+        "url": format!("https://api.github.com/repos/ddnet/ddnet/labels/{}", name),
+        "name": name,
+        "color": "d73a4a",
+        "default": false,
+        "description": null,
+    })
+}
+
+/// Expects the CLA check of the PR to be reported as `conclusion` `times` times.
+async fn expect_cla_check(github: &MockServer, conclusion: &str, times: u64) {
+    Mock::given(method("POST"))
+        .and(path("/repos/ddnet/ddnet/check-runs"))
+        .and(body_partial_json(json!({
+            "name": "CLA",
+            "head_sha": HEAD_SHA,
+            "conclusion": conclusion,
+        })))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!({
+            "id": 1,
+            "node_id": "CR_1",
+            "head_sha": HEAD_SHA,
+            "url": "https://api.github.com/repos/ddnet/ddnet/check-runs/1",
+            "conclusion": conclusion,
+            "output": {
+                "title": "CLA",
+                "summary": "",
+                "annotations_count": 0,
+                "annotations_url": "https://api.github.com/repos/ddnet/ddnet/check-runs/1/annotations",
+            },
+            "name": "CLA",
+            "pull_requests": [],
+        })))
+        .expect(times)
+        .mount(github)
+        .await;
+}
+
+/// Posts a recorded delivery to the app like GitHub would.
+async fn deliver(app: Router, recording: &Recording) -> StatusCode {
+    let mut req = Request::post("/");
+    for (name, value) in &recording.headers {
+        req = req.header(name, value);
+    }
+    let body = serde_json::to_vec(&recording.body).unwrap();
+    let res = app
+        .oneshot(req.body(Body::from(body)).unwrap())
+        .await
+        .unwrap();
+    res.status()
+}
+
+#[tokio::test]
+async fn opened_issue_needs_triage() {
+    let github = MockServer::start().await;
+    let app = mock_app(&github).await;
+    Mock::given(method("POST"))
+        .and(path(format!(
+            "/repositories/{}/issues/9876/labels",
+            REPO_ID
+        )))
+        .and(body_json(json!({ "labels": ["triage-needed"] })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .expect(1)
+        .mount(&github)
+        .await;
+
+    let recording = fixture("issues-7d3e5f20-00b1-11f0-8e8a-3c9e41d2b7a1.json");
+    assert_eq!(deliver(app, &recording).await, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn claim_assigns_commenter() {
+    let github = MockServer::start().await;
+    let app = mock_app(&github).await;
+    let recording = fixture("issue_comment-a41c9e70-00b6-11f0-9d2f-1e5b7c0a4f88.json");
+    Mock::given(method("POST"))
+        .and(path(format!(
+            "/repositories/{}/issues/9877/assignees",
+            REPO_ID
+        )))
+        .and(body_json(json!({ "assignees": ["heinrich5991"] })))
+        .respond_with(ResponseTemplate::new(201).set_body_json(&recording.body["issue"]))
+        .expect(1)
+        .mount(&github)
+        .await;
+
+    assert_eq!(deliver(app, &recording).await, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn failed_handler_is_kept_as_dead_letter() {
+    let github = MockServer::start().await;
+    let app = mock_app(&github).await;
+    Mock::given(method("POST"))
+        .and(path(format!(
+            "/repositories/{}/issues/9876/labels",
+            REPO_ID
+        )))
+        .respond_with(ResponseTemplate::new(422).set_body_json(json!({ "message": "nope" })))
+        .mount(&github)
+        .await;
+
+    let recording = fixture("issues-7d3e5f20-00b1-11f0-8e8a-3c9e41d2b7a1.json");
+    assert_eq!(deliver(app.clone(), &recording).await, StatusCode::OK);
+
+    let res = app
+        .clone()
+        .oneshot(
+            Request::get("/admin/dead-letters")
+                .header("Authorization", "Bearer admin")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let letters: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(letters.as_array().unwrap().len(), 1);
+    assert_eq!(letters[0]["handler"], "triager");
+    assert_eq!(
+        letters[0]["job"]["delivery"],
+        recording.header("x-github-delivery")
+    );
+
+    let res = app
+        .oneshot(
+            Request::get("/admin/dead-letters")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn personal_token_handles_repo_webhooks() {
+    let github = MockServer::start().await;
+    let figment = figment::Figment::from(figment::providers::Serialized::defaults(json!({
+        "github_token": "ghp_test",
+        "github_api_url": github.uri(),
+        "database_path": ":memory:",
+    })));
+    let config = Config::from_figment(&figment).unwrap();
+    let app = ddbot::router(
+        AppState::from_config(&config).unwrap(),
+        &config.webhook_path,
+    );
+    Mock::given(method("POST"))
+        .and(path(format!(
+            "/repositories/{}/issues/9876/labels",
+            REPO_ID
+        )))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .expect(1)
+        .mount(&github)
+        .await;
+
+    // Repo webhooks don't say which installation they're for.
+    let mut recording = fixture("issues-7d3e5f20-00b1-11f0-8e8a-3c9e41d2b7a1.json");
+    recording
+        .body
+        .as_object_mut()
+        .unwrap()
+        .remove("installation");
+    assert_eq!(deliver(app, &recording).await, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn label_command_keeps_concurrent_labels() {
+    let github = MockServer::start().await;
+    let app = mock_app(&github).await;
+    let mut recording = fixture("issue_comment-a41c9e70-00b6-11f0-9d2f-1e5b7c0a4f88.json");
+    recording.body["comment"]["body"] = json!("!ddnetbot label +bug -crash");
+    Mock::given(method("GET"))
+        .and(path(format!("/repositories/{}/labels", REPO_ID)))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(json!([label(1, "bug"), label(2, "crash")])),
+        )
+        .mount(&github)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(format!(
+            "/repositories/{}/issues/9877/labels",
+            REPO_ID
+        )))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([label(2, "crash")])))
+        .mount(&github)
+        .await;
+    // `feature` was added by someone else after the labels were read.
+    Mock::given(method("POST"))
+        .and(path(format!(
+            "/repositories/{}/issues/9877/labels",
+            REPO_ID
+        )))
+        .and(body_json(json!({ "labels": ["bug"] })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            label(1, "bug"),
+            label(2, "crash"),
+            label(3, "feature")
+        ])))
+        .expect(1)
+        .mount(&github)
+        .await;
+    Mock::given(method("DELETE"))
+        .and(path(format!(
+            "/repositories/{}/issues/9877/labels/crash",
+            REPO_ID
+        )))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(json!([label(1, "bug"), label(3, "feature")])),
+        )
+        .expect(1)
+        .mount(&github)
+        .await;
+    Mock::given(method("PUT"))
+        .and(path(format!(
+            "/repositories/{}/issues/9877/labels",
+            REPO_ID
+        )))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .expect(0)
+        .mount(&github)
+        .await;
+    Mock::given(method("POST"))
+        .and(path(format!(
+            "/repositories/{}/issues/9877/comments",
+            REPO_ID
+        )))
+        .respond_with(ResponseTemplate::new(201).set_body_json(&recording.body["comment"]))
+        .expect(1)
+        .mount(&github)
+        .await;
+
+    assert_eq!(deliver(app, &recording).await, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn delivery_without_event_is_rejected() {
+    let github = MockServer::start().await;
+    let app = mock_app(&github).await;
+    let mut recording = fixture("issues-7d3e5f20-00b1-11f0-8e8a-3c9e41d2b7a1.json");
+    recording
+        .headers
+        .retain(|name, _| !name.eq_ignore_ascii_case("x-github-event"));
+    assert_eq!(deliver(app, &recording).await, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn delivery_without_id_is_rejected() {
+    let github = MockServer::start().await;
+    let app = mock_app(&github).await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .expect(0)
+        .mount(&github)
+        .await;
+    let mut recording = fixture("issues-7d3e5f20-00b1-11f0-8e8a-3c9e41d2b7a1.json");
+    recording
+        .headers
+        .retain(|name, _| !name.eq_ignore_ascii_case("x-github-delivery"));
+    assert_eq!(deliver(app, &recording).await, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn triage_feed_is_unavailable_until_built() {
+    let github = MockServer::start().await;
+    let app = mock_app(&github).await;
+    let res = app
+        .oneshot(
+            Request::get("/feeds/triage.xml")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[tokio::test]
+async fn dashboard_needs_the_admin_token_or_a_session() {
+    let github = MockServer::start().await;
+    let app = mock_app(&github).await;
+    let get = |auth: Option<&str>| {
+        let mut req = Request::get("/triage/next");
+        if let Some(auth) = auth {
+            req = req.header("Authorization", auth);
+        }
+        req.body(Body::empty()).unwrap()
+    };
+    let res = app.clone().oneshot(get(None)).await.unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    let res = app.oneshot(get(Some("Bearer admin"))).await.unwrap();
+    assert_ne!(res.status(), StatusCode::UNAUTHORIZED);
+
+    // Without an admin token or login, the dashboard isn't open to everyone.
+    let figment = figment::Figment::from(figment::providers::Serialized::defaults(json!({
+        "github_token": "ghp_test",
+        "github_api_url": github.uri(),
+        "database_path": ":memory:",
+    })));
+    let config = Config::from_figment(&figment).unwrap();
+    let app = ddbot::router(
+        AppState::from_config(&config).unwrap(),
+        &config.webhook_path,
+    );
+    let res = app.oneshot(get(None)).await.unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn outsiders_claim_issues_unless_maintainer_only() {
+    let blocked = label(1, "blocked");
+    for (labels, assigned) in [(json!([]), true), (json!([blocked]), false)] {
+        let github = MockServer::start().await;
+        let app = mock_app(&github).await;
+        let mut recording = fixture("issue_comment-a41c9e70-00b6-11f0-9d2f-1e5b7c0a4f88.json");
+        recording.body["comment"]["author_association"] = json!("NONE");
+        recording.body["issue"]["labels"] = labels;
+        // Neither an org member nor a collaborator, the unmatched checks answer 404.
+        Mock::given(method("POST"))
+            .and(path(format!(
+                "/repositories/{}/issues/9877/assignees",
+                REPO_ID
+            )))
+            .and(body_json(json!({ "assignees": ["heinrich5991"] })))
+            .respond_with(ResponseTemplate::new(201).set_body_json(&recording.body["issue"]))
+            .expect(u64::from(assigned))
+            .mount(&github)
+            .await;
+        Mock::given(method("POST"))
+            .and(path(format!(
+                "/repositories/{}/issues/9877/comments",
+                REPO_ID
+            )))
+            .respond_with(ResponseTemplate::new(201).set_body_json(&recording.body["comment"]))
+            .expect(u64::from(!assigned))
+            .mount(&github)
+            .await;
+
+        assert_eq!(deliver(app, &recording).await, StatusCode::OK);
+    }
+}
+
+#[tokio::test]
+async fn protocol_changes_are_flagged_once() {
+    let github = MockServer::start().await;
+    let app = mock_app_with(
+        &github,
+        json!({ "protocol_paths": ["src/engine/shared/protocol"] }),
+    )
+    .await;
+    Mock::given(method("GET"))
+        .and(path("/repos/ddnet/ddnet/pulls/9880/files"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+            "sha": "bbcd538c8e72b8c175046e27cc8f907076331401",
+            "filename": "src/engine/shared/protocol7.h",
+            "status": "modified",
+            "additions": 12,
+            "deletions": 2,
+            "changes": 14,
+            "blob_url": "https://github.com/ddnet/ddnet/blob/4f1c2e9/src/engine/shared/protocol7.h",
+            "raw_url": "https://github.com/ddnet/ddnet/raw/4f1c2e9/src/engine/shared/protocol7.h",
+            "contents_url": "https://api.github.com/repos/ddnet/ddnet/contents/src/engine/shared/protocol7.h",
+        }])))
+        .mount(&github)
+        .await;
+    Mock::given(method("POST"))
+        .and(path(format!(
+            "/repositories/{}/issues/9880/labels",
+            REPO_ID
+        )))
+        .and(body_json(json!({ "labels": ["breaking-protocol"] })))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(json!([label(1, "breaking-protocol")])),
+        )
+        .expect(1)
+        .mount(&github)
+        .await;
+    Mock::given(method("POST"))
+        .and(path(format!(
+            "/repositories/{}/issues/9880/comments",
+            REPO_ID
+        )))
+        .and(body_string_contains("protocol"))
+        .respond_with(ResponseTemplate::new(201).set_body_json(comment()))
+        .expect(1)
+        .mount(&github)
+        .await;
+
+    // Maintainers may take the label off, a push doesn't put it back.
+    let opened = pull_request("opened", "c2b9e4a0-00f4-11f0-8f3c-5a7d2e1b9c44");
+    assert_eq!(deliver(app.clone(), &opened).await, StatusCode::OK);
+    let pushed = pull_request("synchronize", "d81f0b36-00f5-11f0-8e2d-4b6a9c3e7f15");
+    assert_eq!(deliver(app, &pushed).await, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn unacknowledged_protocol_changes_arent_ready_to_merge() {
+    for acknowledged in [false, true] {
+        let github = MockServer::start().await;
+        let app = mock_app_with(
+            &github,
+            json!({ "protocol_paths": ["src/engine/shared/protocol"] }),
+        )
+        .await;
+        if acknowledged {
+            // A maintainer acknowledges the change on the PR.
+            let ack = pr_comment(
+                "!ddnetbot ack-protocol",
+                "4e1b7a90-00f6-11f0-9a4b-2c8d5f1e3b70",
+            );
+            assert_eq!(deliver(app.clone(), &ack).await, StatusCode::OK);
+        }
+        Mock::given(method("DELETE"))
+            // The dashes of the label are percent-encoded.
+            .and(path(format!(
+                "/repositories/{}/issues/9880/labels/ready%2Dto%2Dmerge",
+                REPO_ID
+            )))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(json!([label(1, "breaking-protocol")])),
+            )
+            .expect(u64::from(!acknowledged))
+            .mount(&github)
+            .await;
+        Mock::given(method("POST"))
+            .and(path(format!(
+                "/repositories/{}/issues/9880/comments",
+                REPO_ID
+            )))
+            .respond_with(ResponseTemplate::new(201).set_body_json(comment()))
+            .expect(u64::from(!acknowledged))
+            .mount(&github)
+            .await;
+
+        let mut labeled = pull_request("labeled", "e5a7c912-00f6-11f0-9a4b-2c8d5f1e3b70");
+        labeled.body["label"] = label(2, "ready-to-merge");
+        labeled.body["pull_request"]["labels"] = json!([
+            label(1, "breaking-protocol"),
+            label(2, "ready-to-merge"),
+            label(3, "waiting-for-reviews")
+        ]);
+        assert_eq!(deliver(app, &labeled).await, StatusCode::OK);
+    }
+}
+
+#[tokio::test]
+async fn ci_results_update_one_status_comment() {
+    let github = MockServer::start().await;
+    let app = mock_app(&github).await;
+    let comment = comment();
+    Mock::given(method("POST"))
+        .and(path(format!(
+            "/repositories/{}/issues/9880/comments",
+            REPO_ID
+        )))
+        .and(body_string_contains(format!(
+            "**CI (GitHub Actions)**: failed for {}",
+            &HEAD_SHA[..7]
+        )))
+        .respond_with(ResponseTemplate::new(201).set_body_json(&comment))
+        .expect(1)
+        .mount(&github)
+        .await;
+    // Octocrab edits comments with a POST, which GitHub takes like a PATCH.
+    Mock::given(method("POST"))
+        .and(path(format!(
+            "/repositories/{}/issues/comments/{}",
+            REPO_ID, comment["id"]
+        )))
+        .and(body_string_contains(format!(
+            "**CI (GitHub Actions)**: passed for {}",
+            &HEAD_SHA[..7]
+        )))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&comment))
+        .expect(1)
+        .mount(&github)
+        .await;
+
+    let name = "check_suite-6e2b9d14-00f9-11f0-9f3a-8c1e5d7b2a46.json";
+    assert_eq!(deliver(app.clone(), &fixture(name)).await, StatusCode::OK);
+    // The rerun passes.
+    let mut passed = redelivered(name, "7c3e0a25-00f9-11f0-9f3a-8c1e5d7b2a46");
+    passed.body["check_suite"]["conclusion"] = json!("success");
+    assert_eq!(deliver(app, &passed).await, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn cla_check_follows_the_signatories() {
+    let cla = json!({
+        "document": "https://ddnet.org/cla",
+        "signatories": ["Kog-Player"],
+    });
+    // Signatories are matched ignoring case, bots aren't checked at all.
+    for (author, author_type, conclusion) in [
+        ("kog-player", "User", Some("success")),
+        ("heinrich5991", "User", Some("failure")),
+        ("dependabot[bot]", "Bot", None),
+    ] {
+        let github = MockServer::start().await;
+        let app = mock_app_with(&github, json!({ "cla": cla })).await;
+        for expected in ["success", "failure"] {
+            let times = u64::from(conclusion == Some(expected));
+            expect_cla_check(&github, expected, times).await;
+        }
+        // Only those who haven't accepted it are asked to.
+        Mock::given(method("POST"))
+            .and(path(format!(
+                "/repositories/{}/issues/9880/comments",
+                REPO_ID
+            )))
+            .and(body_string_contains("cla accept"))
+            .respond_with(ResponseTemplate::new(201).set_body_json(comment()))
+            .expect(u64::from(conclusion == Some("failure")))
+            .mount(&github)
+            .await;
+
+        let mut opened = pull_request("opened", "f3c8d5e1-00f7-11f0-8b6e-7d2a4c9f1e58");
+        opened.body["pull_request"]["user"]["login"] = json!(author);
+        opened.body["pull_request"]["user"]["type"] = json!(author_type);
+        assert_eq!(deliver(app, &opened).await, StatusCode::OK);
+    }
+}
+
+#[tokio::test]
+async fn accepting_the_cla_turns_the_check_green() {
+    let github = MockServer::start().await;
+    let app = mock_app_with(
+        &github,
+        json!({ "cla": { "document": "https://ddnet.org/cla" } }),
+    )
+    .await;
+    let opened = pull_request("opened", "f3c8d5e1-00f7-11f0-8b6e-7d2a4c9f1e58");
+    expect_cla_check(&github, "failure", 1).await;
+    expect_cla_check(&github, "success", 1).await;
+    Mock::given(method("POST"))
+        .and(path(format!(
+            "/repositories/{}/issues/9880/comments",
+            REPO_ID
+        )))
+        .and(body_string_contains("cla accept"))
+        .respond_with(ResponseTemplate::new(201).set_body_json(comment()))
+        .expect(1)
+        .mount(&github)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/repos/ddnet/ddnet/pulls/9880"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&opened.body["pull_request"]))
+        .mount(&github)
+        .await;
+    assert_eq!(deliver(app.clone(), &opened).await, StatusCode::OK);
+
+    // The author, who has no role, accepts it on their PR.
+    let mut accept = pr_comment(
+        "!ddnetbot cla accept",
+        "2d0a5f74-00f8-11f0-8c1d-9e4f2a6b8d31",
+    );
+    accept.body["comment"]["user"] = opened.body["pull_request"]["user"].clone();
+    accept.body["comment"]["author_association"] = json!("CONTRIBUTOR");
+    assert_eq!(deliver(app, &accept).await, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn leaked_secrets_are_only_forwarded_privately() {
+    let github = MockServer::start().await;
+    let app = mock_app_with(
+        &github,
+        json!({
+            "security_alerts": {
+                "discord_webhook": format!("{}/discord", github.uri()),
+                "repo": "ddnet/security",
+            },
+        }),
+    )
+    .await;
+    let issue = fixture("issues-7d3e5f20-00b1-11f0-8e8a-3c9e41d2b7a1.json").body["issue"].clone();
+    Mock::given(method("POST"))
+        .and(path("/repos/ddnet/security/issues"))
+        .and(body_partial_json(json!({
+            "title": "Leaked secret in ddnet/ddnet: Discord Bot Token",
+        })))
+        .respond_with(ResponseTemplate::new(201).set_body_json(&issue))
+        .expect(1)
+        .mount(&github)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/discord"))
+        .and(body_string_contains("Leaked secret"))
+        .respond_with(ResponseTemplate::new(204))
+        .expect(1)
+        .mount(&github)
+        .await;
+    // Nothing about it goes to the public repo.
+    Mock::given(path_regex(format!(
+        "^/(repos/ddnet/ddnet|repositories/{})/",
+        REPO_ID
+    )))
+    .respond_with(ResponseTemplate::new(500))
+    .expect(0)
+    .mount(&github)
+    .await;
+
+    let recording = fixture("secret_scanning_alert-9b5d2e38-00fb-11f0-8a6f-4c8e1b3d7f25.json");
+    assert_eq!(deliver(app, &recording).await, StatusCode::OK);
+    let secret = recording.body["alert"]["secret"].as_str().unwrap();
+    for request in github.received_requests().await.unwrap() {
+        assert!(!String::from_utf8_lossy(&request.body).contains(secret));
+    }
+}
+
+#[tokio::test]
+async fn published_releases_are_announced() {
+    let github = MockServer::start().await;
+    let app = mock_app_with(
+        &github,
+        json!({
+            "releases": {
+                "discord_webhooks": [format!("{}/discord", github.uri())],
+                "matrix": [{
+                    "homeserver": github.uri(),
+                    "room_id": "!ddnet:matrix.org",
+                    "access_token": "syt_test",
+                }],
+            },
+        }),
+    )
+    .await;
+    Mock::given(method("POST"))
+        .and(path("/discord"))
+        .and(body_string_contains("DDNet 19.1"))
+        .and(body_string_contains("DDNet-19.1-win64.zip"))
+        .respond_with(ResponseTemplate::new(204))
+        .expect(1)
+        .mount(&github)
+        .await;
+    // Redelivered announcements have the same transaction.
+    Mock::given(method("PUT"))
+        .and(path(
+            "/_matrix/client/v3/rooms/!ddnet:matrix.org/send/m.room.message/release-205118342",
+        ))
+        .and(body_partial_json(json!({ "msgtype": "m.text" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "event_id": "$1" })))
+        .expect(1)
+        .mount(&github)
+        .await;
+
+    let name = "release-8a4c1f27-00fa-11f0-8d5e-3b7f9a2c6e14.json";
+    assert_eq!(deliver(app.clone(), &fixture(name)).await, StatusCode::OK);
+    // Prereleases and edits aren't announced.
+    let mut prerelease = redelivered(name, "a9d3e6f1-00fa-11f0-8d5e-3b7f9a2c6e14");
+    prerelease.body["release"]["prerelease"] = json!(true);
+    assert_eq!(deliver(app.clone(), &prerelease).await, StatusCode::OK);
+    let mut edited = redelivered(name, "b1e4f7a2-00fa-11f0-8d5e-3b7f9a2c6e14");
+    edited.body["action"] = json!("edited");
+    assert_eq!(deliver(app, &edited).await, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn commands_run_once_per_comment() {
+    let github = MockServer::start().await;
+    let app = mock_app(&github).await;
+    let created = fixture("issue_comment-a41c9e70-00b6-11f0-9d2f-1e5b7c0a4f88.json");
+    Mock::given(method("POST"))
+        .and(path(format!(
+            "/repositories/{}/issues/9877/assignees",
+            REPO_ID
+        )))
+        .respond_with(ResponseTemplate::new(201).set_body_json(&created.body["issue"]))
+        .expect(1)
+        .mount(&github)
+        .await;
+
+    // Broken and fixed again by edits, the command already ran.
+    let edited = |from: &str, to: &str, delivery: &str| {
+        let mut recording = redelivered(
+            "issue_comment-a41c9e70-00b6-11f0-9d2f-1e5b7c0a4f88.json",
+            delivery,
+        );
+        recording.body["action"] = json!("edited");
+        recording.body["changes"] = json!({ "body": { "from": from } });
+        recording.body["comment"]["body"] = json!(to);
+        recording
+    };
+    assert_eq!(deliver(app.clone(), &created).await, StatusCode::OK);
+    let broken = edited(
+        "!ddnetbot claim",
+        "!ddnetbot clam",
+        "0b7e3d52-00f8-11f0-8c1d-9e4f2a6b8d31",
+    );
+    assert_eq!(deliver(app.clone(), &broken).await, StatusCode::OK);
+    let fixed = edited(
+        "!ddnetbot clam",
+        "!ddnetbot claim",
+        "1c9f4e63-00f8-11f0-8c1d-9e4f2a6b8d31",
+    );
+    assert_eq!(deliver(app, &fixed).await, StatusCode::OK);
+}